@@ -0,0 +1,349 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use lib::*;
+use playbook::{Backtest, Dreamrunner, StrategyConfig, grid_search_2d, rank_grid};
+use time_series::*;
+use crate::engine::Engine;
+use crate::replay::{load_events, replay_client};
+use crate::utils::{live_strategy, recv_window, risk_limits};
+use crate::{EQUITY_PCT, INTERVAL, MIN_NOTIONAL};
+
+/// Fewest trades a parameter combination needs to be selected by optimize
+const MIN_TRADES: usize = 2;
+
+pub const USAGE: &str = "\
+Usage:
+  dreamrunner [run]
+      Run the live engine
+  dreamrunner backtest --csv <path> --start <YYYY-MM-DD> --end <YYYY-MM-DD> [options]
+      Backtest a strategy on a CSV of candles
+  dreamrunner optimize --csv <path> --start <YYYY-MM-DD> --end <YYYY-MM-DD> --param-grid <grid> [options]
+      Backtest every combination of the grid and print the highest ROI,
+      optionally rendering the % ROI of every combination with --heatmap <png>
+      and saving the optimized strategy with --save-config <json>
+  dreamrunner replay --events <path> [--quote <qty>] [--base <qty>] [--base-asset <asset>] [--quote-asset <asset>]
+      Replay websocket frames (one per line) through the live engine against a mock client,
+      printing the orders it would place. Balances default to 1000 quote and 0 base.
+      The ticker traded is the base asset then the quote asset, defaults to SOL and USDT.
+      Set RECORD_PATH when running the live engine to record frames for replay.
+      The live engine and replay trade the strategy config at STRATEGY_CONFIG, or the sol preset if unset
+
+Options:
+  --strategy <sol|eth|btc|btc_1d|atlas_1h>   Dreamrunner preset or a strategy config .json, defaults to sol
+  --interval <interval>                      Candle interval of the CSV (e.g. 30m, 1h, 1d), defaults to 30m
+  --capital <quote>                          Starting capital, defaults to 1000
+  --fee <pct>                                Fee per trade in percent, defaults to 0.02
+  --param-grid <grid>                        Ranges as param=start:end:step, comma separated.
+                                             Params are k_rev and ma_period, e.g. k_rev=0.01:0.1:0.01,ma_period=2:12:1
+  --heatmap <png>                            Render the % ROI of every optimize combination as a heatmap
+  --save-config <json>                       Save the optimized strategy to load with --strategy or STRATEGY_CONFIG";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+  Run,
+  Backtest(BacktestArgs),
+  /// Backtest args, param grid, where to render the ROI heatmap, and where to save the optimized strategy
+  Optimize(BacktestArgs, ParamGrid, Option<PathBuf>, Option<PathBuf>),
+  Replay(ReplayArgs)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestArgs {
+  pub csv: PathBuf,
+  pub start: Time,
+  pub end: Time,
+  pub strategy: String,
+  pub interval: Interval,
+  pub capital: f64,
+  pub fee: f64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayArgs {
+  pub events: PathBuf,
+  /// Free quote asset balance of the mock account
+  pub quote: f64,
+  /// Free base asset balance of the mock account
+  pub base: f64,
+  /// Base asset of the replayed ticker, the mock client has no exchange info to split a ticker with
+  pub base_asset: String,
+  /// Quote asset of the replayed ticker
+  pub quote_asset: String
+}
+
+impl ReplayArgs {
+  /// Ticker replayed, the base asset then the quote asset
+  pub fn ticker(&self) -> String {
+    format!("{}{}", self.base_asset, self.quote_asset)
+  }
+}
+
+/// Inclusive ranges of Dreamrunner params to search. `k_rev` is an absolute reversal amount.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ParamGrid {
+  pub k_rev: Option<Vec<f64>>,
+  pub ma_period: Option<Vec<usize>>
+}
+
+impl Command {
+  /// Parse the args after the binary name, no args runs the live engine
+  pub fn parse<I: IntoIterator<Item = String>>(args: I) -> DreamrunnerResult<Self> {
+    let mut args = args.into_iter();
+    let subcommand = match args.next() {
+      None => return Ok(Command::Run),
+      Some(subcommand) => subcommand
+    };
+    let flags = Self::flags(args)?;
+    let flag = |name: &str| flags.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+    match subcommand.as_str() {
+      "run" => Ok(Command::Run),
+      "backtest" => Ok(Command::Backtest(BacktestArgs::parse(&flag)?)),
+      "optimize" => {
+        let grid = flag("param-grid").ok_or(usage_error("--param-grid is required"))?;
+        Ok(Command::Optimize(
+          BacktestArgs::parse(&flag)?,
+          ParamGrid::from_str(grid)?,
+          flag("heatmap").map(PathBuf::from),
+          flag("save-config").map(PathBuf::from)
+        ))
+      },
+      "replay" => {
+        let events = flag("events").ok_or(usage_error("--events is required"))?;
+        Ok(Command::Replay(ReplayArgs {
+          events: PathBuf::from(events),
+          quote: flag("quote").unwrap_or("1000").parse()?,
+          base: flag("base").unwrap_or("0").parse()?,
+          base_asset: flag("base-asset").unwrap_or("SOL").to_uppercase(),
+          quote_asset: flag("quote-asset").unwrap_or("USDT").to_uppercase()
+        }))
+      },
+      "-h" | "--help" | "help" => Err(usage_error("")),
+      other => Err(usage_error(&format!("Unknown command: {}", other)))
+    }
+  }
+
+  /// `--key value` pairs
+  fn flags<I: Iterator<Item = String>>(mut args: I) -> DreamrunnerResult<Vec<(String, String)>> {
+    let mut flags = vec![];
+    while let Some(arg) = args.next() {
+      let key = arg.strip_prefix("--").ok_or(usage_error(&format!("Unexpected argument: {}", arg)))?;
+      let value = args.next().ok_or(usage_error(&format!("Missing value for --{}", key)))?;
+      flags.push((key.to_string(), value));
+    }
+    Ok(flags)
+  }
+}
+
+impl BacktestArgs {
+  fn parse<'a, F: Fn(&str) -> Option<&'a str>>(flag: &F) -> DreamrunnerResult<Self> {
+    let required = |name: &str| flag(name).ok_or(usage_error(&format!("--{} is required", name)));
+    Ok(Self {
+      csv: PathBuf::from(required("csv")?),
+      start: parse_date(required("start")?)?,
+      end: parse_date(required("end")?)?,
+      strategy: flag("strategy").unwrap_or("sol").to_string(),
+      interval: Interval::from_str(flag("interval").unwrap_or("30m"))?,
+      capital: flag("capital").unwrap_or("1000").parse()?,
+      fee: flag("fee").unwrap_or("0.02").parse()?
+    })
+  }
+
+  pub fn strategy(&self) -> DreamrunnerResult<Dreamrunner> {
+    match self.strategy.as_str() {
+      "sol" => Ok(Dreamrunner::solusdt_optimized()),
+      "eth" => Ok(Dreamrunner::ethusdt_optimized()),
+      "btc" => Ok(Dreamrunner::btcusdt_optimized()),
+      "btc_1d" => Ok(Dreamrunner::btcusd_1d_optimized(None)),
+      "atlas_1h" => Ok(Dreamrunner::atlasusd_1h_optimized(Some(1.0))),
+      config if config.ends_with(".json") => Ok(Dreamrunner::from_config_file(Path::new(config))?),
+      other => Err(usage_error(&format!("Unknown strategy: {}", other)))
+    }
+  }
+
+  /// Spot backtest of `strategy` on the CSV, mirroring how the live engine trades
+  fn backtest(&self, strategy: Dreamrunner, candles: Vec<Candle>) -> anyhow::Result<Summary> {
+    let ticker = strategy.ticker.clone();
+    let mut backtest = Backtest::new(strategy, self.capital, self.fee, Bet::Percent(100.0), 1, false, self.interval, 0.0);
+    backtest.candles.insert(ticker, candles);
+    backtest.backtest()
+  }
+
+  fn candles(&self, ticker: &str) -> anyhow::Result<Vec<Candle>> {
+    Ok(Dataframe::csv_series(&self.csv, Some(self.start), Some(self.end), ticker.to_string(), false)?.candles)
+  }
+}
+
+impl FromStr for ParamGrid {
+  type Err = DreamrunnerError;
+
+  fn from_str(s: &str) -> DreamrunnerResult<Self> {
+    let mut grid = ParamGrid::default();
+    for param in s.split(',') {
+      let (name, range) = param.split_once('=').ok_or(usage_error(&format!("Invalid param range: {}", param)))?;
+      let bounds = range.split(':').map(|b| b.parse::<f64>()).collect::<Result<Vec<f64>, _>>()?;
+      let (start, end, step) = match bounds[..] {
+        [start, end, step] if step > 0.0 && end >= start => (start, end, step),
+        _ => return Err(usage_error(&format!("Range must be start:end:step with end >= start and step > 0: {}", param)))
+      };
+      // epsilon so float error in the step doesn't drop the end of the range
+      let steps = ((end - start) / step + 1e-9).floor() as usize;
+      let values = (0..=steps).map(|i| trunc!(start + i as f64 * step, 8));
+      match name {
+        "k_rev" => grid.k_rev = Some(values.collect()),
+        "ma_period" => grid.ma_period = Some(values.map(|v| v as usize).collect()),
+        other => return Err(usage_error(&format!("Unknown param: {}", other)))
+      }
+    }
+    Ok(grid)
+  }
+}
+
+fn usage_error(msg: &str) -> DreamrunnerError {
+  DreamrunnerError::Custom(format!("{}\n{}", msg, USAGE))
+}
+
+fn parse_date(date: &str) -> DreamrunnerResult<Time> {
+  let parts = date.split('-').map(|p| p.parse::<u32>()).collect::<Result<Vec<u32>, _>>()?;
+  match parts[..] {
+    [year, month, day] if (1..=12).contains(&month) && (1..=31).contains(&day) => {
+      Ok(Time::new(year as i32, &Month::from_num(month), &Day::from_num(day), None, None, None))
+    },
+    _ => Err(usage_error(&format!("Date must be YYYY-MM-DD: {}", date)))
+  }
+}
+
+pub fn backtest(args: &BacktestArgs) -> anyhow::Result<()> {
+  let strategy = args.strategy()?;
+  let ticker = strategy.ticker.clone();
+  let summary = args.backtest(strategy, args.candles(&ticker)?)?;
+  summary.print(&ticker);
+  Ok(())
+}
+
+pub fn optimize(args: &BacktestArgs, grid: &ParamGrid, heatmap: Option<&Path>, save_config: Option<&Path>) -> anyhow::Result<()> {
+  let strategy = args.strategy()?;
+  let ticker = strategy.ticker.clone();
+  let candles = args.candles(&ticker)?;
+
+  // a param missing from the grid keeps the preset value
+  let k_revs: Vec<KagiReversal> = match &grid.k_rev {
+    Some(k_revs) => k_revs.iter().map(|k_rev| KagiReversal::Absolute(*k_rev)).collect(),
+    None => vec![strategy.k_rev]
+  };
+  let ma_periods = grid.ma_period.clone().unwrap_or(vec![strategy.ma_period]);
+  let run = |k_rev: KagiReversal, ma_period: usize| {
+    let mut strat = strategy.clone().with_ma_period(ma_period);
+    strat.k_rev = k_rev;
+    args.backtest(strat, candles.clone())
+  };
+
+  // a single trade isn't a result worth optimizing for
+  let results = grid_search_2d(&k_revs, &ma_periods, &ticker, MIN_TRADES, run, |summary| summary.pct_roi(&ticker), |completed, total| {
+    println!("Optimization progress: {}/{}", completed, total);
+  })?;
+  if let Some(heatmap) = heatmap {
+    let out_file = heatmap.to_str().ok_or(anyhow::anyhow!("Heatmap path is not UTF-8"))?;
+    Plot::heatmap(&results, out_file, &format!("{} % ROI by Kagi Rev (x) and WMA Period (y)", ticker))?;
+    println!("Heatmap written to {}", out_file);
+  }
+
+  // highest percent ROI
+  let optimized = rank_grid(&results).into_iter().next();
+  match optimized {
+    Some((k_rev, ma_period, _)) => {
+      println!("==== Optimized Backtest ====");
+      println!("WMA Period: {}", ma_period);
+      println!("Kagi Rev: {:?}", k_rev);
+      run(k_rev, ma_period)?.print(&ticker);
+      if let Some(path) = save_config {
+        let mut optimized = strategy.clone().with_ma_period(ma_period);
+        optimized.k_rev = k_rev;
+        optimized.to_config_file(path)?;
+        println!("Strategy config written to {}", path.display());
+      }
+    },
+    None => println!("No parameters produced at least {} trades", MIN_TRADES)
+  }
+  Ok(())
+}
+
+/// Replay recorded websocket frames through the live engine and print the orders it placed
+pub async fn replay(args: &ReplayArgs) -> DreamrunnerResult<()> {
+  let events = load_events(&args.events)?;
+  let ticker = args.ticker();
+  let client = replay_client(&ticker, &args.quote_asset, &args.base_asset, args.quote, args.base);
+  let (_, rx) = crossbeam::channel::unbounded::<WebSocketEvent>();
+  let mut engine = Engine::new(
+    client.clone(),
+    rx,
+    false,
+    args.base_asset.clone(),
+    args.quote_asset.clone(),
+    ticker,
+    INTERVAL,
+    MIN_NOTIONAL,
+    EQUITY_PCT,
+    recv_window()?,
+    live_strategy()?,
+    risk_limits()?
+  );
+  println!("Replaying {} events", events.len());
+  engine.replay(events).await?;
+  for order in client.orders() {
+    let params = order.params();
+    let param = |key: &str| params.get(key).cloned().unwrap_or_default();
+    println!(
+      "{} {} {} {} @ {}",
+      param("newClientOrderId"),
+      param("side"),
+      param("type"),
+      param("quantity"),
+      params.get("stopPrice").or(params.get("price")).cloned().unwrap_or("market".to_string())
+    );
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(args: &str) -> Vec<String> {
+    args.split_whitespace().map(|a| a.to_string()).collect()
+  }
+
+  #[test]
+  fn test_parse_command() -> DreamrunnerResult<()> {
+    assert_eq!(Command::parse(vec![])?, Command::Run);
+    assert_eq!(Command::parse(args("run"))?, Command::Run);
+
+    let cmd = Command::parse(args("optimize --csv sol.csv --start 2023-01-01 --end 2024-04-30 --interval 1h --param-grid k_rev=0.01:0.03:0.01,ma_period=2:4:1"))?;
+    let Command::Optimize(backtest, grid, heatmap, save_config) = cmd else {
+      panic!("expected optimize command");
+    };
+    assert_eq!(backtest.csv, PathBuf::from("sol.csv"));
+    assert_eq!(backtest.start, Time::new(2023, &Month::from_num(1), &Day::from_num(1), None, None, None));
+    assert_eq!(backtest.interval, Interval::OneHour);
+    assert_eq!(backtest.strategy, "sol");
+    assert_eq!(grid.k_rev, Some(vec![0.01, 0.02, 0.03]));
+    assert_eq!(grid.ma_period, Some(vec![2, 3, 4]));
+    assert_eq!(heatmap, None);
+    assert_eq!(save_config, None);
+
+    assert!(Command::parse(args("backtest --csv sol.csv --start 2023-01-01")).is_err());
+    assert!(Command::parse(args("optimize --csv sol.csv --start 2023-01-01 --end 2024-01-01")).is_err());
+    assert!(Command::parse(args("backtest --csv")).is_err());
+    assert!(Command::parse(args("trade")).is_err());
+
+    assert_eq!(Command::parse(args("replay --events frames.jsonl --quote 500"))?, Command::Replay(ReplayArgs {
+      events: PathBuf::from("frames.jsonl"),
+      quote: 500.0,
+      base: 0.0,
+      base_asset: "SOL".to_string(),
+      quote_asset: "USDT".to_string()
+    }));
+    let replay = Command::parse(args("replay --events frames.jsonl --base-asset eth --quote-asset usdc"))?;
+    assert!(matches!(replay, Command::Replay(args) if args.ticker() == "ETHUSDC"));
+    Ok(())
+  }
+}
@@ -4,33 +4,63 @@ use std::marker::PhantomData;
 use lib::*;
 use log::*;
 use serde::de::DeserializeOwned;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use chrono::Timelike;
-use crossbeam::channel::Receiver;
+use crossbeam::channel::{Receiver, RecvTimeoutError};
 use lib::trade::*;
-use time_series::{trunc, Candle, Time, Signal, SignalInfo};
+use time_series::{trunc, Candle, Time, Signal, SignalInfo, SignalOrderType, TimeInForce};
 use playbook::Strategy;
+use crate::risk::{CircuitBreaker, DeadManSwitch, RiskLimits};
 
-pub struct Engine<T, S: Strategy<T>> {
-  pub client: Client,
+pub struct Engine<T, S: Strategy<T>, C: BinanceApi = Client> {
+  pub client: C,
   pub rx: Receiver<WebSocketEvent>,
   pub disable_trading: bool,
   pub base_asset: String,
   pub quote_asset: String,
   pub ticker: String,
   pub interval: Interval,
+  /// Minimum equalize order notional in quote, applied on top of the exchange min notional
   pub min_notional: f64,
   pub equity_pct: f64,
+  /// Maximum notional of an order in quote, on top of the exchange max quantity and position filters
+  pub max_notional: Option<f64>,
+  /// Millis a signed request stays valid, applied to every signed request
+  pub recv_window: u32,
   pub active_order: ActiveOrder,
   pub assets: Assets,
   pub strategy: S,
+  pub circuit_breaker: CircuitBreaker,
+  pub filters: SymbolFilters,
+  /// Candle date (unix millis) of the last entry signal acted on, for the strategy cooldown
+  pub last_entry: Option<i64>,
+  /// Client timestamp (unix millis) the active order was sent, to measure fill latency
+  pub order_placed_at: Option<i64>,
+  /// Limit price policy for entries and exits, market orders if None
+  pub limit_offset: Option<LimitOffset>,
+  /// Place limit orders as LIMIT_MAKER so they are rejected rather than fill as taker
+  pub maker_only: bool,
+  /// Reprice and retry LIMIT_MAKER orders rejected for crossing the book, rather than reset the active order
+  pub post_only_retry: Option<PostOnlyRetry>,
+  /// Self-trade prevention mode sent with every order, so strategies sharing the account don't match each other
+  pub stp_mode: Option<StpMode>,
+  /// Wait after the websocket marks a bar final, then re-read it over REST so the close is settled.
+  /// Zero acts on the websocket bar immediately.
+  pub close_confirmation: Duration,
+  /// Candles to load at startup instead of the strategy's history, to over-fetch for filters outside the strategy.
+  /// Raised to [`Strategy::min_history`] if less, see [`Engine::history_limit`].
+  pub warmup_candles: Option<u16>,
+  /// Cancel a resting stop loss before placing an exit signal's order, so both can't fill and sell twice
+  pub cancel_stop_on_exit: bool,
+  /// Cancel open orders if no websocket event arrives within its timeout, disabled if None
+  pub dead_man: Option<DeadManSwitch>,
   _data: PhantomData<T>
 }
 
-impl<T, S: Strategy<T>> Engine<T, S> {
+impl<T: Clone + Into<Candle>, S: Strategy<T>, C: BinanceApi> Engine<T, S, C> {
   #[allow(clippy::too_many_arguments)]
   pub fn new(
-    client: Client,
+    client: C,
     rx: Receiver<WebSocketEvent>,
     disable_trading: bool,
     base_asset: String,
@@ -39,7 +69,9 @@ impl<T, S: Strategy<T>> Engine<T, S> {
     interval: Interval,
     min_notional: f64,
     equity_pct: f64,
+    recv_window: u32,
     strategy: S,
+    risk_limits: RiskLimits,
   ) -> Self {
     Self {
       client: client.clone(),
@@ -51,14 +83,33 @@ impl<T, S: Strategy<T>> Engine<T, S> {
       interval,
       min_notional,
       equity_pct,
+      max_notional: None,
+      recv_window,
       active_order: ActiveOrder::new(),
       assets: Assets::default(),
       strategy,
+      circuit_breaker: CircuitBreaker::new(risk_limits),
+      filters: SymbolFilters::default(),
+      last_entry: None,
+      order_placed_at: None,
+      limit_offset: None,
+      maker_only: false,
+      post_only_retry: None,
+      stp_mode: None,
+      close_confirmation: Duration::ZERO,
+      warmup_candles: None,
+      cancel_stop_on_exit: true,
+      dead_man: None,
       _data: PhantomData
     }
   }
 
   pub async fn ignition(&mut self) -> DreamrunnerResult<()> {
+    // sign requests with server time so local clock drift doesn't exceed the recv window
+    self.client.sync_time().await?;
+    // cache symbol trading rules once, they rarely change
+    self.filters = SymbolFilters::from_exchange_info(&self.exchange_info().await?, &self.ticker)?;
+    info!("{} filters: {:?}", self.ticker, self.filters);
     if !self.disable_trading {
       // cancel all open orders to start with a clean slate
       self.cancel_all_open_orders().await?;
@@ -68,64 +119,161 @@ impl<T, S: Strategy<T>> Engine<T, S> {
     // get initial asset balances
     self.update_assets().await?;
     self.log_assets();
-    // load one less than required rolling period.
-    // if we fetch the entire period, the most recent candle could be old.
-    // for example: 15m candles, closed at 1:00pm, we fetch at 1:14pm, we trade using old data.
-    // so we fetch one less than the rolling period and wait for the next candle to close to ensure we trade immediately.
-    let candles = self.strategy.cache(None).ok_or(DreamrunnerError::CandleCacheMissing)?;
-    self.load_recent_candles(Some(candles.capacity as u16)).await?;
+    // fill the candle cache so the strategy can signal on the next closed candle
+    let history = self.history_limit()?;
+    info!("Loading {} candles of history", history);
+    self.load_recent_candles(Some(history)).await?;
 
     info!("🚀 Starting Dreamrunner!");
-    while let Ok(event) = self.rx.recv() {
-      match event {
-        WebSocketEvent::Kline(kline) => {
-          // cancel active order if not filled within 10 minutes,
-          // or set active order to none if completely filled.
-          // this is called here since kline updates come frequently which is a good way to crank state.
-          let kline_date = kline.kline.to_candle()?.date;
-          // check if date lands on 1m intervals within an hour.
-          // if we check on every kline (every second) we risk being rate limited by Binance.
-          if kline_date.to_datetime()?.second() == 0 {
-            self.check_active_order().await?;
-          }
-
-          // only accept if this candle is at the end of the bar period
-          if kline.kline.is_final_bar {
-            let candle = kline.kline.to_candle()?;
-            info!("Kline update, close price: {}, open time: {}", candle.close, candle.date.to_string());
-            self.process_candle(candle).await?;
+    loop {
+      let event = match self.dead_man {
+        Some(dead_man) => match self.rx.recv_timeout(dead_man.timeout) {
+          Ok(event) => event,
+          Err(RecvTimeoutError::Timeout) => {
+            self.trip_dead_man().await?;
+            continue;
           }
+          Err(RecvTimeoutError::Disconnected) => break
+        },
+        None => match self.rx.recv() {
+          Ok(event) => event,
+          Err(_) => break
         }
-        WebSocketEvent::AccountUpdate(account_update) => {
-          let assets = account_update.assets(&self.quote_asset, &self.base_asset)?;
-          info!(
-            "Account update, {}: {}, {}: {}",
-            self.quote_asset, assets.free_quote, self.base_asset, assets.free_base
-          );
-        }
-        WebSocketEvent::OrderTrade(event) => {
-          let entry_price = trunc!(event.price.parse::<f64>()?, 2);
-          info!(
-            "Order update, {},  {},  {} @ {}, {}",
-            event.symbol,
-            event.new_client_order_id,
-            event.side,
-            entry_price,
-            event.order_status,
-          );
-          // update state
-          self.update_active_order(TradeInfo::try_from(&event)?)?;
-          // cancel active order if not filled within 10 minutes
-          self.check_active_order().await?;
-        }
-        _ => (),
       };
+      if let Some(dead_man) = &mut self.dead_man {
+        dead_man.heartbeat();
+      }
+      self.handle_event(event).await?;
     }
     warn!("🟡 Shutting down engine");
+    info!("Request metrics: {:#?}", self.client.metrics_snapshot());
     Ok(())
   }
 
-  #[allow(dead_code)]
+  /// No websocket event within the [`DeadManSwitch`] timeout, so the engine can't see fills or candles.
+  /// Cancels open orders and resets the active order, then if [`DeadManSwitch::flatten`]
+  /// sells the base bought by a filled entry at market. Does nothing again until the next event.
+  pub async fn trip_dead_man(&mut self) -> DreamrunnerResult<()> {
+    let dead_man = match &mut self.dead_man {
+      Some(dead_man) if !dead_man.tripped => {
+        dead_man.tripped = true;
+        *dead_man
+      }
+      _ => return Ok(())
+    };
+    error!("🛑 No websocket event within {:?}, cancel open orders", dead_man.timeout);
+    let exited = |order: &Option<OrderState>| match order {
+      Some(OrderState::Active(exit)) => exit.executed_qty,
+      _ => 0.0
+    };
+    let position = self.active_order.position_qty
+      - exited(&self.active_order.stop_loss)
+      - exited(&self.active_order.take_profit);
+    self.reset_active_order().await?;
+    self.active_order.close_position();
+    let qty = self.filters.floor_qty(position);
+    if dead_man.flatten && !self.disable_trading && qty > 0.0 {
+      warn!("🟡 Dead man switch selling {} {} at market", qty, self.base_asset);
+      let flatten = BinanceTrade::new(
+        self.ticker.to_string(),
+        format!("{}-DEAD_MAN", self.client.timestamp()),
+        Side::Short,
+        OrderType::Market,
+        qty,
+        None,
+        Some(self.recv_window),
+        self.client.timestamp() as i64,
+        None,
+        None,
+        self.stp_mode
+      );
+      let flatten = self.cap_to_held(flatten).await?;
+      self.trade::<LimitOrderResponse>(flatten).await?;
+    }
+    Ok(())
+  }
+
+  /// Update state from a websocket event (candles, account balance updates, trade updates)
+  pub async fn handle_event(&mut self, event: WebSocketEvent) -> DreamrunnerResult<()> {
+    match event {
+      WebSocketEvent::Kline(kline) => {
+        // cancel active order if not filled within 10 minutes,
+        // or set active order to none if completely filled.
+        // this is called here since kline updates come frequently which is a good way to crank state.
+        let kline_date = kline.kline.to_candle()?.date;
+        // resume trading if the circuit breaker tripped on a previous UTC day
+        self.circuit_breaker.roll_day(kline_date.to_unix_ms());
+        // check if date lands on 1m intervals within an hour.
+        // if we check on every kline (every second) we risk being rate limited by Binance.
+        if kline_date.to_datetime()?.second() == 0 {
+          self.check_active_order().await?;
+        }
+
+        // only accept if this candle is at the end of the bar period
+        if kline.kline.is_final_bar {
+          let candle = self.confirm_close(kline.kline.to_candle()?).await;
+          let prices: Vec<String> = self.strategy.sources().iter().map(|s| format!("{:?} price: {}", s, s.value(&candle))).collect();
+          info!("Kline update, {}, open time: {}", prices.join(", "), candle.date.to_string());
+          self.process_candle(candle).await?;
+        }
+      }
+      WebSocketEvent::AccountUpdate(account_update) => {
+        let assets = account_update.assets(&self.quote_asset, &self.base_asset)?;
+        info!(
+          "Account update, {}: {}, {}: {}",
+          self.quote_asset, assets.free_quote, self.base_asset, assets.free_base
+        );
+      }
+      WebSocketEvent::OrderTrade(event) => {
+        let entry_price = trunc!(event.price.parse::<f64>()?, 2);
+        info!(
+          "Order update, {},  {},  {} @ {}, {}",
+          event.symbol,
+          event.new_client_order_id,
+          event.side,
+          entry_price,
+          event.order_status,
+        );
+        // update state
+        self.update_active_order(TradeInfo::try_from(&event)?)?;
+        // cancel active order if not filled within 10 minutes
+        self.check_active_order().await?;
+      }
+      _ => (),
+    };
+    Ok(())
+  }
+
+  /// Bar marked final by the websocket, re-read over REST after [`Engine::close_confirmation`].
+  /// Binance can mark a bar final slightly before the true close, so the REST close is the settled one.
+  /// Falls back to the websocket bar if the REST bar can't be read.
+  async fn confirm_close(&self, candle: Candle) -> Candle {
+    if self.close_confirmation.is_zero() {
+      return candle;
+    }
+    tokio::time::sleep(self.close_confirmation).await;
+    let open_time = candle.date.to_unix_ms();
+    match self.klines(Some(1), Some(open_time), None).await {
+      Ok(klines) => match klines.into_iter().find(|k| k.open_time as i64 == open_time) {
+        Some(kline) => {
+          let settled = kline.to_candle();
+          if settled.close != candle.close {
+            warn!("🟡 Settled close {} differs from websocket close {}", settled.close, candle.close);
+          }
+          settled
+        }
+        None => {
+          warn!("🟡 Bar {} not returned by REST, using websocket close", candle.date.to_string());
+          candle
+        }
+      },
+      Err(e) => {
+        warn!("🟡 Failed to confirm close, using websocket close: {:?}", e);
+        candle
+      }
+    }
+  }
+
   pub async fn exchange_info(&self) -> DreamrunnerResult<ExchangeInformation> {
     let req = ExchangeInfo::request(self.ticker.clone());
     self.client
@@ -134,12 +282,36 @@ impl<T, S: Strategy<T>> Engine<T, S> {
 
   /// Place a trade
   pub async fn trade<D: DeserializeOwned>(&self, trade: BinanceTrade) -> DreamrunnerResult<D> {
-    let req = trade.request();
+    let req = trade.request(self.client.timestamp());
     self.client.post_signed::<D>(API::Spot(Spot::Order), req).await
   }
 
+  /// Place a trade, retrying a LIMIT_MAKER order that would take as taker per [`Engine::post_only_retry`].
+  /// Each retry moves the limit price back by the cached tick size, so it can't retry without filters loaded.
+  async fn trade_post_only<D: DeserializeOwned>(&self, mut trade: BinanceTrade) -> DreamrunnerResult<D> {
+    let mut attempts = 0;
+    loop {
+      let err = match self.trade::<D>(trade.clone()).await {
+        Err(DreamrunnerError::Binance(err)) if err.would_take() => err,
+        res => return res
+      };
+      match (self.post_only_retry, trade.price) {
+        (Some(retry), Some(price)) if attempts < retry.max_attempts && self.filters.tick_size > 0.0 => {
+          attempts += 1;
+          let repriced = self.filters.floor_price(retry.reprice(trade.side, price, self.filters.tick_size));
+          warn!(
+            "🟡 Post only {} would take at {}, retry {} of {} at {}",
+            trade.client_order_id, price, attempts, retry.max_attempts, repriced
+          );
+          trade.price = Some(repriced);
+        }
+        _ => return Err(DreamrunnerError::Binance(err))
+      }
+    }
+  }
+
   pub async fn trade_or_reset<D: DeserializeOwned>(&mut self, trade: BinanceTrade) -> DreamrunnerResult<D> {
-    match self.trade::<D>(trade.clone()).await {
+    match self.trade_post_only::<D>(trade.clone()).await {
       Ok(res) => Ok(res),
       Err(e) => {
         let order_type = ActiveOrder::client_order_id_suffix(&trade.client_order_id);
@@ -155,6 +327,64 @@ impl<T, S: Strategy<T>> Engine<T, S> {
     }
   }
 
+  /// Place an exit capped to the base held per [`Engine::cap_to_held`], resetting the active order if it's rejected
+  async fn trade_exit_or_reset<D: DeserializeOwned>(&mut self, exit: BinanceTrade) -> DreamrunnerResult<D> {
+    let client_order_id = exit.client_order_id.clone();
+    match self.cap_to_held(exit).await {
+      Ok(exit) => self.trade_or_reset::<D>(exit).await,
+      Err(e) => {
+        error!("🛑 Error placing exit {}: {:?}", client_order_id, e);
+        self.reset_active_order().await?;
+        Err(e)
+      }
+    }
+  }
+
+  /// Place a stop loss and take profit as one OCO order list, resetting the active order if it's rejected.
+  /// Both legs are exits, so the quantity is capped to the free base like any other exit.
+  async fn trade_oco_or_reset<D: DeserializeOwned>(&mut self, mut oco: OcoOrder) -> DreamrunnerResult<D> {
+    let held = self.held_qty(oco.quantity).await?;
+    if held <= 0.0 {
+      self.reset_active_order().await?;
+      return Err(DreamrunnerError::Custom(format!("No {} held to sell for OCO {}", self.base_asset, oco.list_client_order_id)));
+    }
+    if held < oco.quantity {
+      warn!("🟡 Capped OCO {} quantity {} to the {} held", oco.list_client_order_id, oco.quantity, held);
+      oco.quantity = held;
+    }
+    let req = oco.request(self.client.timestamp());
+    match self.client.post_signed::<D>(API::Spot(Spot::Oco), req).await {
+      Ok(res) => Ok(res),
+      Err(e) => {
+        error!("🛑 Error placing OCO {}: {:?}", oco.list_client_order_id, e);
+        self.reset_active_order().await?;
+        Err(e)
+      }
+    }
+  }
+
+  /// Quantity a sell of `qty` can sell, capped to the free base on the exchange.
+  /// The cached assets can miss base a fill just bought or a cancel just unlocked.
+  async fn held_qty(&self, qty: f64) -> DreamrunnerResult<f64> {
+    let assets = self.account_info().await?.account_assets(&self.quote_asset, &self.base_asset)?;
+    Ok(qty.min(self.round_qty(assets.free_base)))
+  }
+
+  /// Cap the quantity of an exit to the free base, so an oversized exit sells what is held rather than being rejected.
+  /// Spot has no `reduceOnly`, this is what keeps an exit from selling more than the position.
+  /// Errors rather than send a zero quantity if nothing is held.
+  async fn cap_to_held(&self, mut exit: BinanceTrade) -> DreamrunnerResult<BinanceTrade> {
+    let held = self.held_qty(exit.quantity).await?;
+    if held <= 0.0 {
+      return Err(DreamrunnerError::Custom(format!("No {} held to sell for exit {}", self.base_asset, exit.client_order_id)));
+    }
+    if exit.quantity > held {
+      warn!("🟡 Capped exit {} quantity {} to the {} held", exit.client_order_id, exit.quantity, held);
+      exit.quantity = held;
+    }
+    Ok(exit)
+  }
+
   fn trade_qty(&self, side: Side, price: f64) -> DreamrunnerResult<f64> {
     let assets = self.assets();
     info!(
@@ -169,36 +399,104 @@ impl<T, S: Strategy<T>> Engine<T, S> {
     let long_qty = assets.free_quote / price * (self.equity_pct / 100_f64);
     let short_qty = assets.free_base * (self.equity_pct / 100_f64);
 
-    Ok(match side {
-      Side::Long => trunc!(long_qty, 2),
-      Side::Short => trunc!(short_qty, 2)
-    })
+    let qty = match side {
+      Side::Long => long_qty,
+      Side::Short => short_qty
+    };
+    let mut capped = self.filters.cap_qty(side, qty, assets.free_base + assets.locked_base);
+    if let Some(max_notional) = self.max_notional {
+      capped = capped.min(max_notional / price);
+    }
+    if capped < qty {
+      warn!("🟡 Capped {:?} quantity {} to {} by the max quantity, position or notional", side, qty, capped);
+    }
+    Ok(self.round_qty(capped))
+  }
+
+  /// Floor `qty` to the symbol's lot step size, or 2 decimals if filters haven't been loaded
+  fn round_qty(&self, qty: f64) -> f64 {
+    match self.filters.step_size > 0.0 {
+      true => self.filters.floor_qty(qty),
+      false => trunc!(qty, 2)
+    }
+  }
+
+  /// Floor `price` to the symbol's tick size, or 2 decimals if filters haven't been loaded
+  fn round_price(&self, price: f64) -> f64 {
+    match self.filters.tick_size > 0.0 {
+      true => self.filters.floor_price(price),
+      false => trunc!(price, 2)
+    }
+  }
+
+  /// Best bid and ask of the ticker
+  pub async fn book_ticker(&self) -> DreamrunnerResult<Tickers> {
+    let req = Price::request(self.ticker.to_string());
+    self.client
+      .get::<Tickers>(API::Spot(Spot::BookTicker), Some(req)).await
+  }
+
+  async fn build_order(&mut self, price: f64, time: Time, entry_side: Side) -> DreamrunnerResult<OrderBuilder> {
+    self.build_order_with(price, time, entry_side, None, None).await
+  }
+
+  /// Order for a signal, honoring its [`SignalInfo::order_type`] and [`SignalInfo::time_in_force`] overrides
+  async fn build_signal_order(&mut self, info: &SignalInfo, entry_side: Side) -> DreamrunnerResult<OrderBuilder> {
+    self.build_order_with(info.price, info.date, entry_side, info.order_type, info.time_in_force).await
   }
 
-  fn build_order(&mut self, price: f64, time: Time, entry_side: Side) -> DreamrunnerResult<OrderBuilder> {
+  /// Entry order of `order_type`, the [`Engine::limit_offset`] policy if None, with its stop loss and take profit.
+  /// A limit order type without a limit offset is placed at the signal price.
+  async fn build_order_with(
+    &mut self,
+    price: f64,
+    time: Time,
+    entry_side: Side,
+    order_type: Option<SignalOrderType>,
+    time_in_force: Option<TimeInForce>
+  ) -> DreamrunnerResult<OrderBuilder> {
     let entry_qty = self.trade_qty(entry_side, price)?;
-    let limit = trunc!(price, 2);
+    let limit_offset = match order_type {
+      Some(SignalOrderType::Market) => None,
+      Some(SignalOrderType::Limit) => Some(self.limit_offset.unwrap_or_default()),
+      None => self.limit_offset
+    };
+    let (order_type, limit) = match limit_offset {
+      Some(offset) => {
+        let book = match offset {
+          LimitOffset::AtBestBidAsk => Some(self.book_ticker().await?),
+          _ => None
+        };
+        let order_type = match self.maker_only {
+          true => OrderType::LimitMaker,
+          false => OrderType::Limit
+        };
+        (order_type, Some(offset.limit_price(entry_side, price, book.as_ref(), &self.filters)?))
+      }
+      None => (OrderType::Market, None)
+    };
     let timestamp = time.to_unix_ms();
     let entry = BinanceTrade::new(
       self.ticker.to_string(),
       format!("{}-{}", timestamp, "ENTRY"),
       entry_side,
-      OrderType::Market, // OrderType::Limit,
+      order_type,
       entry_qty,
-      None, // Some(limit),
+      limit,
+      Some(self.recv_window),
+      self.client.timestamp() as i64,
       None,
-      Time::now().to_unix_ms(),
       None,
-      None
-    );
-    let stop_loss = match self.strategy.stop_loss_pct() {
+      self.stp_mode
+    ).with_time_in_force(time_in_force.unwrap_or_default());
+    let stop_loss = match self.stop_loss_pct(price) {
       Some(stop_loss_pct) => {
         // stop loss is opposite side of entry (buy entry has sell stop loss)
         let stop_loss_side = match entry_side {
           Side::Long => Side::Short,
           Side::Short => Side::Long
         };
-        let stop_price = BinanceTrade::calc_stop_loss(entry_side, price, stop_loss_pct);
+        let stop_price = self.round_price(BinanceTrade::calc_stop_loss(entry_side, price, stop_loss_pct));
         Some(BinanceTrade::new(
           self.ticker.to_string(),
           format!("{}-{}", timestamp, "STOP_LOSS"),
@@ -206,43 +504,140 @@ impl<T, S: Strategy<T>> Engine<T, S> {
           OrderType::StopLoss, //OrderType::StopLossLimit,
           entry_qty,
           None, // Some(limit), // stop order is triggered at entry to start tracking immediately
-          None,
-          Time::now().to_unix_ms(),
+          Some(self.recv_window),
+          self.client.timestamp() as i64,
           Some(stop_price), // stop order exists at the stop loss
-          None
+          None,
+          self.stp_mode
         ))
       }
       None => None
     };
-    
+    let take_profit = match self.strategy.take_profit_pct() {
+      Some(take_profit_pct) => {
+        let take_profit_side = match entry_side {
+          Side::Long => Side::Short,
+          Side::Short => Side::Long
+        };
+        let take_profit_price = self.round_price(BinanceTrade::calc_take_profit(entry_side, price, take_profit_pct));
+        Some(BinanceTrade::new(
+          self.ticker.to_string(),
+          format!("{}-{}", timestamp, "TAKE_PROFIT"),
+          take_profit_side,
+          OrderType::TakeProfitLimit,
+          entry_qty,
+          Some(take_profit_price),
+          Some(self.recv_window),
+          self.client.timestamp() as i64,
+          Some(take_profit_price), // limit order is placed once price reaches the take profit
+          None,
+          self.stp_mode
+        ))
+      }
+      None => None
+    };
+
     Ok(OrderBuilder {
       entry,
-      stop_loss
+      stop_loss,
+      take_profit
     })
   }
 
+  /// Stop distance as a % of `price` by the strategy's [`playbook::StopLoss`], with the ATR computed from the candle cache.
+  /// If the cache is too short for the ATR it falls back to the fixed [`Strategy::stop_loss_pct`].
+  /// None if the strategy has no stop loss, or neither applies.
+  fn stop_loss_pct(&self, price: f64) -> Option<f64> {
+    let stop_loss = self.strategy.stop_loss()?;
+    let candles: Vec<Candle> = match self.strategy.cache(None) {
+      Some(cache) => cache.vec().into_iter().map(Into::into).collect(),
+      None => vec![]
+    };
+    stop_loss.pct(price, &candles).or_else(|| {
+      let fixed = self.strategy.stop_loss_pct();
+      warn!("🟡 {} candles cached, not enough for {:?}, falling back to a fixed {:?}% stop", candles.len(), stop_loss, fixed);
+      fixed
+    })
+  }
+
+  /// True if `date` is within [`Strategy::cooldown`] candles of the last entry.
+  fn in_cooldown(&self, date: &Time) -> bool {
+    let cooldown = self.strategy.cooldown() as i64;
+    let cooldown_ms = cooldown * self.interval.minutes() as i64 * 60_000;
+    matches!(self.last_entry, Some(last) if cooldown > 0 && date.to_unix_ms() - last <= cooldown_ms)
+  }
+
+  /// True if a stop loss has been sent to Binance and is still open
+  fn stop_loss_resting(&self) -> bool {
+    match &self.active_order.stop_loss {
+      Some(OrderState::Active(stop_loss)) => matches!(stop_loss.status, OrderStatus::New | OrderStatus::PartiallyFilled),
+      Some(OrderState::Pending(_)) => self.active_order.stop_loss_placed,
+      None => false
+    }
+  }
+
   // todo: support shorting
   pub async fn handle_signal(&mut self, signal: Signal) -> DreamrunnerResult<()> {
     match signal {
       Signal::EnterLong(info) => {
-        let builder = self.build_order(info.price, info.date, Side::Long)?;
+        if self.circuit_breaker.is_tripped() {
+          warn!("🛑 Circuit breaker tripped, ignoring entry until UTC day reset");
+          return Ok(());
+        }
+        if self.in_cooldown(&info.date) {
+          warn!("🟡 Strategy cooldown active, ignoring entry");
+          return Ok(());
+        }
+        if self.strategy.stop_loss().is_some() && self.stop_loss_pct(info.price).is_none() {
+          warn!("🛑 No stop loss can be placed, ignoring entry");
+          return Ok(());
+        }
+        let builder = self.build_signal_order(&info, Side::Long).await?;
+        self.last_entry = Some(info.date.to_unix_ms());
         self.active_order.add_entry(builder.entry.clone());
         if let Some(stop_loss) = builder.stop_loss {
           info!("🟣 Adding stop loss to long entry: {:#?}", &stop_loss);
           self.active_order.add_stop_loss(stop_loss.clone());
         }
+        if let Some(take_profit) = builder.take_profit {
+          info!("🟣 Adding take profit to long entry: {:#?}", &take_profit);
+          self.active_order.add_take_profit(take_profit.clone());
+        }
         if !self.disable_trading {
+          self.order_placed_at = Some(self.client.timestamp() as i64);
           self.trade_or_reset::<LimitOrderResponse>(builder.entry).await?;
         }
         Ok(())
       },
       Signal::ExitLong(info) => {
-        // no stop loss on long take profit (exit order) so don't need to handle stop loss
-        let builder = self.build_order(info.price, info.date, Side::Short)?;
-        self.active_order.add_entry(builder.entry.clone());
-        if !self.disable_trading {
-          self.trade_or_reset::<LimitOrderResponse>(builder.entry).await?;
+        if self.cancel_stop_on_exit && self.stop_loss_resting() {
+          info!("🟡 Cancel resting stop loss before exit");
+          self.cancel_all_open_orders().await?;
+          // the take profit is cancelled with the rest of the open orders
+          self.active_order.stop_loss = None;
+          self.active_order.stop_loss_placed = false;
+          self.active_order.take_profit = None;
+          self.active_order.take_profit_placed = false;
+        }
+        let builder = self.build_signal_order(&info, Side::Short).await?;
+        let mut exit = builder.entry;
+        // sell the tracked position rather than a share of the free base, if the engine bought it
+        if self.active_order.position_qty > 0.0 {
+          exit.quantity = self.round_qty(self.active_order.position_qty);
+        }
+        if self.disable_trading {
+          self.active_order.add_entry(exit);
+          return Ok(());
         }
+        // exits only ever close the position, never reverse it
+        exit.quantity = self.held_qty(exit.quantity).await?;
+        if exit.quantity <= 0.0 {
+          warn!("🟡 No free {} to sell, ignoring exit", self.base_asset);
+          return Ok(());
+        }
+        self.active_order.add_entry(exit.clone());
+        self.order_placed_at = Some(self.client.timestamp() as i64);
+        self.trade_or_reset::<LimitOrderResponse>(exit).await?;
         Ok(())
       },
       _ => Ok(())
@@ -251,7 +646,14 @@ impl<T, S: Strategy<T>> Engine<T, S> {
 
   // todo: support multiple signals
   pub async fn process_candle(&mut self, candle: Candle) -> DreamrunnerResult<()> {
-    let signals = self.strategy.process_candle(candle, None)?;
+    let signals = self.strategy.process_candle_async(candle, None).await?;
+    if self.circuit_breaker.is_tripped() {
+      error!(
+        "🛑🛑 Circuit breaker tripped, new entries halted until UTC day reset. Daily PnL: {}%, consecutive losses: {}",
+        trunc!(self.circuit_breaker.daily_pnl_pct, 2),
+        self.circuit_breaker.consecutive_losses
+      );
+    }
     for signal in signals {
       match &self.active_order.entry {
         None => {
@@ -291,7 +693,7 @@ impl<T, S: Strategy<T>> Engine<T, S> {
 
   /// Get account info which includes token balances
   pub async fn account_info(&self) -> DreamrunnerResult<AccountInfoResponse> {
-    let builder = AccountInfo::request(None);
+    let builder = AccountInfo::request(Some(self.recv_window), self.client.timestamp());
     let req = builder.request;
     let pre = SystemTime::now();
     let res = self
@@ -300,7 +702,7 @@ impl<T, S: Strategy<T>> Engine<T, S> {
     let dur = SystemTime::now().duration_since(pre).unwrap().as_millis();
     debug!("Request time: {:?}ms", dur);
     if let Err(e) = res {
-      let now = AccountInfo::get_timestamp()?;
+      let now = self.client.timestamp();
       let req_time = builder
         .btree
         .get("timestamp")
@@ -325,7 +727,7 @@ impl<T, S: Strategy<T>> Engine<T, S> {
   /// Not available on testnet
   #[allow(dead_code)]
   pub async fn all_assets(&self) -> DreamrunnerResult<Vec<CoinInfo>> {
-    let req = AllAssets::request(Some(5000));
+    let req = AllAssets::request(Some(self.recv_window), self.client.timestamp());
     self.client
         .get_signed::<Vec<CoinInfo>>(API::Savings(Sapi::AllCoins), Some(req)).await
   }
@@ -341,7 +743,7 @@ impl<T, S: Strategy<T>> Engine<T, S> {
 
   /// Get historical orders for a single symbol
   pub async fn all_orders(&self) -> DreamrunnerResult<Vec<HistoricalOrder>> {
-    let req = AllOrders::request(self.ticker.clone(), Some(5000));
+    let req = AllOrders::request(self.ticker.clone(), Some(self.recv_window), self.client.timestamp());
     let mut orders = self
       .client
       .get_signed::<Vec<HistoricalOrder>>(API::Spot(Spot::AllOrders), Some(req)).await?;
@@ -354,7 +756,7 @@ impl<T, S: Strategy<T>> Engine<T, S> {
   /// Returns Some if there is an open trade, None otherwise
   #[allow(dead_code)]
   pub async fn open_orders(&self) -> DreamrunnerResult<Vec<HistoricalOrder>> {
-    let req = AllOrders::request(self.ticker.clone(), Some(5000));
+    let req = AllOrders::request(self.ticker.clone(), Some(self.recv_window), self.client.timestamp());
     let orders = self
       .client
       .get_signed::<Vec<HistoricalOrder>>(API::Spot(Spot::AllOrders), Some(req)).await?;
@@ -369,7 +771,7 @@ impl<T, S: Strategy<T>> Engine<T, S> {
   /// Cancel all open orders for a single symbol
   pub async fn cancel_all_open_orders(&self) -> DreamrunnerResult<Vec<OrderCanceled>> {
     info!("🟡 Cancel all active orders");
-    let req = CancelOrders::request(self.ticker.clone(), Some(10000));
+    let req = CancelOrders::request(self.ticker.clone(), Some(self.recv_window), self.client.timestamp());
     let res = self
       .client
       .delete_signed::<Vec<OrderCanceled>>(API::Spot(Spot::OpenOrders), Some(req)).await;
@@ -389,7 +791,7 @@ impl<T, S: Strategy<T>> Engine<T, S> {
 
   pub async fn cancel_order(&self, order_id: u64) -> DreamrunnerResult<OrderCanceled> {
     debug!("Cancel order {}", order_id);
-    let req = CancelOrder::request(order_id, self.ticker.to_string(), Some(10000));
+    let req = CancelOrder::request(order_id, self.ticker.to_string(), Some(self.recv_window), self.client.timestamp());
     let res = self
       .client
       .delete_signed::<OrderCanceled>(API::Spot(Spot::Order), Some(req)).await;
@@ -406,27 +808,40 @@ impl<T, S: Strategy<T>> Engine<T, S> {
     res
   }
 
+  /// Track an order update in its slot of the active order, averaging entry fills into the position.
+  /// Updates for an order the slot no longer tracks, such as a cancelled partial exit replaced by its remainder, are ignored.
   pub fn update_active_order(&mut self, trade: TradeInfo) -> DreamrunnerResult<()> {
     let id = ActiveOrder::client_order_id_suffix(&trade.client_order_id);
-    match &*id {
-      "ENTRY" => {
-        self.active_order.entry = Some(OrderState::Active(trade))
+    let replaced = |order: &Option<OrderState>| matches!(order, Some(order) if order.client_order_id() != trade.client_order_id);
+    if id == "ENTRY" && trade.side == Side::Long && !replaced(&self.active_order.entry) {
+      self.active_order.record_entry_fill(&trade);
+    }
+    let slot = match &*id {
+      "ENTRY" => &mut self.active_order.entry,
+      "STOP_LOSS" => &mut self.active_order.stop_loss,
+      "TAKE_PROFIT" => &mut self.active_order.take_profit,
+      _ => {
+        debug!("Unknown order id: {}", id);
+        return Ok(());
       }
-      "STOP_LOSS" => {
-        self.active_order.stop_loss = Some(OrderState::Active(trade))
+    };
+    match slot {
+      Some(order) if order.client_order_id() != trade.client_order_id => {
+        debug!("Ignore update for replaced order: {}", trade.client_order_id)
       }
-      _ => debug!("Unknown order id: {}", id),
+      _ => *slot = Some(OrderState::Active(trade))
     }
     Ok(())
   }
 
-  fn trade_pnl(&self, entry: &TradeInfo, exit: &TradeInfo) -> DreamrunnerResult<f64> {
-    let factor = match entry.side {
-      Side::Long => 1.0,
-      Side::Short => -1.0
-    };
-    let pnl = (exit.price - entry.price) / entry.price * factor * 100.0;
-    Ok(trunc!(pnl, 2))
+  /// Unlevered % PnL of a spot exit's fill against the average entry price of the position, None without a position.
+  /// There are no futures endpoints yet, so leverage and funding are not accounted for.
+  fn trade_pnl(&self, exit: &TradeInfo) -> Option<f64> {
+    let avg_entry_price = self.active_order.avg_entry_price;
+    match self.active_order.position_qty > 0.0 && avg_entry_price > 0.0 {
+      true => Some(trunc!((exit.fill_price - avg_entry_price) / avg_entry_price * 100.0, 2)),
+      false => None
+    }
   }
 
   pub async fn check_active_order(&mut self) -> DreamrunnerResult<()> {
@@ -435,24 +850,34 @@ impl<T, S: Strategy<T>> Engine<T, S> {
       match entry {
         // entry order has been placed on binance and is new, partially filled, filled, or canceled
         OrderState::Active(entry) => {
-          if entry.status == OrderStatus::PartiallyFilled || entry.status == OrderStatus::New {
+          if entry.status == OrderStatus::PartiallyFilled && entry.side == Side::Short {
+            // exit signal order, don't cancel it and strand what's left of the position
+            self.handle_partial_exit(entry).await?;
+          } else if entry.status == OrderStatus::PartiallyFilled || entry.status == OrderStatus::New {
             // using updated entry, check if order hasn't filled within 10 minutes
-            self.reset_if_stale(entry, false).await?;
+            self.reset_if_stale(entry, "entry").await?;
           } else if entry.status == OrderStatus::Filled {
+            if let Some(placed_at) = self.order_placed_at.take() {
+              self.client.metrics().record_ms(ORDER_FILL_METRIC, (entry.event_time - placed_at) as f64);
+            }
             // entry/exit is filled, place stop loss
             if entry.side == Side::Long {
               info!("🟢 Entry order filled: {:#?}", entry);
+              // realize exits against the whole position, not just this entry
+              self.circuit_breaker.record_entry(TradeInfo { fill_price: self.active_order.avg_entry_price, ..entry.clone() });
               self.check_stop_loss().await?;
             } else {
               info!("🔴 Exit order filled: {:#?}", entry);
+              self.record_exit(entry).await?;
               self.reset_active_order().await?;
             }
             self.check_stop_loss().await?;
+            self.check_take_profit().await?;
           }
         }
         // entry order has not been placed on binance and pending in local state
         OrderState::Pending(order) => {
-          self.reset_if_stale(order, false).await?
+          self.reset_if_stale(order, "entry").await?
         }
       }
     }
@@ -461,7 +886,8 @@ impl<T, S: Strategy<T>> Engine<T, S> {
   
   /// If entry is filled and stop loss is pending, then place the stop loss order.
   /// If stop loss is active, check if it has filled. 
-  /// If stop loss is partially filled and stale then reset it. If filled then reset the active order.
+  /// If stop loss is partially filled see [`Engine::handle_partial_exit`]. If filled then reset the active order,
+  /// which cancels the take profit.
   async fn check_stop_loss(&mut self) -> DreamrunnerResult<()> {
     let copy = self.active_order.clone();
     match &copy.stop_loss {
@@ -471,18 +897,30 @@ impl<T, S: Strategy<T>> Engine<T, S> {
             if let Some(OrderState::Active(entry)) = &copy.entry {
               // place stop loss order if entry is filled
               if (entry.status == OrderStatus::PartiallyFilled || entry.status == OrderStatus::Filled) && !self.active_order.stop_loss_placed {
-                info!("🟣🟣 Place stop loss order");
-                self.trade_or_reset::<LimitOrderResponse>(stop_loss.clone()).await?;
+                match &copy.take_profit {
+                  // both legs sell the same base, so they go out as one order list rather than two orders
+                  Some(OrderState::Pending(take_profit)) if !self.active_order.take_profit_placed => {
+                    info!("🟣🟣 Place stop loss and take profit OCO order");
+                    let oco = OcoOrder::bracket(stop_loss, take_profit)?;
+                    self.trade_oco_or_reset::<OcoOrderResponse>(oco).await?;
+                    self.active_order.take_profit_placed = true;
+                  }
+                  _ => {
+                    info!("🟣🟣 Place stop loss order");
+                    self.trade_exit_or_reset::<LimitOrderResponse>(stop_loss.clone()).await?;
+                  }
+                }
                 self.active_order.stop_loss_placed = true;
               }
             }
           }
           OrderState::Active(stop_loss) => {
             if stop_loss.status == OrderStatus::PartiallyFilled {
-              self.reset_if_stale(stop_loss, true).await?;
+              self.handle_partial_exit(stop_loss).await?;
             } else if stop_loss.status == OrderStatus::Filled {
               // entry and stop loss have completed, reset everything for the next trade
               info!("🔴 Stop loss order filled: {:#?}", stop_loss);
+              self.record_exit(stop_loss).await?;
               self.reset_active_order().await?;
             }
           }
@@ -490,8 +928,8 @@ impl<T, S: Strategy<T>> Engine<T, S> {
       }
       None => {
         if let Some(OrderState::Active(entry)) = &self.active_order.entry {
-          // no stop loss, if entry is filled reset active order
-          if entry.status == OrderStatus::Filled {
+          // no stop loss or take profit, if entry is filled reset active order
+          if entry.status == OrderStatus::Filled && self.active_order.take_profit.is_none() {
             info!("🟣 Filled entry with no stop loss, reset active order");
             self.reset_active_order().await?;
           }
@@ -501,21 +939,107 @@ impl<T, S: Strategy<T>> Engine<T, S> {
     
     Ok(())
   }
+
+  /// Place the pending take profit once the entry fills, the other side of the stop loss bracket.
+  /// If the take profit fills, reset the active order, which cancels the stop loss.
+  async fn check_take_profit(&mut self) -> DreamrunnerResult<()> {
+    let copy = self.active_order.clone();
+    match &copy.take_profit {
+      Some(OrderState::Pending(take_profit)) => {
+        if let Some(OrderState::Active(entry)) = &copy.entry {
+          if (entry.status == OrderStatus::PartiallyFilled || entry.status == OrderStatus::Filled) && !self.active_order.take_profit_placed {
+            info!("🟣🟣 Place take profit order");
+            self.trade_exit_or_reset::<LimitOrderResponse>(take_profit.clone()).await?;
+            self.active_order.take_profit_placed = true;
+          }
+        }
+      }
+      Some(OrderState::Active(take_profit)) => {
+        if take_profit.status == OrderStatus::PartiallyFilled {
+          self.handle_partial_exit(take_profit).await?;
+        } else if take_profit.status == OrderStatus::Filled {
+          info!("🟢 Take profit order filled: {:#?}", take_profit);
+          self.record_exit(take_profit).await?;
+          self.reset_active_order().await?;
+        }
+      }
+      None => ()
+    }
+    Ok(())
+  }
   
-  async fn reset_if_stale<O: Timestamp>(&mut self, order: &O, is_stop_loss: bool) -> DreamrunnerResult<()> {
+  /// Realize the closed trade in the circuit breaker and cancel open orders if a risk limit trips
+  async fn record_exit(&mut self, exit: &TradeInfo) -> DreamrunnerResult<()> {
+    if let Some(pnl) = self.trade_pnl(exit) {
+      info!(
+        "Closed {} {} at {}, average entry {}: {}%",
+        self.active_order.position_qty, self.base_asset, exit.fill_price, self.active_order.avg_entry_price, pnl
+      );
+    }
+    self.active_order.close_position();
+    if self.circuit_breaker.record_exit(exit) {
+      error!(
+        "🛑🛑 Circuit breaker tripped, daily PnL: {}%, consecutive losses: {}, limits: {:?}",
+        trunc!(self.circuit_breaker.daily_pnl_pct, 2),
+        self.circuit_breaker.consecutive_losses,
+        self.circuit_breaker.limits
+      );
+      self.cancel_all_open_orders().await?;
+    }
+    Ok(())
+  }
+
+  /// A partially filled exit is left to rest, since cancelling it with the rest of the active order would strand
+  /// the unfilled base. If nothing more fills within 10 minutes, open orders are cancelled and the remaining quantity
+  /// is sold at market. The remainder takes the exit's slot in the active order, so its fill closes the trade.
+  async fn handle_partial_exit(&mut self, exit: &TradeInfo) -> DreamrunnerResult<()> {
+    let last_fill = Time::from_unix_ms(exit.event_time);
+    if last_fill.diff_minutes(&self.now())?.abs() <= 10 {
+      debug!("Exit {} filled {} of {}, let the rest fill", exit.client_order_id, exit.executed_qty, exit.orig_qty);
+      return Ok(());
+    }
+    self.cancel_all_open_orders().await?;
+    let remaining = self.filters.floor_qty(exit.remaining_qty());
+    if remaining <= 0.0 {
+      info!("🟡 Stale partial exit {} has no remaining quantity to sell", exit.client_order_id);
+      self.active_order.reset();
+      return Ok(());
+    }
+    let suffix = ActiveOrder::client_order_id_suffix(&exit.client_order_id);
+    let remainder = BinanceTrade::new(
+      self.ticker.to_string(),
+      format!("{}-{}", self.client.timestamp(), suffix),
+      exit.side,
+      OrderType::Market,
+      remaining,
+      None,
+      Some(self.recv_window),
+      self.client.timestamp() as i64,
+      None,
+      None,
+      self.stp_mode
+    );
+    warn!("🟡 Stale partial exit {}, {} {} remaining at market", exit.client_order_id, remaining, self.base_asset);
+    match &*suffix {
+      "STOP_LOSS" => self.active_order.add_stop_loss(remainder.clone()),
+      "TAKE_PROFIT" => self.active_order.add_take_profit(remainder.clone()),
+      _ => self.active_order.add_entry(remainder.clone())
+    }
+    self.trade_exit_or_reset::<LimitOrderResponse>(remainder).await?;
+    Ok(())
+  }
+
+  async fn reset_if_stale<O: Timestamp>(&mut self, order: &O, label: &str) -> DreamrunnerResult<()> {
     let placed_at = Time::from_unix_ms(order.timestamp());
-    let now = Time::now();
+    let now = self.now();
     if placed_at.diff_minutes(&now)?.abs() > 10 {
-      if is_stop_loss {
-        info!("🟡 Reset stale stop loss");
-      } else {
-        info!("🟡 Reset stale entry");
-      }
+      info!("🟡 Reset stale {}", label);
       self.reset_active_order().await?;
     }
     Ok(())
   }
 
+  /// Trade free quote and base to 50/50 in value, sized by the symbol filters
   pub async fn equalize_assets(&self) -> DreamrunnerResult<()> {
     if self.disable_trading {
       return Ok(());
@@ -523,78 +1047,62 @@ impl<T, S: Strategy<T>> Engine<T, S> {
     info!("Equalizing assets");
     let account_info = self.account_info().await?;
     let assets = account_info.account_assets(&self.quote_asset, &self.base_asset)?;
-    let price = self.price().await?;
-
-    // USDT
-    let quote_balance = assets.free_quote / price;
-    // BTC
-    let base_balance = assets.free_base;
-
-    let sum = quote_balance + base_balance;
-    let equal = trunc!(sum / 2_f64, 2);
-    let quote_diff = trunc!(quote_balance - equal, 2);
-    let base_diff = trunc!(base_balance - equal, 2);
-
-    // buy base asset
-    if quote_diff > 0_f64 && quote_diff > self.min_notional {
-      let timestamp = BinanceTrade::get_timestamp()?;
-      let client_order_id = format!("{}-{}", timestamp, "EQUALIZE_QUOTE");
-      let long_qty = trunc!(quote_diff, 2);
-      info!(
-          "Quote asset too high = {} {}, 50/50 = {} {}, buy base asset = {} {}",
-          quote_balance * price,
-          self.quote_asset,
-          equal * price,
-          self.quote_asset,
-          long_qty,
-          self.base_asset
-      );
-      let buy_base = BinanceTrade::new(
-        self.ticker.to_string(),
-        client_order_id,
-        Side::Long,
-        OrderType::Limit,
-        long_qty,
-        Some(price),
-        None,
-        Time::now().to_unix_ms(),
-        None,
-        None
-      );
-      if let Err(e) = self.trade::<LimitOrderResponse>(buy_base).await {
-        error!("🛑 Error equalizing quote asset with error: {:?}", e);
-        return Err(e);
+    let price = self.filters.floor_price(self.price().await?);
+    // the configured min notional is a floor on top of the exchange filter
+    let filters = SymbolFilters {
+      min_notional: self.filters.min_notional.max(self.min_notional),
+      ..self.filters
+    };
+    let (side, qty) = match assets.equalize(price, &filters) {
+      Some(trade) => trade,
+      None => {
+        info!("Assets within min notional of 50/50, skip equalize");
+        return Ok(());
       }
+    };
+    let suffix = match side {
+      Side::Long => "EQUALIZE_QUOTE",
+      Side::Short => "EQUALIZE_BASE"
+    };
+    info!(
+      "Equalize {} {}, {}: {}, {}: {}, {} {} @ {}",
+      self.quote_asset,
+      self.base_asset,
+      self.quote_asset,
+      assets.free_quote,
+      self.base_asset,
+      assets.free_base,
+      side.fmt_binance(),
+      qty,
+      price
+    );
+    let equalize = BinanceTrade::new(
+      self.ticker.to_string(),
+      format!("{}-{}", self.client.timestamp(), suffix),
+      side,
+      OrderType::Limit,
+      qty,
+      Some(price),
+      Some(self.recv_window),
+      self.client.timestamp() as i64,
+      None,
+      None,
+      self.stp_mode
+    );
+    if let Err(e) = self.trade::<LimitOrderResponse>(equalize).await {
+      error!("🛑 Error equalizing assets with error: {:?}", e);
+      return Err(e);
     }
+    Ok(())
+  }
 
-    // sell base asset
-    if base_diff > 0_f64 && base_diff > self.min_notional {
-      let timestamp = BinanceTrade::get_timestamp()?;
-      let client_order_id = format!("{}-{}", timestamp, "EQUALIZE_BASE");
-      let short_qty = trunc!(base_diff, 2);
-      info!(
-          "Base asset too high = {} {}, 50/50 = {} {}, sell base asset = {} {}",
-          base_balance, self.base_asset, equal, self.base_asset, short_qty, self.base_asset
-      );
-      let sell_base = BinanceTrade::new(
-        self.ticker.to_string(),
-        client_order_id,
-        Side::Short,
-        OrderType::Limit,
-        short_qty,
-        Some(price),
-        None,
-        Time::now().to_unix_ms(),
-        None,
-        None
-      );
-      if let Err(e) = self.trade::<LimitOrderResponse>(sell_base).await {
-        error!("🛑 Error equalizing base asset with error: {:?}", e);
-        return Err(e);
-      }
-    }
+  /// Current time by the client clock, so a mock client can replay recorded time
+  pub fn now(&self) -> Time {
+    Time::from_unix_ms(self.client.timestamp() as i64)
+  }
 
-    Ok(())
+  pub fn filters(&self) -> &SymbolFilters {
+    &self.filters
   }
 
   pub fn assets(&self) -> Assets {
@@ -625,6 +1133,26 @@ impl<T, S: Strategy<T>> Engine<T, S> {
     Ok(klines)
   }
 
+  /// Candles to load at startup, the larger of the cache capacity and [`Strategy::min_history`],
+  /// or [`Engine::warmup_candles`] if set and at least the strategy's min history.
+  /// Capped at 1000, the most klines Binance returns in one request.
+  pub fn history_limit(&self) -> DreamrunnerResult<u16> {
+    let candles = self.strategy.cache(None).ok_or(DreamrunnerError::CandleCacheMissing)?;
+    let min_history = self.strategy.min_history();
+    let history = match self.warmup_candles {
+      Some(warmup) if (warmup as usize) < min_history => {
+        warn!("🟡 {} warmup candles is less than the strategy's {} candle history, loading {}", warmup, min_history, min_history);
+        min_history
+      },
+      Some(warmup) => warmup as usize,
+      None => candles.capacity.max(min_history)
+    };
+    if history > 1000 {
+      warn!("🟡 Strategy needs {} candles of history, loading the max of 1000", history);
+    }
+    Ok(history.min(1000) as u16)
+  }
+
   /// Load recent candles into the strategy's candle cache
   pub async fn load_recent_candles(&mut self, limit: Option<u16>) -> DreamrunnerResult<()> {
     let klines = self.klines(limit, None, None).await?;
@@ -634,3 +1162,592 @@ impl<T, S: Strategy<T>> Engine<T, S> {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use playbook::Dreamrunner;
+  use time_series::SignalInfo;
+  use crate::replay::replay_client;
+
+  const NOW: i64 = 1_700_000_000_000;
+  const MINUTE: i64 = 60_000;
+
+  fn engine(client: MockClient) -> Engine<Candle, Dreamrunner, MockClient> {
+    let (_, rx) = crossbeam::channel::unbounded::<WebSocketEvent>();
+    client.set_time(NOW);
+    let mut engine = Engine::new(
+      client,
+      rx,
+      false,
+      "SOL".to_string(),
+      "USDT".to_string(),
+      "SOLUSDT".to_string(),
+      Interval::ThirtyMinutes,
+      5.0,
+      90.0,
+      DEFAULT_RECV_WINDOW,
+      Dreamrunner::solusdt_optimized(),
+      RiskLimits::default()
+    );
+    // SOLUSDT price filter, so limit prices snap to cents
+    engine.filters = SymbolFilters { tick_size: 0.01, price_decimals: 2, ..SymbolFilters::default() };
+    engine
+  }
+
+  /// Requests that change orders, without the account reads that cap exits to the base held
+  fn trades(client: &MockClient) -> Vec<MockRequest> {
+    client.requests().into_iter().filter(|r| r.method != MockMethod::Get).collect()
+  }
+
+  fn entry(status: OrderStatus, event_time: i64) -> OrderState {
+    OrderState::Active(TradeInfo {
+      client_order_id: format!("{}-ENTRY", event_time),
+      order_type: OrderType::Market,
+      status,
+      event_time,
+      quantity: 1.0,
+      price: 100.0,
+      fill_price: 100.0,
+      side: Side::Long,
+      orig_qty: 1.0,
+      executed_qty: 1.0
+    })
+  }
+
+  #[test]
+  fn test_trade_qty_uses_step_size() -> DreamrunnerResult<()> {
+    let mut engine = engine(MockClient::new());
+    engine.assets = Assets { free_quote: 1000.0, free_base: 2.82743338, ..Assets::default() };
+    // no filters loaded, 2 decimals: 1000 / 0.0027 * 0.9 = 333333.333
+    assert_eq!(engine.trade_qty(Side::Long, 0.0027)?, 333333.33);
+
+    let lot_size = |step_size: &str| SymbolFilters::new(&[Filters::LotSize {
+      min_qty: step_size.to_string(),
+      max_qty: "9000000.00000000".to_string(),
+      step_size: step_size.to_string()
+    }]);
+    engine.filters = lot_size("0.00000100")?;
+    assert_eq!(engine.trade_qty(Side::Short, 100.0)?, 2.544690);
+    engine.filters = lot_size("0.01000000")?;
+    assert_eq!(engine.trade_qty(Side::Short, 100.0)?, 2.54);
+    engine.filters = lot_size("1.00000000")?;
+    assert_eq!(engine.trade_qty(Side::Long, 0.0027)?, 333333.0);
+    Ok(())
+  }
+
+  #[test]
+  fn test_trade_qty_capped() -> DreamrunnerResult<()> {
+    let mut engine = engine(MockClient::new());
+    engine.assets = Assets { free_quote: 100_000.0, free_base: 10.0, ..Assets::default() };
+    engine.filters = SymbolFilters::new(&[
+      Filters::LotSize {
+        min_qty: "0.01000000".to_string(),
+        max_qty: "500.00000000".to_string(),
+        step_size: "0.01000000".to_string()
+      },
+      Filters::MaxPosition { max_position: "400.00000000".to_string() }
+    ])?;
+    // 100k / 100 * 0.9 = 900 SOL, capped at the 400 max position less the 10 held
+    assert_eq!(engine.trade_qty(Side::Long, 100.0)?, 390.0);
+    engine.max_notional = Some(25_000.0);
+    assert_eq!(engine.trade_qty(Side::Long, 100.0)?, 250.0);
+    // sells are capped by notional but not the position
+    assert_eq!(engine.trade_qty(Side::Short, 100.0)?, 9.0);
+    engine.max_notional = Some(500.0);
+    assert_eq!(engine.trade_qty(Side::Short, 100.0)?, 5.0);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_check_active_order_resets_stale_entry() -> DreamrunnerResult<()> {
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 1000.0, 0.0);
+    let mut engine = engine(client.clone());
+
+    engine.active_order.entry = Some(entry(OrderStatus::PartiallyFilled, NOW - 5 * MINUTE));
+    engine.check_active_order().await?;
+    assert!(engine.active_order.entry.is_some());
+    assert!(client.requests().is_empty());
+
+    engine.active_order.entry = Some(entry(OrderStatus::New, NOW - 11 * MINUTE));
+    engine.check_active_order().await?;
+    assert!(engine.active_order.entry.is_none());
+    let requests = client.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, MockMethod::Delete);
+    assert_eq!(requests[0].path, String::from(API::Spot(Spot::OpenOrders)));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_check_active_order_places_stop_loss_once() -> DreamrunnerResult<()> {
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 1000.0, 9.0);
+    let mut engine = engine(client.clone());
+    engine.assets = Assets { free_quote: 1000.0, ..Assets::default() };
+    let builder = engine.build_order(100.0, Time::from_unix_ms(NOW), Side::Long).await?;
+    let stop_loss = builder.stop_loss.ok_or(DreamrunnerError::Custom("Missing stop loss".to_string()))?;
+    engine.active_order.add_stop_loss(stop_loss);
+    engine.active_order.entry = Some(entry(OrderStatus::Filled, NOW));
+
+    engine.check_active_order().await?;
+    engine.check_active_order().await?;
+    assert!(engine.active_order.stop_loss_placed);
+    let orders = client.orders();
+    assert_eq!(orders.len(), 1);
+    assert_eq!(orders[0].param("type"), Some("STOP_LOSS".to_string()));
+    assert_eq!(orders[0].param("side"), Some("SELL".to_string()));
+    assert_eq!(orders[0].param("quantity"), Some("9".to_string()));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_take_profit_fill_closes_bracket() -> DreamrunnerResult<()> {
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 1000.0, 9.0);
+    let mut engine = engine(client.clone());
+    engine.assets = Assets { free_quote: 1000.0, ..Assets::default() };
+    engine.strategy.take_profit_pct = Some(2.0);
+    let builder = engine.build_order(100.0, Time::from_unix_ms(NOW), Side::Long).await?;
+    let missing = |order: &str| DreamrunnerError::Custom(format!("Missing {}", order));
+    engine.active_order.add_stop_loss(builder.stop_loss.ok_or(missing("stop loss"))?);
+    let take_profit = builder.take_profit.ok_or(missing("take profit"))?;
+    let take_profit_id = take_profit.client_order_id.clone();
+    engine.active_order.add_take_profit(take_profit);
+    engine.active_order.entry = Some(entry(OrderStatus::Filled, NOW));
+
+    engine.check_active_order().await?;
+    engine.check_active_order().await?;
+    // the stop loss and take profit lock the same base, so they are placed once as an OCO order list
+    assert!(client.orders().is_empty());
+    assert!(engine.active_order.stop_loss_placed && engine.active_order.take_profit_placed);
+    let oco: Vec<MockRequest> = client.requests().into_iter().filter(|r| r.path == String::from(API::Spot(Spot::Oco))).collect();
+    assert_eq!(oco.len(), 1);
+    assert_eq!(oco[0].param("side"), Some("SELL".to_string()));
+    assert_eq!(oco[0].param("price"), Some("102".to_string()));
+    assert_eq!(oco[0].param("stopPrice"), Some("99".to_string()));
+    assert_eq!(oco[0].param("quantity"), Some("9".to_string()));
+    assert_eq!(oco[0].param("limitClientOrderId"), Some(take_profit_id.clone()));
+
+    // take profit fills, the stop loss is cancelled with the rest of the open orders
+    client.clear_requests();
+    engine.update_active_order(TradeInfo {
+      client_order_id: take_profit_id,
+      order_type: OrderType::TakeProfitLimit,
+      status: OrderStatus::Filled,
+      event_time: NOW + MINUTE,
+      quantity: 1.0,
+      price: 102.0,
+      fill_price: 102.0,
+      side: Side::Short,
+      orig_qty: 1.0,
+      executed_qty: 1.0
+    })?;
+    engine.check_active_order().await?;
+    assert!(engine.active_order.entry.is_none());
+    assert!(engine.active_order.stop_loss.is_none());
+    assert!(engine.active_order.take_profit.is_none());
+    assert!(client.orders().is_empty());
+    assert_eq!(client.requests().last().map(|r| r.method), Some(MockMethod::Delete));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_partial_stop_loss_sells_remainder() -> DreamrunnerResult<()> {
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 1000.0, 0.6);
+    let mut engine = engine(client.clone());
+    engine.active_order.entry = Some(entry(OrderStatus::Filled, NOW));
+    engine.active_order.stop_loss_placed = true;
+    let stop_loss = |status: OrderStatus, client_order_id: &str, event_time: i64, executed_qty: f64| TradeInfo {
+      client_order_id: client_order_id.to_string(),
+      order_type: OrderType::StopLoss,
+      status,
+      event_time,
+      quantity: 1.0,
+      price: 99.0,
+      fill_price: 99.0,
+      side: Side::Short,
+      orig_qty: 1.0,
+      executed_qty
+    };
+    let stop_loss_id = format!("{}-STOP_LOSS", NOW);
+    engine.update_active_order(stop_loss(OrderStatus::PartiallyFilled, &stop_loss_id, NOW - 5 * MINUTE, 0.4))?;
+
+    // recent partial fill rests
+    engine.check_active_order().await?;
+    assert!(client.requests().is_empty());
+    assert!(engine.active_order.entry.is_some());
+
+    // stale partial fill cancels the rest and sells the remainder at market
+    client.set_time(NOW + 6 * MINUTE);
+    engine.check_active_order().await?;
+    let requests = client.requests();
+    assert_eq!(requests[0].method, MockMethod::Delete);
+    let orders = client.orders();
+    assert_eq!(orders.len(), 1);
+    let remainder_id = format!("{}-STOP_LOSS", NOW + 6 * MINUTE);
+    assert_eq!(orders[0].param("newClientOrderId"), Some(remainder_id.clone()));
+    assert_eq!(orders[0].param("side"), Some("SELL".to_string()));
+    assert_eq!(orders[0].param("type"), Some("MARKET".to_string()));
+    assert_eq!(orders[0].param("quantity"), Some("0.6".to_string()));
+    assert!(engine.active_order.entry.is_some());
+
+    // the cancelled partial exit is no longer tracked, the remainder fill closes the trade
+    engine.update_active_order(stop_loss(OrderStatus::Canceled, &stop_loss_id, NOW + 6 * MINUTE, 0.4))?;
+    assert_eq!(engine.active_order.stop_loss.as_ref().map(|o| o.client_order_id()), Some(remainder_id.clone()));
+    engine.update_active_order(stop_loss(OrderStatus::Filled, &remainder_id, NOW + 6 * MINUTE, 1.0))?;
+    engine.check_active_order().await?;
+    assert!(engine.active_order.entry.is_none());
+    assert!(engine.active_order.stop_loss.is_none());
+    assert_eq!(client.orders().len(), 1);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_market_remainder_realized_at_fill_price() -> DreamrunnerResult<()> {
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 1000.0, 1.0);
+    let mut engine = engine(client.clone());
+    engine.circuit_breaker = CircuitBreaker::new(RiskLimits { max_daily_loss_pct: Some(1.0), max_consecutive_losses: Some(1) });
+    // MARKET orders report an order price of 0, only the fill price says where they traded
+    let market = |client_order_id: String, side: Side, fill_price: f64| TradeInfo {
+      client_order_id,
+      order_type: OrderType::Market,
+      status: OrderStatus::Filled,
+      event_time: NOW,
+      quantity: 1.0,
+      price: 0.0,
+      fill_price,
+      side,
+      orig_qty: 1.0,
+      executed_qty: 1.0
+    };
+    engine.update_active_order(market(format!("{}-ENTRY", NOW), Side::Long, 100.0))?;
+    engine.active_order.stop_loss_placed = true;
+    // the remainder of a stale partial stop loss, sold at market
+    engine.update_active_order(market(format!("{}-STOP_LOSS", NOW + 11 * MINUTE), Side::Short, 101.0))?;
+    engine.check_active_order().await?;
+
+    assert!(engine.active_order.entry.is_none());
+    assert!(!engine.circuit_breaker.is_tripped());
+    assert_eq!(engine.circuit_breaker.daily_pnl_pct, 1.0);
+    assert_eq!(engine.circuit_breaker.consecutive_losses, 0);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_post_only_retry_reprices() -> DreamrunnerResult<()> {
+    let would_take = || BinanceContentError { code: -2010, msg: "Order would immediately match and take.".to_string() };
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 1000.0, 0.0)
+      .with_errors(MockMethod::Post, API::Spot(Spot::Order), vec![would_take(), would_take()]);
+    let mut engine = engine(client.clone());
+    engine.strategy.stop_loss_pct = None;
+    engine.assets = Assets { free_quote: 1000.0, ..Assets::default() };
+    engine.filters = SymbolFilters::new(&[Filters::PriceFilter {
+      min_price: "0.01000000".to_string(),
+      max_price: "10000.00000000".to_string(),
+      tick_size: "0.01000000".to_string()
+    }])?;
+    engine.limit_offset = Some(LimitOffset::AtSignal);
+    engine.maker_only = true;
+    engine.post_only_retry = Some(PostOnlyRetry { max_attempts: 3, tick_back: 2 });
+    let signal = |date: i64| Signal::EnterLong(SignalInfo { price: 100.0, date: Time::from_unix_ms(date), ticker: "SOLUSDT".to_string(), exit_fraction: None, order_type: None, time_in_force: None });
+
+    engine.handle_signal(signal(NOW)).await?;
+    let prices: Vec<Option<String>> = client.orders().iter().map(|o| o.param("price")).collect();
+    assert_eq!(prices, vec![Some("100".to_string()), Some("99.98".to_string()), Some("99.96".to_string())]);
+    assert!(client.orders().iter().all(|o| o.param("type") == Some("LIMIT_MAKER".to_string())));
+    assert!(engine.active_order.entry.is_some());
+
+    // out of retries, the active order is reset as before
+    client.clear_requests();
+    let client = client.with_errors(MockMethod::Post, API::Spot(Spot::Order), vec![would_take(), would_take()]);
+    engine.active_order.reset();
+    engine.last_entry = None;
+    engine.post_only_retry = Some(PostOnlyRetry { max_attempts: 1, tick_back: 1 });
+    assert!(engine.handle_signal(signal(NOW + 3_600_000)).await.is_err());
+    assert_eq!(client.orders().len(), 2);
+    assert!(engine.active_order.entry.is_none());
+    Ok(())
+  }
+
+  #[test]
+  fn test_history_limit() -> DreamrunnerResult<()> {
+    let mut engine = engine(MockClient::new());
+    assert_eq!(engine.history_limit()?, engine.strategy.warmup() as u16);
+    engine.strategy = engine.strategy.with_capacity(50);
+    assert_eq!(engine.history_limit()?, 50);
+    engine.strategy = engine.strategy.with_capacity(5000);
+    assert_eq!(engine.history_limit()?, 1000);
+
+    engine.strategy = Dreamrunner::solusdt_optimized();
+    engine.warmup_candles = Some(200);
+    assert_eq!(engine.history_limit()?, 200);
+    // never less than the strategy needs
+    engine.warmup_candles = Some(1);
+    assert_eq!(engine.history_limit()?, engine.strategy.min_history() as u16);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_build_order_atr_stop_loss() -> DreamrunnerResult<()> {
+    let mut engine = engine(MockClient::new());
+    engine.strategy = engine.strategy.with_atr_stop(2, 1.5);
+    engine.strategy.push_candle(Candle { date: Time::from_unix(0), open: 100.0, high: 101.0, low: 99.0, close: 100.0, volume: None }, None);
+    engine.strategy.push_candle(Candle { date: Time::from_unix(1800), open: 100.0, high: 102.0, low: 99.0, close: 101.0, volume: None }, None);
+    // not enough candles for the ATR, falls back to the fixed 1% stop
+    let stop_loss = engine.build_order(100.0, Time::from_unix_ms(NOW), Side::Long).await?.stop_loss;
+    assert_eq!(stop_loss.and_then(|s| s.stop_price), Some(99.0));
+
+    engine.strategy.push_candle(Candle { date: Time::from_unix(3600), open: 101.0, high: 102.0, low: 101.0, close: 101.0, volume: None }, None);
+    // true ranges 3 and 1, ATR 2, stop 1.5 ATR below entry
+    let stop_loss = engine.build_order(100.0, Time::from_unix_ms(NOW), Side::Long).await?.stop_loss;
+    assert_eq!(stop_loss.and_then(|s| s.stop_price), Some(97.0));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_atr_stop_without_fallback_refuses_entry() -> DreamrunnerResult<()> {
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 1000.0, 0.0);
+    let mut engine = engine(client.clone());
+    engine.strategy = engine.strategy.with_atr_stop(2, 1.5);
+    engine.strategy.stop_loss_pct = None;
+    let info = SignalInfo { price: 100.0, date: Time::from_unix_ms(NOW), ticker: "SOLUSDT".to_string(), exit_fraction: None, order_type: None, time_in_force: None };
+
+    // empty cache and no fixed stop to fall back to, so the entry would go out unprotected
+    engine.handle_signal(Signal::EnterLong(info)).await?;
+    assert!(client.orders().is_empty());
+    assert!(engine.active_order.entry.is_none());
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_close_confirmation_uses_settled_close() -> DreamrunnerResult<()> {
+    let open_time = NOW / 1_800_000 * 1_800_000;
+    let client = MockClient::new().with_response(MockMethod::Get, API::Spot(Spot::Klines), serde_json::json!([
+      [open_time, "100", "102", "99", "101.5", "10", open_time + 1_799_999, "1000", 5, "5", "500", "0"]
+    ]));
+    let mut engine = engine(client.clone());
+    let event = WebSocketEvent::parse(&serde_json::json!({
+      "stream": "solusdt@kline_30m",
+      "data": {
+        "e": "kline", "E": open_time + 1_800_000, "s": "SOLUSDT",
+        "k": {
+          "t": open_time, "T": open_time + 1_799_999, "s": "SOLUSDT", "i": "30m", "f": 0, "L": 0,
+          "o": "100", "c": "101", "h": "102", "l": "99", "v": "9", "n": 1, "x": true, "q": "1", "V": "1", "Q": "1"
+        }
+      }
+    }).to_string())?.ok_or(DreamrunnerError::Custom("Not a kline event".to_string()))?;
+
+    // no delay acts on the websocket close without a REST request
+    engine.handle_event(event.clone()).await?;
+    assert_eq!(engine.strategy.cache(None).and_then(|c| c.vec.front().map(|c| c.close)), Some(101.0));
+    assert!(client.requests().is_empty());
+
+    engine.close_confirmation = Duration::from_millis(1);
+    engine.handle_event(event).await?;
+    assert_eq!(engine.strategy.cache(None).and_then(|c| c.vec.front().map(|c| c.close)), Some(101.5));
+    assert_eq!(client.requests().len(), 1);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_exit_signal_cancels_stop_loss_first() -> DreamrunnerResult<()> {
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 0.0, 10.0);
+    let mut engine = engine(client.clone());
+    engine.assets = Assets { free_base: 10.0, ..Assets::default() };
+    engine.active_order.entry = Some(entry(OrderStatus::Filled, NOW - 30 * MINUTE));
+    engine.active_order.stop_loss = Some(OrderState::Active(TradeInfo {
+      client_order_id: format!("{}-STOP_LOSS", NOW - 30 * MINUTE),
+      order_type: OrderType::StopLoss,
+      status: OrderStatus::New,
+      event_time: NOW - 30 * MINUTE,
+      quantity: 0.0,
+      price: 99.0,
+      fill_price: 0.0,
+      side: Side::Short,
+      orig_qty: 10.0,
+      executed_qty: 0.0
+    }));
+    engine.active_order.stop_loss_placed = true;
+    let exit = Signal::ExitLong(SignalInfo { price: 100.0, date: Time::from_unix_ms(NOW), ticker: "SOLUSDT".to_string(), exit_fraction: None, order_type: None, time_in_force: None });
+
+    engine.handle_signal(exit.clone()).await?;
+    let requests = trades(&client);
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method, MockMethod::Delete);
+    assert_eq!(requests[0].path, String::from(API::Spot(Spot::OpenOrders)));
+    assert_eq!(requests[1].method, MockMethod::Post);
+    assert_eq!(requests[1].param("side"), Some("SELL".to_string()));
+    assert!(engine.active_order.stop_loss.is_none());
+    assert!(!engine.active_order.stop_loss_placed);
+    assert!(matches!(&engine.active_order.entry, Some(OrderState::Pending(order)) if order.side == Side::Short));
+
+    // nothing resting, the exit is sent alone
+    client.clear_requests();
+    engine.active_order.reset();
+    engine.handle_signal(exit.clone()).await?;
+    assert_eq!(trades(&client).len(), 1);
+    assert_eq!(trades(&client)[0].method, MockMethod::Post);
+
+    // disabled, the stop loss is left resting
+    client.clear_requests();
+    engine.cancel_stop_on_exit = false;
+    engine.active_order.stop_loss_placed = true;
+    engine.active_order.stop_loss = Some(OrderState::Pending(engine.build_order(100.0, Time::from_unix_ms(NOW), Side::Long).await?.entry));
+    engine.handle_signal(exit).await?;
+    assert_eq!(trades(&client).len(), 1);
+    assert!(engine.active_order.stop_loss_placed);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_dead_man_switch_flattens_once() -> DreamrunnerResult<()> {
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 1000.0, 1.0);
+    let mut engine = engine(client.clone());
+    engine.active_order.entry = Some(entry(OrderStatus::Filled, NOW - 30 * MINUTE));
+    engine.active_order.position_qty = 1.0;
+    engine.active_order.avg_entry_price = 100.0;
+
+    // disabled does nothing
+    engine.trip_dead_man().await?;
+    assert!(client.requests().is_empty());
+
+    engine.dead_man = Some(DeadManSwitch::new(Duration::from_secs(60), true));
+    engine.trip_dead_man().await?;
+    assert!(engine.active_order.entry.is_none());
+    let requests = trades(&client);
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method, MockMethod::Delete);
+    let orders = client.orders();
+    assert_eq!(orders[0].param("side"), Some("SELL".to_string()));
+    assert_eq!(orders[0].param("type"), Some("MARKET".to_string()));
+    assert_eq!(orders[0].param("quantity"), Some("1".to_string()));
+
+    // tripped until the next event
+    client.clear_requests();
+    engine.trip_dead_man().await?;
+    assert!(client.requests().is_empty());
+    if let Some(dead_man) = &mut engine.dead_man {
+      dead_man.heartbeat();
+    }
+    engine.trip_dead_man().await?;
+    assert_eq!(trades(&client).len(), 1);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_signal_order_type_override() -> DreamrunnerResult<()> {
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 0.0, 10.0);
+    let mut engine = engine(client.clone());
+    engine.assets = Assets { free_base: 10.0, ..Assets::default() };
+    let info = SignalInfo { price: 100.0, date: Time::from_unix_ms(NOW), ticker: "SOLUSDT".to_string(), exit_fraction: None, order_type: None, time_in_force: None };
+
+    // market by default, a limit at the signal price if the signal asks for one
+    engine.handle_signal(Signal::ExitLong(info.clone().with_order_type(SignalOrderType::Limit).with_time_in_force(TimeInForce::Ioc))).await?;
+    let orders = client.orders();
+    assert_eq!(orders[0].param("type"), Some("LIMIT".to_string()));
+    assert_eq!(orders[0].param("price"), Some("100".to_string()));
+    assert_eq!(orders[0].param("timeInForce"), Some("IOC".to_string()));
+
+    // market overrides the configured limit offset
+    client.clear_requests();
+    engine.active_order.reset();
+    engine.limit_offset = Some(LimitOffset::BpsThrough(10.0));
+    engine.handle_signal(Signal::ExitLong(info.clone().with_order_type(SignalOrderType::Market))).await?;
+    let orders = client.orders();
+    assert_eq!(orders[0].param("type"), Some("MARKET".to_string()));
+    assert_eq!(orders[0].param("price"), None);
+
+    // unset falls back to the configured GTC limit
+    client.clear_requests();
+    engine.active_order.reset();
+    engine.handle_signal(Signal::ExitLong(info)).await?;
+    let orders = client.orders();
+    assert_eq!(orders[0].param("price"), Some("99.9".to_string()));
+    assert_eq!(orders[0].param("timeInForce"), Some("GTC".to_string()));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_exit_capped_to_held() -> DreamrunnerResult<()> {
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 0.0, 10.0);
+    let mut engine = engine(client.clone());
+    engine.assets = Assets { free_base: 10.0, ..Assets::default() };
+    let info = SignalInfo { price: 100.0, date: Time::from_unix_ms(NOW), ticker: "SOLUSDT".to_string(), exit_fraction: None, order_type: None, time_in_force: None };
+
+    // an exit larger than the base held sells only what is held, read from the account once
+    engine.active_order.position_qty = 50.0;
+    engine.handle_signal(Signal::ExitLong(info.clone())).await?;
+    assert_eq!(client.orders()[0].param("quantity"), Some("10".to_string()));
+    assert_eq!(client.orders()[0].param("reduceOnly"), None);
+    assert_eq!(client.requests().iter().filter(|r| r.method == MockMethod::Get).count(), 1);
+
+    // with trading disabled the exit is only tracked, the account isn't read
+    client.clear_requests();
+    engine.active_order.reset();
+    engine.disable_trading = true;
+    engine.handle_signal(Signal::ExitLong(info)).await?;
+    assert!(client.requests().is_empty());
+    assert!(matches!(&engine.active_order.entry, Some(OrderState::Pending(_))));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_exit_without_base_places_nothing() -> DreamrunnerResult<()> {
+    // the cached assets still show base the exchange no longer holds
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 1000.0, 0.0);
+    let mut engine = engine(client.clone());
+    engine.assets = Assets { free_quote: 1000.0, free_base: 10.0, ..Assets::default() };
+    let info = SignalInfo { price: 100.0, date: Time::from_unix_ms(NOW), ticker: "SOLUSDT".to_string(), exit_fraction: None, order_type: None, time_in_force: None };
+
+    engine.handle_signal(Signal::ExitLong(info)).await?;
+    assert!(client.orders().is_empty());
+    assert!(engine.active_order.entry.is_none());
+
+    // a stop loss for base that isn't held is refused rather than sent for zero
+    let builder = engine.build_order(100.0, Time::from_unix_ms(NOW), Side::Long).await?;
+    let stop_loss = builder.stop_loss.ok_or(DreamrunnerError::Custom("Missing stop loss".to_string()))?;
+    assert!(engine.trade_exit_or_reset::<LimitOrderResponse>(stop_loss).await.is_err());
+    assert!(client.orders().is_empty());
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_average_entry_across_fills() -> DreamrunnerResult<()> {
+    let client = replay_client("SOLUSDT", "USDT", "SOL", 0.0, 10.0);
+    let mut engine = engine(client.clone());
+    engine.assets = Assets { free_base: 10.0, ..Assets::default() };
+    let fill = |client_order_id: &str, price: f64, executed_qty: f64, side: Side| TradeInfo {
+      client_order_id: client_order_id.to_string(),
+      order_type: OrderType::Limit,
+      status: OrderStatus::PartiallyFilled,
+      event_time: NOW,
+      quantity: executed_qty,
+      price,
+      fill_price: price,
+      side,
+      orig_qty: 2.0,
+      executed_qty
+    };
+
+    // two fills of one entry, then a pyramided entry after the orders reset
+    engine.update_active_order(fill("1-ENTRY", 100.0, 1.0, Side::Long))?;
+    engine.update_active_order(fill("1-ENTRY", 100.0, 2.0, Side::Long))?;
+    engine.active_order.reset();
+    engine.update_active_order(fill("2-ENTRY", 130.0, 1.0, Side::Long))?;
+    assert_eq!(engine.active_order.position_qty, 3.0);
+    assert_eq!(engine.active_order.avg_entry_price, 110.0);
+    assert_eq!(engine.trade_pnl(&fill("3-ENTRY", 121.0, 3.0, Side::Short)), Some(10.0));
+
+    // the exit sells the position, not a share of the free base, and its fills don't move the average
+    let info = SignalInfo { price: 121.0, date: Time::from_unix_ms(NOW), ticker: "SOLUSDT".to_string(), exit_fraction: None, order_type: None, time_in_force: None };
+    engine.handle_signal(Signal::ExitLong(info)).await?;
+    assert_eq!(client.orders()[0].param("quantity"), Some("3".to_string()));
+    let exit_id = engine.active_order.entry.as_ref().unwrap().client_order_id();
+    engine.update_active_order(fill(&exit_id, 121.0, 3.0, Side::Short))?;
+    assert_eq!(engine.active_order.position_qty, 3.0);
+
+    engine.record_exit(&fill(&exit_id, 121.0, 3.0, Side::Short)).await?;
+    assert_eq!(engine.active_order.position_qty, 0.0);
+    assert_eq!(engine.trade_pnl(&fill(&exit_id, 121.0, 3.0, Side::Short)), None);
+    Ok(())
+  }
+}
@@ -1,5 +1,9 @@
+mod cli;
 mod engine;
+mod replay;
+mod risk;
 mod utils;
+use cli::*;
 use engine::*;
 use utils::*;
 
@@ -8,21 +12,37 @@ use dotenv::dotenv;
 use log::*;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use playbook::Dreamrunner;
 
 
 // Binance spot TEST network
 pub const BINANCE_TEST_API: &str = "https://testnet.binance.vision";
 // Binance spot LIVE network
 pub const BINANCE_LIVE_API: &str = "https://api.binance.us";
-pub const KLINE_STREAM: &str = "solusdt@kline_30m";
 pub const INTERVAL: Interval = Interval::ThirtyMinutes;
-pub const BASE_ASSET: &str = "SOL";
-pub const QUOTE_ASSET: &str = "USDT";
 pub const TICKER: &str = "SOLUSDT";
+pub const EQUITY_PCT: f64 = 90.0;
+// $5 USD is the minimum SOL that can be traded
+pub const MIN_NOTIONAL: f64 = 5.0;
 
 #[tokio::main]
 async fn main() -> DreamrunnerResult<()> {
+  let command = match Command::parse(std::env::args().skip(1)) {
+    Ok(command) => command,
+    Err(e) => {
+      eprintln!("{}", e);
+      std::process::exit(2);
+    }
+  };
+  match command {
+    Command::Run => run().await,
+    Command::Backtest(args) => Ok(backtest(&args)?),
+    Command::Optimize(args, grid, heatmap, save_config) => Ok(optimize(&args, &grid, heatmap.as_deref(), save_config.as_deref())?),
+    Command::Replay(args) => replay(&args).await
+  }
+}
+
+/// Run the live engine until ctrl-c
+async fn run() -> DreamrunnerResult<()> {
   dotenv().ok();
   init_logger()?;
 
@@ -31,11 +51,9 @@ async fn main() -> DreamrunnerResult<()> {
   let binance_live_api_key = std::env::var("BINANCE_LIVE_API_KEY")?;
   let binance_live_api_secret = std::env::var("BINANCE_LIVE_API_SECRET")?;
 
-  let equity_pct = 90.0;
-  let min_notional = 5.0; // $5 USD is the minimum SOL that can be traded
-
   let testnet = is_testnet()?;
   let disable_trading = disable_trading()?;
+  let recv_window = recv_window()?;
 
   let client = match is_testnet()? {
     true => Client::new(
@@ -50,24 +68,38 @@ async fn main() -> DreamrunnerResult<()> {
     )?
   };
 
+  // base and quote assets are read from the exchange so only the ticker needs configuring
+  let exchange_info = client
+    .get::<ExchangeInformation>(API::Spot(Spot::ExchangeInfo), Some(ExchangeInfo::request(TICKER.to_string())))
+    .await?;
+  let (base_asset, quote_asset) = Symbol::split_ticker(TICKER, &exchange_info)?;
+  info!("{} trades {} against {}", TICKER, base_asset, quote_asset);
+
   let (tx, rx) = crossbeam::channel::unbounded::<WebSocketEvent>();
 
   let mut engine = Engine::new(
     client.clone(),
     rx,
     disable_trading,
-    BASE_ASSET.to_string(),
-    QUOTE_ASSET.to_string(),
+    base_asset,
+    quote_asset,
     TICKER.to_string(),
     INTERVAL,
-    min_notional,
-    equity_pct,
-    Dreamrunner::solusdt_optimized()
+    MIN_NOTIONAL,
+    EQUITY_PCT,
+    recv_window,
+    live_strategy()?,
+    risk_limits()?
   );
+  engine.close_confirmation = close_confirmation()?;
+  engine.warmup_candles = warmup_candles()?;
+  engine.max_notional = max_notional()?;
+  engine.dead_man = dead_man_switch()?;
 
   let running = Arc::new(AtomicBool::new(true));
 
   let ws_running = running.clone();
+  let mut backoff = reconnect_backoff()?;
   tokio::task::spawn(async move {
     let callback: Callback = Box::new(move |event: WebSocketEvent| {
       match event {
@@ -84,25 +116,44 @@ async fn main() -> DreamrunnerResult<()> {
       }
     });
     let mut ws = WebSockets::new(testnet, client, callback);
+    if let Some(path) = record_path() {
+      ws = ws.with_record_path(path);
+    }
 
     while ws_running.load(Ordering::Relaxed) {
       // reconnect user stream and update listen key
       ws.connect_user_stream().await?;
 
       // reconnect Binance websocket
-      let subs = vec![KLINE_STREAM.to_string(), ws.listen_key.clone()];
-      match ws.connect_multiple_streams(&subs, testnet).await {
+      let subs = vec![kline_stream(TICKER, INTERVAL), ws.listen_key.clone()];
+      let healthy = match ws.connect_multiple_streams(&subs, testnet).await {
         Err(e) => {
           error!("🛑 Failed to connect websocket: {}", e);
+          false
         }
+        // a failed user stream leaves `is_connected` false, so the event loop would return at once
+        Ok(_) if !ws.is_connected.load(Ordering::Relaxed) => false,
         Ok(_) => {
+          backoff.reset();
           // if user stream is disconnected it will set `is_connected` to false which will break the event loop.
           // then this outer while loop will literate and reconnect the user stream and websocket
           match ws.event_loop().await {
-            Err(e) => error!("🛑 Websocket error: {:#?}", e),
-            Ok(_) => warn!("🟡 Websocket needs to reconnect")
+            Err(e) => {
+              error!("🛑 Websocket error: {:#?}", e);
+              false
+            },
+            Ok(_) => {
+              warn!("🟡 Websocket needs to reconnect");
+              true
+            }
           }
         }
+      };
+      if !healthy {
+        if let Err(e) = backoff.wait().await {
+          error!("🛑 Stop reconnecting websocket: {}", e);
+          break;
+        }
       }
     }
     warn!("🟡 Shutting down websocket stream");
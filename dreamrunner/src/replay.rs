@@ -0,0 +1,224 @@
+use std::path::Path;
+use lib::*;
+use playbook::Strategy;
+use time_series::Candle;
+use crate::engine::Engine;
+
+/// Websocket text frames one per line, either raw or a [`RecordedFrame`] as written by [`WebSockets::record_path`].
+/// Blank lines and frames that aren't events are skipped.
+pub fn load_events(path: &Path) -> DreamrunnerResult<Vec<WebSocketEvent>> {
+  let lines = std::fs::read_to_string(path)?;
+  let mut events = vec![];
+  for line in lines.lines().filter(|l| !l.trim().is_empty()) {
+    let frame = match serde_json::from_str::<RecordedFrame>(line) {
+      Ok(recorded) => recorded.frame,
+      Err(_) => line.to_string()
+    };
+    if let Some(event) = WebSocketEvent::parse(&frame)? {
+      events.push(event);
+    }
+  }
+  Ok(events)
+}
+
+/// Mock client that answers every request the engine makes while trading.
+/// The account holds `quote` and `base` throughout, and orders are acknowledged but only fill by recorded events.
+pub fn replay_client(ticker: &str, quote_asset: &str, base_asset: &str, quote: f64, base: f64) -> MockClient {
+  MockClient::new()
+    .with_balances(&[(quote_asset, quote), (base_asset, base)])
+    .with_response(MockMethod::Post, API::Spot(Spot::Order), serde_json::json!({
+      "symbol": ticker,
+      "orderId": 0,
+      "orderListId": -1,
+      "clientOrderId": "",
+      "transactTime": 0
+    }))
+    .with_response(MockMethod::Post, API::Spot(Spot::Oco), serde_json::json!({
+      "symbol": ticker,
+      "orderListId": 0,
+      "listClientOrderId": "",
+      "transactionTime": 0
+    }))
+    .with_response(MockMethod::Delete, API::Spot(Spot::OpenOrders), serde_json::json!([]))
+}
+
+impl<T: Clone + Into<Candle>, S: Strategy<T>> Engine<T, S, MockClient> {
+  /// Feed recorded websocket events through the engine in order, as [`Engine::ignition`] does from the live stream.
+  /// The mock clock is moved to each event time first, so order timestamps and stale order checks follow recorded time.
+  /// Placed orders are recorded by the client, see [`MockClient::orders`].
+  pub async fn replay(&mut self, events: Vec<WebSocketEvent>) -> DreamrunnerResult<()> {
+    for event in events {
+      self.client.set_time(event.event_time() as i64);
+      self.handle_event(event).await?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use lib::trade::OrderState;
+  use time_series::{DataCache, Signal, SignalInfo};
+  use crate::risk::RiskLimits;
+
+  const TICKER: &str = "SOLUSDT";
+  const START: i64 = 1_700_000_000_000 / 1_800_000 * 1_800_000;
+  const BAR: i64 = 1_800_000;
+
+  /// Enters long on the nth candle
+  #[derive(Clone)]
+  struct EnterOnCandle {
+    n: usize,
+    candles: DataCache<Candle>
+  }
+
+  impl Strategy<Candle> for EnterOnCandle {
+    fn process_candle(&mut self, candle: Candle, _ticker: Option<String>) -> anyhow::Result<Vec<Signal>> {
+      self.candles.push(candle);
+      Ok(match self.candles.vec.len() == self.n {
+        true => vec![Signal::EnterLong(SignalInfo { price: candle.close, date: candle.date, ticker: TICKER.to_string(), exit_fraction: None, order_type: None, time_in_force: None })],
+        false => vec![]
+      })
+    }
+
+    fn push_candle(&mut self, candle: Candle, _ticker: Option<String>) {
+      self.candles.push(candle);
+    }
+
+    fn cache(&self, _ticker: Option<String>) -> Option<&DataCache<Candle>> {
+      Some(&self.candles)
+    }
+
+    fn stop_loss_pct(&self) -> Option<f64> {
+      Some(1.0)
+    }
+  }
+
+  fn engine(client: MockClient) -> Engine<Candle, EnterOnCandle, MockClient> {
+    let (_, rx) = crossbeam::channel::unbounded::<WebSocketEvent>();
+    Engine::new(
+      client,
+      rx,
+      false,
+      "SOL".to_string(),
+      "USDT".to_string(),
+      TICKER.to_string(),
+      Interval::ThirtyMinutes,
+      5.0,
+      90.0,
+      DEFAULT_RECV_WINDOW,
+      EnterOnCandle { n: 3, candles: DataCache::new(10, TICKER.to_string()) },
+      RiskLimits { max_daily_loss_pct: None, max_consecutive_losses: None }
+    )
+  }
+
+  /// Combined stream kline frame for the bar opening at `open_time`, sent at `event_time`
+  fn kline(open_time: i64, event_time: i64, close: f64, is_final: bool) -> String {
+    serde_json::json!({
+      "stream": "solusdt@kline_30m",
+      "data": {
+        "e": "kline", "E": event_time, "s": TICKER,
+        "k": {
+          "t": open_time, "T": open_time + BAR - 1, "s": TICKER, "i": "30m", "f": 0, "L": 0,
+          "o": close.to_string(), "c": close.to_string(), "h": close.to_string(), "l": close.to_string(),
+          "v": "1", "n": 1, "x": is_final, "q": "1", "V": "1", "Q": "1"
+        }
+      }
+    }).to_string()
+  }
+
+  fn execution(event_time: i64, client_order_id: &str, side: &str, order_type: &str, status: &str, price: f64) -> String {
+    serde_json::json!({
+      "e": "executionReport", "E": event_time, "s": TICKER, "c": client_order_id, "S": side, "o": order_type,
+      "f": "GTC", "q": "9", "p": price.to_string(), "x": "TRADE", "X": status, "r": "NONE", "i": 1,
+      "l": "9", "z": "9", "L": price.to_string(), "n": "0", "T": event_time, "t": 1, "m": false
+    }).to_string()
+  }
+
+  fn events(frames: &[String]) -> DreamrunnerResult<Vec<WebSocketEvent>> {
+    frames.iter().flat_map(|f| WebSocketEvent::parse(f).transpose()).collect()
+  }
+
+  #[tokio::test]
+  async fn test_replay_entry_stop_loss_round_trip() -> DreamrunnerResult<()> {
+    // the mock balances don't move with fills, so the account already holds the 9 SOL the stop loss sells
+    let client = replay_client(TICKER, "USDT", "SOL", 1000.0, 9.0);
+    let mut engine = engine(client.clone());
+    let entry_bar = START + 2 * BAR;
+    let entry_id = format!("{}-ENTRY", entry_bar);
+    let stop_loss_id = format!("{}-STOP_LOSS", entry_bar);
+    engine.replay(events(&[
+      kline(START, START + BAR, 100.0, true),
+      kline(START + BAR, START + 2 * BAR, 100.0, true),
+      kline(entry_bar, entry_bar + BAR, 100.0, true),
+      execution(entry_bar + BAR + 1_000, &entry_id, "BUY", "MARKET", "FILLED", 100.0),
+      execution(entry_bar + BAR + 60_000, &stop_loss_id, "SELL", "STOP_LOSS", "FILLED", 99.0),
+    ])?).await?;
+
+    let orders = client.orders();
+    assert_eq!(orders.len(), 2);
+    assert_eq!(orders[0].param("newClientOrderId"), Some(entry_id));
+    assert_eq!(orders[0].param("side"), Some("BUY".to_string()));
+    assert_eq!(orders[0].param("type"), Some("MARKET".to_string()));
+    // 90% of 1000 USDT at 100
+    assert_eq!(orders[0].param("quantity"), Some("9".to_string()));
+    assert_eq!(orders[0].param("timestamp"), Some((entry_bar + BAR).to_string()));
+    assert_eq!(orders[1].param("newClientOrderId"), Some(stop_loss_id));
+    assert_eq!(orders[1].param("side"), Some("SELL".to_string()));
+    assert_eq!(orders[1].param("stopPrice"), Some("99".to_string()));
+    assert_eq!(orders[1].param("quantity"), Some("9".to_string()));
+
+    // stop loss fill resets the active order and cancels what's left
+    assert!(engine.active_order.entry.is_none());
+    assert!(engine.active_order.stop_loss.is_none());
+    assert_eq!(client.requests().last().map(|r| r.method), Some(MockMethod::Delete));
+    Ok(())
+  }
+
+  #[test]
+  fn test_load_recorded_events() -> DreamrunnerResult<()> {
+    let path = std::env::temp_dir().join("test_load_recorded_events.jsonl");
+    let frames = [
+      kline(START, START + BAR, 100.0, true),
+      r#"{"result":null,"id":1}"#.to_string(),
+      execution(START + BAR + 1_000, "1-ENTRY", "BUY", "MARKET", "FILLED", 100.0),
+    ];
+    // a recording and raw frames can be mixed
+    let lines: Vec<String> = vec![
+      serde_json::to_string(&RecordedFrame::new(&frames[0]))?,
+      serde_json::to_string(&RecordedFrame::new(&frames[1]))?,
+      frames[2].clone(),
+    ];
+    std::fs::write(&path, lines.join("\n"))?;
+    let loaded = load_events(&path)?;
+    std::fs::remove_file(&path)?;
+
+    assert_eq!(loaded.len(), 2);
+    assert!(matches!(loaded[0], WebSocketEvent::Kline(_)));
+    assert!(matches!(loaded[1], WebSocketEvent::OrderTrade(_)));
+    assert_eq!(loaded[1].event_time(), (START + BAR + 1_000) as u64);
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_replay_resets_stale_entry() -> DreamrunnerResult<()> {
+    let client = replay_client(TICKER, "USDT", "SOL", 1000.0, 0.0);
+    let mut engine = engine(client.clone());
+    let entry_bar = START + 2 * BAR;
+    engine.replay(events(&[
+      kline(START, START + BAR, 100.0, true),
+      kline(START + BAR, START + 2 * BAR, 100.0, true),
+      kline(entry_bar, entry_bar + BAR, 100.0, true),
+      // unfilled 5 minutes after placement
+      kline(entry_bar + BAR, entry_bar + BAR + 5 * 60_000, 100.0, false),
+    ])?).await?;
+    assert!(matches!(engine.active_order.entry, Some(OrderState::Pending(_))));
+
+    // unfilled 11 minutes after placement, by recorded time rather than wall clock
+    engine.replay(events(&[kline(entry_bar + BAR, entry_bar + BAR + 11 * 60_000, 100.0, false)])?).await?;
+    assert!(engine.active_order.entry.is_none());
+    assert_eq!(client.orders().len(), 1);
+    Ok(())
+  }
+}
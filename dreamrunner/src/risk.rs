@@ -0,0 +1,207 @@
+use std::time::Duration;
+use lib::trade::TradeInfo;
+use lib::Side;
+use log::*;
+use time_series::trunc;
+
+const DAY_MS: i64 = 86_400_000;
+
+/// Limits the [`CircuitBreaker`] trips on, each disabled if None
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RiskLimits {
+  /// Halt new entries once realized % PnL for the UTC day drops below -max_daily_loss_pct
+  pub max_daily_loss_pct: Option<f64>,
+  /// Halt new entries after this many losing trades in a row, the streak carries across UTC days
+  pub max_consecutive_losses: Option<usize>
+}
+
+/// Tracks realized trade results against [`RiskLimits`].
+/// Once tripped, new entries are refused until the next UTC day.
+#[derive(Debug, Clone, Default)]
+pub struct CircuitBreaker {
+  pub limits: RiskLimits,
+  /// Unix day (millis / 86_400_000) the daily counters belong to
+  pub day: i64,
+  /// Realized % PnL of trades closed today
+  pub daily_pnl_pct: f64,
+  pub consecutive_losses: usize,
+  pub tripped: bool,
+  /// Filled entry of the open position, used to realize PnL when the exit fills
+  pub entry: Option<TradeInfo>
+}
+
+impl CircuitBreaker {
+  pub fn new(limits: RiskLimits) -> Self {
+    Self {
+      limits,
+      ..Default::default()
+    }
+  }
+
+  pub fn is_tripped(&self) -> bool {
+    self.tripped
+  }
+
+  /// Reset the daily PnL and the breaker if `now` (unix millis) is past the UTC day boundary.
+  /// The losing streak is kept, a loss right after midnight continues yesterday's streak.
+  pub fn roll_day(&mut self, now: i64) {
+    let day = now / DAY_MS;
+    if day != self.day {
+      if self.tripped {
+        warn!("🟡 Circuit breaker reset at UTC day boundary");
+      }
+      self.day = day;
+      self.daily_pnl_pct = 0.0;
+      self.tripped = false;
+    }
+  }
+
+  pub fn record_entry(&mut self, entry: TradeInfo) {
+    self.entry = Some(entry);
+  }
+
+  /// Realize PnL of the open position against the fill price of `exit`, MARKET exits have no order price.
+  /// Returns true if this trade just tripped the breaker.
+  pub fn record_exit(&mut self, exit: &TradeInfo) -> bool {
+    let entry = match self.entry.take() {
+      Some(entry) => entry,
+      None => return false
+    };
+    let factor = match entry.side {
+      Side::Long => 1.0,
+      Side::Short => -1.0
+    };
+    let pnl = trunc!((exit.fill_price - entry.fill_price) / entry.fill_price * factor * 100.0, 2);
+    self.roll_day(exit.event_time);
+    self.daily_pnl_pct += pnl;
+    if pnl < 0.0 {
+      self.consecutive_losses += 1;
+    } else {
+      self.consecutive_losses = 0;
+    }
+    info!(
+      "Realized trade: {}%, daily: {}%, consecutive losses: {}",
+      pnl, trunc!(self.daily_pnl_pct, 2), self.consecutive_losses
+    );
+
+    if self.tripped {
+      return false;
+    }
+    let daily_loss = matches!(self.limits.max_daily_loss_pct, Some(max) if self.daily_pnl_pct <= -max);
+    let losses = matches!(self.limits.max_consecutive_losses, Some(max) if self.consecutive_losses >= max);
+    self.tripped = daily_loss || losses;
+    self.tripped
+  }
+}
+
+/// Cancels open orders, and optionally sells the open position, if the websocket goes silent.
+/// Any event resets the timer, klines alone arrive every few seconds on a healthy stream.
+#[derive(Debug, Clone, Copy)]
+pub struct DeadManSwitch {
+  /// Longest wait for the next websocket event before tripping
+  pub timeout: Duration,
+  /// Sell the base bought by a filled entry at market as well as cancelling open orders
+  pub flatten: bool,
+  /// Tripped since the last event, so a silent stream only cancels once
+  pub tripped: bool
+}
+
+impl DeadManSwitch {
+  pub fn new(timeout: Duration, flatten: bool) -> Self {
+    Self {
+      timeout,
+      flatten,
+      tripped: false
+    }
+  }
+
+  /// An event arrived, re-arm the switch
+  pub fn heartbeat(&mut self) {
+    if self.tripped {
+      info!("🟢 Websocket events resumed, dead man switch re-armed");
+    }
+    self.tripped = false;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use lib::{OrderStatus, OrderType};
+
+  const NOON: i64 = 1_700_000_000_000 / DAY_MS * DAY_MS + DAY_MS / 2;
+
+  fn fill(side: Side, price: f64, event_time: i64) -> TradeInfo {
+    TradeInfo {
+      client_order_id: format!("{}", event_time),
+      order_type: OrderType::Market,
+      status: OrderStatus::Filled,
+      event_time,
+      quantity: 1.0,
+      // MARKET orders report an order price of 0
+      price: 0.0,
+      fill_price: price,
+      side,
+      orig_qty: 1.0,
+      executed_qty: 1.0
+    }
+  }
+
+  /// Round trip long from 100 to `exit` closing at `event_time`, true if it tripped the breaker
+  fn round_trip(breaker: &mut CircuitBreaker, exit: f64, event_time: i64) -> bool {
+    breaker.record_entry(fill(Side::Long, 100.0, event_time));
+    breaker.record_exit(&fill(Side::Short, exit, event_time))
+  }
+
+  #[test]
+  fn daily_loss_resets_at_day_boundary() {
+    let mut breaker = CircuitBreaker::new(RiskLimits { max_daily_loss_pct: Some(5.0), max_consecutive_losses: None });
+    assert!(!round_trip(&mut breaker, 97.0, NOON));
+    assert!(!round_trip(&mut breaker, 104.0, NOON + 1));
+    // -3 + 4 - 6 = -5
+    assert!(round_trip(&mut breaker, 94.0, NOON + 2));
+    assert!(breaker.is_tripped());
+    assert_eq!(breaker.daily_pnl_pct, -5.0);
+
+    // still tripped until midnight
+    breaker.roll_day(NOON + DAY_MS / 2 - 1);
+    assert!(breaker.is_tripped());
+    breaker.roll_day(NOON + DAY_MS / 2);
+    assert!(!breaker.is_tripped());
+    assert_eq!(breaker.daily_pnl_pct, 0.0);
+
+    // yesterday's loss doesn't count towards today's
+    assert!(!round_trip(&mut breaker, 96.0, NOON + DAY_MS));
+    assert_eq!(breaker.daily_pnl_pct, -4.0);
+  }
+
+  #[test]
+  fn losing_streak_carries_across_day_boundary() {
+    let mut breaker = CircuitBreaker::new(RiskLimits { max_daily_loss_pct: None, max_consecutive_losses: Some(3) });
+    assert!(!round_trip(&mut breaker, 99.0, NOON));
+    assert!(!round_trip(&mut breaker, 99.0, NOON + 1));
+    // third loss in a row is on the next day
+    assert!(round_trip(&mut breaker, 99.0, NOON + DAY_MS));
+    assert_eq!(breaker.consecutive_losses, 3);
+
+    // the breaker resets the day after, but the streak doesn't, so one more loss trips it again
+    breaker.roll_day(NOON + 2 * DAY_MS);
+    assert!(!breaker.is_tripped());
+    assert!(round_trip(&mut breaker, 99.0, NOON + 2 * DAY_MS));
+
+    // a win ends the streak
+    breaker.roll_day(NOON + 3 * DAY_MS);
+    assert!(!round_trip(&mut breaker, 101.0, NOON + 3 * DAY_MS));
+    assert_eq!(breaker.consecutive_losses, 0);
+    assert!(!round_trip(&mut breaker, 99.0, NOON + 3 * DAY_MS + 1));
+  }
+
+  #[test]
+  fn no_limits_never_trip() {
+    let mut breaker = CircuitBreaker::new(RiskLimits::default());
+    for i in 0..10 {
+      assert!(!round_trip(&mut breaker, 50.0, NOON + i));
+    }
+    assert!(!breaker.is_tripped());
+  }
+}
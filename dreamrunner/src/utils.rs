@@ -1,5 +1,8 @@
 use lib::*;
 use log::*;
+use playbook::{Dreamrunner, StrategyConfig};
+use time_series::Source;
+use crate::risk::{DeadManSwitch, RiskLimits};
 use simplelog::{
   ColorChoice, Config as SimpleLogConfig, TermLogger,
   TerminalMode,
@@ -25,4 +28,112 @@ pub fn disable_trading() -> DreamrunnerResult<bool> {
   std::env::var("DISABLE_TRADING")?
     .parse::<bool>()
     .map_err(DreamrunnerError::ParseBool)
-}
\ No newline at end of file
+}
+
+/// Millis signed requests stay valid, bump `RECV_WINDOW` if the clock drifts. Defaults to [`DEFAULT_RECV_WINDOW`].
+pub fn recv_window() -> DreamrunnerResult<u32> {
+  match std::env::var("RECV_WINDOW") {
+    Ok(recv_window) => Ok(recv_window.parse::<u32>()?),
+    Err(_) => Ok(DEFAULT_RECV_WINDOW)
+  }
+}
+
+/// File to record raw websocket frames to for replay, from `RECORD_PATH`. Nothing is recorded if unset.
+pub fn record_path() -> Option<std::path::PathBuf> {
+  std::env::var("RECORD_PATH").ok().map(std::path::PathBuf::from)
+}
+
+/// Millis to wait after a bar closes before acting on it, from `CLOSE_CONFIRMATION_MS`. Defaults to 0.
+pub fn close_confirmation() -> DreamrunnerResult<std::time::Duration> {
+  match std::env::var("CLOSE_CONFIRMATION_MS") {
+    Ok(millis) => Ok(std::time::Duration::from_millis(millis.parse::<u64>()?)),
+    Err(_) => Ok(std::time::Duration::ZERO)
+  }
+}
+
+/// Websocket reconnect backoff, capped at `RECONNECT_MAX_DELAY_SECS` (60 if unset)
+/// and giving up after `RECONNECT_MAX_ATTEMPTS` consecutive failures (never if unset)
+pub fn reconnect_backoff() -> DreamrunnerResult<Backoff> {
+  let max_delay = match std::env::var("RECONNECT_MAX_DELAY_SECS") {
+    Ok(secs) => std::time::Duration::from_secs(secs.parse::<u64>()?),
+    Err(_) => std::time::Duration::from_secs(60)
+  };
+  let max_attempts = match std::env::var("RECONNECT_MAX_ATTEMPTS") {
+    Ok(attempts) => Some(attempts.parse::<u32>()?),
+    Err(_) => None
+  };
+  Ok(Backoff::new(std::time::Duration::from_secs(1), max_delay, max_attempts))
+}
+
+/// Maximum notional of an order in quote from `MAX_NOTIONAL`, uncapped if unset
+pub fn max_notional() -> DreamrunnerResult<Option<f64>> {
+  match std::env::var("MAX_NOTIONAL") {
+    Ok(notional) => Ok(Some(notional.parse::<f64>()?)),
+    Err(_) => Ok(None)
+  }
+}
+
+/// Circuit breaker limits from `MAX_DAILY_LOSS_PCT` and `MAX_CONSECUTIVE_LOSSES`, each disabled if unset
+pub fn risk_limits() -> DreamrunnerResult<RiskLimits> {
+  let max_daily_loss_pct = match std::env::var("MAX_DAILY_LOSS_PCT") {
+    Ok(pct) => Some(pct.parse::<f64>()?),
+    Err(_) => None
+  };
+  let max_consecutive_losses = match std::env::var("MAX_CONSECUTIVE_LOSSES") {
+    Ok(losses) => Some(losses.parse::<usize>()?),
+    Err(_) => None
+  };
+  Ok(RiskLimits { max_daily_loss_pct, max_consecutive_losses })
+}
+
+/// Cancel open orders if the websocket is silent for `DEAD_MAN_SECS`, disabled if unset.
+/// Also sells the open position at market if `DEAD_MAN_FLATTEN` is true.
+pub fn dead_man_switch() -> DreamrunnerResult<Option<DeadManSwitch>> {
+  let timeout = match std::env::var("DEAD_MAN_SECS") {
+    Ok(secs) => std::time::Duration::from_secs(secs.parse::<u64>()?),
+    Err(_) => return Ok(None)
+  };
+  let flatten = match std::env::var("DEAD_MAN_FLATTEN") {
+    Ok(flatten) => flatten.parse::<bool>().map_err(DreamrunnerError::ParseBool)?,
+    Err(_) => false
+  };
+  Ok(Some(DeadManSwitch::new(timeout, flatten)))
+}
+
+/// Strategy the live engine trades, loaded from the config file at `STRATEGY_CONFIG`.
+/// Defaults to [`Dreamrunner::solusdt_optimized`] if unset.
+/// The kagi and WMA candle sources are overridden by `K_SRC` and `MA_SRC` if set.
+pub fn live_strategy() -> DreamrunnerResult<Dreamrunner> {
+  let mut strategy = match std::env::var("STRATEGY_CONFIG") {
+    Ok(path) => {
+      let strategy = Dreamrunner::from_config_file(std::path::Path::new(&path))?;
+      info!("Loaded {} strategy config from {}", strategy.ticker, path);
+      strategy
+    },
+    Err(_) => Dreamrunner::solusdt_optimized()
+  };
+  if let Some(k_src) = source("K_SRC")? {
+    strategy.k_src = k_src;
+  }
+  if let Some(ma_src) = source("MA_SRC")? {
+    strategy.ma_src = ma_src;
+  }
+  info!("Kagi source: {:?}, WMA source: {:?}", strategy.k_src, strategy.ma_src);
+  Ok(strategy)
+}
+
+/// Candle source from the env var `name`, e.g. "close". None if unset.
+pub fn source(name: &str) -> DreamrunnerResult<Option<Source>> {
+  match std::env::var(name) {
+    Ok(src) => Ok(Some(src.parse::<Source>()?)),
+    Err(_) => Ok(None)
+  }
+}
+
+/// Candles to load at startup from `WARMUP_CANDLES`, the strategy's history if unset
+pub fn warmup_candles() -> DreamrunnerResult<Option<u16>> {
+  match std::env::var("WARMUP_CANDLES") {
+    Ok(candles) => Ok(Some(candles.parse::<u16>()?)),
+    Err(_) => Ok(None)
+  }
+}
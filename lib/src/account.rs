@@ -1,4 +1,5 @@
 #![allow(clippy::unnecessary_cast)]
+#![allow(clippy::result_large_err)]
 
 use std::collections::HashMap;
 use crate::*;
@@ -10,20 +11,22 @@ use crate::builder::Klines;
 use crate::trade::TradeInfo;
 
 #[derive(Clone)]
-pub struct Account {
-    pub client: Client,
-    pub recv_window: u64,
+pub struct Account<C: BinanceApi = Client> {
+    pub client: C,
+    /// Millis a signed request stays valid, applied to every signed request
+    pub recv_window: u32,
     pub base_asset: String,
     pub quote_asset: String,
     pub ticker: String,
-    pub interval: Interval
+    pub interval: Interval,
+    pub filters: SymbolFilters
 }
 
-impl Account {
+impl<C: BinanceApi> Account<C> {
     #[allow(dead_code)]
     pub fn new(
-        client: Client,
-        recv_window: u64,
+        client: C,
+        recv_window: u32,
         base_asset: String,
         quote_asset: String,
         ticker: String,
@@ -35,20 +38,31 @@ impl Account {
             base_asset,
             quote_asset,
             ticker,
-            interval
+            interval,
+            filters: SymbolFilters::default()
         }
     }
 
-    #[allow(dead_code)]
     pub async fn exchange_info(&self) -> DreamrunnerResult<ExchangeInformation> {
         let req = ExchangeInfo::request(self.ticker.clone());
         self.client
             .get::<ExchangeInformation>(API::Spot(Spot::ExchangeInfo), Some(req)).await
     }
 
+    /// Fetch and cache the exchange filters of the ticker
+    pub async fn load_filters(&mut self) -> DreamrunnerResult<()> {
+        let info = self.exchange_info().await?;
+        self.filters = SymbolFilters::from_exchange_info(&info, &self.ticker)?;
+        Ok(())
+    }
+
+    pub fn filters(&self) -> &SymbolFilters {
+        &self.filters
+    }
+
     /// Get account info which includes token balances
     pub async fn account_info(&self) -> DreamrunnerResult<AccountInfoResponse> {
-        let builder = AccountInfo::request(None);
+        let builder = AccountInfo::request(Some(self.recv_window), self.client.timestamp());
         let req = builder.request;
         let pre = SystemTime::now();
         let res = self
@@ -57,7 +71,7 @@ impl Account {
         let dur = SystemTime::now().duration_since(pre).unwrap().as_millis();
         info!("Request time: {:?}ms", dur);
         if let Err(e) = res {
-            let now = AccountInfo::get_timestamp()?;
+            let now = self.client.timestamp();
             let req_time = builder
                 .btree
                 .get("timestamp")
@@ -82,23 +96,99 @@ impl Account {
     /// Get all assets
     /// Not available on testnet
     pub async fn all_assets(&self) -> DreamrunnerResult<Vec<CoinInfo>> {
-        let req = AllAssets::request(Some(5000));
+        let req = AllAssets::request(Some(self.recv_window), self.client.timestamp());
         self.client
             .get_signed::<Vec<CoinInfo>>(API::Savings(Sapi::AllCoins), Some(req)).await
     }
 
     /// Get price of a single symbol
     pub async fn price(&self) -> DreamrunnerResult<f64> {
-        let req = Price::request(self.ticker.to_string());
+        self.symbol_price(&self.ticker).await
+    }
+
+    /// Get price of any symbol, e.g. to value a commission asset in quote
+    pub async fn symbol_price(&self, symbol: &str) -> DreamrunnerResult<f64> {
+        let req = Price::request(symbol.to_string());
         let res = self
             .client
             .get::<PriceResponse>(API::Spot(Spot::Price), Some(req)).await?;
         Ok(res.price.parse::<f64>()?)
     }
 
+    /// Close of the 1m kline of any symbol at `time` (unix millis), e.g. to value a commission asset when it was paid
+    pub async fn symbol_price_at(&self, symbol: &str, time: u64) -> DreamrunnerResult<f64> {
+        let minute = time - time % 60_000;
+        let req = Klines::request(symbol.to_string(), Interval::OneMinute.as_str(), Some(1), Some(minute as i64), None);
+        let kline = self.client
+            .get::<Vec<serde_json::Value>>(API::Spot(Spot::Klines), Some(req)).await?
+            .into_iter()
+            .flat_map(Kline::try_from)
+            .next()
+            .ok_or(DreamrunnerError::Custom(format!("No {} kline at {}", symbol, minute)))?;
+        Ok(kline.close)
+    }
+
+    /// Fills of `symbol` with the commission paid on each, only those of `order_id` if set.
+    /// Binance returns at most a page of fills per request, so this pages through them all by trade id.
+    pub async fn my_trades(&self, symbol: String, order_id: Option<u64>) -> DreamrunnerResult<Vec<TradeHistory>> {
+        const LIMIT: u16 = 500;
+        let mut fills: Vec<TradeHistory> = Vec::new();
+        let mut from_id = 0;
+        loop {
+            let req = MyTrades::request(symbol.clone(), order_id, Some(from_id), Some(LIMIT), Some(self.recv_window), self.client.timestamp());
+            let mut page = self.client
+                .get_signed::<Vec<TradeHistory>>(API::Spot(Spot::MyTrades), Some(req)).await?;
+            let last_id = match page.iter().map(|fill| fill.id).max() {
+                Some(id) => id,
+                None => break
+            };
+            let is_last_page = page.len() < LIMIT as usize;
+            fills.append(&mut page);
+            if is_last_page {
+                break;
+            }
+            from_id = last_id + 1;
+        }
+        Ok(fills)
+    }
+
+    /// Commissions paid per order of the ticker in quote, keyed by client order id.
+    /// Commissions in the base asset are valued at the fill price, any other asset (e.g. BNB) at its quote price
+    /// the minute of the fill.
+    pub async fn order_commissions(&self) -> DreamrunnerResult<HashMap<String, f64>> {
+        let fills = self.my_trades(self.ticker.clone(), None).await?;
+        // quote price of each commission asset that isn't the base or quote, by asset and minute
+        let mut prices: HashMap<(String, u64), f64> = HashMap::new();
+        let mut by_order: HashMap<u64, f64> = HashMap::new();
+        for fill in fills {
+            let commission = fill.commission.parse::<f64>()?;
+            let quote = if fill.commission_asset == self.quote_asset {
+                commission
+            } else if fill.commission_asset == self.base_asset {
+                commission * fill.price
+            } else {
+                let key = (fill.commission_asset.clone(), fill.time - fill.time % 60_000);
+                let price = match prices.get(&key) {
+                    Some(price) => *price,
+                    None => {
+                        let price = self.symbol_price_at(&format!("{}{}", fill.commission_asset, self.quote_asset), fill.time).await?;
+                        prices.insert(key, price);
+                        price
+                    }
+                };
+                commission * price
+            };
+            *by_order.entry(fill.order_id).or_default() += quote;
+        }
+        Ok(self.all_orders().await?
+            .into_iter()
+            .filter_map(|o| by_order.get(&o.order_id).map(|fee| (o.client_order_id, *fee)))
+            .collect())
+    }
+
     /// Get historical orders for a single symbol
     pub async fn trades(&self) -> DreamrunnerResult<Vec<TradeInfo>> {
-        let req = AllOrders::request(self.ticker.clone(), Some(5000));
+        let req = AllOrders::request(self.ticker.clone(), Some(self.recv_window), self.client.timestamp());
         let orders = self
             .client
             .get_signed::<Vec<HistoricalOrder>>(API::Spot(Spot::AllOrders), Some(req)).await?;
@@ -118,8 +208,19 @@ impl Account {
         Ok(trades)
     }
 
+    /// Filled orders as [`Trade`]s oldest first, the order `playbook::Backtest` keeps its trades in,
+    /// so live executions can be reported with the same machinery as a backtest
+    pub async fn realized_trades(&self) -> DreamrunnerResult<Vec<Trade>> {
+        let mut trades = self.trades().await?
+            .iter()
+            .map(|t| t.to_trade(self.ticker.clone()))
+            .collect::<anyhow::Result<Vec<Trade>>>()?;
+        trades.reverse();
+        Ok(trades)
+    }
+
     pub async fn all_orders(&self) -> DreamrunnerResult<Vec<HistoricalOrder>> {
-        let req = AllOrders::request(self.ticker.clone(), Some(5000));
+        let req = AllOrders::request(self.ticker.clone(), Some(self.recv_window), self.client.timestamp());
         let mut orders = self
           .client
           .get_signed::<Vec<HistoricalOrder>>(API::Spot(Spot::AllOrders), Some(req)).await?;
@@ -128,8 +229,14 @@ impl Account {
         Ok(orders)
     }
 
+    /// Live performance from filled trade history.
+    /// Returns the same [`Summary`] keyed by ticker as `playbook::Backtest::backtest`,
+    /// so live and backtest results share the same metrics and plotting code.
+    /// Quote PnL is net of the commissions actually paid, see [`Account::order_commissions`].
     pub async fn summary(&self) -> DreamrunnerResult<Summary> {
         let trades = self.trades().await?;
+        let commissions = self.order_commissions().await?;
+        let commission = |trade: &TradeInfo| commissions.get(&trade.client_order_id).copied().unwrap_or(0.0);
         let initial_capital = trades[0].price * trades[0].quantity;
         let mut capital = initial_capital;
 
@@ -137,7 +244,7 @@ impl Account {
         let mut cum_pct = Vec::new();
         let mut cum_quote = Vec::new();
         let mut pct_per_trade = Vec::new();
-        for trades in trades.windows(2).rev() {
+        for (i, trades) in trades.windows(2).enumerate().rev() {
             let entry = &trades[0];
             let exit = &trades[1];
             let factor = match entry.side {
@@ -146,7 +253,12 @@ impl Account {
             };
             let pct_pnl = ((exit.price - entry.price) / entry.price * factor) * 100.0;
             // let quote_pnl = pct_pnl / 100.0 * capital;
-            let quote_pnl = pct_pnl / 100.0 * (entry.price * entry.quantity);
+            // each order is the exit of one pair, except the most recent which is only an entry
+            let fees = commission(exit) + match i {
+                0 => commission(entry),
+                _ => 0.0
+            };
+            let quote_pnl = pct_pnl / 100.0 * (entry.price * entry.quantity) - fees;
             
             capital += quote_pnl;
             quote += quote_pnl;
@@ -170,13 +282,47 @@ impl Account {
             trades.clone().into_iter().flat_map(|t| t.to_trade(self.ticker.clone())).collect()
         )]);
         Ok(Summary {
+            initial_capital,
+            interval_minutes: self.interval.minutes(),
+            ticker_capital: HashMap::new(),
+            total_candles: HashMap::new(),
+            withdrawn: HashMap::new(),
             cum_quote: HashMap::from([(self.ticker.clone(), Dataset::new(cum_quote))]),
             cum_pct: HashMap::from([(self.ticker.clone(), Dataset::new(cum_pct))]),
+            equity: HashMap::new(),
             pct_per_trade: HashMap::from([(self.ticker.clone(), Dataset::new(pct_per_trade))]),
             trades,
         })
     }
 
+    /// PnL of the open position at the current price.
+    /// Like [`Account::summary`], the most recent filled order is the entry left unmatched by an exit.
+    /// None if the account is flat, which is when the last fill was a sell or the filled buys and sells net to zero.
+    pub async fn unrealized_pnl(&self) -> DreamrunnerResult<Option<UnrealizedPnl>> {
+        let trades = self.trades().await?;
+        let entry = match trades.first() {
+            Some(entry) if entry.side == Side::Long => entry,
+            _ => return Ok(None)
+        };
+        let net_qty = trades.iter().map(|t| match t.side {
+            Side::Long => t.quantity,
+            Side::Short => -t.quantity,
+        }).sum::<f64>();
+        if trunc!(net_qty, 8) <= 0.0 {
+            return Ok(None);
+        }
+        let current_price = self.price().await?;
+        let pct = (current_price - entry.price) / entry.price * 100.0;
+        Ok(Some(UnrealizedPnl {
+            side: entry.side,
+            entry_price: entry.price,
+            current_price,
+            qty: entry.quantity,
+            unrealized_quote: trunc!(pct / 100.0 * entry.price * entry.quantity, 4),
+            unrealized_pct: trunc!(pct, 4),
+        }))
+    }
+
     pub async fn avg_quote_trade_size(&self) -> DreamrunnerResult<f64> {
         let trades = self.trades().await?;
         let avg = trades.iter().rev().map(|t| {
@@ -188,7 +334,7 @@ impl Account {
     /// Get last open trade for a single symbol
     /// Returns Some if there is an open trade, None otherwise
     pub async fn open_orders(&self) -> DreamrunnerResult<Vec<HistoricalOrder>> {
-        let req = AllOrders::request(self.ticker.clone(), Some(5000));
+        let req = AllOrders::request(self.ticker.clone(), Some(self.recv_window), self.client.timestamp());
         let orders = self
             .client
             .get_signed::<Vec<HistoricalOrder>>(API::Spot(Spot::AllOrders), Some(req)).await?;
@@ -203,7 +349,7 @@ impl Account {
     /// Cancel all open orders for a single symbol
     pub async fn cancel_all_open_orders(&self) -> DreamrunnerResult<Vec<OrderCanceled>> {
         info!("Cancel all active orders");
-        let req = CancelOrders::request(self.ticker.clone(), Some(10000));
+        let req = CancelOrders::request(self.ticker.clone(), Some(self.recv_window), self.client.timestamp());
         let res = self
             .client
             .delete_signed::<Vec<OrderCanceled>>(API::Spot(Spot::OpenOrders), Some(req)).await;
@@ -223,7 +369,7 @@ impl Account {
 
     pub async fn cancel_order(&self, order_id: u64) -> DreamrunnerResult<OrderCanceled> {
         debug!("Cancel order {}", order_id);
-        let req = CancelOrder::request(order_id, self.ticker.to_string(), Some(10000));
+        let req = CancelOrder::request(order_id, self.ticker.to_string(), Some(self.recv_window), self.client.timestamp());
         let res = self
             .client
             .delete_signed::<OrderCanceled>(API::Spot(Spot::Order), Some(req)).await;
@@ -240,96 +386,103 @@ impl Account {
         res
     }
 
+    /// Cancel open orders and market sell the free base asset to quote.
+    /// The quantity is floored to the step size and must meet the min notional.
+    pub async fn flatten(&self) -> DreamrunnerResult<OrderResponse> {
+        info!("Flatten {} to {}", self.base_asset, self.quote_asset);
+        self.cancel_all_open_orders().await?;
+        // canceled orders release their locked base, so fetch assets after canceling
+        let assets = self.assets().await?;
+        let price = self.price().await?;
+        let qty = self.filters.floor_qty(assets.free_base);
+        self.filters.check_notional(qty, price)?;
+        let sell_base = BinanceTrade::new(
+            self.ticker.to_string(),
+            format!("{}-{}", self.client.timestamp(), "FLATTEN"),
+            Side::Short,
+            OrderType::Market,
+            qty,
+            None,
+            Some(self.recv_window),
+            self.client.timestamp() as i64,
+            None,
+            None,
+            None
+        );
+        let res = self.trade::<OrderResponse>(sell_base).await;
+        match &res {
+            Ok(order) => info!("Flattened {} {} at {}", order.executed_qty, self.base_asset, order.cummulative_quote_qty),
+            Err(e) => error!("🛑 Failed to flatten: {:?}", e)
+        }
+        res
+    }
+
     pub async fn trade<T: DeserializeOwned>(&self, trade: BinanceTrade) -> DreamrunnerResult<T> {
-        let req = trade.request();
+        let req = trade.request(self.client.timestamp());
         self.client.post_signed::<T>(API::Spot(Spot::Order), req).await
     }
 
+    /// Trade free quote and base to 50/50 in value, sized by the symbol filters
     pub async fn equalize_account_assets(&self) -> DreamrunnerResult<()> {
         info!("Equalizing account assets");
         let account_info = self.account_info().await?;
         let assets = account_info.account_assets(&self.quote_asset, &self.base_asset)?;
-        let price = self.price().await?;
-
-        // USDT
-        let quote_balance = assets.free_quote / price;
-        // BTC
-        let base_balance = assets.free_base;
-
-        let sum = quote_balance + base_balance;
-        let equal = trunc!(sum / 2_f64, 2);
-        let quote_diff = trunc!(quote_balance - equal, 2);
-        let base_diff = trunc!(base_balance - equal, 2);
-        let min_notional = 5.0;
-        info!("sum: {}", sum);
-        info!("equal: {}", equal);
-        info!("quote_diff: {}", quote_diff);
-        info!("base_diff: {}", base_diff);
-
-        // buy BTC
-        if quote_diff > 0_f64 && quote_diff > min_notional {
-            let timestamp = BinanceTrade::get_timestamp()?;
-            let client_order_id = format!("{}-{}", timestamp, "EQUALIZE_QUOTE");
-            let long_qty = trunc!(quote_diff, 2);
-            info!("long_qty: {}", long_qty);
-            info!(
-                "Quote asset too high = {} {}, 50/50 = {} {}, buy base asset = {} {}",
-                quote_balance * price,
-                self.quote_asset,
-                equal * price,
-                self.quote_asset,
-                long_qty,
-                self.base_asset
-            );
-            let buy_base = BinanceTrade::new(
-                self.ticker.to_string(),
-                client_order_id,
-                Side::Long,
-                OrderType::Limit,
-                long_qty,
-                Some(price),
-                None,
-                Time::now().to_unix_ms(),
-                None,
-                None
-            );
-            if let Err(e) = self.trade::<LimitOrderResponse>(buy_base).await {
-                error!("🛑 Error equalizing quote asset with error: {:?}", e);
-                return Err(e);
-            }
-        }
-
-        // sell BTC
-        if base_diff > 0_f64 && base_diff > min_notional {
-            let timestamp = BinanceTrade::get_timestamp()?;
-            let client_order_id = format!("{}-{}", timestamp, "EQUALIZE_BASE");
-            let short_qty = trunc!(base_diff, 2);
-            info!(
-                "Base asset too high = {} {}, 50/50 = {} {}, sell base asset = {} {}",
-                base_balance, self.base_asset, equal, self.base_asset, short_qty, self.base_asset
-            );
-            let sell_base = BinanceTrade::new(
-                self.ticker.to_string(),
-                client_order_id,
-                Side::Short,
-                OrderType::Limit,
-                short_qty,
-                Some(price),
-                None,
-                Time::now().to_unix_ms(),
-                None,
-                None
-            );
-            if let Err(e) = self.trade::<LimitOrderResponse>(sell_base).await {
-                error!("🛑 Error equalizing base asset with error: {:?}", e);
-                return Err(e);
+        let price = self.filters.floor_price(self.price().await?);
+        let (side, qty) = match assets.equalize(price, &self.filters) {
+            Some(trade) => trade,
+            None => {
+                info!("Assets within min notional of 50/50, skip equalize");
+                return Ok(());
             }
+        };
+        let suffix = match side {
+            Side::Long => "EQUALIZE_QUOTE",
+            Side::Short => "EQUALIZE_BASE",
+        };
+        info!(
+            "Equalize {} {}, {}: {}, {}: {}, {} {} @ {}",
+            self.quote_asset,
+            self.base_asset,
+            self.quote_asset,
+            assets.free_quote,
+            self.base_asset,
+            assets.free_base,
+            side.fmt_binance(),
+            qty,
+            price
+        );
+        let equalize = BinanceTrade::new(
+            self.ticker.to_string(),
+            format!("{}-{}", self.client.timestamp(), suffix),
+            side,
+            OrderType::Limit,
+            qty,
+            Some(price),
+            Some(self.recv_window),
+            self.client.timestamp() as i64,
+            None,
+            None,
+            None
+        );
+        if let Err(e) = self.trade::<LimitOrderResponse>(equalize).await {
+            error!("🛑 Error equalizing assets with error: {:?}", e);
+            return Err(e);
         }
         Ok(())
     }
 
     pub async fn klines(&self, limit: Option<u16>, start_time: Option<i64>, end_time: Option<i64>) -> DreamrunnerResult<Vec<Kline>> {
-        let req = Klines::request(self.ticker.to_string(), self.interval.as_str(), limit, start_time, end_time);
+        self.interval_klines(self.interval, limit, start_time, end_time).await
+    }
+
+    pub async fn interval_klines(
+        &self,
+        interval: Interval,
+        limit: Option<u16>,
+        start_time: Option<i64>,
+        end_time: Option<i64>
+    ) -> DreamrunnerResult<Vec<Kline>> {
+        let req = Klines::request(self.ticker.to_string(), interval.as_str(), limit, start_time, end_time);
         let mut klines = self.client
           .get::<Vec<serde_json::Value>>(API::Spot(Spot::Klines), Some(req)).await?
           .into_iter()
@@ -339,20 +492,268 @@ impl Account {
         Ok(klines)
     }
 
-    // get historical klines for the specified days back
-    pub async fn kline_history(&self, days_back: i64) -> DreamrunnerResult<Vec<Kline>> {
-        let end = Time::now();
-        let start = Time::new(end.year, &end.month, &end.day, end.hour, end.minute, end.second);
+    /// Fetch all klines between `start_time` and `end_time` (unix millis),
+    /// paging through Binance's 1000 kline limit per request.
+    pub async fn paginated_klines(&self, interval: Interval, start_time: i64, end_time: i64) -> DreamrunnerResult<Vec<Kline>> {
+        const LIMIT: u16 = 1000;
+        let interval_ms = interval.minutes() as i64 * 60 * 1000;
 
         let mut data: Vec<Kline> = Vec::new();
-        for i in 0..days_back {
-            let start = start.delta_date(-i);
-            let end = end.delta_date(-i);
-            let mut klines = self.klines(None, Some(start.to_unix_ms()), Some(end.to_unix_ms())).await?;
+        let mut cursor = start_time;
+        while cursor < end_time {
+            let mut klines = self.interval_klines(interval, Some(LIMIT), Some(cursor), Some(end_time)).await?;
+            let last_open_time = match klines.first() {
+                Some(kline) => kline.open_time as i64,
+                None => break
+            };
+            let is_last_page = klines.len() < LIMIT as usize;
             data.append(&mut klines);
+            if is_last_page {
+                break;
+            }
+            cursor = last_open_time + interval_ms;
         }
         // sort so that the latest kline is first
         data.sort_by(|a, b| b.open_time.cmp(&a.open_time));
         Ok(data)
     }
+
+    // get historical klines for the specified days back
+    pub async fn kline_history(&self, days_back: i64) -> DreamrunnerResult<Vec<Kline>> {
+        let end = Time::now();
+        let start = end.delta_date(-days_back);
+        self.paginated_klines(self.interval, start.to_unix_ms(), end.to_unix_ms()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(client: MockClient) -> Account<MockClient> {
+        let mut account = Account::new(
+            client,
+            5000,
+            "SOL".to_string(),
+            "USDT".to_string(),
+            "SOLUSDT".to_string(),
+            Interval::ThirtyMinutes,
+        );
+        account.filters = SymbolFilters {
+            tick_size: 0.01,
+            step_size: 0.01,
+            min_qty: 0.01,
+            min_notional: 5.0,
+            price_decimals: 2,
+            qty_decimals: 2,
+            ..SymbolFilters::default()
+        };
+        account
+    }
+
+    #[tokio::test]
+    async fn test_flatten_sells_free_base() -> DreamrunnerResult<()> {
+        let client = MockClient::new()
+            .with_balances(&[("USDT", 10.0), ("SOL", 1.23456)])
+            .with_response(MockMethod::Delete, API::Spot(Spot::OpenOrders), serde_json::json!([]))
+            .with_response(MockMethod::Get, API::Spot(Spot::Price), serde_json::json!({ "symbol": "SOLUSDT", "price": "150.00" }))
+            .with_response(MockMethod::Post, API::Spot(Spot::Order), serde_json::json!({
+                "symbol": "SOLUSDT", "orderId": 1, "orderListId": -1, "clientOrderId": "FLATTEN", "transactTime": 0,
+                "price": "0", "origQty": "1.23", "executedQty": "1.23", "cummulativeQuoteQty": "184.5",
+                "status": "FILLED", "timeInForce": "GTC", "type": "MARKET", "side": "SELL",
+                "workingTime": 0, "selfTradePreventionMode": "NONE", "fills": []
+            }));
+        let order = account(client.clone()).flatten().await?;
+        assert_eq!(order.executed_qty, "1.23");
+
+        // open orders are canceled before the balance is read
+        let requests = client.requests();
+        assert_eq!(requests[0].method, MockMethod::Delete);
+        let orders = client.orders();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].param("side"), Some("SELL".to_string()));
+        assert_eq!(orders[0].param("type"), Some("MARKET".to_string()));
+        // floored to the step size
+        assert_eq!(orders[0].param("quantity"), Some("1.23".to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flatten_below_min_notional() -> DreamrunnerResult<()> {
+        let client = MockClient::new()
+            .with_balances(&[("USDT", 10.0), ("SOL", 0.02)])
+            .with_response(MockMethod::Delete, API::Spot(Spot::OpenOrders), serde_json::json!([]))
+            .with_response(MockMethod::Get, API::Spot(Spot::Price), serde_json::json!({ "symbol": "SOLUSDT", "price": "150.00" }));
+        let res = account(client.clone()).flatten().await;
+        assert!(matches!(res, Err(DreamrunnerError::BelowMinNotional { .. })));
+        assert!(client.orders().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_my_trades_pages_by_trade_id() -> DreamrunnerResult<()> {
+        let fill = |id: u64| serde_json::json!({
+            "id": id, "orderId": 1, "price": "100", "qty": "1", "commission": "0.1", "commissionAsset": "USDT",
+            "time": 0, "isBuyer": true, "isMaker": false, "isBestMatch": true
+        });
+        let full_page: Vec<serde_json::Value> = (1..=500).map(fill).collect();
+        let client = MockClient::new()
+            .with_responses(MockMethod::Get, API::Spot(Spot::MyTrades), vec![
+                serde_json::json!(full_page),
+                serde_json::json!([fill(501), fill(502)])
+            ]);
+        let account = account(client.clone());
+
+        let fills = account.my_trades("SOLUSDT".to_string(), None).await?;
+        assert_eq!(fills.len(), 502);
+        assert_eq!(fills.last().map(|f| f.id), Some(502));
+        // a short page is the last
+        let requests = client.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].param("fromId"), Some("0".to_string()));
+        assert_eq!(requests[0].param("limit"), Some("500".to_string()));
+        assert_eq!(requests[1].param("fromId"), Some("501".to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_summary_net_of_commissions() -> DreamrunnerResult<()> {
+        let order = |order_id: u64, side: &str, price: &str, update_time: i64| serde_json::json!({
+            "symbol": "SOLUSDT", "orderId": order_id, "orderListId": -1, "clientOrderId": format!("{}-ENTRY", order_id),
+            "price": price, "origQty": "1", "executedQty": "1", "cummulativeQuoteQty": price, "status": "FILLED",
+            "timeInForce": "GTC", "type": "LIMIT", "side": side, "stopPrice": null, "icebergQty": null, "time": update_time,
+            "updateTime": update_time, "isWorking": true, "origQuoteOrderQty": "0", "workingTime": update_time,
+            "selfTradePreventionMode": "NONE"
+        });
+        let fill = |id: u64, order_id: u64, price: &str, commission: &str, asset: &str| serde_json::json!({
+            "id": id, "orderId": order_id, "price": price, "qty": "1", "commission": commission, "commissionAsset": asset,
+            "time": 90_000, "isBuyer": true, "isMaker": false, "isBestMatch": true
+        });
+        let client = MockClient::new()
+            .with_response(MockMethod::Get, API::Spot(Spot::AllOrders), serde_json::json!([
+                order(1, "BUY", "100", 1_000),
+                order(2, "SELL", "110", 2_000),
+                order(3, "BUY", "105", 3_000)
+            ]))
+            .with_response(MockMethod::Get, API::Spot(Spot::MyTrades), serde_json::json!([
+                fill(1, 1, "100", "0.1", "USDT"),
+                // base commission valued at the fill price
+                fill(2, 2, "110", "0.001", "SOL"),
+                // split into two fills, valued at the BNBUSDT close the minute they filled
+                fill(3, 3, "105", "0.0005", "BNB"),
+                fill(4, 3, "105", "0.0005", "BNB")
+            ]))
+            .with_response(MockMethod::Get, API::Spot(Spot::Klines), serde_json::json!([
+                [60_000, "290", "310", "280", "300", "10", 119_999, "3000", 5, "5", "1500", "0"]
+            ]))
+            .with_response(MockMethod::Get, API::Spot(Spot::Price), serde_json::json!({ "symbol": "BNBUSDT", "price": "600.00" }));
+        let account = account(client.clone());
+
+        let commissions = account.order_commissions().await?;
+        assert_eq!(commissions.get("1-ENTRY").map(|c| trunc!(*c, 4)), Some(0.1));
+        assert_eq!(commissions.get("2-ENTRY").map(|c| trunc!(*c, 4)), Some(0.11));
+        assert_eq!(commissions.get("3-ENTRY").map(|c| trunc!(*c, 4)), Some(0.3));
+        // BNB price is fetched once for the minute of both fills, not at the current price
+        let kline_requests: Vec<MockRequest> = client.requests().into_iter().filter(|r| r.path == String::from(API::Spot(Spot::Klines))).collect();
+        assert_eq!(kline_requests.len(), 1);
+        assert_eq!(kline_requests[0].param("symbol"), Some("BNBUSDT".to_string()));
+        assert_eq!(kline_requests[0].param("startTime"), Some("60000".to_string()));
+        assert!(client.requests().iter().all(|r| r.path != String::from(API::Spot(Spot::Price))));
+
+        // gross PnL of the short 110 -> 100 and long 105 -> 110 is 10 and 5, every order's commission is charged once
+        let summary = account.summary().await?;
+        assert_eq!(summary.quote_roi("SOLUSDT"), trunc!(10.0 + 5.0 - 0.1 - 0.11 - 0.3, 3));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unrealized_pnl_of_last_entry() -> DreamrunnerResult<()> {
+        let order = |order_id: u64, side: &str, price: &str, update_time: i64| serde_json::json!({
+            "symbol": "SOLUSDT", "orderId": order_id, "orderListId": -1, "clientOrderId": format!("{}-ENTRY", order_id),
+            "price": price, "origQty": "2", "executedQty": "2", "cummulativeQuoteQty": price, "status": "FILLED",
+            "timeInForce": "GTC", "type": "LIMIT", "side": side, "stopPrice": null, "icebergQty": null, "time": update_time,
+            "updateTime": update_time, "isWorking": true, "origQuoteOrderQty": "0", "workingTime": update_time,
+            "selfTradePreventionMode": "NONE"
+        });
+        let client = MockClient::new()
+            .with_response(MockMethod::Get, API::Spot(Spot::AllOrders), serde_json::json!([
+                order(1, "BUY", "100", 1_000),
+                order(2, "SELL", "110", 2_000),
+                order(3, "BUY", "120", 3_000)
+            ]))
+            .with_response(MockMethod::Get, API::Spot(Spot::Price), serde_json::json!({ "symbol": "SOLUSDT", "price": "126.00" }));
+        let pnl = account(client).unrealized_pnl().await?.unwrap();
+        assert_eq!(pnl.side, Side::Long);
+        assert_eq!(pnl.entry_price, 120.0);
+        assert_eq!(pnl.current_price, 126.0);
+        assert_eq!(pnl.qty, 2.0);
+        assert_eq!(pnl.unrealized_quote, 12.0);
+        assert_eq!(pnl.unrealized_pct, 5.0);
+
+        let client = MockClient::new()
+            .with_response(MockMethod::Get, API::Spot(Spot::AllOrders), serde_json::json!([]));
+        assert!(account(client).unrealized_pnl().await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unrealized_pnl_flat_after_exit() -> DreamrunnerResult<()> {
+        let order = |order_id: u64, side: &str, price: &str, qty: &str, update_time: i64| serde_json::json!({
+            "symbol": "SOLUSDT", "orderId": order_id, "orderListId": -1, "clientOrderId": format!("{}-ENTRY", order_id),
+            "price": price, "origQty": qty, "executedQty": qty, "cummulativeQuoteQty": price, "status": "FILLED",
+            "timeInForce": "GTC", "type": "LIMIT", "side": side, "stopPrice": null, "icebergQty": null, "time": update_time,
+            "updateTime": update_time, "isWorking": true, "origQuoteOrderQty": "0", "workingTime": update_time,
+            "selfTradePreventionMode": "NONE"
+        });
+        let price = serde_json::json!({ "symbol": "SOLUSDT", "price": "126.00" });
+
+        // the last fill was the exit
+        let client = MockClient::new()
+            .with_response(MockMethod::Get, API::Spot(Spot::AllOrders), serde_json::json!([
+                order(1, "BUY", "100", "2", 1_000),
+                order(2, "SELL", "110", "2", 2_000)
+            ]))
+            .with_response(MockMethod::Get, API::Spot(Spot::Price), price.clone());
+        assert!(account(client.clone()).unrealized_pnl().await?.is_none());
+        // no price is fetched for a flat account
+        assert!(client.requests().iter().all(|r| r.path != String::from(API::Spot(Spot::Price))));
+
+        // a buy that closed a prior sell leaves nothing open
+        let client = MockClient::new()
+            .with_response(MockMethod::Get, API::Spot(Spot::AllOrders), serde_json::json!([
+                order(1, "BUY", "100", "2", 1_000),
+                order(2, "SELL", "110", "4", 2_000),
+                order(3, "BUY", "105", "2", 3_000)
+            ]))
+            .with_response(MockMethod::Get, API::Spot(Spot::Price), price);
+        assert!(account(client).unrealized_pnl().await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_realized_trades_oldest_first() -> DreamrunnerResult<()> {
+        let order = |order_id: u64, side: &str, price: &str, status: &str, update_time: i64| serde_json::json!({
+            "symbol": "SOLUSDT", "orderId": order_id, "orderListId": -1, "clientOrderId": format!("{}-ENTRY", order_id),
+            "price": price, "origQty": "2", "executedQty": "2", "cummulativeQuoteQty": price, "status": status,
+            "timeInForce": "GTC", "type": "LIMIT", "side": side, "stopPrice": null, "icebergQty": null, "time": update_time,
+            "updateTime": update_time, "isWorking": true, "origQuoteOrderQty": "0", "workingTime": update_time,
+            "selfTradePreventionMode": "NONE"
+        });
+        let client = MockClient::new()
+            .with_response(MockMethod::Get, API::Spot(Spot::AllOrders), serde_json::json!([
+                order(2, "SELL", "110", "FILLED", 1_700_001_800_000),
+                order(3, "BUY", "105", "CANCELED", 1_700_003_600_000),
+                order(1, "BUY", "100", "FILLED", 1_700_000_000_000)
+            ]));
+        let trades = account(client).realized_trades().await?;
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].ticker, "SOLUSDT");
+        assert_eq!(trades[0].date.to_unix_ms(), Time::from_unix_ms(1_700_000_000_000).to_unix_ms());
+        assert_eq!(trades[0].side, time_series::Order::EnterLong);
+        assert_eq!(trades[0].price, 100.0);
+        assert_eq!(trades[0].quantity, 2.0);
+        assert_eq!(trades[1].date.to_unix_ms(), Time::from_unix_ms(1_700_001_800_000).to_unix_ms());
+        assert_eq!(trades[1].side, time_series::Order::ExitLong);
+        Ok(())
+    }
 }
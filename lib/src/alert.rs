@@ -1,9 +1,14 @@
 #![allow(dead_code)]
+#![allow(clippy::result_large_err)]
 
+use std::collections::{HashMap, VecDeque};
 use std::str::FromStr;
 use actix_web::{Result};
+use hmac::{Hmac, Mac};
+use log::warn;
 use serde::{Serialize, Deserialize};
-use crate::WebSocketEvent;
+use sha2::Sha256;
+use crate::{DreamrunnerError, DreamrunnerResult, WebSocketEvent};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Position {
@@ -31,12 +36,130 @@ impl Position {
   }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertAction {
+  Buy,
+  Sell
+}
+
+impl FromStr for AlertAction {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "buy" => Ok(AlertAction::Buy),
+      "sell" => Ok(AlertAction::Sell),
+      _ => Err(()),
+    }
+  }
+}
+
+impl AlertAction {
+  pub fn as_str(&self) -> &str {
+    match self {
+      AlertAction::Buy => "buy",
+      AlertAction::Sell => "sell",
+    }
+  }
+}
+
+/// TradingView alert payload, e.g.
+/// `{"strategy_id":"dreamrunner","action":"buy","ticker":"SOLUSDT","price":100.5,"timestamp":1700000000000,"size":1.5}`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Alert {
-  pub position: Position,
+  /// TradingView alert id, used as the idempotency key if present
+  #[serde(default)]
+  pub id: Option<String>,
+  pub strategy_id: String,
+  pub action: AlertAction,
+  pub ticker: String,
   #[serde(default)]
   pub price: Option<f64>,
-  pub timestamp: i64
+  /// Unix millis
+  pub timestamp: i64,
+  /// Position size in base asset, otherwise the engine sizes the order
+  #[serde(default)]
+  pub size: Option<f64>
+}
+
+impl Alert {
+  /// Key identifying repeated deliveries of the same alert
+  pub fn idempotency_key(&self) -> String {
+    match &self.id {
+      Some(id) => id.clone(),
+      None => format!("{}:{}:{}:{}", self.strategy_id, self.ticker, self.timestamp, self.action.as_str())
+    }
+  }
+
+  /// Verify `signature` is the hex encoded HMAC-SHA256 of `body` keyed by `secret`.
+  /// Comparison is constant time.
+  pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let signature = match hex::decode(signature.trim()) {
+      Ok(sig) => sig,
+      Err(_) => return false
+    };
+    let mut mac = match Hmac::<Sha256>::new_from_slice(secret.as_bytes()) {
+      Ok(mac) => mac,
+      Err(_) => return false
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+  }
+
+  /// Authenticate and parse a raw alert body, `signature` being the `X-Signature` header of the request.
+  /// Returns [`DreamrunnerError::AlertSignatureInvalid`], a 401, if the signature is missing or doesn't match,
+  /// so unauthenticated alerts never reach the engine channel.
+  pub fn from_signed_body(secret: &str, body: &[u8], signature: Option<&str>) -> DreamrunnerResult<Self> {
+    match signature {
+      Some(sig) if Self::verify_signature(secret, body, sig) => Ok(serde_json::from_slice::<Alert>(body)?),
+      _ => Err(DreamrunnerError::AlertSignatureInvalid)
+    }
+  }
+}
+
+/// Bounded cache of recently seen alert keys to drop duplicate TradingView deliveries.
+/// Check each alert after [`Alert::from_signed_body`] accepts it and before it's sent on the engine channel,
+/// so a duplicate never places a second order.
+#[derive(Debug, Clone)]
+pub struct AlertDeduper {
+  /// Duplicates received within this many millis of the first delivery are dropped
+  pub window_ms: i64,
+  /// Max number of keys retained, oldest are evicted first
+  pub capacity: usize,
+  seen: HashMap<String, i64>,
+  order: VecDeque<String>
+}
+
+impl AlertDeduper {
+  pub fn new(window_ms: i64, capacity: usize) -> Self {
+    Self {
+      window_ms,
+      capacity,
+      seen: HashMap::new(),
+      order: VecDeque::new()
+    }
+  }
+
+  /// Returns true if the alert is new and should be handled, false if it's a duplicate.
+  /// `now` is unix millis when the alert was received.
+  pub fn check(&mut self, alert: &Alert, now: i64) -> bool {
+    let key = alert.idempotency_key();
+    if let Some(first_seen) = self.seen.get(&key) {
+      if now - first_seen < self.window_ms {
+        warn!("🟡 Dropping duplicate alert {} received {}ms after first delivery", key, now - first_seen);
+        return false;
+      }
+      self.order.retain(|k| k != &key);
+    }
+    self.seen.insert(key.clone(), now);
+    self.order.push_back(key);
+    while self.order.len() > self.capacity {
+      if let Some(oldest) = self.order.pop_front() {
+        self.seen.remove(&oldest);
+      }
+    }
+    true
+  }
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -44,4 +167,55 @@ pub struct Alert {
 pub enum ChannelMsg {
   Websocket(WebSocketEvent),
   Alert(Alert)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use actix_web::http::StatusCode;
+  use actix_web::ResponseError;
+
+  fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+  }
+
+  #[test]
+  fn test_signed_alert() {
+    let secret = "secret";
+    let body = br#"{"strategy_id":"dreamrunner","action":"buy","ticker":"SOLUSDT","price":100.5,"timestamp":1700000000000}"#;
+    let sig = sign(secret, body);
+
+    let alert = Alert::from_signed_body(secret, body, Some(&sig)).unwrap();
+    assert_eq!(alert.ticker, "SOLUSDT");
+    assert_eq!(alert.size, None);
+
+    for rejected in [
+      Alert::from_signed_body("wrong", body, Some(&sig)),
+      Alert::from_signed_body(secret, body, None),
+      Alert::from_signed_body(secret, body, Some("zz")),
+      Alert::from_signed_body(secret, br#"{"strategy_id":"dreamrunner","action":"sell"}"#, Some(&sig))
+    ] {
+      let err = rejected.unwrap_err();
+      assert!(matches!(err, DreamrunnerError::AlertSignatureInvalid));
+      assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+    }
+  }
+
+  #[test]
+  fn test_dedup_alerts() {
+    let body = br#"{"strategy_id":"dreamrunner","action":"buy","ticker":"SOLUSDT","timestamp":1700000000000}"#;
+    let alert = serde_json::from_slice::<Alert>(body).unwrap();
+    let mut other = alert.clone();
+    other.action = AlertAction::Sell;
+
+    let mut deduper = AlertDeduper::new(60_000, 1);
+    assert!(deduper.check(&alert, 0));
+    assert!(!deduper.check(&alert, 1_000));
+    assert!(deduper.check(&alert, 60_000));
+    // capacity of 1 evicts the first key
+    assert!(deduper.check(&other, 61_000));
+    assert!(deduper.check(&alert, 62_000));
+  }
+}
@@ -0,0 +1,98 @@
+#![allow(clippy::result_large_err)]
+
+use crate::errors::{DreamrunnerError, DreamrunnerResult};
+use log::*;
+use std::time::Duration;
+
+/// Exponential backoff with jitter between reconnect attempts,
+/// so clients retrying through an outage don't hit the endpoint in lockstep.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    /// Delay before the first retry, doubled each attempt after
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Retries before giving up, unlimited if None
+    pub max_attempts: Option<u32>,
+    attempts: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1), Duration::from_secs(60), None)
+    }
+}
+
+impl Backoff {
+    pub fn new(initial_delay: Duration, max_delay: Duration, max_attempts: Option<u32>) -> Self {
+        Self {
+            initial_delay,
+            max_delay,
+            max_attempts,
+            attempts: 0,
+        }
+    }
+
+    /// Retries since the last [`Backoff::reset`]
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Start over from the initial delay, e.g. once a connection is healthy again
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+
+    /// Delay before the next retry, None once the max attempts are used up.
+    /// The exponential delay is capped at the max delay, then jittered to between half and all of it.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if let Some(max_attempts) = self.max_attempts {
+            if self.attempts >= max_attempts {
+                return None;
+            }
+        }
+        let exp = self.initial_delay.saturating_mul(2_u32.saturating_pow(self.attempts));
+        let delay = exp.min(self.max_delay);
+        self.attempts += 1;
+        Some(delay.mul_f64(0.5 + rand::random::<f64>() * 0.5))
+    }
+
+    /// Sleep for the next delay, Err once the max attempts are used up
+    pub async fn wait(&mut self) -> DreamrunnerResult<()> {
+        match self.next_delay() {
+            Some(delay) => {
+                warn!("🟡 Reconnect attempt {} in {}ms", self.attempts, delay.as_millis());
+                tokio::time::sleep(delay).await;
+                Ok(())
+            }
+            None => Err(DreamrunnerError::ReconnectAttemptsExhausted(self.attempts)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delays() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5), Some(5));
+        // 1s, 2s, 4s, then capped at 5s, each jittered to at least half
+        for max in [1, 2, 4, 5, 5] {
+            let max = Duration::from_secs(max);
+            let delay = backoff.next_delay().unwrap();
+            assert!(delay >= max / 2 && delay <= max, "{:?} outside {:?}", delay, max);
+        }
+        assert_eq!(backoff.attempts(), 5);
+        assert!(backoff.next_delay().is_none());
+
+        backoff.reset();
+        assert!(backoff.next_delay().unwrap() <= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_backoff_wait_exhausted() {
+        let mut backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(1), Some(1));
+        assert!(backoff.wait().await.is_ok());
+        assert!(matches!(backoff.wait().await, Err(DreamrunnerError::ReconnectAttemptsExhausted(1))));
+    }
+}
@@ -0,0 +1,183 @@
+#![allow(clippy::result_large_err)]
+
+use std::collections::{BTreeMap, VecDeque};
+use crate::errors::{DreamrunnerError, DreamrunnerResult};
+use crate::model::{DepthSnapshot, DepthUpdateEvent, PriceLevel};
+
+/// Price levels are keyed in integer units of 1e-8 so the book is ordered without float keys
+const PRICE_SCALE: f64 = 1e8;
+
+fn price_key(price: f64) -> i64 {
+    (price * PRICE_SCALE).round() as i64
+}
+
+/// Local order book synced from a [`DepthSnapshot`] and `@depth` diffs, exposing top-of-book volume imbalance.
+///
+/// Diffs received before a snapshot is loaded are buffered. On [`BookImbalance::sync`] the buffered diffs
+/// already contained in the snapshot (`u <= lastUpdateId`) are dropped and the rest replayed.
+/// Once synced, a diff whose first update id `U` skips past the last applied update id + 1 means
+/// an update was missed, so the book is reset and a new snapshot is required.
+#[derive(Debug, Clone, Default)]
+pub struct BookImbalance {
+  bids: BTreeMap<i64, f64>,
+  asks: BTreeMap<i64, f64>,
+  /// Last applied update id, None until a snapshot is loaded
+  last_update_id: Option<u64>,
+  /// Diffs received while not synced, oldest first
+  buffer: VecDeque<DepthUpdateEvent>,
+}
+
+impl BookImbalance {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn is_synced(&self) -> bool {
+    self.last_update_id.is_some()
+  }
+
+  pub fn last_update_id(&self) -> Option<u64> {
+    self.last_update_id
+  }
+
+  /// Replace the book with `snapshot` and replay buffered diffs on top of it
+  pub fn sync(&mut self, snapshot: DepthSnapshot) -> DreamrunnerResult<()> {
+    self.bids = snapshot.bids.iter().filter(|l| l.qty > 0.0).map(|l| (price_key(l.price), l.qty)).collect();
+    self.asks = snapshot.asks.iter().filter(|l| l.qty > 0.0).map(|l| (price_key(l.price), l.qty)).collect();
+    self.last_update_id = Some(snapshot.last_update_id);
+    while let Some(event) = self.buffer.pop_front() {
+      self.apply(event)?;
+    }
+    Ok(())
+  }
+
+  /// Apply a diff from the depth stream, or buffer it until [`BookImbalance::sync`] if not synced.
+  /// Returns [`DreamrunnerError::DepthOutOfSync`] if an update was missed, after which the book is reset.
+  pub fn update(&mut self, event: DepthUpdateEvent) -> DreamrunnerResult<()> {
+    match self.last_update_id {
+      Some(_) => self.apply(event),
+      None => {
+        self.buffer.push_back(event);
+        Ok(())
+      }
+    }
+  }
+
+  fn apply(&mut self, event: DepthUpdateEvent) -> DreamrunnerResult<()> {
+    let last = match self.last_update_id {
+      Some(last) => last,
+      None => return Err(DreamrunnerError::Custom("Order book not synced".to_string()))
+    };
+    // already included in the snapshot or a prior diff
+    if event.final_update_id <= last {
+      return Ok(());
+    }
+    if event.first_update_id > last + 1 {
+      self.reset();
+      return Err(DreamrunnerError::DepthOutOfSync {
+        expected: last + 1,
+        first_update_id: event.first_update_id
+      });
+    }
+    Self::apply_levels(&mut self.bids, &event.bids);
+    Self::apply_levels(&mut self.asks, &event.asks);
+    self.last_update_id = Some(event.final_update_id);
+    Ok(())
+  }
+
+  fn apply_levels(side: &mut BTreeMap<i64, f64>, levels: &[PriceLevel]) {
+    for level in levels {
+      match level.qty == 0.0 {
+        true => side.remove(&price_key(level.price)),
+        false => side.insert(price_key(level.price), level.qty)
+      };
+    }
+  }
+
+  /// Clear the book and buffer, so diffs are buffered until the next snapshot
+  pub fn reset(&mut self) {
+    self.bids.clear();
+    self.asks.clear();
+    self.last_update_id = None;
+    self.buffer.clear();
+  }
+
+  pub fn best_bid(&self) -> Option<PriceLevel> {
+    self.bids.iter().next_back().map(|(price, qty)| PriceLevel { price: *price as f64 / PRICE_SCALE, qty: *qty })
+  }
+
+  pub fn best_ask(&self) -> Option<PriceLevel> {
+    self.asks.iter().next().map(|(price, qty)| PriceLevel { price: *price as f64 / PRICE_SCALE, qty: *qty })
+  }
+
+  /// (bid volume - ask volume) / (bid volume + ask volume) of the best `levels` on each side.
+  /// 1.0 is all bids, -1.0 is all asks, None if not synced or the book is empty.
+  pub fn imbalance(&self, levels: usize) -> Option<f64> {
+    if !self.is_synced() {
+      return None;
+    }
+    let bid_volume: f64 = self.bids.values().rev().take(levels).sum();
+    let ask_volume: f64 = self.asks.values().take(levels).sum();
+    let total = bid_volume + ask_volume;
+    match total == 0.0 {
+      true => None,
+      false => Some((bid_volume - ask_volume) / total)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn diff(json: &str) -> DepthUpdateEvent {
+    serde_json::from_str(json).unwrap()
+  }
+
+  #[test]
+  fn test_book_sync_and_imbalance() -> DreamrunnerResult<()> {
+    let snapshot: DepthSnapshot = serde_json::from_str(r#"{
+      "lastUpdateId": 100,
+      "bids": [["99.00", "2.0"], ["98.00", "3.0"], ["97.00", "5.0"]],
+      "asks": [["101.00", "1.0"], ["102.00", "4.0"]]
+    }"#)?;
+    // recorded diff sequence, the first two arrive before the snapshot is fetched
+    let diffs = [
+      r#"{"e":"depthUpdate","E":1,"s":"SOLUSDT","U":95,"u":99,"b":[["99.00","9.0"]],"a":[]}"#,
+      r#"{"e":"depthUpdate","E":2,"s":"SOLUSDT","U":100,"u":102,"b":[["99.00","4.0"]],"a":[["101.00","0"]]}"#,
+      r#"{"e":"depthUpdate","E":3,"s":"SOLUSDT","U":103,"u":104,"b":[["100.00","1.0"]],"a":[["103.00","2.0"]]}"#,
+    ];
+
+    let mut book = BookImbalance::new();
+    book.update(diff(diffs[0]))?;
+    book.update(diff(diffs[1]))?;
+    assert!(!book.is_synced());
+    assert_eq!(book.imbalance(2), None);
+
+    // first diff is already in the snapshot and dropped, second straddles it and is applied
+    book.sync(snapshot)?;
+    assert_eq!(book.last_update_id(), Some(102));
+    assert_eq!(book.best_bid(), Some(PriceLevel { price: 99.0, qty: 4.0 }));
+    assert_eq!(book.best_ask(), Some(PriceLevel { price: 102.0, qty: 4.0 }));
+
+    book.update(diff(diffs[2]))?;
+    assert_eq!(book.best_bid(), Some(PriceLevel { price: 100.0, qty: 1.0 }));
+    // top 2 bids 1 + 4, top 2 asks 4 + 2
+    assert_eq!(book.imbalance(2), Some((5.0 - 6.0) / 11.0));
+    Ok(())
+  }
+
+  #[test]
+  fn test_book_gap_resets() -> DreamrunnerResult<()> {
+    let mut book = BookImbalance::new();
+    book.sync(DepthSnapshot { last_update_id: 10, bids: vec![], asks: vec![] })?;
+    // stale diff is ignored
+    book.update(diff(r#"{"e":"depthUpdate","E":1,"s":"SOLUSDT","U":5,"u":10,"b":[["1.0","1.0"]],"a":[]}"#))?;
+    assert_eq!(book.best_bid(), None);
+
+    let err = book.update(diff(r#"{"e":"depthUpdate","E":2,"s":"SOLUSDT","U":12,"u":13,"b":[],"a":[]}"#));
+    assert!(matches!(err, Err(DreamrunnerError::DepthOutOfSync { expected: 11, first_update_id: 12 })));
+    assert!(!book.is_synced());
+    Ok(())
+  }
+}
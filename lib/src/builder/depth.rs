@@ -0,0 +1,34 @@
+use std::collections::BTreeMap;
+
+pub struct Depth {
+    /// Ticker symbol (e.g. BTCUSDC)
+    pub symbol: String,
+    /// Number of levels per side, Binance defaults to 100 and allows up to 5000
+    pub limit: Option<u16>,
+}
+
+impl Depth {
+    pub fn request(symbol: String, limit: Option<u16>) -> String {
+        let me = Self { symbol, limit };
+        me.create_request()
+    }
+
+    fn build(&self) -> BTreeMap<String, String> {
+        let mut btree = BTreeMap::<String, String>::new();
+        btree.insert("symbol".to_string(), self.symbol.to_string());
+        if let Some(limit) = self.limit {
+            btree.insert("limit".to_string(), limit.to_string());
+        }
+        btree
+    }
+
+    fn create_request(&self) -> String {
+        let btree = self.build();
+        let mut request = String::new();
+        for (key, value) in btree.iter() {
+            request.push_str(&format!("{}={}&", key, value));
+        }
+        request.pop();
+        request
+    }
+}
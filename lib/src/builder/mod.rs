@@ -7,6 +7,9 @@ pub mod exchange_info;
 pub mod price;
 pub mod trade;
 pub mod klines;
+pub mod depth;
+pub mod my_trades;
+pub mod oco;
 
 pub use account_info::*;
 pub use all_assets::*;
@@ -17,3 +20,6 @@ pub use exchange_info::*;
 pub use price::*;
 pub use trade::*;
 pub use klines::*;
+pub use depth::*;
+pub use my_trades::*;
+pub use oco::*;
@@ -0,0 +1,50 @@
+use std::collections::BTreeMap;
+
+pub struct MyTrades {}
+
+impl MyTrades {
+    /// Fills of `symbol`, only those of `order_id` if set.
+    /// Up to `limit` fills (500 by default, 1000 at most) with trade id of at least `from_id`, else the most recent.
+    pub fn request(
+        symbol: String,
+        order_id: Option<u64>,
+        from_id: Option<u64>,
+        limit: Option<u16>,
+        recv_window: Option<u32>,
+        timestamp: u64
+    ) -> String {
+        let btree = Self::build(symbol, order_id, from_id, limit, recv_window, timestamp);
+        let mut request = String::new();
+        for (key, value) in btree.iter() {
+            request.push_str(&format!("{}={}&", key, value));
+        }
+        request.pop();
+        request
+    }
+
+    fn build(
+        symbol: String,
+        order_id: Option<u64>,
+        from_id: Option<u64>,
+        limit: Option<u16>,
+        recv_window: Option<u32>,
+        timestamp: u64
+    ) -> BTreeMap<String, String> {
+        let mut btree = BTreeMap::<String, String>::new();
+        btree.insert("symbol".to_string(), symbol);
+        btree.insert("timestamp".to_string(), timestamp.to_string());
+        if let Some(order_id) = order_id {
+            btree.insert("orderId".to_string(), order_id.to_string());
+        }
+        if let Some(from_id) = from_id {
+            btree.insert("fromId".to_string(), from_id.to_string());
+        }
+        if let Some(limit) = limit {
+            btree.insert("limit".to_string(), limit.to_string());
+        }
+        if let Some(recv_window) = recv_window {
+            btree.insert("recvWindow".to_string(), recv_window.to_string());
+        }
+        btree
+    }
+}
@@ -0,0 +1,133 @@
+#![allow(clippy::result_large_err)]
+
+use crate::model::{Side, StpMode};
+use crate::{BinanceTrade, DreamrunnerError, DreamrunnerResult};
+
+/// Stop loss and take profit placed together as one OCO order list.
+/// On spot each resting sell locks the base it sells, so placed as separate orders for the full quantity
+/// the second is rejected for insufficient balance. Filling either leg of the list cancels the other.
+#[derive(Debug, Clone)]
+pub struct OcoOrder {
+    pub symbol: String,
+    /// Side of both legs, opposite the entry
+    pub side: Side,
+    pub quantity: f64,
+    pub list_client_order_id: String,
+    /// Client order ID of the LIMIT_MAKER take profit leg
+    pub limit_client_order_id: String,
+    /// Take profit limit price
+    pub price: f64,
+    /// Client order ID of the STOP_LOSS leg
+    pub stop_client_order_id: String,
+    /// Stop loss trigger price, sold at market once hit
+    pub stop_price: f64,
+    /// The number of milliseconds the request is valid for
+    pub recv_window: u32,
+    /// Self-trade prevention mode, the account default if None
+    pub self_trade_prevention_mode: Option<StpMode>,
+}
+
+impl OcoOrder {
+    /// OCO of the stop loss and take profit of one entry, which must sell the same symbol and quantity.
+    /// Each leg keeps its client order ID, so fills of either are tracked in its slot of the active order.
+    pub fn bracket(stop_loss: &BinanceTrade, take_profit: &BinanceTrade) -> DreamrunnerResult<Self> {
+        if stop_loss.symbol != take_profit.symbol || stop_loss.side != take_profit.side || stop_loss.quantity != take_profit.quantity {
+            return Err(DreamrunnerError::Custom(format!(
+                "Stop loss {} and take profit {} must have the same symbol, side and quantity",
+                stop_loss.client_order_id, take_profit.client_order_id
+            )));
+        }
+        let stop_price = stop_loss.stop_price.ok_or(DreamrunnerError::Custom(format!(
+            "Stop loss {} has no stop price", stop_loss.client_order_id
+        )))?;
+        let price = take_profit.price.ok_or(DreamrunnerError::Custom(format!(
+            "Take profit {} has no limit price", take_profit.client_order_id
+        )))?;
+        let prefix = stop_loss.client_order_id.split('-').next().unwrap_or_default();
+        Ok(Self {
+            symbol: stop_loss.symbol.clone(),
+            side: stop_loss.side,
+            quantity: stop_loss.quantity,
+            list_client_order_id: format!("{}-OCO", prefix),
+            limit_client_order_id: take_profit.client_order_id.clone(),
+            price,
+            stop_client_order_id: stop_loss.client_order_id.clone(),
+            stop_price,
+            recv_window: stop_loss.recv_window,
+            self_trade_prevention_mode: stop_loss.self_trade_prevention_mode,
+        })
+    }
+
+    fn build(&self, timestamp: u64) -> Vec<(String, String)> {
+        let mut btree = vec![
+            ("symbol".to_string(), self.symbol.clone()),
+            ("listClientOrderId".to_string(), self.list_client_order_id.clone()),
+            ("side".to_string(), self.side.fmt_binance().to_string()),
+            ("quantity".to_string(), self.quantity.to_string()),
+            ("limitClientOrderId".to_string(), self.limit_client_order_id.clone()),
+            ("price".to_string(), self.price.to_string()),
+            ("stopClientOrderId".to_string(), self.stop_client_order_id.clone()),
+            // no stopLimitPrice, so the stop leg is a STOP_LOSS market order
+            ("stopPrice".to_string(), self.stop_price.to_string()),
+        ];
+        if let Some(stp_mode) = self.self_trade_prevention_mode {
+            btree.push(("selfTradePreventionMode".to_string(), stp_mode.fmt_binance().to_string()));
+        }
+        btree.push(("timestamp".to_string(), timestamp.to_string()));
+        btree.push(("recvWindow".to_string(), self.recv_window.to_string()));
+        btree
+    }
+
+    /// Request query signed at `timestamp`, which should come from [`crate::Client::timestamp`]
+    pub fn request(&self, timestamp: u64) -> String {
+        let data = self.build(timestamp);
+        let mut request = String::new();
+        for (key, value) in data.iter() {
+            request.push_str(&format!("{}={}&", key, value));
+        }
+        request.pop();
+        request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::OrderType;
+
+    fn leg(client_order_id: &str, order_type: OrderType, price: Option<f64>, stop_price: Option<f64>) -> BinanceTrade {
+        BinanceTrade::new(
+            "SOLUSDT".to_string(),
+            client_order_id.to_string(),
+            Side::Short,
+            order_type,
+            1.5,
+            price,
+            Some(5000),
+            1_700_000_000_000,
+            stop_price,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_bracket_request() -> DreamrunnerResult<()> {
+        let stop_loss = leg("1700000000000-STOP_LOSS", OrderType::StopLoss, None, Some(98.0));
+        let take_profit = leg("1700000000000-TAKE_PROFIT", OrderType::TakeProfitLimit, Some(102.0), Some(102.0));
+        let req = OcoOrder::bracket(&stop_loss, &take_profit)?.request(1_700_000_000_000);
+        assert_eq!(
+            req,
+            "symbol=SOLUSDT&listClientOrderId=1700000000000-OCO&side=SELL&quantity=1.5\
+            &limitClientOrderId=1700000000000-TAKE_PROFIT&price=102\
+            &stopClientOrderId=1700000000000-STOP_LOSS&stopPrice=98\
+            &timestamp=1700000000000&recvWindow=5000"
+        );
+
+        let smaller = BinanceTrade { quantity: 1.0, ..take_profit.clone() };
+        assert!(OcoOrder::bracket(&stop_loss, &smaller).is_err());
+        let no_stop = BinanceTrade { stop_price: None, ..stop_loss };
+        assert!(OcoOrder::bracket(&no_stop, &take_profit).is_err());
+        Ok(())
+    }
+}
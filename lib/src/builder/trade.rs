@@ -1,9 +1,7 @@
 #![allow(clippy::result_large_err)]
 
-use crate::model::{OrderType, Side};
-use crate::errors::{DreamrunnerResult};
-use std::time::{SystemTime, UNIX_EPOCH};
-use time_series::trunc;
+use crate::model::{OrderType, Side, StpMode};
+use time_series::TimeInForce;
 use crate::Timestamp;
 
 #[derive(Debug, Clone)]
@@ -12,7 +10,7 @@ pub struct BinanceTrade {
     pub symbol: String,
     /// Side of the trade (BUY or SELL)
     pub side: Side,
-    /// Type of order (LIMIT, MARKET, STOP_LOSS_LIMIT, STOP_LOSS)
+    /// Type of order (LIMIT, MARKET, STOP_LOSS_LIMIT, STOP_LOSS, LIMIT_MAKER)
     pub order_type: OrderType,
     /// Quantity in quote asset of the symbol to trade (e.g. BTCUSDC with quantity 10000 would trade 10000 USDC)
     pub quantity: f64,
@@ -28,6 +26,11 @@ pub struct BinanceTrade {
     pub stop_price: Option<f64>,
     /// Trailing stop
     pub trailing_delta: Option<u32>,
+    /// Self-trade prevention mode, the account default if None.
+    /// Only sent to the spot order endpoint, which is the only order path this client has.
+    pub self_trade_prevention_mode: Option<StpMode>,
+    /// Time in force of limit orders, ignored by market and LIMIT_MAKER orders
+    pub time_in_force: TimeInForce,
 }
 impl Timestamp for BinanceTrade {
     fn timestamp(&self) -> i64 {
@@ -48,6 +51,7 @@ impl BinanceTrade {
         timestamp: i64,
         stop_price: Option<f64>,
         trailing_delta: Option<u32>,
+        self_trade_prevention_mode: Option<StpMode>,
     ) -> Self {
         let recv_window = recv_window.unwrap_or(10000);
         Self {
@@ -60,19 +64,19 @@ impl BinanceTrade {
             recv_window,
             timestamp,
             stop_price,
-            trailing_delta
+            trailing_delta,
+            self_trade_prevention_mode,
+            time_in_force: TimeInForce::Gtc
         }
     }
 
-    pub fn get_timestamp() -> DreamrunnerResult<u64> {
-        let system_time = SystemTime::now();
-        let since_epoch = system_time
-            .duration_since(UNIX_EPOCH)
-            .expect("System time is before UNIX EPOCH");
-        Ok(since_epoch.as_secs() * 1000 + u64::from(since_epoch.subsec_nanos()) / 1_000_000)
+    /// Rest a limit order per `time_in_force` rather than until canceled
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
     }
 
-    fn build(&self) -> Vec<(String, String)> {
+    fn build(&self, timestamp: u64) -> Vec<(String, String)> {
         let mut btree = Vec::<(String, String)>::new();
         btree.push(("symbol".to_string(), self.symbol.clone()));
         btree.push(("side".to_string(), self.side.fmt_binance().to_string()));
@@ -80,11 +84,12 @@ impl BinanceTrade {
             "type".to_string(),
             self.order_type.fmt_binance().to_string(),
         ));
+        // LIMIT_MAKER is a limit order but Binance rejects it with a timeInForce
         if self.order_type == OrderType::StopLossLimit
             || self.order_type == OrderType::Limit
             || self.order_type == OrderType::TakeProfitLimit
         {
-            btree.push(("timeInForce".to_string(), "GTC".to_string()));
+            btree.push(("timeInForce".to_string(), self.time_in_force.fmt_binance().to_string()));
         }
         btree.push(("quantity".to_string(), self.quantity.to_string()));
         if let Some(price) = self.price {
@@ -96,7 +101,9 @@ impl BinanceTrade {
         if let Some(stop_loss) = self.stop_price {
             btree.push(("stopPrice".to_string(), stop_loss.to_string()));
         }
-        let timestamp = Self::get_timestamp().expect("Failed to get timestamp");
+        if let Some(stp_mode) = self.self_trade_prevention_mode {
+            btree.push(("selfTradePreventionMode".to_string(), stp_mode.fmt_binance().to_string()));
+        }
         btree.push(("timestamp".to_string(), timestamp.to_string()));
         btree.push(("recvWindow".to_string(), self.recv_window.to_string()));
         btree.push((
@@ -106,8 +113,10 @@ impl BinanceTrade {
         btree
     }
 
-    pub fn request(&self) -> String {
-        let data = self.build();
+    /// Request query signed at `timestamp`, which should come from [`crate::Client::timestamp`]
+    /// since the order may be placed well after it was created.
+    pub fn request(&self, timestamp: u64) -> String {
+        let data = self.build(timestamp);
         let mut request = String::new();
         for (key, value) in data.iter() {
             request.push_str(&format!("{}={}&", key, value));
@@ -121,10 +130,21 @@ impl BinanceTrade {
         trailing_stop_pct * 100.0
     }
 
+    /// Stop price `stop_loss_pct` against the entry, unrounded so it can be snapped to the symbol's tick size
+    /// with [`crate::SymbolFilters::floor_price`]
     pub fn calc_stop_loss(entry_side: Side, price: f64, stop_loss_pct: f64) -> f64 {
         match entry_side {
-            Side::Long => trunc!(price * (1.0 - (stop_loss_pct / 100.0)), 2),
-            Side::Short => trunc!(price * (1.0 + (stop_loss_pct / 100.0)), 2),
+            Side::Long => price * (1.0 - (stop_loss_pct / 100.0)),
+            Side::Short => price * (1.0 + (stop_loss_pct / 100.0)),
+        }
+    }
+
+    /// Take profit price `take_profit_pct` in favor of the entry, unrounded so it can be snapped to the symbol's tick size
+    /// with [`crate::SymbolFilters::floor_price`]
+    pub fn calc_take_profit(entry_side: Side, price: f64, take_profit_pct: f64) -> f64 {
+        match entry_side {
+            Side::Long => price * (1.0 + (take_profit_pct / 100.0)),
+            Side::Short => price * (1.0 - (take_profit_pct / 100.0)),
         }
     }
 }
@@ -132,6 +152,7 @@ impl BinanceTrade {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use time_series::trunc;
 
     #[test]
     fn test_round_quantity() {
@@ -139,4 +160,86 @@ mod tests {
         let rounded = trunc!(qty, 5);
         println!("rounded: {}", rounded);
     }
+
+    #[test]
+    fn test_request_signed_at_timestamp() {
+        let trade = BinanceTrade::new(
+            "SOLUSDT".to_string(),
+            "1700000000000-ENTRY".to_string(),
+            Side::Long,
+            OrderType::Market,
+            1.0,
+            None,
+            Some(5000),
+            1_700_000_000_000,
+            None,
+            None,
+            None,
+        );
+        let req = trade.request(1_700_000_123_456);
+        assert!(req.contains("timestamp=1700000123456&recvWindow=5000"));
+    }
+
+    #[test]
+    fn test_limit_maker_omits_time_in_force() {
+        let trade = BinanceTrade::new(
+            "SOLUSDT".to_string(),
+            "1700000000000-ENTRY".to_string(),
+            Side::Long,
+            OrderType::LimitMaker,
+            1.0,
+            Some(100.0),
+            None,
+            1_700_000_000_000,
+            None,
+            None,
+            None,
+        );
+        let req = trade.request(1_700_000_000_000);
+        assert!(req.contains("type=LIMIT_MAKER"));
+        assert!(req.contains("price=100"));
+        assert!(!req.contains("timeInForce"));
+    }
+
+    #[test]
+    fn test_time_in_force() {
+        let trade = BinanceTrade::new(
+            "SOLUSDT".to_string(),
+            "1700000000000-ENTRY".to_string(),
+            Side::Short,
+            OrderType::Limit,
+            1.0,
+            Some(100.0),
+            None,
+            1_700_000_000_000,
+            None,
+            None,
+            None,
+        );
+        assert!(trade.request(1_700_000_000_000).contains("timeInForce=GTC"));
+        let trade = trade.with_time_in_force(TimeInForce::Ioc);
+        assert!(trade.request(1_700_000_000_000).contains("timeInForce=IOC"));
+        let trade = BinanceTrade { order_type: OrderType::Market, price: None, ..trade };
+        assert!(!trade.request(1_700_000_000_000).contains("timeInForce"));
+    }
+
+    #[test]
+    fn test_self_trade_prevention_mode() {
+        let mut trade = BinanceTrade::new(
+            "SOLUSDT".to_string(),
+            "1700000000000-ENTRY".to_string(),
+            Side::Long,
+            OrderType::Market,
+            1.0,
+            None,
+            None,
+            1_700_000_000_000,
+            None,
+            None,
+            Some(StpMode::ExpireMaker),
+        );
+        assert!(trade.request(1_700_000_000_000).contains("selfTradePreventionMode=EXPIRE_MAKER"));
+        trade.self_trade_prevention_mode = None;
+        assert!(!trade.request(1_700_000_000_000).contains("selfTradePreventionMode"));
+    }
 }
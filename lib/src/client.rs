@@ -1,15 +1,22 @@
 #![allow(clippy::result_large_err)]
 
-use crate::api::API;
+use crate::api::{Spot, API};
 use crate::errors::{DreamrunnerResult};
-use crate::{BinanceContentError, DreamrunnerError};
+use crate::metrics::{EndpointMetrics, Metrics};
+use crate::model::ServerTime;
+use crate::BinanceContentError;
+use async_trait::async_trait;
 use hex::encode as hex_encode;
 use hmac::{Hmac, Mac};
 use log::*;
-use reqwest::Response;
+use reqwest::{RequestBuilder, Response};
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, USER_AGENT};
 use serde::de::DeserializeOwned;
 use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Clone)]
 pub struct Client {
@@ -17,6 +24,10 @@ pub struct Client {
     secret_key: String,
     host: String,
     inner_client: reqwest::Client,
+    /// Server time minus local time in milliseconds, shared by all clones
+    time_offset: Arc<AtomicI64>,
+    /// Request durations per endpoint, shared by all clones
+    metrics: Metrics,
 }
 
 impl Client {
@@ -30,30 +41,73 @@ impl Client {
                 .timeout(std::time::Duration::from_secs(10))
                 .connect_timeout(std::time::Duration::from_secs(10))
                 .build()?,
+            time_offset: Arc::new(AtomicI64::new(0)),
+            metrics: Metrics::new(),
         })
     }
 
+    /// Local UNIX timestamp in milliseconds
+    pub fn local_timestamp() -> u64 {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time is before UNIX EPOCH");
+        since_epoch.as_secs() * 1000 + u64::from(since_epoch.subsec_nanos()) / 1_000_000
+    }
+
+    /// UNIX timestamp in milliseconds corrected by the server time offset.
+    /// Every signed request should be timestamped with this.
+    pub fn timestamp(&self) -> u64 {
+        (Self::local_timestamp() as i64 + self.time_offset()) as u64
+    }
+
+    pub fn time_offset(&self) -> i64 {
+        self.time_offset.load(Ordering::Relaxed)
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Count, p50 and p99 request duration per endpoint
+    pub fn metrics_snapshot(&self) -> BTreeMap<String, EndpointMetrics> {
+        self.metrics.snapshot()
+    }
+
+    /// Refresh the server time offset from `Spot::Time`.
+    /// Local time is taken at the midpoint of the request to cancel out network latency.
+    pub async fn sync_time(&self) -> DreamrunnerResult<i64> {
+        let pre = Self::local_timestamp();
+        let res = self.get::<ServerTime>(API::Spot(Spot::Time), None).await?;
+        let post = Self::local_timestamp();
+        let offset = res.server_time as i64 - ((pre + post) / 2) as i64;
+        self.time_offset.store(offset, Ordering::Relaxed);
+        info!("Server time offset: {}ms", offset);
+        Ok(offset)
+    }
+
     pub async fn get_signed<T: DeserializeOwned>(
         &self,
         endpoint: API,
         request: Option<String>,
     ) -> DreamrunnerResult<T> {
-        let url = self.sign_request(endpoint, request);
+        let path = String::from(endpoint);
+        let url = self.sign_request(&path, request);
         debug!("url: {}", url);
         let client = &self.inner_client;
-        let response = client
+        let request = client
             .get(url.as_str())
-            .headers(self.build_headers(true)?)
-            .send().await?;
+            .headers(self.build_headers(true)?);
+        let response = self.send(&path, request).await?;
         self.handler(response).await
     }
 
     pub async fn post_signed<T: DeserializeOwned>(&self, endpoint: API, request: String) -> DreamrunnerResult<T> {
-        let url = self.sign_request(endpoint, Some(request));
+        let path = String::from(endpoint);
+        let url = self.sign_request(&path, Some(request));
         info!("url: {}", url);
         let client = &self.inner_client;
         let request = client.post(url.as_str()).headers(self.build_headers(true)?);
-        let response = request.send().await?;
+        let response = self.send(&path, request).await?;
         self.handler(response).await
     }
 
@@ -62,18 +116,20 @@ impl Client {
         endpoint: API,
         request: Option<String>,
     ) -> DreamrunnerResult<T> {
-        let url = self.sign_request(endpoint, request);
+        let path = String::from(endpoint);
+        let url = self.sign_request(&path, request);
         debug!("url: {}", url);
         let client = &self.inner_client;
-        let response = client
+        let request = client
             .delete(url.as_str())
-            .headers(self.build_headers(true)?)
-            .send().await?;
+            .headers(self.build_headers(true)?);
+        let response = self.send(&path, request).await?;
         self.handler(response).await
     }
 
     pub async fn get<T: DeserializeOwned>(&self, endpoint: API, request: Option<String>) -> DreamrunnerResult<T> {
-        let mut url: String = format!("{}{}", self.host, String::from(endpoint));
+        let path = String::from(endpoint);
+        let mut url: String = format!("{}{}", self.host, path);
         if let Some(request) = request {
             if !request.is_empty() {
                 url.push_str(format!("?{}", request).as_str());
@@ -81,64 +137,67 @@ impl Client {
         }
         debug!("url: {}", url);
         let client = &self.inner_client;
-        let response = client.get(url.as_str()).send().await?;
+        let response = self.send(&path, client.get(url.as_str())).await?;
         self.handler(response).await
     }
 
     #[allow(dead_code)]
     pub async fn post<T: DeserializeOwned>(&self, endpoint: API) -> DreamrunnerResult<T> {
-        let url: String = format!("{}{}", self.host, String::from(endpoint));
+        let path = String::from(endpoint);
+        let url: String = format!("{}{}", self.host, path);
         debug!("url: {}", url);
         let client = &self.inner_client;
-        let response = client
+        let request = client
             .post(url.as_str())
-            .headers(self.build_headers(false)?)
-            .send().await?;
+            .headers(self.build_headers(false)?);
+        let response = self.send(&path, request).await?;
         self.handler(response).await
     }
 
     #[allow(dead_code)]
     pub async fn put<T: DeserializeOwned>(&self, endpoint: API, listen_key: &str) -> DreamrunnerResult<T> {
-        let url: String = format!("{}{}", self.host, String::from(endpoint));
+        let path = String::from(endpoint);
+        let url: String = format!("{}{}", self.host, path);
         debug!("url: {}", url);
         let data: String = format!("listenKey={}", listen_key);
         let client = &self.inner_client;
-        let response = client
+        let request = client
             .put(url.as_str())
             .headers(self.build_headers(false)?)
-            .body(data)
-            .send().await?;
+            .body(data);
+        let response = self.send(&path, request).await?;
         self.handler(response).await
     }
 
     #[allow(dead_code)]
     pub async fn delete<T: DeserializeOwned>(&self, endpoint: API, listen_key: &str) -> DreamrunnerResult<T> {
-        let url: String = format!("{}{}", self.host, String::from(endpoint));
+        let path = String::from(endpoint);
+        let url: String = format!("{}{}", self.host, path);
         debug!("url: {}", url);
         let data: String = format!("listenKey={}", listen_key);
         let client = &self.inner_client;
-        let response = client
+        let request = client
             .delete(url.as_str())
             .headers(self.build_headers(false)?)
-            .body(data)
-            .send().await?;
+            .body(data);
+        let response = self.send(&path, request).await?;
         self.handler(response).await
     }
 
     // Request must be signed
-    fn sign_request(&self, endpoint: API, request: Option<String>) -> String {
+    fn sign_request(&self, path: &str, request: Option<String>) -> String {
         if let Some(request) = request {
             let mut signed_key =
                 Hmac::<Sha256>::new_from_slice(self.secret_key.as_bytes()).unwrap();
             signed_key.update(request.as_bytes());
             let signature = hex_encode(signed_key.finalize().into_bytes());
             let request_body: String = format!("{}&signature={}", request, signature);
-            format!("{}{}?{}", self.host, String::from(endpoint), request_body)
+            format!("{}{}?{}", self.host, path, request_body)
         } else {
             let signed_key = Hmac::<Sha256>::new_from_slice(self.secret_key.as_bytes()).unwrap();
             let signature = hex_encode(signed_key.finalize().into_bytes());
             let request_body: String = format!("&signature={}", signature);
-            format!("{}{}?{}", self.host, String::from(endpoint), request_body)
+            format!("{}{}?{}", self.host, path, request_body)
         }
     }
 
@@ -155,12 +214,77 @@ impl Client {
         Ok(custom_headers)
     }
 
+    /// Send the request and record its round trip duration under the endpoint path
+    async fn send(&self, path: &str, request: RequestBuilder) -> DreamrunnerResult<Response> {
+        let start = Instant::now();
+        let response = request.send().await;
+        self.metrics.record(path, start.elapsed());
+        Ok(response?)
+    }
+
     async fn handler<T: DeserializeOwned>(&self, response: Response) -> DreamrunnerResult<T> {
         if response.status().is_success() {
             Ok(response.json::<T>().await?)
         } else {
             let error: BinanceContentError = response.json().await?;
-            Err(DreamrunnerError::Binance(error))
+            Err(error.into())
         }
     }
 }
+
+/// Binance REST requests the engine depends on.
+/// Implemented by [`Client`] and by [`crate::MockClient`] to run the engine offline.
+#[async_trait]
+pub trait BinanceApi: Clone + Send + Sync {
+    /// UNIX timestamp in milliseconds every signed request is stamped with
+    fn timestamp(&self) -> u64;
+
+    fn metrics(&self) -> &Metrics;
+
+    /// Count, p50 and p99 request duration per endpoint
+    fn metrics_snapshot(&self) -> BTreeMap<String, EndpointMetrics> {
+        self.metrics().snapshot()
+    }
+
+    /// Refresh the server time offset, returns the offset in milliseconds
+    async fn sync_time(&self) -> DreamrunnerResult<i64>;
+
+    async fn get<T: DeserializeOwned>(&self, endpoint: API, request: Option<String>) -> DreamrunnerResult<T>;
+
+    async fn get_signed<T: DeserializeOwned>(&self, endpoint: API, request: Option<String>) -> DreamrunnerResult<T>;
+
+    async fn post_signed<T: DeserializeOwned>(&self, endpoint: API, request: String) -> DreamrunnerResult<T>;
+
+    async fn delete_signed<T: DeserializeOwned>(&self, endpoint: API, request: Option<String>) -> DreamrunnerResult<T>;
+}
+
+#[async_trait]
+impl BinanceApi for Client {
+    fn timestamp(&self) -> u64 {
+        Client::timestamp(self)
+    }
+
+    fn metrics(&self) -> &Metrics {
+        Client::metrics(self)
+    }
+
+    async fn sync_time(&self) -> DreamrunnerResult<i64> {
+        Client::sync_time(self).await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, endpoint: API, request: Option<String>) -> DreamrunnerResult<T> {
+        Client::get(self, endpoint, request).await
+    }
+
+    async fn get_signed<T: DeserializeOwned>(&self, endpoint: API, request: Option<String>) -> DreamrunnerResult<T> {
+        Client::get_signed(self, endpoint, request).await
+    }
+
+    async fn post_signed<T: DeserializeOwned>(&self, endpoint: API, request: String) -> DreamrunnerResult<T> {
+        Client::post_signed(self, endpoint, request).await
+    }
+
+    async fn delete_signed<T: DeserializeOwned>(&self, endpoint: API, request: Option<String>) -> DreamrunnerResult<T> {
+        Client::delete_signed(self, endpoint, request).await
+    }
+}
@@ -1,3 +1,6 @@
+/// Millis a signed request stays valid unless configured otherwise, Binance's own default
+pub const DEFAULT_RECV_WINDOW: u32 = 5000;
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub rest_api_endpoint: String,
@@ -10,7 +13,7 @@ impl Default for Config {
         Self {
             rest_api_endpoint: "https://api.binance.us".into(),
             ws_endpoint: "wss://stream.binance.us:9443/ws".into(),
-            recv_window: 5000,
+            recv_window: DEFAULT_RECV_WINDOW as u64,
         }
     }
 }
@@ -1,5 +1,5 @@
 use log::error;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::env::VarError;
 use std::num::ParseFloatError;
 use std::str::ParseBoolError;
@@ -26,8 +26,18 @@ pub enum DreamrunnerError {
     SideInvalid,
     #[error("OrderTypeInvalid")]
     OrderTypeInvalid,
+    #[error("StpModeInvalid")]
+    StpModeInvalid,
+    #[error("IntervalInvalid: {0}")]
+    IntervalInvalid(String),
     #[error("WebSocketDisconnected")]
     WebSocketDisconnected,
+    #[error("ReconnectAttemptsExhausted: gave up after {0} attempts")]
+    ReconnectAttemptsExhausted(u32),
+    #[error("DataStall: no websocket data for {0}s")]
+    DataStall(u64),
+    #[error("DepthOutOfSync: expected update {expected}, received first update {first_update_id}")]
+    DepthOutOfSync { expected: u64, first_update_id: u64 },
     #[error("Reqwest: {0}")]
     Reqwest(#[from] reqwest::Error),
     #[error("InvalidHeader: {0}")]
@@ -72,6 +82,21 @@ pub enum DreamrunnerError {
     PayloadError(#[from] actix_web::error::PayloadError),
     #[error("Alert missing price")]
     AlertMissingPrice,
+    #[error("Alert signature invalid")]
+    AlertSignatureInvalid,
+    #[error("AssetNotFound: {0}")]
+    AssetNotFound(String),
+    #[error("BelowMinNotional: {notional} < {min_notional}")]
+    BelowMinNotional { notional: f64, min_notional: f64 },
+    /// Binance rejected an order below the symbol's minimum notional
+    #[error("NotionalViolation: {0}")]
+    NotionalViolation(#[source] BinanceContentError),
+    /// Binance rejected an order quantity outside the symbol's lot size
+    #[error("LotSizeViolation: {0}")]
+    LotSizeViolation(#[source] BinanceContentError),
+    /// Binance rejected an order price outside the symbol's price filters
+    #[error("PriceFilterViolation: {0}")]
+    PriceFilterViolation(#[source] BinanceContentError),
     #[error("JoinError: {0}")]
     JoinError(#[from] tokio::task::JoinError),
 }
@@ -79,28 +104,156 @@ pub enum DreamrunnerError {
 impl ResponseError for DreamrunnerError {
     fn status_code(&self) -> StatusCode {
         match &self {
+            Self::Binance(err) => err.status_code(),
             Self::SideInvalid => StatusCode::BAD_REQUEST,
             Self::OrderTypeInvalid => StatusCode::BAD_REQUEST,
+            Self::StpModeInvalid => StatusCode::BAD_REQUEST,
+            Self::IntervalInvalid(_) => StatusCode::BAD_REQUEST,
             Self::ParseFloat(_) => StatusCode::BAD_REQUEST,
             Self::ParseBool(_) => StatusCode::BAD_REQUEST,
+            Self::ParseInt(_) => StatusCode::BAD_REQUEST,
             Self::PayloadError(_) => StatusCode::BAD_REQUEST,
+            Self::Overflow => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::BelowMinNotional { .. } => StatusCode::BAD_REQUEST,
+            Self::NotionalViolation(_) => StatusCode::BAD_REQUEST,
+            Self::LotSizeViolation(_) => StatusCode::BAD_REQUEST,
+            Self::PriceFilterViolation(_) => StatusCode::BAD_REQUEST,
+            Self::AlertMissingPrice => StatusCode::BAD_REQUEST,
+            Self::AlertSignatureInvalid => StatusCode::UNAUTHORIZED,
+            Self::AssetNotFound(_) => StatusCode::NOT_FOUND,
+            Self::Reqwest(_) => StatusCode::BAD_GATEWAY,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 
+    /// JSON body `{code, message}` where `code` is the Binance error code if upstream rejected the request,
+    /// otherwise the HTTP status code.
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code()).body(self.to_string())
+        let status = self.status_code();
+        let code = match self.binance() {
+            Some(err) => err.code as i64,
+            None => status.as_u16() as i64,
+        };
+        HttpResponse::build(status).json(ErrorBody {
+            code,
+            message: self.to_string(),
+        })
     }
 }
 
+impl DreamrunnerError {
+    /// Raw Binance error if upstream rejected the request
+    pub fn binance(&self) -> Option<&BinanceContentError> {
+        match &self {
+            Self::Binance(err)
+            | Self::NotionalViolation(err)
+            | Self::LotSizeViolation(err)
+            | Self::PriceFilterViolation(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Symbol filter rejects get their own variant so callers can match on them, every other error stays [`DreamrunnerError::Binance`]
+impl From<BinanceContentError> for DreamrunnerError {
+    fn from(err: BinanceContentError) -> Self {
+        match err.filter_failure() {
+            Some("NOTIONAL" | "MIN_NOTIONAL") => Self::NotionalViolation(err),
+            Some("LOT_SIZE" | "MARKET_LOT_SIZE") => Self::LotSizeViolation(err),
+            Some("PRICE_FILTER" | "PERCENT_PRICE" | "PERCENT_PRICE_BY_SIDE") => Self::PriceFilterViolation(err),
+            _ => Self::Binance(err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub code: i64,
+    pub message: String,
+}
+
 #[derive(Debug, Clone, Deserialize, Error)]
 pub struct BinanceContentError {
     pub code: i16,
     pub msg: String,
 }
 
+impl BinanceContentError {
+    /// A LIMIT_MAKER order rejected because it would cross the book and fill as taker
+    pub fn would_take(&self) -> bool {
+        self.code == -2010 && self.msg.contains("immediately match")
+    }
+
+    /// Symbol filter an order failed, e.g. "LOT_SIZE" from "Filter failure: LOT_SIZE".
+    /// Binance rejects filter failures with -1013, or -2010 when the order is rejected by the matching engine.
+    pub fn filter_failure(&self) -> Option<&str> {
+        match self.code {
+            -1013 | -2010 => self.msg.strip_prefix("Filter failure: ").map(str::trim),
+            _ => None,
+        }
+    }
+
+    /// HTTP status to surface for a Binance error code.
+    /// See https://binance-docs.github.io/apidocs/spot/en/#error-codes
+    pub fn status_code(&self) -> StatusCode {
+        match self.code {
+            // TOO_MANY_REQUESTS, TOO_MANY_ORDERS
+            -1003 | -1015 => StatusCode::TOO_MANY_REQUESTS,
+            // DISCONNECTED, TIMEOUT, INVALID_TIMESTAMP (clock drift or recv_window expired)
+            -1001 | -1007 | -1021 => StatusCode::SERVICE_UNAVAILABLE,
+            // NO_SUCH_ORDER
+            -2013 => StatusCode::NOT_FOUND,
+            // malformed request parameters, NEW_ORDER_REJECTED, CANCEL_REJECTED
+            -1199..=-1100 | -2010 | -2011 => StatusCode::BAD_REQUEST,
+            _ => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+
 impl std::fmt::Display for BinanceContentError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "BinanceContentError: code: {}, msg: {}", self.code, self.msg)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_status_codes() {
+        let binance = |code: i16| DreamrunnerError::Binance(BinanceContentError {
+            code,
+            msg: String::new(),
+        });
+        assert_eq!(binance(-1003).status_code(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(binance(-1021).status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(binance(-1102).status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(binance(-9999).status_code(), StatusCode::BAD_GATEWAY);
+        assert_eq!(DreamrunnerError::SideInvalid.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            DreamrunnerError::BelowMinNotional { notional: 1.0, min_notional: 5.0 }.status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(DreamrunnerError::AssetNotFound("SOL".to_string()).status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_filter_failures() {
+        let error = |code: i16, msg: &str| DreamrunnerError::from(BinanceContentError {
+            code,
+            msg: msg.to_string(),
+        });
+        assert!(matches!(error(-1013, "Filter failure: NOTIONAL"), DreamrunnerError::NotionalViolation(_)));
+        assert!(matches!(error(-1013, "Filter failure: MIN_NOTIONAL"), DreamrunnerError::NotionalViolation(_)));
+        assert!(matches!(error(-1013, "Filter failure: LOT_SIZE"), DreamrunnerError::LotSizeViolation(_)));
+        assert!(matches!(error(-2010, "Filter failure: PRICE_FILTER"), DreamrunnerError::PriceFilterViolation(_)));
+        assert!(matches!(error(-2010, "Order would immediately match and take."), DreamrunnerError::Binance(_)));
+        assert!(matches!(error(-1013, "Filter failure: ICEBERG_PARTS"), DreamrunnerError::Binance(_)));
+
+        let lot_size = error(-1013, "Filter failure: LOT_SIZE");
+        assert_eq!(lot_size.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(lot_size.binance().map(|err| err.code), Some(-1013));
+        assert!(std::error::Error::source(&lot_size).is_some());
+    }
+}
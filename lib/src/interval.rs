@@ -1,6 +1,7 @@
+use std::str::FromStr;
+use crate::DreamrunnerError;
 
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Interval {
   OneMinute,
   ThreeMinutes,
@@ -60,4 +61,51 @@ impl Interval {
       Interval::OneMonth => 43200,
     }
   }
-}
\ No newline at end of file
+
+  /// Number of candles in a year of 24/7 trading, the annualization factor for per-candle returns
+  pub fn periods_per_year(&self) -> f64 {
+    match self {
+      Interval::OneMonth => 12.0,
+      _ => 365.0 * 1440.0 / self.minutes() as f64
+    }
+  }
+}
+
+impl FromStr for Interval {
+  type Err = DreamrunnerError;
+  /// Parse a Binance interval string such as "30m" or "1d"
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "1m" => Ok(Interval::OneMinute),
+      "3m" => Ok(Interval::ThreeMinutes),
+      "5m" => Ok(Interval::FiveMinutes),
+      "15m" => Ok(Interval::FifteenMinutes),
+      "30m" => Ok(Interval::ThirtyMinutes),
+      "1h" => Ok(Interval::OneHour),
+      "2h" => Ok(Interval::TwoHours),
+      "4h" => Ok(Interval::FourHours),
+      "6h" => Ok(Interval::SixHours),
+      "8h" => Ok(Interval::EightHours),
+      "12h" => Ok(Interval::TwelveHours),
+      "1d" => Ok(Interval::OneDay),
+      "3d" => Ok(Interval::ThreeDays),
+      "1w" => Ok(Interval::OneWeek),
+      "1M" => Ok(Interval::OneMonth),
+      _ => Err(DreamrunnerError::IntervalInvalid(s.to_string())),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_periods_per_year() {
+    assert_eq!(Interval::ThirtyMinutes.periods_per_year(), 365.0 * 24.0 * 2.0);
+    assert_eq!(Interval::OneHour.periods_per_year(), 365.0 * 24.0);
+    assert_eq!(Interval::OneDay.periods_per_year(), 365.0);
+    assert_eq!(Interval::OneWeek.periods_per_year(), 365.0 / 7.0);
+    assert_eq!(Interval::OneMonth.periods_per_year(), 12.0);
+  }
+}
@@ -11,6 +11,10 @@ pub mod trade;
 pub mod interval;
 pub mod alert;
 pub mod traits;
+pub mod metrics;
+pub mod book;
+pub mod mock;
+pub mod backoff;
 
 pub use account::*;
 pub use api::*;
@@ -24,3 +28,7 @@ pub use websocket::*;
 pub use interval::*;
 pub use alert::*;
 pub use traits::*;
+pub use metrics::*;
+pub use book::*;
+pub use mock::*;
+pub use backoff::*;
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Most recent durations kept per endpoint to compute percentiles from
+const MAX_SAMPLES: usize = 1000;
+
+/// Key under which the engine records order fill latency
+pub const ORDER_FILL_METRIC: &str = "order_fill";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EndpointMetrics {
+    /// Total durations recorded
+    pub count: u64,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    count: u64,
+    /// Duration in millis, oldest first
+    millis: VecDeque<f64>,
+}
+
+/// Per-endpoint duration accumulator shared by all clones of a [`crate::Client`].
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Mutex<HashMap<String, Samples>>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, endpoint: &str, duration: Duration) {
+        self.record_ms(endpoint, duration.as_micros() as f64 / 1000.0);
+    }
+
+    pub fn record_ms(&self, endpoint: &str, millis: f64) {
+        let mut inner = self.inner.lock().expect("Metrics lock poisoned");
+        let samples = inner.entry(endpoint.to_string()).or_default();
+        samples.count += 1;
+        if samples.millis.len() == MAX_SAMPLES {
+            samples.millis.pop_front();
+        }
+        samples.millis.push_back(millis);
+    }
+
+    /// Count and p50/p99 of the last [`MAX_SAMPLES`] durations per endpoint
+    pub fn snapshot(&self) -> BTreeMap<String, EndpointMetrics> {
+        let inner = self.inner.lock().expect("Metrics lock poisoned");
+        inner
+            .iter()
+            .map(|(endpoint, samples)| {
+                let mut sorted: Vec<f64> = samples.millis.iter().copied().collect();
+                sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let metrics = EndpointMetrics {
+                    count: samples.count,
+                    p50_ms: percentile(&sorted, 50.0),
+                    p99_ms: percentile(&sorted, 99.0),
+                };
+                (endpoint.clone(), metrics)
+            })
+            .collect()
+    }
+}
+
+/// Nearest-rank percentile of an ascending slice
+fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (pct / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_percentiles() {
+        let metrics = Metrics::new();
+        for ms in 1..=100 {
+            metrics.record_ms("/api/v3/order", ms as f64);
+        }
+        metrics.record(ORDER_FILL_METRIC, Duration::from_millis(250));
+
+        let snapshot = metrics.snapshot();
+        let order = snapshot.get("/api/v3/order").unwrap();
+        assert_eq!(order.count, 100);
+        assert_eq!(order.p50_ms, 50.0);
+        assert_eq!(order.p99_ms, 99.0);
+        let fill = snapshot.get(ORDER_FILL_METRIC).unwrap();
+        assert_eq!(fill.count, 1);
+        assert_eq!(fill.p99_ms, 250.0);
+    }
+
+    #[test]
+    fn test_samples_bounded() {
+        let metrics = Metrics::new();
+        for _ in 0..MAX_SAMPLES {
+            metrics.record_ms("/api/v3/account", 1000.0);
+        }
+        for _ in 0..MAX_SAMPLES {
+            metrics.record_ms("/api/v3/account", 1.0);
+        }
+        let account = metrics.snapshot()["/api/v3/account"];
+        assert_eq!(account.count, 2 * MAX_SAMPLES as u64);
+        assert_eq!(account.p99_ms, 1.0);
+    }
+}
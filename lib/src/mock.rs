@@ -0,0 +1,212 @@
+#![allow(clippy::result_large_err)]
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use crate::api::{Spot, API};
+use crate::client::{BinanceApi, Client};
+use crate::errors::{BinanceContentError, DreamrunnerError, DreamrunnerResult};
+use crate::metrics::Metrics;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MockMethod {
+    Get,
+    Post,
+    Delete,
+}
+
+/// Responses keyed by method and endpoint path
+type MockResponses<T> = Arc<Mutex<HashMap<(MockMethod, String), T>>>;
+
+/// A request received by [`MockClient`]
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: MockMethod,
+    pub path: String,
+    pub request: Option<String>,
+}
+
+impl MockRequest {
+    /// Query string params of the request
+    pub fn params(&self) -> BTreeMap<String, String> {
+        self.request
+            .iter()
+            .flat_map(|req| req.split('&'))
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    pub fn param(&self, key: &str) -> Option<String> {
+        self.params().remove(key)
+    }
+}
+
+/// [`BinanceApi`] that records every request and answers with canned JSON instead of calling Binance.
+///
+/// Responses are keyed by method and endpoint, so each call to an endpoint returns the same response,
+/// unless errors or responses are queued for it with [`MockClient::with_errors`] or [`MockClient::with_responses`],
+/// which are returned first.
+/// The clock is fixed until [`MockClient::set_time`] so order timestamps are deterministic.
+/// Clones share responses, requests and the clock.
+#[derive(Debug, Clone, Default)]
+pub struct MockClient {
+    responses: MockResponses<serde_json::Value>,
+    errors: MockResponses<VecDeque<BinanceContentError>>,
+    queued: MockResponses<VecDeque<serde_json::Value>>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    time: Arc<AtomicI64>,
+    metrics: Metrics,
+}
+
+impl MockClient {
+    /// Clock starts at the local time
+    pub fn new() -> Self {
+        let client = Self::default();
+        client.set_time(Client::local_timestamp() as i64);
+        client
+    }
+
+    pub fn with_response(self, method: MockMethod, endpoint: API, response: serde_json::Value) -> Self {
+        self.set_response(method, endpoint, response);
+        self
+    }
+
+    pub fn set_response(&self, method: MockMethod, endpoint: API, response: serde_json::Value) {
+        let mut responses = self.responses.lock().expect("MockClient lock poisoned");
+        responses.insert((method, String::from(endpoint)), response);
+    }
+
+    /// Answer the next calls to an endpoint with these Binance errors in order, then with its response
+    pub fn with_errors(self, method: MockMethod, endpoint: API, errors: Vec<BinanceContentError>) -> Self {
+        self.errors
+            .lock()
+            .expect("MockClient lock poisoned")
+            .entry((method, String::from(endpoint)))
+            .or_default()
+            .extend(errors);
+        self
+    }
+
+    /// Answer the next calls to an endpoint with these responses in order, e.g. pages, then with its response
+    pub fn with_responses(self, method: MockMethod, endpoint: API, responses: Vec<serde_json::Value>) -> Self {
+        self.queued
+            .lock()
+            .expect("MockClient lock poisoned")
+            .entry((method, String::from(endpoint)))
+            .or_default()
+            .extend(responses);
+        self
+    }
+
+    /// Answer `Spot::Account` with these free balances and nothing locked
+    pub fn with_balances(self, balances: &[(&str, f64)]) -> Self {
+        let balances: Vec<serde_json::Value> = balances
+            .iter()
+            .map(|(asset, free)| serde_json::json!({ "asset": asset, "free": free.to_string(), "locked": "0" }))
+            .collect();
+        self.with_response(MockMethod::Get, API::Spot(Spot::Account), serde_json::json!({
+            "makerCommission": 0,
+            "takerCommission": 0,
+            "buyerCommission": 0,
+            "sellerCommission": 0,
+            "commissionRates": { "maker": "0", "taker": "0", "buyer": "0", "seller": "0" },
+            "canTrade": true,
+            "canWithdraw": false,
+            "canDeposit": false,
+            "brokered": false,
+            "requireSelfTradePrevention": false,
+            "updateTime": 0,
+            "accountType": "SPOT",
+            "balances": balances,
+            "permissions": ["SPOT"]
+        }))
+    }
+
+    pub fn set_time(&self, unix_ms: i64) {
+        self.time.store(unix_ms, Ordering::Relaxed);
+    }
+
+    /// All requests received, oldest first
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().expect("MockClient lock poisoned").clone()
+    }
+
+    /// Orders placed with `Spot::Order`, oldest first
+    pub fn orders(&self) -> Vec<MockRequest> {
+        let path = String::from(API::Spot(Spot::Order));
+        self.requests()
+            .into_iter()
+            .filter(|r| r.method == MockMethod::Post && r.path == path)
+            .collect()
+    }
+
+    pub fn clear_requests(&self) {
+        self.requests.lock().expect("MockClient lock poisoned").clear();
+    }
+
+    fn respond<T: DeserializeOwned>(&self, method: MockMethod, endpoint: API, request: Option<String>) -> DreamrunnerResult<T> {
+        let path = String::from(endpoint);
+        self.requests.lock().expect("MockClient lock poisoned").push(MockRequest {
+            method,
+            path: path.clone(),
+            request,
+        });
+        let error = self
+            .errors
+            .lock()
+            .expect("MockClient lock poisoned")
+            .get_mut(&(method, path.clone()))
+            .and_then(|errors| errors.pop_front());
+        if let Some(error) = error {
+            return Err(error.into());
+        }
+        let queued = self
+            .queued
+            .lock()
+            .expect("MockClient lock poisoned")
+            .get_mut(&(method, path.clone()))
+            .and_then(|queued| queued.pop_front());
+        if let Some(response) = queued {
+            return Ok(serde_json::from_value(response)?);
+        }
+        let responses = self.responses.lock().expect("MockClient lock poisoned");
+        let response = responses
+            .get(&(method, path.clone()))
+            .ok_or(DreamrunnerError::Custom(format!("No mock response for {:?} {}", method, path)))?;
+        Ok(serde_json::from_value(response.clone())?)
+    }
+}
+
+#[async_trait]
+impl BinanceApi for MockClient {
+    fn timestamp(&self) -> u64 {
+        self.time.load(Ordering::Relaxed) as u64
+    }
+
+    fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    async fn sync_time(&self) -> DreamrunnerResult<i64> {
+        Ok(0)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, endpoint: API, request: Option<String>) -> DreamrunnerResult<T> {
+        self.respond(MockMethod::Get, endpoint, request)
+    }
+
+    async fn get_signed<T: DeserializeOwned>(&self, endpoint: API, request: Option<String>) -> DreamrunnerResult<T> {
+        self.respond(MockMethod::Get, endpoint, request)
+    }
+
+    async fn post_signed<T: DeserializeOwned>(&self, endpoint: API, request: String) -> DreamrunnerResult<T> {
+        self.respond(MockMethod::Post, endpoint, Some(request))
+    }
+
+    async fn delete_signed<T: DeserializeOwned>(&self, endpoint: API, request: Option<String>) -> DreamrunnerResult<T> {
+        self.respond(MockMethod::Delete, endpoint, request)
+    }
+}
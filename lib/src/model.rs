@@ -48,6 +48,19 @@ pub struct Symbol {
     pub filters: Vec<Filters>,
 }
 
+impl Symbol {
+    /// Base and quote asset of `ticker`, e.g. ("SOL", "USDT") for "SOLUSDT".
+    /// Read from the exchange info since tickers like "BTCUSDT" and "BTCUSD" can't be split by string alone.
+    pub fn split_ticker(ticker: &str, exchange_info: &ExchangeInformation) -> DreamrunnerResult<(String, String)> {
+        let symbol = exchange_info
+            .symbols
+            .iter()
+            .find(|s| s.symbol == ticker)
+            .ok_or(DreamrunnerError::Custom(format!("Symbol {} missing from exchange info", ticker)))?;
+        Ok((symbol.base_asset.clone(), symbol.quote_asset.clone()))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "filterType")]
 pub enum Filters {
@@ -129,6 +142,131 @@ pub enum Filters {
     },
 }
 
+/// Trading rules for a single symbol, parsed once from its exchange [`Filters`].
+/// A value of 0.0 means the exchange doesn't enforce that filter.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct SymbolFilters {
+    /// Price must be a multiple of this
+    pub tick_size: f64,
+    /// Quantity must be a multiple of this
+    pub step_size: f64,
+    pub min_qty: f64,
+    /// Minimum price * quantity of an order in quote asset
+    pub min_notional: f64,
+    /// Decimals of the tick size, parsed from the filter string
+    pub price_decimals: i32,
+    /// Decimals of the step size, parsed from the filter string
+    pub qty_decimals: i32,
+    /// Maximum quantity of a single order
+    pub max_qty: f64,
+    /// Maximum base held, including base locked in open orders
+    pub max_position: f64,
+}
+
+impl SymbolFilters {
+    pub fn new(filters: &[Filters]) -> DreamrunnerResult<Self> {
+        let mut symbol_filters = Self::default();
+        for filter in filters {
+            match filter {
+                Filters::PriceFilter { tick_size, .. } => {
+                    symbol_filters.tick_size = tick_size.parse::<f64>()?;
+                    symbol_filters.price_decimals = Self::decimals(tick_size);
+                }
+                Filters::LotSize { min_qty, max_qty, step_size } => {
+                    symbol_filters.min_qty = min_qty.parse::<f64>()?;
+                    symbol_filters.max_qty = max_qty.parse::<f64>()?;
+                    symbol_filters.step_size = step_size.parse::<f64>()?;
+                    symbol_filters.qty_decimals = Self::decimals(step_size);
+                }
+                Filters::MaxPosition { max_position } => {
+                    symbol_filters.max_position = max_position.parse::<f64>()?;
+                }
+                Filters::MinNotional { min_notional, notional, .. }
+                | Filters::Notional { min_notional, notional, .. } => {
+                    if let Some(min_notional) = min_notional.as_ref().or(notional.as_ref()) {
+                        symbol_filters.min_notional = min_notional.parse::<f64>()?;
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(symbol_filters)
+    }
+
+    pub fn from_exchange_info(info: &ExchangeInformation, symbol: &str) -> DreamrunnerResult<Self> {
+        let symbol = info
+            .symbols
+            .iter()
+            .find(|s| s.symbol == symbol)
+            .ok_or(DreamrunnerError::Custom(format!("Symbol {} missing from exchange info", symbol)))?;
+        Self::new(&symbol.filters)
+    }
+
+    /// Decimals of a Binance step string ignoring trailing zeros, so 6 for "0.00000100" and 0 for "1.00000000"
+    pub fn decimals(step: &str) -> i32 {
+        match step.split_once('.') {
+            Some((_, fraction)) => fraction.trim_end_matches('0').len() as i32,
+            None => 0,
+        }
+    }
+
+    /// Floor `qty` to a multiple of the step size, unchanged if filters aren't loaded
+    pub fn floor_qty(&self, qty: f64) -> f64 {
+        Self::floor_to_step(qty, self.step_size, self.qty_decimals)
+    }
+
+    /// Floor `price` to a multiple of the tick size, unchanged if filters aren't loaded
+    pub fn floor_price(&self, price: f64) -> f64 {
+        Self::floor_to_step(price, self.tick_size, self.price_decimals)
+    }
+
+    /// Ceil `price` to a multiple of the tick size, unchanged if filters aren't loaded
+    pub fn ceil_price(&self, price: f64) -> f64 {
+        if self.tick_size <= 0.0 {
+            return price;
+        }
+        // epsilon so a price already on a tick isn't raised a tick by float error
+        let steps = (price / self.tick_size - 1e-9).ceil();
+        let scale = 10_f64.powi(self.price_decimals);
+        (steps * self.tick_size * scale).round() / scale
+    }
+
+    fn floor_to_step(value: f64, step: f64, decimals: i32) -> f64 {
+        if step <= 0.0 {
+            return value;
+        }
+        // epsilon so a value already on a step isn't floored a step down by float error
+        let steps = (value / step + 1e-9).floor();
+        let scale = 10_f64.powi(decimals);
+        (steps * step * scale).round() / scale
+    }
+
+    /// Cap `qty` at the max order quantity and, for a buy, at the room left under the max position
+    /// given `held` base. Filters that aren't enforced are ignored.
+    pub fn cap_qty(&self, side: Side, qty: f64, held: f64) -> f64 {
+        let mut capped = qty;
+        if self.max_qty > 0.0 {
+            capped = capped.min(self.max_qty);
+        }
+        if self.max_position > 0.0 && side == Side::Long {
+            capped = capped.min((self.max_position - held).max(0.0));
+        }
+        capped
+    }
+
+    /// Err if `qty` at `price` is below the minimum quantity or notional
+    pub fn check_notional(&self, qty: f64, price: f64) -> DreamrunnerResult<()> {
+        let notional = qty * price;
+        if qty <= 0.0 || qty < self.min_qty || notional < self.min_notional {
+            return Err(DreamrunnerError::BelowMinNotional {
+                notional,
+                min_notional: self.min_notional
+            });
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AccountInformation {
@@ -291,6 +429,49 @@ pub struct UserDataStream {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Success {}
 
+/// Price and quantity of one order book level, sent by Binance as `["price", "qty"]`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct PriceLevel {
+    #[serde(with = "string_or_float")]
+    pub price: f64,
+    #[serde(with = "string_or_float")]
+    pub qty: f64,
+}
+
+/// Order book snapshot from the depth endpoint
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DepthSnapshot {
+    pub last_update_id: u64,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+/// Diff of the order book from the `<symbol>@depth` stream, quantities are absolute and 0 removes the level
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DepthUpdateEvent {
+    #[serde(rename = "e")]
+    pub event_type: String,
+
+    #[serde(rename = "E")]
+    pub event_time: u64,
+
+    #[serde(rename = "s")]
+    pub symbol: String,
+
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+
+    #[serde(rename = "b")]
+    pub bids: Vec<PriceLevel>,
+
+    #[serde(rename = "a")]
+    pub asks: Vec<PriceLevel>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Tickers {
@@ -309,6 +490,7 @@ pub struct Tickers {
 #[serde(rename_all = "camelCase")]
 pub struct TradeHistory {
     pub id: u64,
+    pub order_id: u64,
     #[serde(with = "string_or_float")]
     pub price: f64,
     #[serde(with = "string_or_float")]
@@ -389,9 +571,7 @@ impl AccountUpdateEvent {
             .balances
             .iter()
             .find(|b| b.asset == quote_asset)
-            .ok_or(DreamrunnerError::Custom(
-                "Failed to find quote balance".to_string(),
-            ))?;
+            .ok_or(DreamrunnerError::AssetNotFound(quote_asset.to_string()))?;
         let free_quote = quote.free.parse::<f64>()?;
         let locked_quote = quote.locked.parse::<f64>()?;
 
@@ -399,9 +579,7 @@ impl AccountUpdateEvent {
             self.balances
                 .iter()
                 .find(|b| b.asset == base_asset)
-                .ok_or(DreamrunnerError::Custom(
-                    "Failed to find quote balance".to_string(),
-                ))?;
+                .ok_or(DreamrunnerError::AssetNotFound(base_asset.to_string()))?;
         let free_base = base.free.parse::<f64>()?;
         let locked_base = base.locked.parse::<f64>()?;
         Ok(Assets {
@@ -866,6 +1044,8 @@ pub enum OrderType {
     StopLoss,
     TakeProfitLimit,
     TakeProfit,
+    /// Maker-only limit order, rejected if it would cross the book
+    LimitMaker,
 }
 impl OrderType {
     pub fn fmt_binance(&self) -> &str {
@@ -876,6 +1056,7 @@ impl OrderType {
             OrderType::StopLoss => "STOP_LOSS",
             OrderType::TakeProfitLimit => "TAKE_PROFIT_LIMIT",
             OrderType::TakeProfit => "TAKE_PROFIT",
+            OrderType::LimitMaker => "LIMIT_MAKER",
         }
     }
 }
@@ -889,11 +1070,43 @@ impl FromStr for OrderType {
             "STOP_LOSS" => Ok(OrderType::StopLoss),
             "TAKE_PROFIT_LIMIT" => Ok(OrderType::TakeProfitLimit),
             "TAKE_PROFIT" => Ok(OrderType::TakeProfit),
+            "LIMIT_MAKER" => Ok(OrderType::LimitMaker),
             _ => Err(DreamrunnerError::OrderTypeInvalid),
         }
     }
 }
 
+/// Self-trade prevention mode, which side of a self-match Binance expires
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum StpMode {
+    ExpireTaker,
+    ExpireMaker,
+    ExpireBoth,
+    None,
+}
+impl StpMode {
+    pub fn fmt_binance(&self) -> &str {
+        match self {
+            StpMode::ExpireTaker => "EXPIRE_TAKER",
+            StpMode::ExpireMaker => "EXPIRE_MAKER",
+            StpMode::ExpireBoth => "EXPIRE_BOTH",
+            StpMode::None => "NONE",
+        }
+    }
+}
+impl FromStr for StpMode {
+    type Err = DreamrunnerError;
+    fn from_str(s: &str) -> DreamrunnerResult<Self> {
+        match s {
+            "EXPIRE_TAKER" => Ok(StpMode::ExpireTaker),
+            "EXPIRE_MAKER" => Ok(StpMode::ExpireMaker),
+            "EXPIRE_BOTH" => Ok(StpMode::ExpireBoth),
+            "NONE" => Ok(StpMode::None),
+            _ => Err(DreamrunnerError::StpModeInvalid),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LimitOrderResponse {
@@ -904,6 +1117,16 @@ pub struct LimitOrderResponse {
     pub transact_time: u64,
 }
 
+/// Response to placing an [`crate::OcoOrder`], the legs report their own fills
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OcoOrderResponse {
+    pub symbol: String,
+    pub order_list_id: i64,
+    pub list_client_order_id: String,
+    pub transaction_time: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderResponse {
@@ -963,6 +1186,19 @@ impl Assets {
         let quote = self.free_quote + self.locked_quote;
         trunc!(quote + base_to_quote, 4)
     }
+
+    /// Side and quantity of the trade that moves free quote and base to equal value at `price`.
+    /// Quantity is floored to the step size. None if the trade is below the min qty or notional.
+    pub fn equalize(&self, price: f64, filters: &SymbolFilters) -> Option<(Side, f64)> {
+        // half the difference in base units moves both sides to the midpoint
+        let diff = (self.free_quote / price - self.free_base) / 2.0;
+        let side = match diff > 0.0 {
+            true => Side::Long,
+            false => Side::Short,
+        };
+        let qty = filters.floor_qty(diff.abs());
+        filters.check_notional(qty, price).ok().map(|_| (side, qty))
+    }
 }
 
 impl Default for Assets {
@@ -976,6 +1212,17 @@ impl Default for Assets {
     }
 }
 
+/// Mark to market of the open position against the price of its entry order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UnrealizedPnl {
+    pub side: Side,
+    pub entry_price: f64,
+    pub current_price: f64,
+    pub qty: f64,
+    pub unrealized_quote: f64,
+    pub unrealized_pct: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AccountInfoResponse {
@@ -1000,10 +1247,7 @@ impl AccountInfoResponse {
         Ok(self.balances
             .iter()
             .find(|&x| x.asset == asset)
-            .ok_or(DreamrunnerError::Custom(format!(
-                "Failed to find asset {}",
-                asset
-            )))?
+            .ok_or(DreamrunnerError::AssetNotFound(asset.to_string()))?
             .free
             .parse::<f64>()?)
     }
@@ -1012,10 +1256,7 @@ impl AccountInfoResponse {
         Ok(self.balances
             .iter()
             .find(|&x| x.asset == asset)
-            .ok_or(DreamrunnerError::Custom(format!(
-                "Failed to find asset {}",
-                asset
-            )))?
+            .ok_or(DreamrunnerError::AssetNotFound(asset.to_string()))?
             .locked
             .parse::<f64>()?)
     }
@@ -1206,4 +1447,140 @@ impl Kline {
             volume: Some(self.volume),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_filters_qty() {
+        let filters = SymbolFilters {
+            tick_size: 0.01,
+            step_size: 0.001,
+            min_qty: 0.001,
+            min_notional: 5.0,
+            price_decimals: 2,
+            qty_decimals: 3,
+            ..SymbolFilters::default()
+        };
+        assert_eq!(filters.floor_qty(1.23456), 1.234);
+        assert_eq!(filters.floor_qty(0.3), 0.3);
+        assert_eq!(SymbolFilters::default().floor_qty(1.23456), 1.23456);
+
+        assert!(filters.check_notional(0.05, 150.0).is_ok());
+        assert!(matches!(
+            filters.check_notional(0.01, 150.0),
+            Err(DreamrunnerError::BelowMinNotional { .. })
+        ));
+        assert!(filters.check_notional(0.0, 150.0).is_err());
+        assert_eq!(filters.floor_price(150.1299), 150.12);
+    }
+
+    #[test]
+    fn test_symbol_filters_decimals_from_step_size() -> DreamrunnerResult<()> {
+        let lot_size = |step_size: &str| SymbolFilters::new(&[Filters::LotSize {
+            min_qty: step_size.to_string(),
+            max_qty: "9000000.00000000".to_string(),
+            step_size: step_size.to_string(),
+        }]);
+        let atlas = lot_size("0.00000100")?;
+        assert_eq!(atlas.qty_decimals, 6);
+        assert_eq!(atlas.floor_qty(12345.6789123), 12345.678912);
+        let sol = lot_size("0.01000000")?;
+        assert_eq!(sol.qty_decimals, 2);
+        assert_eq!(sol.floor_qty(12345.6789123), 12345.67);
+        let whole = lot_size("1.00000000")?;
+        assert_eq!(whole.qty_decimals, 0);
+        assert_eq!(whole.floor_qty(12345.6789123), 12345.0);
+        assert_eq!(SymbolFilters::decimals("1"), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_symbol_filters_cap_qty() -> DreamrunnerResult<()> {
+        let filters = SymbolFilters::new(&[
+            Filters::LotSize {
+                min_qty: "0.01000000".to_string(),
+                max_qty: "100.00000000".to_string(),
+                step_size: "0.01000000".to_string(),
+            },
+            Filters::MaxPosition { max_position: "150.00000000".to_string() },
+        ])?;
+        assert_eq!(filters.max_qty, 100.0);
+        assert_eq!(filters.max_position, 150.0);
+        assert_eq!(filters.cap_qty(Side::Long, 50.0, 0.0), 50.0);
+        assert_eq!(filters.cap_qty(Side::Long, 500.0, 0.0), 100.0);
+        // 120 held leaves room for 30 more
+        assert_eq!(filters.cap_qty(Side::Long, 50.0, 120.0), 30.0);
+        assert_eq!(filters.cap_qty(Side::Long, 50.0, 200.0), 0.0);
+        // sells only reduce the position
+        assert_eq!(filters.cap_qty(Side::Short, 50.0, 200.0), 50.0);
+        assert_eq!(SymbolFilters::default().cap_qty(Side::Long, 500.0, 200.0), 500.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_ticker() -> DreamrunnerResult<()> {
+        let symbol = |symbol: &str, base_asset: &str, quote_asset: &str| Symbol {
+            symbol: symbol.to_string(),
+            status: "TRADING".to_string(),
+            base_asset: base_asset.to_string(),
+            base_asset_precision: 8,
+            quote_asset: quote_asset.to_string(),
+            quote_precision: 8,
+            order_types: vec![],
+            iceberg_allowed: true,
+            is_spot_trading_allowed: true,
+            is_margin_trading_allowed: false,
+            filters: vec![],
+        };
+        let info = ExchangeInformation {
+            timezone: "UTC".to_string(),
+            server_time: 0,
+            rate_limits: vec![],
+            symbols: vec![symbol("BTCUSD", "BTC", "USD"), symbol("BTCUSDT", "BTC", "USDT")],
+        };
+        assert_eq!(Symbol::split_ticker("BTCUSDT", &info)?, ("BTC".to_string(), "USDT".to_string()));
+        assert_eq!(Symbol::split_ticker("BTCUSD", &info)?, ("BTC".to_string(), "USD".to_string()));
+        assert!(Symbol::split_ticker("SOLUSDT", &info).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_equalize_snaps_to_filters() {
+        let sol = SymbolFilters {
+            tick_size: 0.01,
+            step_size: 0.001,
+            min_qty: 0.001,
+            min_notional: 5.0,
+            price_decimals: 2,
+            qty_decimals: 3,
+            ..SymbolFilters::default()
+        };
+        let assets = |free_quote: f64, free_base: f64| Assets {
+            free_quote,
+            free_base,
+            ..Assets::default()
+        };
+        // 1000 USDT and 2 SOL at 150 is 6.667 SOL of quote, buy half the 4.667 SOL gap
+        assert_eq!(assets(1000.0, 2.0).equalize(150.0, &sol), Some((Side::Long, 2.333)));
+        // 0 USDT and 3 SOL, sell half
+        assert_eq!(assets(0.0, 3.0).equalize(150.0, &sol), Some((Side::Short, 1.5)));
+        // 6 USDT of imbalance is a 3 USDT trade, below min notional
+        assert_eq!(assets(306.0, 2.0).equalize(150.0, &sol), None);
+
+        // whole unit lots at fractions of a cent
+        let atlas = SymbolFilters {
+            tick_size: 0.000001,
+            step_size: 1.0,
+            min_qty: 1.0,
+            min_notional: 10.0,
+            price_decimals: 6,
+            qty_decimals: 0,
+            ..SymbolFilters::default()
+        };
+        assert_eq!(assets(100.0, 0.0).equalize(0.0025, &atlas), Some((Side::Long, 20_000.0)));
+        assert_eq!(assets(100.0, 39_990.5).equalize(0.0025, &atlas), None);
+    }
+}
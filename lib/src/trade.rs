@@ -1,7 +1,8 @@
 #![allow(dead_code)]
+#![allow(clippy::result_large_err)]
 
 use std::str::FromStr;
-use crate::{BinanceTrade, Timestamp};
+use crate::{BinanceTrade, DreamrunnerError, DreamrunnerResult, Timestamp};
 use crate::model::*;
 use serde::{Serialize, Deserialize};
 use time_series::{Time, Trade};
@@ -13,8 +14,16 @@ pub struct TradeInfo {
   pub status: OrderStatus,
   pub event_time: i64,
   pub quantity: f64,
+  /// Limit price of the order, 0 for MARKET orders
   pub price: f64,
+  /// Price the order actually filled at: the last fill of a stream update, the average fill of a queried order.
+  /// 0 until something fills
+  pub fill_price: f64,
   pub side: Side,
+  /// Quantity the order was placed for
+  pub orig_qty: f64,
+  /// Cumulative quantity filled so far
+  pub executed_qty: f64,
 }
 impl Timestamp for TradeInfo {
   fn timestamp(&self) -> i64 {
@@ -33,7 +42,13 @@ impl TryFrom<&HistoricalOrder> for TradeInfo {
       event_time: historical_order.update_time,
       quantity: historical_order.executed_qty.parse::<f64>()?,
       price: historical_order.price.parse::<f64>()?,
+      fill_price: match historical_order.executed_qty.parse::<f64>()? {
+        qty if qty > 0.0 => historical_order.cummulative_quote_qty.parse::<f64>()? / qty,
+        _ => 0.0
+      },
       side: Side::from_str(&historical_order.side)?,
+      orig_qty: historical_order.orig_qty.parse::<f64>()?,
+      executed_qty: historical_order.executed_qty.parse::<f64>()?,
     })
   }
 }
@@ -49,12 +64,20 @@ impl TryFrom<&OrderTradeEvent> for TradeInfo {
       event_time: order_trade_event.event_time as i64,
       quantity: order_trade_event.qty.parse::<f64>()?,
       price: order_trade_event.price.parse::<f64>()?,
+      fill_price: order_trade_event.price_last_filled_trade.parse::<f64>()?,
       side: Side::from_str(&order_trade_event.side)?,
+      orig_qty: order_trade_event.qty.parse::<f64>()?,
+      executed_qty: order_trade_event.accumulated_qty_filled_trades.parse::<f64>()?,
     })
   }
 }
 
 impl TradeInfo {
+  /// Quantity of the order left to fill
+  pub fn remaining_qty(&self) -> f64 {
+    (self.orig_qty - self.executed_qty).max(0.0)
+  }
+
   pub fn to_trade(&self, ticker: String) -> anyhow::Result<Trade> {
     Ok(Trade {
       ticker,
@@ -72,7 +95,66 @@ impl TradeInfo {
 
 pub struct OrderBuilder {
   pub entry: BinanceTrade,
-  pub stop_loss: Option<BinanceTrade>
+  pub stop_loss: Option<BinanceTrade>,
+  pub take_profit: Option<BinanceTrade>
+}
+
+/// Where to place the limit price of a live order relative to the signal
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LimitOffset {
+  /// Limit at the signal candle close
+  #[default]
+  AtSignal,
+  /// Limit this many bps through the signal price, above it for buys and below it for sells
+  BpsThrough(f64),
+  /// Limit at the best ask for buys and the best bid for sells
+  AtBestBidAsk
+}
+
+impl LimitOffset {
+  /// Limit price for an order on `side`, snapped to the tick size away from the market:
+  /// floored for buys and ceiled for sells.
+  /// `book` is the bookTicker of the symbol, required by [`LimitOffset::AtBestBidAsk`].
+  pub fn limit_price(&self, side: Side, signal_price: f64, book: Option<&Tickers>, filters: &SymbolFilters) -> DreamrunnerResult<f64> {
+    let price = match self {
+      LimitOffset::AtSignal => signal_price,
+      LimitOffset::BpsThrough(bps) => match side {
+        Side::Long => signal_price * (1.0 + bps / 10_000.0),
+        Side::Short => signal_price * (1.0 - bps / 10_000.0)
+      },
+      LimitOffset::AtBestBidAsk => {
+        let book = book.ok_or(DreamrunnerError::Custom("Book ticker required for best bid/ask limit".to_string()))?;
+        match side {
+          Side::Long => book.ask_price,
+          Side::Short => book.bid_price
+        }
+      }
+    };
+    Ok(match side {
+      Side::Long => filters.floor_price(price),
+      Side::Short => filters.ceil_price(price)
+    })
+  }
+}
+
+/// Retry a LIMIT_MAKER order rejected for crossing the book, repriced further from the market each attempt
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostOnlyRetry {
+  /// Retries after the first rejection
+  pub max_attempts: u32,
+  /// Ticks to move the limit price back per retry, lower for buys and higher for sells
+  pub tick_back: u32
+}
+
+impl PostOnlyRetry {
+  /// Limit price one retry further from the market than `price`
+  pub fn reprice(&self, side: Side, price: f64, tick_size: f64) -> f64 {
+    let offset = self.tick_back as f64 * tick_size;
+    match side {
+      Side::Long => price - offset,
+      Side::Short => price + offset
+    }
+  }
 }
 
 #[derive(Debug, Clone)]
@@ -101,7 +183,13 @@ impl Timestamp for OrderState {
 pub struct ActiveOrder {
   pub entry: Option<OrderState>,
   pub stop_loss: Option<OrderState>,
-  pub stop_loss_placed: bool
+  pub stop_loss_placed: bool,
+  pub take_profit: Option<OrderState>,
+  pub take_profit_placed: bool,
+  /// Base bought by the entries of the open position
+  pub position_qty: f64,
+  /// Quantity weighted average price of the entry fills, 0 without a position
+  pub avg_entry_price: f64
 }
 
 impl ActiveOrder {
@@ -125,9 +213,136 @@ impl ActiveOrder {
     self.stop_loss = Some(OrderState::Pending(order));
   }
 
+  pub fn add_take_profit(&mut self, order: BinanceTrade) {
+    self.take_profit = Some(OrderState::Pending(order));
+  }
+
+  /// Clear the orders. The position and its average entry price are kept until [`ActiveOrder::close_position`].
   pub fn reset(&mut self) {
     self.entry = None;
     self.stop_loss = None;
     self.stop_loss_placed = false;
+    self.take_profit = None;
+    self.take_profit_placed = false;
+  }
+
+  /// Average the base `entry` filled since the last update of the same order into the position, at its fill price.
+  /// Each update carries the cumulative fill of its order, and entries of a pyramided position are separate orders.
+  pub fn record_entry_fill(&mut self, entry: &TradeInfo) {
+    let prev_qty = match &self.entry {
+      Some(OrderState::Active(prev)) if prev.client_order_id == entry.client_order_id => prev.executed_qty,
+      _ => 0.0
+    };
+    let filled = entry.executed_qty - prev_qty;
+    if filled <= 0.0 {
+      return;
+    }
+    let qty = self.position_qty + filled;
+    self.avg_entry_price = (self.avg_entry_price * self.position_qty + entry.fill_price * filled) / qty;
+    self.position_qty = qty;
+  }
+
+  pub fn close_position(&mut self) {
+    self.position_qty = 0.0;
+    self.avg_entry_price = 0.0;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_limit_price() -> DreamrunnerResult<()> {
+    let book = Tickers {
+      symbol: "SOLUSDT".to_string(),
+      bid_price: 99.5,
+      bid_qty: 1.0,
+      ask_price: 100.5,
+      ask_qty: 1.0
+    };
+    let filters = SymbolFilters { tick_size: 0.01, price_decimals: 2, ..SymbolFilters::default() };
+    assert_eq!(LimitOffset::AtSignal.limit_price(Side::Long, 100.0, None, &filters)?, 100.0);
+    assert_eq!(LimitOffset::BpsThrough(10.0).limit_price(Side::Long, 100.0, None, &filters)?, 100.1);
+    assert_eq!(LimitOffset::BpsThrough(10.0).limit_price(Side::Short, 100.0, None, &filters)?, 99.9);
+    assert_eq!(LimitOffset::AtBestBidAsk.limit_price(Side::Long, 100.0, Some(&book), &filters)?, 100.5);
+    assert_eq!(LimitOffset::AtBestBidAsk.limit_price(Side::Short, 100.0, Some(&book), &filters)?, 99.5);
+    assert!(LimitOffset::AtBestBidAsk.limit_price(Side::Long, 100.0, None, &filters).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_limit_price_sub_cent_tick() -> DreamrunnerResult<()> {
+    // ATLASUSDT trades around $0.002 with a 0.000001 tick, 2 decimals would round it to 0
+    let filters = SymbolFilters { tick_size: 0.000001, price_decimals: 6, ..SymbolFilters::default() };
+    assert_eq!(LimitOffset::AtSignal.limit_price(Side::Long, 0.0021234, None, &filters)?, 0.002123);
+    assert_eq!(LimitOffset::AtSignal.limit_price(Side::Short, 0.0021234, None, &filters)?, 0.002124);
+    // already on a tick stays put on both sides
+    assert_eq!(LimitOffset::AtSignal.limit_price(Side::Short, 0.002123, None, &filters)?, 0.002123);
+    assert_eq!(LimitOffset::BpsThrough(10.0).limit_price(Side::Long, 0.002, None, &filters)?, 0.002002);
+    Ok(())
+  }
+
+  #[test]
+  fn test_post_only_reprice() {
+    let retry = PostOnlyRetry { max_attempts: 3, tick_back: 2 };
+    assert_eq!(retry.reprice(Side::Long, 100.0, 0.5), 99.0);
+    assert_eq!(retry.reprice(Side::Short, 100.0, 0.5), 101.0);
+  }
+
+  #[test]
+  fn test_average_entry_price() {
+    let fill = |client_order_id: &str, price: f64, executed_qty: f64| TradeInfo {
+      client_order_id: client_order_id.to_string(),
+      order_type: OrderType::Limit,
+      status: OrderStatus::PartiallyFilled,
+      event_time: 0,
+      quantity: executed_qty,
+      price,
+      fill_price: price,
+      side: Side::Long,
+      orig_qty: 4.0,
+      executed_qty
+    };
+    let mut active_order = ActiveOrder::new();
+    // updates of the same order carry its cumulative fill
+    for update in [fill("1-ENTRY", 100.0, 1.0), fill("1-ENTRY", 100.0, 2.0), fill("1-ENTRY", 100.0, 2.0)] {
+      active_order.record_entry_fill(&update);
+      active_order.entry = Some(OrderState::Active(update));
+    }
+    assert_eq!(active_order.position_qty, 2.0);
+    assert_eq!(active_order.avg_entry_price, 100.0);
+
+    // a pyramided entry is averaged in, and survives the orders being reset
+    active_order.reset();
+    active_order.record_entry_fill(&fill("2-ENTRY", 130.0, 1.0));
+    assert_eq!(active_order.position_qty, 3.0);
+    assert_eq!(active_order.avg_entry_price, 110.0);
+
+    active_order.close_position();
+    assert_eq!(active_order.position_qty, 0.0);
+    assert_eq!(active_order.avg_entry_price, 0.0);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_average_market_entry() -> anyhow::Result<()> {
+    // a MARKET order has no limit price, each fill carries its own in `L`
+    let update = |status: &str, executed_qty: &str, fill_price: &str| -> anyhow::Result<TradeInfo> {
+      let event: OrderTradeEvent = serde_json::from_value(serde_json::json!({
+        "e": "executionReport", "E": 1, "s": "SOLUSDT", "c": "1-ENTRY", "S": "BUY", "o": "MARKET",
+        "f": "GTC", "q": "2", "p": "0", "x": "TRADE", "X": status, "r": "NONE", "i": 1,
+        "l": "1", "z": executed_qty, "L": fill_price, "n": "0", "T": 1, "t": 1, "m": false
+      }))?;
+      TradeInfo::try_from(&event)
+    };
+    let mut active_order = ActiveOrder::new();
+    for update in [update("PARTIALLY_FILLED", "1", "100")?, update("FILLED", "2", "102")?] {
+      assert_eq!(update.price, 0.0);
+      active_order.record_entry_fill(&update);
+      active_order.entry = Some(OrderState::Active(update));
+    }
+    assert_eq!(active_order.position_qty, 2.0);
+    assert_eq!(active_order.avg_entry_price, 101.0);
+    Ok(())
+  }
+}
@@ -10,8 +10,11 @@ use crate::model::{
 use log::*;
 use serde::{Deserialize, Serialize};
 use tokio::net::TcpStream;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use futures::{StreamExt, SinkExt};
 use tokio::runtime::Handle;
 use tokio_tungstenite::tungstenite::handshake::client::Response;
@@ -20,7 +23,12 @@ use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::WebSocketStream;
 use tokio_tungstenite::connect_async;
 use url::Url;
-use crate::{Client, UserStream};
+use crate::{Client, Interval, UserStream};
+
+/// Kline stream name for `symbol` at `interval`, e.g. `solusdt@kline_30m` for SOLUSDT at 30 minutes
+pub fn kline_stream(symbol: &str, interval: Interval) -> String {
+    format!("{}@kline_{}", symbol.to_lowercase(), interval.as_str())
+}
 
 #[allow(clippy::all)]
 enum WebSocketAPI {
@@ -66,6 +74,53 @@ pub enum WebSocketEvent {
     Kline(KlineEvent),
 }
 
+impl WebSocketEvent {
+    /// Parse a raw websocket text frame, unwrapping the combined stream `data` envelope.
+    /// None if the frame isn't a known event, such as a subscription reply.
+    pub fn parse(msg: &str) -> DreamrunnerResult<Option<Self>> {
+        let mut value: serde_json::Value = serde_json::from_str(msg)?;
+        if let Some(data) = value.get_mut("data") {
+            value = data.take();
+        }
+        Ok(serde_json::from_value::<Events>(value).ok().map(|events| match events {
+            Events::BalanceUpdate(v) => WebSocketEvent::BalanceUpdate(v),
+            Events::AccountUpdate(v) => WebSocketEvent::AccountUpdate(v),
+            Events::OrderTrade(v) => WebSocketEvent::OrderTrade(v),
+            Events::Trade(v) => WebSocketEvent::Trade(v),
+            Events::Kline(v) => WebSocketEvent::Kline(v),
+        }))
+    }
+
+    /// Event time in UNIX milliseconds
+    pub fn event_time(&self) -> u64 {
+        match self {
+            WebSocketEvent::AccountUpdate(v) => v.event_time,
+            WebSocketEvent::BalanceUpdate(v) => v.event_time,
+            WebSocketEvent::OrderTrade(v) => v.event_time,
+            WebSocketEvent::Trade(v) => v.event_time,
+            WebSocketEvent::Kline(v) => v.event_time,
+        }
+    }
+}
+
+/// Line of a websocket recording, a raw text frame and the local time it was received
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RecordedFrame {
+    /// UNIX milliseconds
+    pub received_at: u64,
+    pub frame: String,
+}
+
+impl RecordedFrame {
+    pub fn new(frame: &str) -> Self {
+        let received_at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        Self { received_at, frame: frame.to_string() }
+    }
+}
+
 // pub type Callback = Box<dyn Fn(WebSocketEvent) -> Pin<Box<dyn Future<Output = DreamrunnerResult<()>> + Send>> + Sync>;
 pub type Callback = Box<dyn Fn(WebSocketEvent) -> DreamrunnerResult<()> + Send + Sync>;
 
@@ -75,9 +130,22 @@ pub struct WebSockets {
     pub testnet: bool,
     pub last_ping: SystemTime,
     pub last_restart: SystemTime,
+    /// Time the last text message was received, to detect a stalled stream that never sends a close frame
+    pub last_data: SystemTime,
+    /// Max time without a text message before the event loop returns [`DreamrunnerError::DataStall`]
+    pub data_timeout: Duration,
     pub is_connected: AtomicBool,
     pub listen_key: String,
-    pub user_stream: UserStream
+    pub user_stream: UserStream,
+    /// Streams the combined stream connection is subscribed to, in subscription order
+    pub subscriptions: Vec<String>,
+    /// Id of the next SUBSCRIBE/UNSUBSCRIBE request, echoed back by Binance in the response
+    next_request_id: u64,
+    /// Append every text frame received to this file as a newline delimited [`RecordedFrame`], for replay.
+    /// Nothing is recorded if None.
+    pub record_path: Option<PathBuf>,
+    /// Recording opened on the first frame received
+    recorder: Option<File>
 }
 
 impl Drop for WebSockets {
@@ -116,12 +184,24 @@ impl WebSockets {
             testnet,
             last_ping: SystemTime::now(),
             last_restart: SystemTime::now(),
+            last_data: SystemTime::now(),
+            data_timeout: Duration::from_secs(60),
             is_connected: AtomicBool::new(false),
             listen_key: String::new(),
-            user_stream: UserStream { client }
+            user_stream: UserStream { client },
+            subscriptions: vec![],
+            next_request_id: 1,
+            record_path: None,
+            recorder: None
         }
     }
 
+    /// Record every text frame received to `path`, appending if it exists
+    pub fn with_record_path(mut self, path: PathBuf) -> Self {
+        self.record_path = Some(path);
+        self
+    }
+
     #[allow(dead_code)]
     pub async fn connect(&mut self, subscription: &str) -> DreamrunnerResult<()> {
         self.connect_wss(&WebSocketAPI::Default.params(subscription, self.testnet)).await
@@ -135,15 +215,68 @@ impl WebSockets {
 
     pub async fn connect_multiple_streams(&mut self, endpoints: &[String], testnet: bool) -> DreamrunnerResult<()> {
         self.connect_wss(&WebSocketAPI::MultiStream.params(&endpoints.join("/"), testnet)).await?;
+        self.subscriptions = endpoints.to_vec();
         info!("🟢 Reconnected Binance websocket");
         Ok(())
     }
 
+    /// Subscribe the open combined stream connection to `streams` without reconnecting.
+    /// Streams already subscribed are skipped.
+    pub async fn subscribe(&mut self, streams: &[String]) -> DreamrunnerResult<()> {
+        let streams: Vec<String> = streams.iter()
+            .filter(|s| !self.subscriptions.contains(s))
+            .cloned()
+            .collect();
+        if streams.is_empty() {
+            return Ok(());
+        }
+        self.send_subscription("SUBSCRIBE", &streams).await?;
+        info!("🟢 Subscribed to {:?}", streams);
+        self.subscriptions.extend(streams);
+        Ok(())
+    }
+
+    /// Unsubscribe the open combined stream connection from `streams` without reconnecting.
+    /// Streams not subscribed are skipped.
+    pub async fn unsubscribe(&mut self, streams: &[String]) -> DreamrunnerResult<()> {
+        let streams: Vec<String> = streams.iter()
+            .filter(|s| self.subscriptions.contains(s))
+            .cloned()
+            .collect();
+        if streams.is_empty() {
+            return Ok(());
+        }
+        self.send_subscription("UNSUBSCRIBE", &streams).await?;
+        info!("🟡 Unsubscribed from {:?}", streams);
+        self.subscriptions.retain(|s| !streams.contains(s));
+        Ok(())
+    }
+
+    async fn send_subscription(&mut self, method: &str, streams: &[String]) -> DreamrunnerResult<()> {
+        let frame = Self::subscription_frame(method, streams, self.next_request_id);
+        match self.socket {
+            Some(ref mut socket) => socket.0.send(Message::Text(frame)).await?,
+            None => return Err(DreamrunnerError::WebSocketDisconnected)
+        }
+        self.next_request_id += 1;
+        Ok(())
+    }
+
+    /// JSON-RPC control message for the combined stream, e.g. `{"method":"SUBSCRIBE","params":["solusdt@kline_30m"],"id":1}`
+    fn subscription_frame(method: &str, streams: &[String], id: u64) -> String {
+        serde_json::json!({
+            "method": method,
+            "params": streams,
+            "id": id
+        }).to_string()
+    }
+
     async fn connect_wss(&mut self, wss: &str) -> DreamrunnerResult<()> {
         let url = Url::parse(wss)?;
         match connect_async(url).await {
             Ok(answer) => {
                 self.socket = Some(answer);
+                self.last_data = SystemTime::now();
                 Ok(())
             }
             Err(e) => Err(DreamrunnerError::TokioTungstenite(e)),
@@ -159,30 +292,23 @@ impl WebSockets {
     }
 
     async fn handle_msg(&mut self, msg: &str) -> DreamrunnerResult<()> {
-        let value: serde_json::Value = serde_json::from_str(msg)?;
-        if let Some(data) = value.get("data") {
-            let msg = &data.to_string();
-            let value: serde_json::Value = serde_json::from_str(msg)?;
-            if let Ok(events) = serde_json::from_value::<Events>(value) {
-                let action = match events {
-                    Events::BalanceUpdate(v) => WebSocketEvent::BalanceUpdate(v),
-                    Events::AccountUpdate(v) => WebSocketEvent::AccountUpdate(v),
-                    Events::OrderTrade(v) => WebSocketEvent::OrderTrade(v),
-                    Events::Trade(v) => WebSocketEvent::Trade(v),
-                    Events::Kline(v) => WebSocketEvent::Kline(v),
-                };
-                (self.handler)(action)?;
-            }
+        if let Some(event) = WebSocketEvent::parse(msg)? {
+            (self.handler)(event)?;
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, msg: &str) -> DreamrunnerResult<()> {
+        let path = match &self.record_path {
+            Some(path) => path,
+            None => return Ok(())
+        };
+        if self.recorder.is_none() {
+            info!("Recording websocket frames to {}", path.display());
+            self.recorder = Some(OpenOptions::new().create(true).append(true).open(path)?);
         }
-        if let Ok(events) = serde_json::from_value::<Events>(value) {
-            let action = match events {
-                Events::BalanceUpdate(v) => WebSocketEvent::BalanceUpdate(v),
-                Events::AccountUpdate(v) => WebSocketEvent::AccountUpdate(v),
-                Events::OrderTrade(v) => WebSocketEvent::OrderTrade(v),
-                Events::Trade(v) => WebSocketEvent::Trade(v),
-                Events::Kline(v) => WebSocketEvent::Kline(v),
-            };
-            (self.handler)(action)?;
+        if let Some(recorder) = &mut self.recorder {
+            writeln!(recorder, "{}", serde_json::to_string(&RecordedFrame::new(msg))?)?;
         }
         Ok(())
     }
@@ -250,14 +376,36 @@ impl WebSockets {
                     self.last_ping = now;
                 }
 
-                if let Some(msg) = socket.0.next().await {
+                // klines push every few seconds, so a long silence means the stream stalled without closing
+                let since_data = now.duration_since(self.last_data).unwrap_or_default();
+                let remaining = match self.data_timeout.checked_sub(since_data) {
+                    Some(remaining) => remaining,
+                    None => {
+                        error!("🛑 No websocket data for {}s", since_data.as_secs());
+                        return Err(DreamrunnerError::DataStall(since_data.as_secs()));
+                    }
+                };
+                // wake up by the deadline even if nothing arrives, so the stall and ping timers are checked
+                let next = match tokio::time::timeout(remaining, socket.0.next()).await {
+                    Ok(next) => next,
+                    Err(_) => continue
+                };
+
+                if let Some(msg) = next {
                     match msg? {
-                        Message::Text(msg) => match self.handle_msg(&msg).await {
-                            Ok(_) => {}
-                            Err(e) => {
-                                if let DreamrunnerError::WebSocketDisconnected = e {
-                                    error!("Websocket disconnected: {:#?}", e);
-                                    return Err(e);
+                        Message::Text(msg) => {
+                            self.last_data = SystemTime::now();
+                            // a failed recording shouldn't interrupt trading
+                            if let Err(e) = self.record(&msg) {
+                                error!("🛑 Failed to record websocket frame: {:?}", e);
+                            }
+                            match self.handle_msg(&msg).await {
+                                Ok(_) => {}
+                                Err(e) => {
+                                    if let DreamrunnerError::WebSocketDisconnected = e {
+                                        error!("Websocket disconnected: {:#?}", e);
+                                        return Err(e);
+                                    }
                                 }
                             }
                         },
@@ -296,6 +444,26 @@ impl WebSockets {
 }
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kline_stream() {
+        assert_eq!(kline_stream("SOLUSDT", Interval::ThirtyMinutes), "solusdt@kline_30m");
+        assert_eq!(kline_stream("btcusdt", Interval::OneHour), "btcusdt@kline_1h");
+    }
+
+    #[test]
+    fn test_subscription_frame() {
+        let streams = vec!["solusdt@kline_30m".to_string(), "btcusdt@kline_30m".to_string()];
+        assert_eq!(
+            WebSockets::subscription_frame("SUBSCRIBE", &streams, 7),
+            r#"{"id":7,"method":"SUBSCRIBE","params":["solusdt@kline_30m","btcusdt@kline_30m"]}"#
+        );
+    }
+}
+
 
 
 
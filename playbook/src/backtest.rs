@@ -2,9 +2,12 @@
 #![allow(clippy::unnecessary_cast)]
 
 use std::collections::HashMap;
-use time_series::{Bet, Candle, Data, DataCache, Dataset, Order, Signal, Summary, Time, Trade, trunc};
+use std::sync::Arc;
+use std::sync::mpsc;
+use rayon::prelude::*;
+use time_series::{Allocation, Bet, Candle, Data, DataCache, Dataset, ExecutionTiming, Order, ProfitPolicy, Signal, SignalInfo, Summary, Time, Trade, trunc};
 use std::marker::PhantomData;
-use lib::{Account};
+use lib::{Account, Interval};
 use crate::Strategy;
 
 #[derive(Debug, Clone, Default)]
@@ -29,6 +32,92 @@ impl EmptyStrategy {
   }
 }
 
+/// Replays the signals a strategy emitted for each candle of each ticker, with a fixed % stop loss.
+/// Lets [`Backtest::stop_loss_sweep`] share one signal pass across every stop level.
+#[derive(Debug, Clone)]
+struct SignalReplay {
+  /// Signals per candle per ticker, oldest candle first
+  signals: Arc<HashMap<String, Vec<Vec<Signal>>>>,
+  /// Index of the next candle to replay per ticker
+  next: HashMap<String, usize>,
+  stop_loss_pct: f64,
+  cooldown: usize
+}
+
+impl Strategy<f64> for SignalReplay {
+  fn process_candle(&mut self, _candle: Candle, ticker: Option<String>) -> anyhow::Result<Vec<Signal>> {
+    let ticker = ticker.ok_or(anyhow::anyhow!("Signal replay needs a ticker"))?;
+    let i = self.next.entry(ticker.clone()).or_insert(0);
+    let signals = self.signals.get(&ticker).and_then(|s| s.get(*i)).cloned().unwrap_or_default();
+    *i += 1;
+    Ok(signals)
+  }
+
+  fn push_candle(&mut self, _candle: Candle, _ticker: Option<String>) {}
+
+  fn cache(&self, _ticker: Option<String>) -> Option<&DataCache<f64>> {
+    None
+  }
+
+  fn stop_loss_pct(&self) -> Option<f64> {
+    Some(self.stop_loss_pct)
+  }
+
+  fn cooldown(&self) -> usize {
+    self.cooldown
+  }
+}
+
+/// Realized account of each ticker during [`Backtest::backtest_with_progress`], updated as positions open and close
+#[derive(Debug, Default)]
+struct Ledger {
+  /// Traded quote capital, net of fees and funding, before the profit policy sweeps
+  cum_capital: HashMap<String, f64>,
+  /// Quote swept out of the account by the profit policy
+  withdrawn: HashMap<String, f64>,
+  /// Cumulative realized quote PnL
+  quote: HashMap<String, f64>,
+  cum_quote: HashMap<String, Vec<Data<i64, f64>>>,
+  cum_pct: HashMap<String, Vec<Data<i64, f64>>>,
+  pct_per_trade: HashMap<String, Vec<Data<i64, f64>>>,
+  /// Full quote size of the open position, fixed at entry so every tranche is a share of the same size
+  /// and other tickers closing trades in the meantime don't resize it
+  position_sizes: HashMap<String, f64>,
+  /// Fraction of the open position not yet scaled out of, all of it if absent
+  open_fractions: HashMap<String, f64>,
+  /// Entry of the open position per ticker, None if flat
+  active_trades: HashMap<String, Option<Trade>>,
+  /// Stop loss % of the open position per ticker, fixed at entry
+  stop_loss_pcts: HashMap<String, f64>,
+  /// Candle index of the last entry per ticker, to suppress entries during the strategy cooldown
+  last_entry: HashMap<String, usize>
+}
+
+impl Ledger {
+  /// Start `ticker` with `capital` and nothing realized
+  fn open(&mut self, ticker: &str, capital: f64) {
+    self.active_trades.insert(ticker.to_string(), None);
+    self.cum_capital.insert(ticker.to_string(), capital);
+    self.withdrawn.insert(ticker.to_string(), 0.0);
+    self.quote.insert(ticker.to_string(), 0.0);
+    self.cum_quote.insert(ticker.to_string(), vec![]);
+    self.cum_pct.insert(ticker.to_string(), vec![]);
+    self.pct_per_trade.insert(ticker.to_string(), vec![]);
+  }
+
+  /// Quote size of what's left of the open position on `ticker`
+  fn open_size(&self, ticker: &str) -> f64 {
+    self.position_sizes.get(ticker).copied().unwrap_or(0.0) * self.open_fractions.get(ticker).copied().unwrap_or(1.0)
+  }
+
+  /// Flatten the position on `ticker` once it's fully closed
+  fn close(&mut self, ticker: &str) {
+    self.active_trades.insert(ticker.to_string(), None);
+    self.position_sizes.remove(ticker);
+    self.open_fractions.remove(ticker);
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct Backtest<T, S: Strategy<T>> {
   pub strategy: S,
@@ -41,8 +130,32 @@ pub struct Backtest<T, S: Strategy<T>> {
   pub leverage: u8,
   /// False if spot trading, true if margin trading which allows short selling
   pub short_selling: bool,
+  /// Candle interval of the series, used to scale per-day costs to each candle
+  pub interval: Interval,
+  /// Funding or borrow cost in basis points per day.
+  /// Charged each candle on the leveraged notional of longs and the borrowed base asset of shorts.
+  pub funding_rate_bps_per_day: f64,
+  /// Whether profits compound or are periodically swept out of the account, defaults to [`ProfitPolicy::Reinvest`]
+  pub profit_policy: ProfitPolicy,
+  /// Exit open positions at the close of the last candle so their PnL is realized
+  pub close_at_end: bool,
+  /// Split of capital between quote and base on the first candle, defaults to [`Allocation::AllQuote`].
+  /// The base portion is held and marked to market in `cum_pct`, only the quote portion is traded.
+  pub initial_allocation: Allocation,
+  /// When a signal fills, defaults to [`ExecutionTiming::SameBarClose`]
+  pub execution_timing: ExecutionTiming,
+  /// Error if the strategy emits a signal dated after the candle it was given, which means it looked ahead.
+  /// Off by default so large backtests and optimizations skip the check.
+  pub check_lookahead: bool,
+  /// Most positions open at once across all tickers, unlimited if None. Entries at the cap are skipped.
+  /// When set the tickers share one account: each starts with an equal share of the capital
+  /// and every position is sized from the whole account split across the cap.
+  pub max_open_positions: Option<usize>,
+  /// Candles per ticker, oldest first. Every series must be the same length and index aligned,
+  /// the backtest steps through the ith candle of each ticker together. See [`time_series::Dataframe::align_series`].
   pub candles: HashMap<String, Vec<Candle>>,
   pub trades: HashMap<String, Vec<Trade>>,
+  /// Signals the strategy emitted per ticker in the last backtest, oldest first, before cooldown or position filtering
   pub signals: HashMap<String, Vec<Signal>>,
   _data: PhantomData<T>
 }
@@ -56,6 +169,14 @@ impl Default for Backtest<f64, EmptyStrategy> {
       bet: Bet::Static,
       leverage: 1,
       short_selling: false,
+      interval: Interval::ThirtyMinutes,
+      funding_rate_bps_per_day: 0.0,
+      profit_policy: ProfitPolicy::Reinvest,
+      close_at_end: false,
+      initial_allocation: Allocation::AllQuote,
+      execution_timing: ExecutionTiming::SameBarClose,
+      check_lookahead: false,
+      max_open_positions: None,
       candles: HashMap::new(),
       trades: HashMap::new(),
       signals: HashMap::new(),
@@ -65,7 +186,17 @@ impl Default for Backtest<f64, EmptyStrategy> {
 }
 
 impl<T, S: Strategy<T>> Backtest<T, S> {
-  pub fn new(strategy: S, capital: f64, fee: f64, bet: Bet, leverage: u8, short_selling: bool) -> Self {
+  #[allow(clippy::too_many_arguments)]
+  pub fn new(
+    strategy: S,
+    capital: f64,
+    fee: f64,
+    bet: Bet,
+    leverage: u8,
+    short_selling: bool,
+    interval: Interval,
+    funding_rate_bps_per_day: f64
+  ) -> Self {
     Self {
       strategy,
       capital,
@@ -73,6 +204,14 @@ impl<T, S: Strategy<T>> Backtest<T, S> {
       bet,
       leverage,
       short_selling,
+      interval,
+      funding_rate_bps_per_day,
+      profit_policy: ProfitPolicy::Reinvest,
+      close_at_end: false,
+      initial_allocation: Allocation::AllQuote,
+      execution_timing: ExecutionTiming::SameBarClose,
+      check_lookahead: false,
+      max_open_positions: None,
       candles: HashMap::new(),
       trades: HashMap::new(),
       signals: HashMap::new(),
@@ -139,6 +278,35 @@ impl<T, S: Strategy<T>> Backtest<T, S> {
     self.signals.clear();
   }
 
+  /// Funding or borrow cost in quote for holding `notional` over a single candle
+  pub fn funding_cost(&self, notional: f64) -> f64 {
+    let days_per_candle = self.interval.minutes() as f64 / 1440.0;
+    notional.abs() * (self.funding_rate_bps_per_day / 10_000.0) * days_per_candle
+  }
+
+  /// `Summary::cum_pct` forward filled onto every candle of the ticker, so each point is one candle of returns
+  pub fn equity_curve(&self, summary: &Summary, ticker: &str) -> anyhow::Result<Dataset<i64, f64>> {
+    let candles = self.candles.get(ticker).ok_or(anyhow::anyhow!("No candles for ticker"))?;
+    let cum_pct = summary.cum_pct(ticker)?.data();
+    let mut next = 0;
+    let mut pct = 0.0;
+    let data = candles.iter().map(|c| {
+      let x = c.date.to_unix_ms();
+      while next < cum_pct.len() && cum_pct[next].x <= x {
+        pct = cum_pct[next].y;
+        next += 1;
+      }
+      Data { x, y: pct }
+    }).collect();
+    Ok(Dataset::new(data))
+  }
+
+  /// Rolling Sharpe of the per-candle equity curve, annualized by the backtest interval
+  pub fn rolling_sharpe(&self, summary: &Summary, ticker: &str, window: usize) -> anyhow::Result<Dataset<i64, f64>> {
+    let equity = self.equity_curve(summary, ticker)?;
+    Ok(equity.rolling_sharpe(window, self.interval.periods_per_year()))
+  }
+
   pub fn buy_and_hold(
     &mut self,
   ) -> anyhow::Result<HashMap<String, Vec<Data<i64, f64>>>> {
@@ -163,44 +331,137 @@ impl<T, S: Strategy<T>> Backtest<T, S> {
     Ok(all_data)
   }
 
+  /// Buy and hold of every ticker with equal capital, see [`Backtest::weighted_benchmark`]
+  pub fn equal_weight_benchmark(&self) -> anyhow::Result<Dataset<i64, f64>> {
+    let weights = self.candles.keys().map(|ticker| (ticker.clone(), 1.0)).collect();
+    self.weighted_benchmark(weights)
+  }
+
+  /// % ROI of a basket that splits capital across tickers by `weights` on the first candle and holds without rebalancing.
+  /// Weights are normalized to sum to 1, so a 60/40 mix is `{"BTC": 60, "ETH": 40}` or `{"BTC": 0.6, "ETH": 0.4}`.
+  /// Series are assumed aligned by index, as the backtest itself steps through them,
+  /// and the basket is dated by the candles of the alphabetically first ticker.
+  pub fn weighted_benchmark(&self, weights: HashMap<String, f64>) -> anyhow::Result<Dataset<i64, f64>> {
+    let total_weight: f64 = weights.values().sum();
+    if weights.is_empty() || total_weight <= 0.0 {
+      return Err(anyhow::anyhow!("Benchmark weights must sum to a positive value"));
+    }
+    // sorted so the leg dating the basket doesn't depend on hash order
+    let mut weights: Vec<(String, f64)> = weights.into_iter().collect();
+    weights.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut legs = vec![];
+    for (ticker, weight) in weights.iter() {
+      let candles = self.candles.get(ticker).ok_or(anyhow::anyhow!("No candles for ticker {}", ticker))?;
+      let first = candles.first().ok_or(anyhow::anyhow!("No candles for ticker {}", ticker))?;
+      legs.push((candles, first.close, weight / total_weight));
+    }
+    let length = legs.iter().map(|(candles, _, _)| candles.len()).min().unwrap_or(0);
+    let data = (0..length).map(|i| {
+      let equity: f64 = legs.iter().map(|(candles, first, weight)| weight * candles[i].close / first).sum();
+      Data {
+        x: legs[0].0[i].date.to_unix_ms(),
+        y: (equity - 1.0) * 100.0
+      }
+    }).collect();
+    Ok(Dataset::new(data))
+  }
+
+  /// Errors if the series in `candles` differ in length, naming each ticker and its length
+  pub fn validate_candles(&self) -> anyhow::Result<()> {
+    let mut lengths: Vec<(&String, usize)> = self.candles.iter().map(|(ticker, c)| (ticker, c.len())).collect();
+    lengths.sort();
+    match lengths.windows(2).any(|w| w[0].1 != w[1].1) {
+      true => {
+        let lengths: Vec<String> = lengths.iter().map(|(ticker, len)| format!("{}: {}", ticker, len)).collect();
+        Err(anyhow::anyhow!(
+          "Candle series must be the same length, align them with Dataframe::align_series ({})", lengths.join(", ")
+        ))
+      },
+      false => Ok(())
+    }
+  }
+
+  /// Quote size of a new position, a share of the whole account split across [`Backtest::max_open_positions`] if set
+  fn bet_size(&self, static_capital: f64, cum_capital: &HashMap<String, f64>, ticker: &str) -> f64 {
+    match (self.bet, self.max_open_positions) {
+      (Bet::Static, _) => static_capital,
+      (Bet::Percent(pct), None) => *cum_capital.get(ticker).unwrap() * pct / 100.0,
+      (Bet::Percent(pct), Some(max)) => cum_capital.values().sum::<f64>() * pct / 100.0 / max.max(1) as f64
+    }
+  }
+
+  /// True if [`Backtest::max_open_positions`] are already open across all tickers
+  fn at_max_open_positions(&self, active_trades: &HashMap<String, Option<Trade>>) -> bool {
+    matches!(self.max_open_positions, Some(max) if active_trades.values().filter(|trade| trade.is_some()).count() >= max)
+  }
+
+  /// Step through the ith candle of every ticker together, see [`Backtest::candles`].
+  /// Errors without trading if the series differ in length.
   pub fn backtest(
     &mut self,
   ) -> anyhow::Result<Summary> {
+    self.backtest_with_progress(|_, _| ())
+  }
+
+  /// Same as [`Backtest::backtest`], calling `progress(completed, total)` after each candle index is processed
+  pub fn backtest_with_progress<F: Fn(usize, usize)>(
+    &mut self,
+    progress: F
+  ) -> anyhow::Result<Summary> {
+    self.validate_candles()?;
     let candles = self.candles.clone();
     
-    let static_capital = self.capital * self.leverage as f64;
     let initial_capital = self.capital;
+    // capital each ticker starts with, a share of the account if the tickers share one
+    let ticker_capital = match self.max_open_positions {
+      Some(_) => self.capital / candles.len().max(1) as f64,
+      None => self.capital
+    };
+    let quote_capital = self.initial_allocation.quote_capital(ticker_capital)?;
+    let static_capital = match self.max_open_positions {
+      Some(max) => self.initial_allocation.quote_capital(self.capital)? * self.leverage as f64 / max.max(1) as f64,
+      None => quote_capital * self.leverage as f64
+    };
 
-    let mut cum_capital: HashMap<String, f64> = HashMap::new();
-    let mut quote: HashMap<String, f64> = HashMap::new();
-    let mut cum_pct: HashMap<String, Vec<Data<i64, f64>>> = HashMap::new();
-    let mut cum_quote: HashMap<String, Vec<Data<i64, f64>>> = HashMap::new();
-    let mut pct_per_trade:  HashMap<String, Vec<Data<i64, f64>>> = HashMap::new();
+    let mut ledger = Ledger::default();
+    let mut equity: HashMap<String, Vec<Data<i64, f64>>> = HashMap::new();
+    // base asset bought on the first candle by the initial allocation
+    let mut base_qty: HashMap<String, f64> = HashMap::new();
 
     if let Some((_, first_series)) = candles.iter().next() {
       let length = first_series.len();
 
-      let mut active_trades: HashMap<String, Option<Trade>> = HashMap::new();
-      for (ticker, _) in candles.iter() {
-        // populate active trades with None values for each ticker so getter doesn't panic
-        active_trades.insert(ticker.clone(), None);
+      for (ticker, series) in candles.iter() {
         // populate with empty vec for each ticker so getter doesn't panic
         self.trades.insert(ticker.clone(), vec![]);
+        self.signals.insert(ticker.clone(), vec![]);
         // populate all tickers with starting values
-        cum_capital.insert(ticker.clone(), self.capital * self.leverage as f64);
-        quote.insert(ticker.clone(), 0.0);
-        cum_pct.insert(ticker.clone(), vec![]);
-        cum_quote.insert(ticker.clone(), vec![]);
-        pct_per_trade.insert(ticker.clone(), vec![]);
+        ledger.open(ticker, quote_capital * self.leverage as f64);
+        let first_close = series.first().map(|c| c.close).unwrap_or(0.0);
+        base_qty.insert(ticker.clone(), match first_close > 0.0 {
+          true => (ticker_capital - quote_capital) / first_close,
+          false => 0.0
+        });
+        equity.insert(ticker.clone(), vec![]);
       }
 
+      // value at the close of candle `i` of the base asset held by the initial allocation
+      let mark_base = |ticker: &String, i: usize| -> f64 {
+        base_qty.get(ticker).unwrap() * candles.get(ticker).unwrap()[i].close
+      };
+
+      // signals per ticker waiting to fill at the next candle's open, see [`ExecutionTiming::NextBarOpen`]
+      let mut pending: HashMap<String, Vec<Signal>> = HashMap::new();
+
       // Iterate over the index of each series
       for i in 0..length {
         // Access the i-th element of each vector to simulate getting price update
-        // for every ticker at roughly the same time
-        let entries = candles.iter().map(|(ticker, candles)| {
+        // for every ticker at roughly the same time.
+        // Sorted by ticker so which entry is skipped at max open positions doesn't depend on hash order.
+        let mut entries = candles.iter().map(|(ticker, candles)| {
           (ticker.clone(), candles.clone())
         }).collect::<Vec<(String, Vec<Candle>)>>();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
         for (ticker, candles) in entries.iter() {
           let candle = candles[i];
 
@@ -208,308 +469,712 @@ impl<T, S: Strategy<T>> Backtest<T, S> {
             println!("first: {}", ticker);
           }
 
-          // check if stop loss is hit
-          if let (Some(entry), Some(stop_loss_pct)) = (active_trades.get(ticker).unwrap(), self.strategy.stop_loss_pct()) {
-            match entry.side {
-              Order::EnterLong => {
-                let pct_diff = (candle.low - entry.price) / entry.price * 100.0;
-                if pct_diff < stop_loss_pct * -1.0 {
-                  let price_at_stop_loss = entry.price * (1.0 - stop_loss_pct / 100.0);
-                  // longs are stopped out by the low
-                  let pct_pnl = (price_at_stop_loss - entry.price) / entry.price * 100.0;
-                  let position_size = match self.bet {
-                    Bet::Static => static_capital,
-                    Bet::Percent(pct) => *cum_capital.get(ticker).unwrap() * pct / 100.0
-                  };
-
-                  // add entry trade with updated quantity
-                  let quantity = position_size / entry.price;
-                  let updated_entry = Trade {
-                    ticker: ticker.clone(),
-                    date: entry.date,
-                    side: entry.side,
-                    quantity,
-                    price: entry.price
-                  };
-                  self.add_trade(updated_entry, ticker.clone());
-
-                  // fee on trade entry capital
-                  let entry_fee = position_size.abs() * (self.fee / 100.0);
-                  let cum_capital = cum_capital.get_mut(ticker).unwrap();
-                  *cum_capital -= entry_fee;
-                  
-                  // fee on profit made
-                  let mut quote_pnl = pct_pnl / 100.0 * position_size;
-                  let profit_fee = quote_pnl.abs() * (self.fee / 100.0);
-                  quote_pnl -= profit_fee;
-
-                  *cum_capital += quote_pnl;
-                  let quote = quote.get_mut(ticker).unwrap();
-                  *quote += quote_pnl;
-
-                  cum_quote.get_mut(ticker).unwrap().push(Data {
-                    x: entry.date.to_unix_ms(),
-                    y: trunc!(*quote, 2)
-                  });
-                  cum_pct.get_mut(ticker).unwrap().push(Data {
-                    x: entry.date.to_unix_ms(),
-                    y: trunc!(*cum_capital / initial_capital * 100.0 - 100.0, 2)
-                  });
-                  pct_per_trade.get_mut(ticker).unwrap().push(Data {
-                    x: entry.date.to_unix_ms(),
-                    y: trunc!(pct_pnl, 2)
-                  });
-
-                  // stop loss exit
-                  let quantity = position_size / price_at_stop_loss;
-                  let exit = Trade {
-                    ticker: ticker.clone(),
-                    date: candle.date,
-                    side: Order::ExitLong,
-                    quantity,
-                    price: price_at_stop_loss,
-                  };
-                  active_trades.insert(ticker.clone(), None);
-                  self.add_trade(exit, ticker.clone());
-                }
-              }
-              Order::EnterShort => {
-                // can only be stopped out if entering a short is allowed,
-                // spot markets do not allow short selling
-                if self.short_selling {
-                  let pct_diff = (candle.high - entry.price) / entry.price * 100.0;
-                  if pct_diff > stop_loss_pct {
-                    let price_at_stop_loss = entry.price * (1.0 + stop_loss_pct / 100.0);
-                    // longs are stopped out by the low
-                    let pct_pnl = (price_at_stop_loss - entry.price) / entry.price * -1.0 * 100.0;
-                    let position_size = match self.bet {
-                      Bet::Static => static_capital,
-                      Bet::Percent(pct) => *cum_capital.get(ticker).unwrap() * pct / 100.0
-                    };
-
-                    // add entry trade with updated quantity
-                    let quantity = position_size / entry.price;
-                    let updated_entry = Trade {
-                      ticker: ticker.clone(),
-                      date: entry.date,
-                      side: entry.side,
-                      quantity,
-                      price: entry.price
-                    };
-                    self.add_trade(updated_entry, ticker.clone());
-
-                    // fee on trade entry capital
-                    let entry_fee = position_size.abs() * (self.fee / 100.0);
-                    let cum_capital = cum_capital.get_mut(ticker).unwrap();
-                    *cum_capital -= entry_fee;
-                    // fee on profit made
-                    let mut quote_pnl = pct_pnl / 100.0 * position_size;
-                    let profit_fee = quote_pnl.abs() * (self.fee / 100.0);
-                    quote_pnl -= profit_fee;
-
-                    *cum_capital += quote_pnl;
-                    let quote = quote.get_mut(ticker).unwrap();
-                    *quote += quote_pnl;
-
-                    cum_quote.get_mut(ticker).unwrap().push(Data {
-                      x: entry.date.to_unix_ms(),
-                      y: trunc!(*quote, 2)
-                    });
-                    cum_pct.get_mut(ticker).unwrap().push(Data {
-                      x: entry.date.to_unix_ms(),
-                      y: trunc!(*cum_capital / initial_capital * 100.0 - 100.0, 2)
-                    });
-                    pct_per_trade.get_mut(ticker).unwrap().push(Data {
-                      x: entry.date.to_unix_ms(),
-                      y: trunc!(pct_pnl, 2)
-                    });
-
-                    // stop loss exit
-                    let quantity = position_size / price_at_stop_loss;
-                    let exit = Trade {
-                      ticker: ticker.clone(),
-                      date: candle.date,
-                      side: Order::ExitShort,
-                      quantity,
-                      price: price_at_stop_loss,
-                    };
-                    active_trades.insert(ticker.clone(), None);
-                    self.add_trade(exit, ticker.clone());
-                  }
-                }
+          // with next bar open execution, the prior candle's signals fill at this open, before the stop loss is checked.
+          // The candle itself is unknown at its open.
+          if let Some(signals) = pending.remove(ticker) {
+            let opens = signals.into_iter().map(|signal| signal.repriced(candle.open, candle.date)).collect();
+            self.fill(&mut ledger, opens, i, &candles[..i], static_capital, ticker_capital, &mark_base);
+          }
+
+          // accrue funding for every candle a leveraged or short position is held
+          if let Some(entry) = ledger.active_trades.get(ticker).unwrap() {
+            if self.funding_rate_bps_per_day > 0.0 {
+              let position_size = ledger.open_size(ticker);
+              let notional = match entry.side {
+                // longs only borrow when leveraged
+                Order::EnterLong if self.leverage > 1 => Some(position_size),
+                // shorts borrow the base asset, so pay on its current value
+                Order::EnterShort if self.short_selling => Some(position_size / entry.price * candle.close),
+                _ => None
+              };
+              if let Some(notional) = notional {
+                let funding = self.funding_cost(notional);
+                *ledger.cum_capital.get_mut(ticker).unwrap() -= funding;
+                *ledger.quote.get_mut(ticker).unwrap() -= funding;
               }
-              _ => ()
             }
           }
 
-          // place new trade if signal is present
-          let signals = self.strategy.process_candle(candle, Some(ticker.clone()))?;
-          for signal in signals {
-            match signal {
-              Signal::EnterLong(info) => {
-                // only place if no active trade to prevent pyramiding
-                if active_trades.get(&info.ticker).unwrap().is_none() {
-                  let trade = Trade {
-                    ticker: info.ticker.clone(),
-                    date: info.date,
-                    side: Order::EnterLong,
-                    quantity: 0.0, // quantity doesn't matter, since exit trade computes it
-                    price: info.price,
-                  };
-                  active_trades.insert(info.ticker.clone(), Some(trade.clone()));
-                }
-              },
-              Signal::ExitLong(info) => {
-                if let Some(entry) = active_trades.get(&info.ticker).unwrap() {
-                  if entry.side == Order::EnterLong {
-                    let pct_pnl = (info.price - entry.price) / entry.price * 100.0;
-                    let position_size = match self.bet {
-                      Bet::Static => static_capital,
-                      Bet::Percent(pct) => *cum_capital.get(&info.ticker).unwrap() * pct / 100.0
-                    };
-
-                    let quantity = position_size / entry.price;
-                    let updated_entry = Trade {
-                      ticker: ticker.clone(),
-                      date: entry.date,
-                      side: entry.side,
-                      quantity,
-                      price: entry.price
-                    };
-                    self.add_trade(updated_entry, info.ticker.clone());
-
-                    // fee on trade entry capital
-                    let entry_fee = position_size.abs() * (self.fee / 100.0);
-                    let cum_capital = cum_capital.get_mut(&info.ticker).unwrap();
-                    *cum_capital -= entry_fee;
-                    // fee on profit made
-                    let mut quote_pnl = pct_pnl / 100.0 * position_size;
-                    let profit_fee = quote_pnl.abs() * (self.fee / 100.0);
-                    quote_pnl -= profit_fee;
-
-                    *cum_capital += quote_pnl;
-                    let quote = quote.get_mut(&info.ticker).unwrap();
-                    *quote += quote_pnl;
-
-                    cum_quote.get_mut(&info.ticker).unwrap().push(Data {
-                      x: entry.date.to_unix_ms(),
-                      y: trunc!(*quote, 2)
-                    });
-                    cum_pct.get_mut(&info.ticker).unwrap().push(Data {
-                      x: entry.date.to_unix_ms(),
-                      y: trunc!(*cum_capital / initial_capital * 100.0 - 100.0, 2)
-                    });
-                    pct_per_trade.get_mut(&info.ticker).unwrap().push(Data {
-                      x: entry.date.to_unix_ms(),
-                      y: trunc!(pct_pnl, 2)
-                    });
-
-                    let quantity = position_size / info.price;
-                    let exit = Trade {
-                      ticker: info.ticker.clone(),
-                      date: info.date,
-                      side: Order::ExitLong,
-                      quantity,
-                      price: info.price,
-                    };
-                    active_trades.insert(info.ticker.clone(), None);
-                    self.add_trade(exit, info.ticker.clone());
-                  }
-                }
-              },
-              Signal::EnterShort(info) => {
-                // only place if no active trade to prevent pyramiding
-                // todo: allow pyramiding to enable hedging
-                if active_trades.get(&info.ticker).unwrap().is_none() && self.short_selling {
-                  let trade = Trade {
-                    ticker: info.ticker.clone(),
-                    date: info.date,
-                    side: Order::EnterShort,
-                    quantity: 0.0, // quantity doesn't matter, since exit trade recomputes it
-                    price: info.price,
-                  };
-                  active_trades.insert(info.ticker.clone(), Some(trade.clone()));
-                }
+          // check if stop loss is hit
+          if let (Some(entry), Some(&stop_loss_pct)) = (ledger.active_trades.get(ticker).unwrap().clone(), ledger.stop_loss_pcts.get(ticker)) {
+            let price_at_stop_loss = match entry.side {
+              // longs are stopped out by the low
+              Order::EnterLong if (candle.low - entry.price) / entry.price * 100.0 < stop_loss_pct * -1.0 => {
+                Some(entry.price * (1.0 - stop_loss_pct / 100.0))
               },
-              Signal::ExitShort(info) => {
-                if let Some(entry) = active_trades.get(&info.ticker).unwrap() {
-                  if entry.side == Order::EnterShort && self.short_selling {
-                    let pct_pnl = (info.price - entry.price) / entry.price * -1.0 * 100.0;
-                    let position_size = match self.bet {
-                      Bet::Static => static_capital,
-                      Bet::Percent(pct) => *cum_capital.get(&info.ticker).unwrap() * pct / 100.0
-                    };
-
-                    let quantity = position_size / entry.price;
-                    let updated_entry = Trade {
-                      ticker: info.ticker.clone(),
-                      date: entry.date,
-                      side: entry.side,
-                      quantity,
-                      price: entry.price
-                    };
-                    self.add_trade(updated_entry, info.ticker.clone());
-
-                    // fee on trade entry capital
-                    let entry_fee = position_size.abs() * (self.fee / 100.0);
-                    let cum_capital = cum_capital.get_mut(&info.ticker).unwrap();
-                    *cum_capital -= entry_fee;
-                    // fee on profit made
-                    let mut quote_pnl = pct_pnl / 100.0 * position_size;
-                    let profit_fee = quote_pnl.abs() * (self.fee / 100.0);
-                    quote_pnl -= profit_fee;
-
-                    *cum_capital += quote_pnl;
-                    let quote = quote.get_mut(&info.ticker).unwrap();
-                    *quote += quote_pnl;
-
-                    cum_quote.get_mut(&info.ticker).unwrap().push(Data {
-                      x: entry.date.to_unix_ms(),
-                      y: trunc!(*quote, 2)
-                    });
-                    cum_pct.get_mut(&info.ticker).unwrap().push(Data {
-                      x: entry.date.to_unix_ms(),
-                      y: trunc!(*cum_capital / initial_capital * 100.0 - 100.0, 2)
-                    });
-                    pct_per_trade.get_mut(&info.ticker).unwrap().push(Data {
-                      x: entry.date.to_unix_ms(),
-                      y: trunc!(pct_pnl, 2)
-                    });
-
-                    let quantity = position_size / info.price;
-                    let exit = Trade {
-                      ticker: info.ticker.clone(),
-                      date: info.date,
-                      side: Order::ExitShort,
-                      quantity,
-                      price: info.price,
-                    };
-                    active_trades.insert(info.ticker.clone(), None);
-                    self.add_trade(exit, info.ticker.clone());
-                  }
-                }
+              // can only be stopped out if entering a short is allowed,
+              // spot markets do not allow short selling
+              Order::EnterShort if self.short_selling && (candle.high - entry.price) / entry.price * 100.0 > stop_loss_pct => {
+                Some(entry.price * (1.0 + stop_loss_pct / 100.0))
               },
-              _ => ()
+              _ => None
+            };
+            if let Some(price_at_stop_loss) = price_at_stop_loss {
+              // the stop closes whatever is left of the position
+              let position_size = ledger.open_size(ticker);
+              ledger.close(ticker);
+              self.close_position(&mut ledger, &entry, price_at_stop_loss, candle.date, position_size, mark_base(ticker, i), ticker_capital);
+            }
+          }
+
+          // place new trade if signal is present
+          let signals = self.strategy.process_candle(candle, Some(ticker.clone()))?;
+          if self.check_lookahead {
+            if let Some(date) = signals.iter().filter_map(|s| s.date()).find(|date| date.to_unix_ms() > candle.date.to_unix_ms()) {
+              return Err(anyhow::anyhow!(
+                "Lookahead: {} signal dated {} on candle {}",
+                ticker,
+                date.to_string(),
+                candle.date.to_string()
+              ));
             }
           }
+          self.signals.get_mut(ticker).unwrap().extend(signals.iter().cloned());
+          let mut close = vec![];
+          if self.close_at_end && i == length - 1 {
+            if let Some(entry) = ledger.active_trades.get(ticker).unwrap() {
+              let info = SignalInfo {
+                price: candle.close,
+                date: candle.date,
+                ticker: ticker.clone(),
+                exit_fraction: None,
+                order_type: None,
+                time_in_force: None
+              };
+              match entry.side {
+                Order::EnterLong => close.push(Signal::ExitLong(info)),
+                Order::EnterShort => close.push(Signal::ExitShort(info)),
+                _ => ()
+              }
+            }
+          }
+          let fills = match self.execution_timing {
+            ExecutionTiming::SameBarClose => signals.into_iter().chain(close).collect(),
+            ExecutionTiming::NextBarOpen => {
+              pending.insert(ticker.clone(), signals);
+              close
+            }
+          };
+          self.fill(&mut ledger, fills, i, &candles[..i + 1], static_capital, ticker_capital, &mark_base);
+
+          // mark the open trade to the close, net of the entry fee charged when it closes
+          let unrealized = match ledger.active_trades.get(ticker).unwrap() {
+            Some(entry) => {
+              let position_size = ledger.open_size(ticker);
+              let pct_pnl = match entry.side {
+                Order::EnterShort => (candle.close - entry.price) / entry.price * -1.0,
+                _ => (candle.close - entry.price) / entry.price
+              };
+              pct_pnl * position_size - position_size.abs() * (self.fee / 100.0)
+            },
+            None => 0.0
+          };
+          let nav = ledger.cum_capital.get(ticker).unwrap() + ledger.withdrawn.get(ticker).unwrap() + mark_base(ticker, i) + unrealized;
+          equity.get_mut(ticker).unwrap().push(Data {
+            x: candle.date.to_unix_ms(),
+            y: trunc!(nav, 2)
+          });
         }
+        progress(i + 1, length);
       }
     }
 
-    let cum_quote = cum_quote.iter().map(|(ticker, data)| {
-      (ticker.clone(), Dataset::new(data.clone()))
-    }).collect();
-    let cum_pct = cum_pct.iter().map(|(ticker, data)| {
-      (ticker.clone(), Dataset::new(data.clone()))
-    }).collect();
-    let pct_per_trade = pct_per_trade.iter().map(|(ticker, data)| {
-      (ticker.clone(), Dataset::new(data.clone()))
-    }).collect();
+    let datasets = |series: HashMap<String, Vec<Data<i64, f64>>>| -> HashMap<String, Dataset<i64, f64>> {
+      series.into_iter().map(|(ticker, data)| (ticker, Dataset::new(data))).collect()
+    };
     Ok(Summary {
-      cum_quote,
-      cum_pct,
-      pct_per_trade,
+      initial_capital,
+      interval_minutes: self.interval.minutes(),
+      ticker_capital: match self.max_open_positions {
+        Some(_) => candles.keys().map(|ticker| (ticker.clone(), ticker_capital)).collect(),
+        None => HashMap::new()
+      },
+      total_candles: candles.iter().map(|(ticker, candles)| (ticker.clone(), candles.len())).collect(),
+      withdrawn: ledger.withdrawn,
+      cum_quote: datasets(ledger.cum_quote),
+      cum_pct: datasets(ledger.cum_pct),
+      equity: datasets(equity),
+      pct_per_trade: datasets(ledger.pct_per_trade),
       trades: self.trades.clone()
     })
   }
-}
\ No newline at end of file
+
+  /// Fill `signals` on the ith candle, `known` being the candles up to the fill used to size an ATR stop.
+  /// Entries are skipped while a position is open, in cooldown or at [`Backtest::max_open_positions`],
+  /// exits close their fraction of the open position with [`Backtest::close_position`].
+  #[allow(clippy::too_many_arguments)]
+  fn fill(
+    &mut self,
+    ledger: &mut Ledger,
+    signals: Vec<Signal>,
+    i: usize,
+    known: &[Candle],
+    static_capital: f64,
+    ticker_capital: f64,
+    mark_base: &impl Fn(&String, usize) -> f64
+  ) {
+    let cooldown = self.strategy.cooldown();
+    for signal in signals {
+      let (info, side) = match signal {
+        Signal::EnterLong(info) => (info, Order::EnterLong),
+        Signal::EnterShort(info) => (info, Order::EnterShort),
+        Signal::ExitLong(info) => (info, Order::ExitLong),
+        Signal::ExitShort(info) => (info, Order::ExitShort),
+        _ => continue
+      };
+      match side {
+        Order::EnterLong | Order::EnterShort => {
+          let in_cooldown = matches!(ledger.last_entry.get(&info.ticker), Some(last) if cooldown > 0 && i - last <= cooldown);
+          let at_capacity = self.at_max_open_positions(&ledger.active_trades);
+          // spot markets do not allow short selling
+          let allowed = side == Order::EnterLong || self.short_selling;
+          // only place if no active trade to prevent pyramiding
+          // todo: allow pyramiding to enable hedging
+          if ledger.active_trades.get(&info.ticker).unwrap().is_none() && !in_cooldown && !at_capacity && allowed {
+            let trade = Trade {
+              ticker: info.ticker.clone(),
+              date: info.date,
+              side,
+              quantity: 0.0, // quantity doesn't matter, since exit trade computes it
+              price: info.price,
+            };
+            ledger.active_trades.insert(info.ticker.clone(), Some(trade));
+            ledger.last_entry.insert(info.ticker.clone(), i);
+            let position_size = self.bet_size(static_capital, &ledger.cum_capital, &info.ticker);
+            ledger.position_sizes.insert(info.ticker.clone(), position_size);
+            match self.strategy.stop_loss().and_then(|stop_loss| stop_loss.pct(info.price, known)) {
+              Some(pct) => ledger.stop_loss_pcts.insert(info.ticker.clone(), pct),
+              None => ledger.stop_loss_pcts.remove(&info.ticker)
+            };
+          }
+        },
+        _ => {
+          let entry_side = match side {
+            Order::ExitLong => Order::EnterLong,
+            _ => Order::EnterShort
+          };
+          let allowed = entry_side == Order::EnterLong || self.short_selling;
+          if let Some(entry) = ledger.active_trades.get(&info.ticker).unwrap().clone() {
+            if entry.side == entry_side && allowed && info.fraction() > 0.0 {
+              let open_fraction = ledger.open_fractions.get(&info.ticker).copied().unwrap_or(1.0);
+              let closed_fraction = open_fraction * info.fraction();
+              // a partial exit closes its share of the position, the rest stays open at the entry price
+              let position_size = ledger.position_sizes.get(&info.ticker).unwrap() * closed_fraction;
+              // float error can leave dust of a position scaled out in tranches
+              match open_fraction - closed_fraction > 1e-9 {
+                true => {
+                  ledger.open_fractions.insert(info.ticker.clone(), open_fraction - closed_fraction);
+                },
+                false => ledger.close(&info.ticker)
+              }
+              self.close_position(ledger, &entry, info.price, info.date, position_size, mark_base(&info.ticker, i), ticker_capital);
+            }
+          }
+        }
+      }
+    }
+  }
+
+  /// Close `position_size` in quote of `entry` at `exit_price`, recording the entry with the closed quantity and the exit.
+  /// Books the PnL net of the entry and profit fees into `ledger` and sweeps it per the profit policy.
+  /// `base_value` is the initial allocation's base marked at this candle, counted in `cum_pct` against `ticker_capital`.
+  #[allow(clippy::too_many_arguments)]
+  fn close_position(
+    &mut self,
+    ledger: &mut Ledger,
+    entry: &Trade,
+    exit_price: f64,
+    exit_date: Time,
+    position_size: f64,
+    base_value: f64,
+    ticker_capital: f64
+  ) {
+    let ticker = &entry.ticker;
+    let (pct_pnl, exit_side) = match entry.side {
+      Order::EnterShort => ((exit_price - entry.price) / entry.price * -1.0 * 100.0, Order::ExitShort),
+      _ => ((exit_price - entry.price) / entry.price * 100.0, Order::ExitLong)
+    };
+
+    // add entry trade with updated quantity
+    self.add_trade(Trade { quantity: position_size / entry.price, ..entry.clone() }, ticker.clone());
+
+    // fee on trade entry capital
+    let entry_fee = position_size.abs() * (self.fee / 100.0);
+    let cum_capital = ledger.cum_capital.get_mut(ticker).unwrap();
+    *cum_capital -= entry_fee;
+    // fee on profit made
+    let mut quote_pnl = pct_pnl / 100.0 * position_size;
+    let profit_fee = quote_pnl.abs() * (self.fee / 100.0);
+    quote_pnl -= profit_fee;
+
+    *cum_capital += quote_pnl;
+    // sweep equity above the threshold out of the account
+    let withdrawn = ledger.withdrawn.get_mut(ticker).unwrap();
+    *withdrawn += self.profit_policy.withdraw(cum_capital);
+    let quote = ledger.quote.get_mut(ticker).unwrap();
+    *quote += quote_pnl;
+
+    let x = entry.date.to_unix_ms();
+    ledger.cum_quote.get_mut(ticker).unwrap().push(Data { x, y: trunc!(*quote, 2) });
+    ledger.cum_pct.get_mut(ticker).unwrap().push(Data {
+      x,
+      y: trunc!((*cum_capital + *withdrawn + base_value) / ticker_capital * 100.0 - 100.0, 2)
+    });
+    ledger.pct_per_trade.get_mut(ticker).unwrap().push(Data { x, y: trunc!(pct_pnl, 2) });
+
+    self.add_trade(Trade {
+      ticker: ticker.clone(),
+      date: exit_date,
+      side: exit_side,
+      quantity: position_size / exit_price,
+      price: exit_price
+    }, ticker.clone());
+  }
+
+  /// Backtest each % stop loss in `levels` in parallel, returning `(level, summary)` in the order of `levels`.
+  /// The strategy's signals don't depend on the stop, so they are computed in one pass over the candles with a
+  /// clone of the strategy and replayed for every level, rather than running the strategy once per level.
+  /// Prints the ROI and win rate of each ticker per level.
+  pub fn stop_loss_sweep(&self, levels: &[f64]) -> anyhow::Result<Vec<(f64, Summary)>> {
+    self.validate_candles()?;
+    let mut strategy = self.strategy.clone();
+    let mut signals: HashMap<String, Vec<Vec<Signal>>> = HashMap::new();
+    let length = self.candles.values().next().map(|c| c.len()).unwrap_or(0);
+    // visit tickers in the order the backtest does, a strategy like StatArb signals on whichever leg is pushed second
+    let mut tickers: Vec<String> = self.candles.keys().cloned().collect();
+    tickers.sort();
+    for i in 0..length {
+      for ticker in tickers.iter() {
+        let candles = self.candles.get(ticker).unwrap();
+        let candle_signals = strategy.process_candle(candles[i], Some(ticker.clone()))?;
+        signals.entry(ticker.clone()).or_default().push(candle_signals);
+      }
+    }
+
+    let replay = Backtest {
+      strategy: SignalReplay {
+        signals: Arc::new(signals),
+        next: HashMap::new(),
+        stop_loss_pct: 0.0,
+        cooldown: self.strategy.cooldown()
+      },
+      capital: self.capital,
+      fee: self.fee,
+      bet: self.bet,
+      leverage: self.leverage,
+      short_selling: self.short_selling,
+      interval: self.interval,
+      funding_rate_bps_per_day: self.funding_rate_bps_per_day,
+      profit_policy: self.profit_policy,
+      close_at_end: self.close_at_end,
+      initial_allocation: self.initial_allocation,
+      execution_timing: self.execution_timing,
+      check_lookahead: self.check_lookahead,
+      max_open_positions: self.max_open_positions,
+      candles: self.candles.clone(),
+      trades: HashMap::new(),
+      signals: HashMap::new(),
+      _data: PhantomData
+    };
+    let results = levels.par_iter().map(|level| {
+      let mut backtest = replay.clone();
+      backtest.strategy.stop_loss_pct = *level;
+      Ok((*level, backtest.backtest()?))
+    }).collect::<anyhow::Result<Vec<(f64, Summary)>>>()?;
+
+    for (level, summary) in results.iter() {
+      for ticker in tickers.iter() {
+        println!(
+          "{} stop loss {}%: {}% ROI, {}% win rate",
+          ticker, level, summary.pct_roi(ticker), trunc!(summary.win_rate(ticker), 2)
+        );
+      }
+    }
+    Ok(results)
+  }
+}
+
+/// Run `run` over every parameter combination in parallel, e.g. an optimization grid of strategy params.
+/// `progress(completed, total)` is called on the calling thread as each combination finishes,
+/// the rayon workers only send completions over a channel.
+pub fn grid_search<P, R, F, C>(params: Vec<P>, run: F, progress: C) -> anyhow::Result<Vec<R>>
+where
+  P: Send,
+  R: Send,
+  F: Fn(P) -> anyhow::Result<R> + Sync,
+  C: Fn(usize, usize)
+{
+  let total = params.len();
+  let (tx, rx) = mpsc::channel::<()>();
+  std::thread::scope(|scope| {
+    let run = &run;
+    let workers = scope.spawn(move || {
+      params.into_par_iter().map_with(tx, |tx, p| {
+        let res = run(p);
+        // the receiver outlives the workers, so the send can't fail
+        let _ = tx.send(());
+        res
+      }).collect::<anyhow::Result<Vec<R>>>()
+    });
+    // ends once every worker's sender is dropped
+    for (completed, _) in rx.iter().enumerate() {
+      progress(completed + 1, total);
+    }
+    workers.join().unwrap_or_else(|e| std::panic::resume_unwind(e))
+  })
+}
+
+/// [`grid_search`] over every `(p1, p2)` pair, backtesting each with `run` and scoring the summary with `score` (e.g. % ROI).
+/// A pair whose backtest of `ticker` has fewer than `min_trades` trades scores NaN, so a degenerate
+/// configuration with a lucky trade or two is never selected.
+/// Returns `(p1, p2, score)` for the whole grid, `p1` major, so the neighborhood around the optimum
+/// can be inspected or rendered with [`time_series::Plot::heatmap`]. See [`rank_grid`] for the best pairs.
+#[allow(clippy::too_many_arguments)]
+pub fn grid_search_2d<P1, P2, F, S, C>(
+  p1: &[P1],
+  p2: &[P2],
+  ticker: &str,
+  min_trades: usize,
+  run: F,
+  score: S,
+  progress: C
+) -> anyhow::Result<Vec<(P1, P2, f64)>>
+where
+  P1: Clone + Send + Sync,
+  P2: Clone + Send + Sync,
+  F: Fn(P1, P2) -> anyhow::Result<Summary> + Sync,
+  S: Fn(&Summary) -> f64 + Sync,
+  C: Fn(usize, usize)
+{
+  let params: Vec<(P1, P2)> = p1.iter().flat_map(|a| {
+    p2.iter().map(move |b| (a.clone(), b.clone()))
+  }).collect();
+  grid_search(params, |(a, b)| {
+    let summary = run(a.clone(), b.clone())?;
+    let trades = summary.cum_pct.get(ticker).map(|d| d.data().len()).unwrap_or(0);
+    let score = match trades >= min_trades {
+      true => score(&summary),
+      false => f64::NAN
+    };
+    Ok((a, b, score))
+  }, progress)
+}
+
+/// Scored pairs of a [`grid_search_2d`] grid, highest score first, without those filtered to NaN
+pub fn rank_grid<P1: Clone, P2: Clone>(grid: &[(P1, P2, f64)]) -> Vec<(P1, P2, f64)> {
+  let mut ranked: Vec<(P1, P2, f64)> = grid.iter().filter(|(_, _, score)| !score.is_nan()).cloned().collect();
+  ranked.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+  ranked
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::cell::RefCell;
+  use time_series::{synthetic_candles, Dataframe, GbmParams};
+
+  /// Signals of the nth candle processed, given the candle and its ticker
+  type Script = Arc<dyn Fn(usize, &Candle, &str) -> Vec<Signal> + Send + Sync>;
+
+  /// Strategy that signals whatever its script returns for each candle
+  #[derive(Clone)]
+  struct Scripted {
+    script: Script,
+    index: usize,
+    stop_loss_pct: Option<f64>
+  }
+
+  impl Scripted {
+    fn new(script: impl Fn(usize, &Candle, &str) -> Vec<Signal> + Send + Sync + 'static) -> Self {
+      Self { script: Arc::new(script), index: 0, stop_loss_pct: None }
+    }
+
+    /// Signals `signals[n]` on the nth candle
+    fn signals(signals: Vec<Vec<Signal>>) -> Self {
+      Self::new(move |n, _, _| signals.get(n).cloned().unwrap_or_default())
+    }
+  }
+
+  impl Strategy<Candle> for Scripted {
+    fn process_candle(&mut self, candle: Candle, ticker: Option<String>) -> anyhow::Result<Vec<Signal>> {
+      self.index += 1;
+      Ok((self.script)(self.index - 1, &candle, &ticker.unwrap_or_default()))
+    }
+    fn push_candle(&mut self, _candle: Candle, _ticker: Option<String>) {}
+    fn cache(&self, _ticker: Option<String>) -> Option<&DataCache<Candle>> {
+      None
+    }
+    fn stop_loss_pct(&self) -> Option<f64> {
+      self.stop_loss_pct
+    }
+  }
+
+  /// One minute candles opening and closing at each of `closes`
+  fn candles(closes: &[f64]) -> Vec<Candle> {
+    closes.iter().enumerate().map(|(i, close)| Candle {
+      date: Time::from_unix_ms(1_700_000_000_000 + i as i64 * 60_000),
+      open: *close,
+      high: *close,
+      low: *close,
+      close: *close,
+      volume: None
+    }).collect()
+  }
+
+  /// Market signal info at the close of `candle`
+  fn info(candle: &Candle, ticker: &str) -> SignalInfo {
+    SignalInfo {
+      price: candle.close,
+      date: candle.date,
+      ticker: ticker.to_string(),
+      exit_fraction: None,
+      order_type: None,
+      time_in_force: None
+    }
+  }
+
+  fn backtest(strategy: Scripted, bet: Bet) -> Backtest<Candle, Scripted> {
+    Backtest::new(strategy, 1_000.0, 0.0, bet, 1, false, Interval::OneMinute, 0.0)
+  }
+
+  #[test]
+  fn backtest_rejects_unequal_series() -> anyhow::Result<()> {
+    let start = Time::from_unix_ms(1_700_000_000_000);
+    let mut backtest = backtest(Scripted::new(|_, _, _| vec![]), Bet::Percent(100.0));
+    backtest.candles.insert("A".to_string(), synthetic_candles(1, 50, start, 1, GbmParams::default())?);
+    backtest.candles.insert("B".to_string(), synthetic_candles(2, 40, start, 1, GbmParams::default())?);
+
+    let err = backtest.backtest().err().ok_or(anyhow::anyhow!("expected unequal series error"))?;
+    assert!(err.to_string().contains("A: 50, B: 40"));
+    assert!(backtest.trades.is_empty());
+
+    Dataframe::align_series(&mut backtest.candles)?;
+    backtest.backtest()?;
+    Ok(())
+  }
+
+  #[test]
+  fn backtest_scales_out_in_tranches() -> anyhow::Result<()> {
+    let ticker = "A";
+    let candles = candles(&[100.0, 110.0, 120.0]);
+    let run = |signals: Vec<Vec<Signal>>| -> anyhow::Result<Summary> {
+      let mut backtest = backtest(Scripted::signals(signals), Bet::Static);
+      backtest.candles.insert(ticker.to_string(), candles.clone());
+      backtest.backtest()
+    };
+
+    // half out at 110, the rest at 120, both tranches sized off the same 1000 entry
+    let scaled = run(vec![
+      vec![Signal::EnterLong(info(&candles[0], ticker))],
+      vec![Signal::ExitLong(info(&candles[1], ticker).with_exit_fraction(0.5))],
+      vec![Signal::ExitLong(info(&candles[2], ticker))]
+    ])?;
+    assert_eq!(scaled.quote_roi(ticker), 150.0);
+    let per_trade: Vec<f64> = scaled.pct_per_trade(ticker)?.data().iter().map(|d| d.y).collect();
+    assert_eq!(per_trade, vec![10.0, 20.0]);
+    assert_eq!(scaled.trades(ticker)?.len(), 4);
+
+    // no fraction exits the whole position
+    let full = run(vec![
+      vec![Signal::EnterLong(info(&candles[0], ticker))],
+      vec![],
+      vec![Signal::ExitLong(info(&candles[2], ticker))]
+    ])?;
+    assert_eq!(full.quote_roi(ticker), 200.0);
+    Ok(())
+  }
+
+  #[test]
+  fn backtest_next_bar_open_execution() -> anyhow::Result<()> {
+    let ticker = "A";
+    // each candle opens 5 below its close
+    let candles: Vec<Candle> = candles(&[100.0, 110.0, 120.0, 130.0]).into_iter().map(|c| Candle {
+      open: c.close - 5.0,
+      low: c.close - 5.0,
+      ..c
+    }).collect();
+    let signals = vec![
+      vec![Signal::EnterLong(info(&candles[0], ticker))],
+      vec![],
+      vec![Signal::ExitLong(info(&candles[2], ticker))],
+      // no candle after the last to fill at
+      vec![Signal::EnterLong(info(&candles[3], ticker))]
+    ];
+    let run = |timing: ExecutionTiming| -> anyhow::Result<Summary> {
+      let mut backtest = backtest(Scripted::signals(signals.clone()), Bet::Static);
+      backtest.execution_timing = timing;
+      backtest.candles.insert(ticker.to_string(), candles.clone());
+      backtest.backtest()
+    };
+
+    // 100 to 120 at the signal closes
+    let close = run(ExecutionTiming::SameBarClose)?;
+    assert_eq!(close.quote_roi(ticker), 200.0);
+
+    // 105 to 125 at the opens after each signal
+    let open = run(ExecutionTiming::NextBarOpen)?;
+    assert_eq!(open.quote_roi(ticker), 190.48);
+    let trades = open.trades(ticker)?;
+    assert_eq!(trades.len(), 2);
+    assert_eq!((trades[0].price, trades[0].date), (105.0, candles[1].date));
+    assert_eq!((trades[1].price, trades[1].date), (125.0, candles[3].date));
+    Ok(())
+  }
+
+  #[test]
+  fn backtest_check_lookahead() -> anyhow::Result<()> {
+    // signals on the next candle's date, as if it had already seen it
+    let peeking = Scripted::new(|_, candle, ticker| vec![Signal::EnterLong(SignalInfo {
+      date: Time::from_unix_ms(candle.date.to_unix_ms() + 60_000),
+      ..info(candle, ticker)
+    })]);
+    let mut backtest = backtest(peeking, Bet::Static);
+    backtest.candles.insert("A".to_string(), candles(&[100.0, 110.0]));
+    assert!(backtest.backtest().is_ok());
+
+    backtest.check_lookahead = true;
+    let err = backtest.backtest().err().ok_or(anyhow::anyhow!("expected lookahead error"))?;
+    assert!(err.to_string().starts_with("Lookahead: A signal dated"));
+    Ok(())
+  }
+
+  #[test]
+  fn backtest_max_open_positions() -> anyhow::Result<()> {
+    // enters every ticker on the first candle and exits on the last
+    let enter_all = Scripted::new(|_, candle, ticker| match candle.close as i64 {
+      100 => vec![Signal::EnterLong(info(candle, ticker))],
+      110 => vec![Signal::ExitLong(info(candle, ticker))],
+      _ => vec![]
+    });
+    let candles = candles(&[100.0, 105.0, 110.0]);
+    let run = |bet: Bet, max_open_positions: Option<usize>| -> anyhow::Result<Summary> {
+      let mut backtest = backtest(enter_all.clone(), bet);
+      backtest.max_open_positions = max_open_positions;
+      for ticker in ["C", "A", "B"] {
+        backtest.candles.insert(ticker.to_string(), candles.clone());
+      }
+      backtest.backtest()
+    };
+    let traded = |summary: &Summary| {
+      let mut tickers: Vec<String> = summary.trades.iter().filter(|(_, trades)| !trades.is_empty()).map(|(ticker, _)| ticker.clone()).collect();
+      tickers.sort();
+      tickers
+    };
+    let quote_roi = |summary: &Summary| summary.cum_quote.values().filter_map(|data| data.data().last().map(|d| d.y)).sum::<f64>();
+
+    // every ticker trades the whole 1000
+    let unlimited = run(Bet::Static, None)?;
+    assert_eq!(traded(&unlimited), vec!["A", "B", "C"]);
+    assert_eq!(quote_roi(&unlimited), 300.0);
+
+    // tickers are visited in order, so C's entry is the one skipped,
+    // and the 1000 account is split across the two that fit
+    for _ in 0..5 {
+      let capped = run(Bet::Static, Some(2))?;
+      assert_eq!(traded(&capped), vec!["A", "B"]);
+      assert_eq!(quote_roi(&capped), 100.0);
+      assert_eq!(capped.portfolio_cum_pct().data().last().map(|d| d.y), Some(10.0));
+    }
+
+    // both positions are sized 500 at entry, B isn't resized by A's profit when it exits after it
+    let percent = run(Bet::Percent(100.0), Some(2))?;
+    assert_eq!(traded(&percent), vec!["A", "B"]);
+    assert_eq!(quote_roi(&percent), 100.0);
+    Ok(())
+  }
+
+  #[test]
+  fn stop_loss_sweep_matches_backtest() -> anyhow::Result<()> {
+    // holds whichever ticker is processed second from the first candle to the last, and the first for the middle two,
+    // so the signals only land on the same ticker as a backtest if the tickers are visited in the same order
+    let second = Scripted::new(|n, candle, ticker| match (n % 2, n / 2) {
+      (1, 0) | (0, 1) => vec![Signal::EnterLong(info(candle, ticker))],
+      (1, 3) | (0, 2) => vec![Signal::ExitLong(info(candle, ticker))],
+      _ => vec![]
+    });
+    let run = |strategy: Scripted| {
+      let mut backtest = backtest(strategy, Bet::Static);
+      backtest.candles.insert("B".to_string(), candles(&[100.0, 95.0, 90.0, 110.0]));
+      backtest.candles.insert("A".to_string(), candles(&[100.0, 101.0, 102.0, 103.0]));
+      backtest
+    };
+    for _ in 0..5 {
+      let sweep = run(second.clone()).stop_loss_sweep(&[4.0])?;
+      let stopped = run(Scripted { stop_loss_pct: Some(4.0), ..second.clone() }).backtest()?;
+      // B is visited second and stopped out, A is held from 101 to 102
+      assert_eq!(stopped.pct_roi("A"), 0.99);
+      assert!(stopped.pct_roi("B") < 0.0);
+      for ticker in ["A", "B"] {
+        assert_eq!(sweep[0].1.trades[ticker].len(), stopped.trades[ticker].len());
+        assert_eq!(sweep[0].1.pct_roi(ticker), stopped.pct_roi(ticker));
+      }
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn grid_search_progress_on_caller_thread() -> anyhow::Result<()> {
+    let caller = std::thread::current().id();
+    // not Sync, so progress can only be called from this thread
+    let seen = RefCell::new(vec![]);
+    let results = grid_search((0..20).collect(), |p: u64| Ok(p * 2), |completed, total| {
+      assert_eq!(std::thread::current().id(), caller);
+      seen.borrow_mut().push((completed, total));
+    })?;
+    assert_eq!(results, (0..20).map(|p| p * 2).collect::<Vec<u64>>());
+    assert_eq!(seen.into_inner(), (1..=20).map(|completed| (completed, 20)).collect::<Vec<(usize, usize)>>());
+
+    assert!(grid_search(vec![1, 2], |p: u64| match p {
+      1 => Ok(p),
+      _ => Err(anyhow::anyhow!("failed"))
+    }, |_, _| ()).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn weighted_benchmark_dated_by_first_ticker() -> anyhow::Result<()> {
+    let mut backtest = backtest(Scripted::new(|_, _, _| vec![]), Bet::Percent(100.0));
+    let a = candles(&[100.0, 110.0, 120.0]);
+    // same length but stamped a second later, so the dates show which leg was used
+    let b: Vec<Candle> = candles(&[10.0, 10.0, 13.0]).into_iter().map(|c| Candle { date: Time::from_unix_ms(c.date.to_unix_ms() + 1_000), ..c }).collect();
+    backtest.candles.insert("B".to_string(), b);
+    backtest.candles.insert("A".to_string(), a.clone());
+
+    let benchmark = backtest.weighted_benchmark(HashMap::from([("B".to_string(), 1.0), ("A".to_string(), 3.0)]))?;
+    assert_eq!(benchmark.x(), a.iter().map(|c| c.date.to_unix_ms()).collect::<Vec<i64>>());
+    // 75% of A up 20% and 25% of B up 30%
+    assert_eq!(benchmark.data().last().map(|d| trunc!(d.y, 2)), Some(22.5));
+    Ok(())
+  }
+
+  #[test]
+  fn grid_search_2d_scores_every_pair() -> anyhow::Result<()> {
+    let ticker = "A";
+    let candles = candles(&[100.0, 110.0, 120.0, 130.0]);
+    // `round_trips` long trades from one close to the next, `bet_pct` of capital each
+    let run = |round_trips: usize, bet_pct: f64| {
+      let mut backtest = backtest(Scripted::new(move |n, candle, ticker| match (n % 2, n / 2 < round_trips) {
+        (0, true) => vec![Signal::EnterLong(info(candle, ticker))],
+        (1, true) => vec![Signal::ExitLong(info(candle, ticker))],
+        _ => vec![]
+      }), Bet::Percent(bet_pct));
+      backtest.candles.insert(ticker.to_string(), candles.clone());
+      backtest.backtest()
+    };
+
+    let grid = grid_search_2d(&[0, 1, 2], &[50.0, 100.0], ticker, 1, run, |summary| summary.pct_roi(ticker), |_, _| ())?;
+    let pairs: Vec<(usize, f64)> = grid.iter().map(|(a, b, _)| (*a, *b)).collect();
+    // p1 major
+    assert_eq!(pairs, vec![(0, 50.0), (0, 100.0), (1, 50.0), (1, 100.0), (2, 50.0), (2, 100.0)]);
+    let scores: Vec<f64> = grid.iter().map(|(_, _, score)| *score).collect();
+    // no trades is below the minimum
+    assert!(scores[0].is_nan() && scores[1].is_nan());
+    // half or all of the 10% of 100 -> 110, then compounded with half or all of the 8.33% of 120 -> 130
+    assert_eq!(&scores[2..], &[5.0, 10.0, 9.38, 19.17]);
+
+    let ranked = rank_grid(&grid);
+    assert_eq!(ranked.len(), 4);
+    assert_eq!((ranked[0].0, ranked[0].1), (2, 100.0));
+    Ok(())
+  }
+}
@@ -1,7 +1,9 @@
 pub mod strategy;
 pub mod backtest;
 pub mod strategies;
+pub mod spread;
 
 pub use strategy::*;
 pub use backtest::*;
-pub use strategies::*;
\ No newline at end of file
+pub use strategies::*;
+pub use spread::*;
\ No newline at end of file
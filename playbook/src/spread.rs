@@ -0,0 +1,118 @@
+use log::debug;
+use serde::{Serialize, Deserialize};
+use time_series::{Data, DataCache, Dataframe, Dataset};
+use tradestats::kalman::dynamic_hedge_kalman_filter;
+use tradestats::metrics::{engle_granger_cointegration_test, half_life, pearson_correlation_coefficient, rolling_cointegration, spread_standard};
+
+/// Spread analytics of a pair of normalized series (see `Dataframe::normalize_series`).
+/// Both series must be aligned, so the i-th element of each is the same timestamp.
+#[derive(Debug, Clone)]
+pub struct SpreadAnalytics {
+  pub x: Dataset<i64, f64>,
+  pub y: Dataset<i64, f64>
+}
+
+impl SpreadAnalytics {
+  pub fn new(x: Dataset<i64, f64>, y: Dataset<i64, f64>) -> anyhow::Result<Self> {
+    if x.len() != y.len() {
+      return Err(anyhow::anyhow!("Pair series have different lengths: {} and {}", x.len(), y.len()));
+    }
+    Ok(Self { x, y })
+  }
+
+  /// Spread of y over x using the static OLS hedge ratio
+  pub fn spread(&self) -> anyhow::Result<Dataset<i64, f64>> {
+    let spread = spread_standard(&self.x.y(), &self.y.y()).map_err(
+      |e| anyhow::anyhow!("Error calculating spread: {}", e)
+    )?;
+    Ok(self.with_timestamps(spread))
+  }
+
+  /// Bars for the spread to revert halfway to its mean
+  pub fn half_life(&self) -> anyhow::Result<f64> {
+    let spread = self.spread()?.y();
+    half_life(&spread).map_err(|e| anyhow::anyhow!("Error calculating half life: {}", e))
+  }
+
+  /// Distance of the Engle-Granger test statistic past the 5% critical value over a rolling window.
+  /// Positive values are cointegrated. The first `window` values are zero.
+  pub fn rolling_cointegration(&self, window: usize) -> anyhow::Result<Dataset<i64, f64>> {
+    let coint = rolling_cointegration(&self.x.y(), &self.y.y(), window).map_err(
+      |e| anyhow::anyhow!("Error calculating rolling cointegration: {}", e)
+    )?;
+    Ok(self.with_timestamps(coint))
+  }
+
+  /// Dynamic hedge ratio of y over x from a Kalman filter
+  pub fn hedge_ratio_kalman(&self) -> anyhow::Result<Dataset<i64, f64>> {
+    let hedge = dynamic_hedge_kalman_filter(&self.x.y(), &self.y.y()).map_err(
+      |e| anyhow::anyhow!("Error calculating dynamic hedge ratio: {}", e)
+    )?;
+    Ok(self.with_timestamps(hedge))
+  }
+
+  fn with_timestamps(&self, values: Vec<f64>) -> Dataset<i64, f64> {
+    Dataset::new(self.x.x().into_iter().zip(values).map(|(x, y)| Data { x, y }).collect())
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PairStatus {
+  Healthy,
+  /// Past a warning threshold, still tradable
+  Degrading,
+  /// Decorrelated or no longer cointegrated, new entries should stop
+  Broken
+}
+
+/// Live monitor of whether a pair still trades like the offline cointegration analysis says it should.
+/// The correlation and Engle-Granger p-value are computed over everything in the caches,
+/// so size their capacity to the window to monitor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairHealth {
+  /// Degrading if the correlation falls below this
+  pub min_correlation: f64,
+  /// Broken if the correlation falls below this
+  pub broken_correlation: f64,
+  /// Degrading if the cointegration p-value rises above this
+  pub max_p_value: f64,
+  /// Broken if the cointegration p-value rises above this
+  pub broken_p_value: f64
+}
+
+impl Default for PairHealth {
+  fn default() -> Self {
+    Self {
+      min_correlation: 0.8,
+      broken_correlation: 0.5,
+      max_p_value: 0.05,
+      broken_p_value: 0.2
+    }
+  }
+}
+
+impl PairHealth {
+  /// Status of the pair from the latest correlation and cointegration p-value of the normalized series
+  pub fn status(&self, x: &DataCache<Data<i64, f64>>, y: &DataCache<Data<i64, f64>>) -> anyhow::Result<PairStatus> {
+    if x.len() != y.len() || x.len() < 3 {
+      return Err(anyhow::anyhow!("Pair health needs equal caches of at least 3, got {} and {}", x.len(), y.len()));
+    }
+    let x = Dataframe::normalize_series(&x.vec())?.y();
+    let y = Dataframe::normalize_series(&y.vec())?.y();
+    let correlation = pearson_correlation_coefficient(&x, &y).map_err(
+      |e| anyhow::anyhow!("Error calculating correlation: {}", e)
+    )?;
+    let p_value = engle_granger_cointegration_test(&x, &y).map_err(
+      |e| anyhow::anyhow!("Error calculating Engle-Granger cointegration test: {}", e)
+    )?.p_value;
+    debug!("Pair correlation: {}, cointegration p-value: {}", correlation, p_value);
+    // a flat window has no correlation to speak of
+    if correlation.is_nan() || p_value.is_nan() || correlation < self.broken_correlation || p_value > self.broken_p_value {
+      return Ok(PairStatus::Broken);
+    }
+    match correlation < self.min_correlation || p_value > self.max_p_value {
+      true => Ok(PairStatus::Degrading),
+      false => Ok(PairStatus::Healthy)
+    }
+  }
+}
@@ -0,0 +1,188 @@
+#![allow(unused_imports)]
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::Strategy;
+use time_series::*;
+use crate::Backtest;
+use lib::Interval;
+
+/// Minimal reference strategy: enters long on the first candle and never exits.
+/// Backtested with `close_at_end`, no fees and a 100% bet, its result equals [`Backtest::buy_and_hold`],
+/// which makes it a sanity check for the backtest engine and a template for new strategies.
+#[derive(Debug, Clone)]
+pub struct BuyAndHoldStrategy {
+  pub ticker: String,
+  /// Last candle, 0th index is current candle.
+  pub candles: DataCache<Candle>,
+  pub entered: bool
+}
+
+impl BuyAndHoldStrategy {
+  pub fn new(ticker: String) -> Self {
+    Self {
+      ticker: ticker.clone(),
+      candles: DataCache::new(1, ticker),
+      entered: false
+    }
+  }
+
+  pub fn signal(&mut self) -> anyhow::Result<Vec<Signal>> {
+    if self.entered {
+      return Ok(vec![]);
+    }
+    let c_0 = self.candles.vec[0];
+    self.entered = true;
+    Ok(vec![Signal::EnterLong(SignalInfo {
+      price: c_0.close,
+      date: c_0.date,
+      ticker: self.ticker.clone(),
+      exit_fraction: None,
+      order_type: None,
+      time_in_force: None
+    })])
+  }
+}
+
+impl Strategy<Candle> for BuyAndHoldStrategy {
+  /// Appends candle to candle cache and enters long on the first candle.
+  fn process_candle(&mut self, candle: Candle, _ticker: Option<String>) -> anyhow::Result<Vec<Signal>> {
+    self.candles.push(candle);
+    self.signal()
+  }
+
+  fn push_candle(&mut self, candle: Candle, _ticker: Option<String>) {
+    self.candles.push(candle);
+  }
+
+  fn cache(&self, _ticker: Option<String>) -> Option<&DataCache<Candle>> {
+    Some(&self.candles)
+  }
+
+  fn stop_loss_pct(&self) -> Option<f64> {
+    None
+  }
+}
+
+
+// ==========================================================================================
+//                                 Buy And Hold Backtests
+// ==========================================================================================
+
+#[tokio::test]
+async fn buy_and_hold_sol() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let ticker = "SOLUSDT".to_string();
+  let strategy = BuyAndHoldStrategy::new(ticker.clone());
+  let capital = 1_000.0;
+  let fee = 0.0;
+  let bet = Bet::Percent(100.0);
+  let leverage = 1;
+  let short_selling = false;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
+
+  let start_time = Time::new(2023, &Month::from_num(1), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(30), None, None, None);
+
+  let csv = PathBuf::from("solusdt_30m.csv");
+  let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  backtest.close_at_end = true;
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+  backtest.candles.insert(ticker.clone(), csv_series.candles);
+
+  let summary = backtest.backtest()?;
+  let all_buy_and_hold = backtest.buy_and_hold()?;
+  let buy_and_hold = all_buy_and_hold
+    .get(&ticker)
+    .ok_or(anyhow::anyhow!("Buy and hold not found for ticker"))?
+    .clone();
+  summary.print(&ticker);
+
+  let expected = buy_and_hold.last().ok_or(anyhow::anyhow!("Empty buy and hold"))?.y;
+  let actual = summary.cum_pct(&ticker)?.data().last().ok_or(anyhow::anyhow!("No trades"))?.y;
+  assert_eq!(summary.trades(&ticker)?.len(), 2);
+  assert_eq!(trunc!(actual, 2), trunc!(expected, 2));
+
+  Ok(())
+}
+
+/// Buy and hold of one minute candles closing at each of `closes`, closed at the end
+#[cfg(test)]
+fn buy_and_hold_backtest(closes: &[f64]) -> Backtest<Candle, BuyAndHoldStrategy> {
+  let ticker = "SOLUSDT".to_string();
+  let mut backtest = Backtest::new(BuyAndHoldStrategy::new(ticker.clone()), 1_000.0, 0.0, Bet::Percent(100.0), 1, false, Interval::OneMinute, 0.0);
+  backtest.close_at_end = true;
+  let candles = closes.iter().enumerate().map(|(i, close)| Candle {
+    date: Time::from_unix_ms(1_700_000_000_000 + i as i64 * 60_000),
+    open: *close,
+    high: *close,
+    low: *close,
+    close: *close,
+    volume: None
+  }).collect();
+  backtest.candles.insert(ticker, candles);
+  backtest
+}
+
+#[test]
+fn buy_and_hold_trade_duration() -> anyhow::Result<()> {
+  let summary = buy_and_hold_backtest(&[100.0, 110.0, 120.0, 130.0]).backtest()?;
+  // one trade held from the first close to the last, 3 minutes later
+  assert_eq!(summary.max_trade_duration("SOLUSDT"), 3.0);
+  assert_eq!(summary.median_trade_duration("SOLUSDT"), 3.0);
+  assert_eq!(summary.avg_trade_duration("SOLUSDT"), 3.0);
+  Ok(())
+}
+
+#[test]
+fn buy_and_hold_exposure() -> anyhow::Result<()> {
+  let summary = buy_and_hold_backtest(&[100.0, 110.0, 120.0, 130.0]).backtest()?;
+  // entered at the close of the first of 4 candles, so held through the other 3
+  assert_eq!(summary.exposure_pct("SOLUSDT")?, 75.0);
+  Ok(())
+}
+
+#[test]
+fn buy_and_hold_streaks() -> anyhow::Result<()> {
+  let summary = buy_and_hold_backtest(&[100.0, 110.0, 120.0, 130.0]).backtest()?;
+  assert_eq!(summary.max_consecutive_wins("SOLUSDT"), 1);
+  assert_eq!(summary.max_consecutive_losses("SOLUSDT"), 0);
+
+  let summary = buy_and_hold_backtest(&[100.0, 90.0]).backtest()?;
+  assert_eq!(summary.max_consecutive_wins("SOLUSDT"), 0);
+  assert_eq!(summary.max_consecutive_losses("SOLUSDT"), 1);
+  Ok(())
+}
+
+#[test]
+fn buy_and_hold_equity_curve() -> anyhow::Result<()> {
+  let summary = buy_and_hold_backtest(&[100.0, 110.0, 90.0, 130.0]).backtest()?;
+  // the held position is marked to every close rather than flat until the exit
+  let equity: Vec<f64> = summary.equity_curve("SOLUSDT")?.data().iter().map(|d| d.y).collect();
+  assert_eq!(equity, vec![1000.0, 1100.0, 900.0, 1300.0]);
+  Ok(())
+}
+
+#[test]
+fn buy_and_hold_weighted_benchmark() -> anyhow::Result<()> {
+  let backtest = buy_and_hold_backtest(&[100.0, 110.0, 90.0, 130.0]);
+  // a basket of one ticker is that ticker's buy and hold, whatever its weight
+  let benchmark: Vec<f64> = backtest.weighted_benchmark(HashMap::from([("SOLUSDT".to_string(), 0.6)]))?.data().iter().map(|d| trunc!(d.y, 2)).collect();
+  assert_eq!(benchmark, vec![0.0, 10.0, -10.0, 30.0]);
+  Ok(())
+}
+
+#[test]
+fn buy_and_hold_split_allocation() -> anyhow::Result<()> {
+  // holding half the capital as base and trading the other half into the same position is still buy and hold
+  let mut backtest = buy_and_hold_backtest(&[100.0, 110.0, 90.0, 130.0]);
+  backtest.initial_allocation = Allocation::Split(50.0);
+  let summary = backtest.backtest()?;
+  assert_eq!(summary.pct_roi("SOLUSDT"), 30.0);
+  let equity: Vec<f64> = summary.equity_curve("SOLUSDT")?.data().iter().map(|d| d.y).collect();
+  assert_eq!(equity, vec![1000.0, 1100.0, 900.0, 1300.0]);
+  Ok(())
+}
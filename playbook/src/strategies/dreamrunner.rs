@@ -2,36 +2,59 @@
 
 use std::path::PathBuf;
 use log::{info, warn};
-use crate::Strategy;
+use serde::{Serialize, Deserialize};
+use crate::{Strategy, StopLoss, StrategyConfig};
 use time_series::*;
 use rayon::prelude::*;
-use crate::Backtest;
+use crate::{Backtest, grid_search, grid_search_2d, rank_grid};
+use lib::Interval;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Dreamrunner {
   pub ticker: String,
-  pub k_rev: f64,
+  pub k_rev: KagiReversal,
   pub k_src: Source,
   pub ma_src: Source,
   pub ma_period: usize,
   /// Last N candles from current candle.
   /// 0th index is current candle, Nth index is oldest candle.
   pub candles: DataCache<Candle>,
+  #[serde(skip)]
   pub kagi: Kagi,
-  pub stop_loss_pct: Option<f64>
+  pub stop_loss_pct: Option<f64>,
+  /// (candle date, kagi line, kagi direction) on each update, if recording is enabled
+  #[serde(skip)]
+  pub kagi_history: Option<Vec<(Time, f64, KagiDirection)>>,
+  /// Candles after an entry during which new entries are suppressed
+  pub cooldown_bars: usize,
+  /// Take profit placed with each live entry, opposite the stop loss
+  pub take_profit_pct: Option<f64>,
+  /// ATR (period, multiple) stop loss, used instead of `stop_loss_pct` if set
+  pub atr_stop: Option<(usize, f64)>,
+  /// Log one JSON record per signal candle with the date, kagi line, WMA and signals
+  #[serde(default)]
+  pub verbose_signals: bool
 }
 
+impl StrategyConfig for Dreamrunner {}
+
 impl Dreamrunner {
+  /// `k_rev` is an absolute reversal amount, set [`Dreamrunner::k_rev`] for a percent reversal
   pub fn new(ticker: String, k_rev: f64, k_src: Source, ma_src: Source, ma_period: usize, stop_loss_pct: Option<f64>) -> Self {
     Self {
       ticker: ticker.clone(),
-      k_rev,
+      k_rev: KagiReversal::Absolute(k_rev),
       k_src,
       ma_src,
       ma_period,
       candles: DataCache::new(ma_period + 1, ticker),
       kagi: Kagi::default(),
-      stop_loss_pct
+      stop_loss_pct,
+      kagi_history: None,
+      cooldown_bars: 0,
+      take_profit_pct: None,
+      atr_stop: None,
+      verbose_signals: false
     }
   }
 
@@ -39,74 +62,148 @@ impl Dreamrunner {
     let ma_period = 4;
     Self {
       ticker: "SOLUSDT".to_string(),
-      k_rev: 0.03,
+      k_rev: KagiReversal::Absolute(0.03),
       k_src: Source::Close,
       ma_src: Source::Open,
       ma_period,
       candles: DataCache::new(ma_period + 1, "SOLUSDT".to_string()),
       kagi: Kagi::default(),
-      stop_loss_pct: Some(1.0)
+      stop_loss_pct: Some(1.0),
+      kagi_history: None,
+      cooldown_bars: 0,
+      take_profit_pct: None,
+      atr_stop: None,
+      verbose_signals: false
     }
   }
   pub fn ethusdt_optimized() -> Self {
     let ma_period = 14;
     Self {
       ticker: "ETHUSDT".to_string(),
-      k_rev: 58.4,
+      k_rev: KagiReversal::Absolute(58.4),
       k_src: Source::Close,
       ma_src: Source::Open,
       ma_period,
       candles: DataCache::new(ma_period + 1, "ETHUSDT".to_string()),
       kagi: Kagi::default(),
-      stop_loss_pct: Some(100.0)
+      stop_loss_pct: Some(100.0),
+      kagi_history: None,
+      cooldown_bars: 0,
+      take_profit_pct: None,
+      atr_stop: None,
+      verbose_signals: false
     }
   }
   pub fn btcusdt_optimized() -> Self {
     let ma_period = 8;
     Self {
       ticker: "BTCUSDT".to_string(),
-      k_rev: 58.0,
+      k_rev: KagiReversal::Absolute(58.0),
       k_src: Source::Close,
       ma_src: Source::Open,
       ma_period,
       candles: DataCache::new(ma_period + 1, "BTCUSDT".to_string()),
       kagi: Kagi::default(),
-      stop_loss_pct: Some(1.0)
+      stop_loss_pct: Some(1.0),
+      kagi_history: None,
+      cooldown_bars: 0,
+      take_profit_pct: None,
+      atr_stop: None,
+      verbose_signals: false
     }
   }
   pub fn btcusd_1d_optimized(stop_loss_pct: Option<f64>) -> Self {
     let ma_period = 8;
     Self {
       ticker: "BTCUSD".to_string(),
-      k_rev: 58.0,
+      k_rev: KagiReversal::Absolute(58.0),
       k_src: Source::Close,
       ma_src: Source::Open,
       ma_period,
       candles: DataCache::new(ma_period + 1, "BTCUSD".to_string()),
       kagi: Kagi::default(),
-      stop_loss_pct
+      stop_loss_pct,
+      kagi_history: None,
+      cooldown_bars: 0,
+      take_profit_pct: None,
+      atr_stop: None,
+      verbose_signals: false
     }
   }
   pub fn atlasusd_1h_optimized(stop_loss_pct: Option<f64>) -> Self {
     let ma_period = 10; // 2
     Self {
       ticker: "ATLASUSD".to_string(),
-      k_rev: 0.00001, //0.00006,
+      k_rev: KagiReversal::Absolute(0.00001), //0.00006,
       k_src: Source::Close,
       ma_src: Source::Open,
       ma_period,
       candles: DataCache::new(ma_period + 1, "ATLASUSD".to_string()),
       kagi: Kagi::default(),
-      stop_loss_pct
+      stop_loss_pct,
+      kagi_history: None,
+      cooldown_bars: 0,
+      take_profit_pct: None,
+      atr_stop: None,
+      verbose_signals: false
     }
   }
 
+  /// Candles needed before a signal, the WMA period for the current and previous candle
+  pub fn warmup(&self) -> usize {
+    self.ma_period + 1
+  }
+
+  /// Hold `capacity` candles in the cache so filters can look back further than the WMA.
+  /// Never less than [`Dreamrunner::warmup`].
+  pub fn with_capacity(mut self, capacity: usize) -> Self {
+    self.candles = DataCache::new(capacity.max(self.warmup()), self.ticker.clone());
+    self
+  }
+
+  /// Change the WMA period, growing the candle cache if it no longer covers the warmup
+  pub fn with_ma_period(mut self, ma_period: usize) -> Self {
+    self.ma_period = ma_period;
+    let capacity = self.candles.capacity;
+    self.with_capacity(capacity)
+  }
+
+  /// Stop out `mult` ATRs over `period` candles from entry instead of a fixed %.
+  /// Grows the candle cache to cover the ATR.
+  pub fn with_atr_stop(mut self, period: usize, mult: f64) -> Self {
+    self.atr_stop = Some((period, mult));
+    let capacity = self.candles.capacity.max(period * 4 + 1);
+    self.with_capacity(capacity)
+  }
+
+  /// Log the indicators and signals of each candle, see [`Dreamrunner::verbose_signals`]
+  pub fn with_verbose_signals(mut self) -> Self {
+    self.verbose_signals = true;
+    self
+  }
+
+  /// Record the kagi on each update so the line the signal used can be plotted or compared.
+  pub fn record_kagi_history(mut self) -> Self {
+    self.kagi_history = Some(vec![]);
+    self
+  }
+
+  /// Kagi (date, line, direction) per candle, oldest first. None if recording is disabled.
+  pub fn kagi_history(&self) -> Option<&Vec<(Time, f64, KagiDirection)>> {
+    self.kagi_history.as_ref()
+  }
+
   pub fn signal(&mut self) -> anyhow::Result<Vec<Signal>> {
     if self.candles.vec.len() < 3 {
       warn!("Insufficient candles to generate kagis");
       return Ok(vec![]);
     }
-    if self.candles.vec.len() < self.candles.capacity {
+    if self.candles.capacity < self.warmup() {
+      return Err(anyhow::anyhow!(
+        "Candle cache capacity {} is less than the {} candle warmup", self.candles.capacity, self.warmup()
+      ));
+    }
+    if self.candles.vec.len() < self.warmup() {
       warn!("Insufficient candles to generate WMA");
       return Ok(vec![]);
     }
@@ -122,13 +219,15 @@ impl Dreamrunner {
     let k_0 = Kagi::update(&self.kagi, self.k_rev, &c_0, &c_1);
     self.kagi.line = k_0.line;
     self.kagi.direction = k_0.direction;
+    if let Some(history) = &mut self.kagi_history {
+      history.push((c_0.date, k_0.line, k_0.direction));
+    }
 
-    let period_1: Vec<&Candle> = self.candles.vec.range(1..self.candles.vec.len()).collect();
-    let period_0: Vec<&Candle> = self.candles.vec.range(0..self.candles.vec.len() - 1).collect();
+    let period_1: Vec<&Candle> = self.candles.vec.range(1..=self.ma_period).collect();
+    let period_0: Vec<&Candle> = self.candles.vec.range(0..self.ma_period).collect();
 
     let wma_1 = self.wma(&period_1);
     let wma_0 = self.wma(&period_0);
-    info!("kagi: {}, wma: {}", k_0.line, trunc!(wma_0, 2));
 
     // long if WMA crosses above Kagi and was below Kagi in previous candle
     let enter_long = wma_0 > k_0.line && wma_1 < k_1.line;
@@ -140,7 +239,10 @@ impl Dreamrunner {
     let info = SignalInfo {
       price: c_0.close,
       date: c_0.date,
-      ticker: self.ticker.clone()
+      ticker: self.ticker.clone(),
+      exit_fraction: None,
+      order_type: None,
+      time_in_force: None
     };
     
     let mut signals = vec![];
@@ -158,6 +260,15 @@ impl Dreamrunner {
     if enter_short {
       signals.push(Signal::EnterShort(info));
     }
+    if self.verbose_signals {
+      let orders: Vec<Order> = signals.iter().filter_map(|s| s.order()).collect();
+      info!("{}", serde_json::json!({
+        "date": c_0.date.to_string(),
+        "kagi": k_0.line,
+        "wma": wma_0,
+        "signals": orders
+      }));
+    }
     Ok(signals)
   }
 
@@ -167,12 +278,7 @@ impl Dreamrunner {
     let len = candles.len();
     for (i,  c) in candles.iter().enumerate() {
       let weight = ((len - i) * len) as f64;
-      let src = match self.ma_src {
-        Source::Open => c.open,
-        Source::High => c.high,
-        Source::Low => c.low,
-        Source::Close => c.close
-      };
+      let src = self.ma_src.value(c);
       norm += weight;
       sum += src * weight;
     }
@@ -194,10 +300,37 @@ impl Strategy<Candle> for Dreamrunner {
   fn cache(&self, _ticker: Option<String>) -> Option<&DataCache<Candle>> {
     Some(&self.candles)
   }
+
+  fn min_history(&self) -> usize {
+    self.warmup()
+  }
   
   fn stop_loss_pct(&self) -> Option<f64> {
     self.stop_loss_pct
   }
+
+  fn stop_loss(&self) -> Option<StopLoss> {
+    match self.atr_stop {
+      Some((period, mult)) => Some(StopLoss::Atr { period, mult }),
+      None => self.stop_loss_pct.map(StopLoss::Pct)
+    }
+  }
+
+  fn take_profit_pct(&self) -> Option<f64> {
+    self.take_profit_pct
+  }
+
+  fn cooldown(&self) -> usize {
+    self.cooldown_bars
+  }
+
+  /// The kagi reverses on `k_src`, the WMA averages `ma_src`
+  fn sources(&self) -> Vec<Source> {
+    match self.k_src == self.ma_src {
+      true => vec![self.k_src],
+      false => vec![self.k_src, self.ma_src]
+    }
+  }
 }
 
 
@@ -216,6 +349,8 @@ async fn dreamrunner_sol() -> anyhow::Result<()> {
   let bet = Bet::Percent(100.0);
   let leverage = 1;
   let short_selling = true;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
   let ticker = "SOLUSDT".to_string();
 
   let start_time = Time::new(2023, &Month::from_num(1), &Day::from_num(1), None, None, None);
@@ -223,8 +358,9 @@ async fn dreamrunner_sol() -> anyhow::Result<()> {
 
   let out_file = "solusdt_30m.csv";
   let csv = PathBuf::from(out_file);
-  let mut backtest = Backtest::new(strategy.clone(), capital, fee, bet, leverage, short_selling);
-  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone())?;
+  let mut backtest = Backtest::new(strategy.clone(), capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  backtest.check_lookahead = true;
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
   backtest.candles.insert(ticker.clone(), csv_series.candles);
 
   let summary = backtest.backtest()?;
@@ -234,6 +370,14 @@ async fn dreamrunner_sol() -> anyhow::Result<()> {
     .ok_or(anyhow::anyhow!("Buy and hold not found for ticker"))?
     .clone();
   summary.print(&ticker);
+  // published SOL 30m result, any change to backtest PnL logic must preserve this
+  assert_eq!(summary.pct_roi(&ticker), 1704.79);
+  assert_eq!(summary.total_trades(&ticker), 9010);
+  let sharpe = backtest.rolling_sharpe(&summary, &ticker, 48 * 30)?;
+  println!("Latest 30 day Sharpe: {}", trunc!(sharpe.data().last().unwrap().y, 2));
+  let per_trade = summary.pct_per_trade(&ticker)?;
+  println!("95% VaR per trade: {}%", trunc!(per_trade.value_at_risk(0.95), 2));
+  println!("95% CVaR per trade: {}%", trunc!(per_trade.conditional_var(0.95), 2));
   Plot::plot(
     vec![summary.cum_pct(&ticker)?.data().clone(), buy_and_hold],
     "dreamrunner_sol_30m_backtest.png",
@@ -245,6 +389,247 @@ async fn dreamrunner_sol() -> anyhow::Result<()> {
   Ok(())
 }
 
+#[tokio::test]
+async fn dreamrunner_sol_funding() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let capital = 1_000.0;
+  let fee = 0.02;
+  let bet = Bet::Percent(100.0);
+  let leverage = 2;
+  let short_selling = true;
+  let interval = Interval::ThirtyMinutes;
+  let ticker = "SOLUSDT".to_string();
+
+  let start_time = Time::new(2023, &Month::from_num(1), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(30), None, None, None);
+
+  let csv = PathBuf::from("solusdt_30m.csv");
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+
+  let mut no_funding = Backtest::new(Dreamrunner::solusdt_optimized(), capital, fee, bet, leverage, short_selling, interval, 0.0);
+  no_funding.candles.insert(ticker.clone(), csv_series.candles.clone());
+  let no_funding = no_funding.backtest()?;
+
+  let mut funding = Backtest::new(Dreamrunner::solusdt_optimized(), capital, fee, bet, leverage, short_selling, interval, 5.0);
+  funding.candles.insert(ticker.clone(), csv_series.candles);
+  let funding = funding.backtest()?;
+
+  println!("ROI without funding: {}%", no_funding.pct_roi(&ticker));
+  println!("ROI with 5 bps/day funding: {}%", funding.pct_roi(&ticker));
+  assert_eq!(no_funding.total_trades(&ticker), funding.total_trades(&ticker));
+  assert!(funding.quote_roi(&ticker) < no_funding.quote_roi(&ticker));
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn dreamrunner_sol_cooldown() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let capital = 1_000.0;
+  let fee = 0.02;
+  let bet = Bet::Percent(100.0);
+  let leverage = 1;
+  let short_selling = true;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
+  let ticker = "SOLUSDT".to_string();
+  let cooldown_bars = 4;
+
+  let start_time = Time::new(2023, &Month::from_num(1), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(30), None, None, None);
+
+  let csv = PathBuf::from("solusdt_30m.csv");
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+
+  let mut no_cooldown = Backtest::new(Dreamrunner::solusdt_optimized(), capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  no_cooldown.candles.insert(ticker.clone(), csv_series.candles.clone());
+  let no_cooldown = no_cooldown.backtest()?;
+
+  let mut strategy = Dreamrunner::solusdt_optimized();
+  strategy.cooldown_bars = cooldown_bars;
+  let mut cooldown = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  cooldown.candles.insert(ticker.clone(), csv_series.candles);
+  let cooldown = cooldown.backtest()?;
+
+  println!("Trades without cooldown: {}", no_cooldown.total_trades(&ticker));
+  println!("Trades with {} candle cooldown: {}", cooldown_bars, cooldown.total_trades(&ticker));
+  assert!(cooldown.total_trades(&ticker) < no_cooldown.total_trades(&ticker));
+
+  // consecutive entries are more than the cooldown apart
+  let cooldown_ms = cooldown_bars as i64 * interval.minutes() as i64 * 60_000;
+  let entries: Vec<i64> = cooldown.trades(&ticker)?.iter()
+    .filter(|t| matches!(t.side, Order::EnterLong | Order::EnterShort))
+    .map(|t| t.date.to_unix_ms())
+    .collect();
+  assert!(entries.windows(2).all(|w| w[1] - w[0] > cooldown_ms));
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn dreamrunner_sol_atr_stop() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let capital = 1_000.0;
+  let fee = 0.02;
+  let bet = Bet::Percent(100.0);
+  let leverage = 1;
+  let short_selling = true;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
+  let ticker = "SOLUSDT".to_string();
+
+  let start_time = Time::new(2023, &Month::from_num(1), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(30), None, None, None);
+
+  let csv = PathBuf::from("solusdt_30m.csv");
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+
+  let mut pct = Backtest::new(Dreamrunner::solusdt_optimized(), capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  pct.candles.insert(ticker.clone(), csv_series.candles.clone());
+  let pct = pct.backtest()?;
+
+  let strategy = Dreamrunner::solusdt_optimized().with_atr_stop(14, 2.0);
+  let mut atr = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  atr.candles.insert(ticker.clone(), csv_series.candles);
+  let atr = atr.backtest()?;
+
+  println!("ROI with {}% stop: {}%", Dreamrunner::solusdt_optimized().stop_loss_pct.unwrap_or_default(), pct.pct_roi(&ticker));
+  println!("ROI with 2 ATR stop: {}%", atr.pct_roi(&ticker));
+  assert!(atr.total_trades(&ticker) > 0);
+  assert_ne!(atr.pct_roi(&ticker), pct.pct_roi(&ticker));
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn dreamrunner_sol_stop_loss_sweep() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let capital = 1_000.0;
+  let fee = 0.02;
+  let bet = Bet::Percent(100.0);
+  let leverage = 1;
+  let short_selling = true;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
+  let ticker = "SOLUSDT".to_string();
+
+  let start_time = Time::new(2023, &Month::from_num(1), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(30), None, None, None);
+
+  let csv = PathBuf::from("solusdt_30m.csv");
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+
+  let mut backtest = Backtest::new(Dreamrunner::solusdt_optimized(), capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  backtest.candles.insert(ticker.clone(), csv_series.candles.clone());
+  let sweep = backtest.stop_loss_sweep(&[1.0, 2.5])?;
+  assert_eq!(sweep.iter().map(|(level, _)| *level).collect::<Vec<f64>>(), vec![1.0, 2.5]);
+  // matches the published result at the optimized 1% stop
+  assert_eq!(sweep[0].1.pct_roi(&ticker), 1704.79);
+  assert_eq!(sweep[0].1.total_trades(&ticker), 9010);
+
+  // and a full backtest at another stop
+  let mut strategy = Dreamrunner::solusdt_optimized();
+  strategy.stop_loss_pct = Some(2.5);
+  let mut full = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  full.candles.insert(ticker.clone(), csv_series.candles);
+  let full = full.backtest()?;
+  assert_eq!(sweep[1].1.pct_roi(&ticker), full.pct_roi(&ticker));
+  assert_eq!(sweep[1].1.win_rate(&ticker), full.win_rate(&ticker));
+  assert_eq!(sweep[1].1.total_trades(&ticker), full.total_trades(&ticker));
+  Ok(())
+}
+
+#[tokio::test]
+async fn dreamrunner_sol_capacity() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let capital = 1_000.0;
+  let fee = 0.02;
+  let bet = Bet::Percent(100.0);
+  let leverage = 1;
+  let short_selling = true;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
+  let ticker = "SOLUSDT".to_string();
+
+  let start_time = Time::new(2024, &Month::from_num(1), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(30), None, None, None);
+
+  let csv = PathBuf::from("solusdt_30m.csv");
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+
+  let mut results = vec![];
+  for strategy in [Dreamrunner::solusdt_optimized(), Dreamrunner::solusdt_optimized().with_capacity(100)] {
+    let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+    backtest.candles.insert(ticker.clone(), csv_series.candles.clone());
+    results.push(backtest.backtest()?);
+  }
+  // a deeper cache for other indicators doesn't change the WMA window
+  assert_eq!(results[0].total_trades(&ticker), results[1].total_trades(&ticker));
+  assert_eq!(results[0].pct_roi(&ticker), results[1].pct_roi(&ticker));
+
+  // the cache never shrinks below the warmup
+  let strategy = Dreamrunner::solusdt_optimized().with_capacity(1).with_ma_period(10);
+  assert_eq!(strategy.candles.capacity, strategy.warmup());
+
+  // a cache that can't hold the warmup errors instead of never signaling
+  let mut strategy = Dreamrunner::solusdt_optimized();
+  strategy.ma_period = 10;
+  for candle in csv_series.candles.iter().take(20) {
+    strategy.push_candle(*candle, None);
+  }
+  assert!(strategy.signal().is_err());
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn dreamrunner_sol_withdraw() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let capital = 1_000.0;
+  let fee = 0.02;
+  let bet = Bet::Percent(100.0);
+  let leverage = 1;
+  let short_selling = true;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
+  let ticker = "SOLUSDT".to_string();
+
+  let start_time = Time::new(2023, &Month::from_num(1), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(30), None, None, None);
+
+  let csv = PathBuf::from("solusdt_30m.csv");
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+
+  let mut reinvest = Backtest::new(Dreamrunner::solusdt_optimized(), capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  reinvest.candles.insert(ticker.clone(), csv_series.candles.clone());
+  let reinvest = reinvest.backtest()?;
+
+  let mut withdraw = Backtest::new(Dreamrunner::solusdt_optimized(), capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  withdraw.profit_policy = ProfitPolicy::WithdrawAbove(capital);
+  withdraw.candles.insert(ticker.clone(), csv_series.candles);
+  let withdraw = withdraw.backtest()?;
+
+  println!("ROI reinvested: {}%", reinvest.pct_roi(&ticker));
+  println!("ROI withdrawing above ${}: {}%, withdrawn: ${}", capital, withdraw.pct_roi(&ticker), withdraw.withdrawn(&ticker));
+  assert_eq!(reinvest.withdrawn(&ticker), 0.0);
+  assert!(withdraw.withdrawn(&ticker) > 0.0);
+  assert_ne!(withdraw.pct_roi(&ticker), reinvest.pct_roi(&ticker));
+
+  Ok(())
+}
+
 #[tokio::test]
 async fn eth_backtest() -> anyhow::Result<()> {
   use super::*;
@@ -256,6 +641,8 @@ async fn eth_backtest() -> anyhow::Result<()> {
   let bet = Bet::Static;
   let leverage = 1;
   let short_selling = false;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
   let ticker = "ETHUSDT".to_string();
 
   let start_time = Time::new(2023, &Month::from_num(1), &Day::from_num(1), None, None, None);
@@ -263,8 +650,8 @@ async fn eth_backtest() -> anyhow::Result<()> {
 
   let out_file = "ethusdt_30m.csv";
   let csv = PathBuf::from(out_file);
-  let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling);
-  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone())?;
+  let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
   backtest.candles.insert(ticker.clone(), csv_series.candles);
 
   let summary = backtest.backtest()?;
@@ -298,6 +685,8 @@ async fn btc_1d_backtest() -> anyhow::Result<()> {
   let bet = Bet::Percent(100.0);
   let leverage = 1;
   let short_selling = true;
+  let interval = Interval::OneDay;
+  let funding_rate = 0.0;
   let ticker = "BTCUSD".to_string();
   let out_file = "btcusd_1d.csv";
   let strategy = Dreamrunner::new(
@@ -313,13 +702,46 @@ async fn btc_1d_backtest() -> anyhow::Result<()> {
   let end_time = Time::new(2024, &Month::from_num(5), &Day::from_num(1), None, None, None);
 
   let csv = PathBuf::from(out_file);
-  let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling);
-  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone())?;
+  let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
   backtest.candles.insert(ticker.clone(), csv_series.candles);
 
   let summary = backtest.backtest()?;
   
   summary.print(&ticker);
+  summary.print_periodic_returns(&ticker, CalendarPeriod::Year)?;
+  // yearly returns compound to the total return
+  let yearly = summary.periodic_returns(&ticker, CalendarPeriod::Year)?;
+  let compounded = yearly.iter().fold(1.0, |acc, (_, pct)| acc * (1.0 + pct / 100.0));
+  let total = 1.0 + summary.cum_pct(&ticker)?.data().last().unwrap().y / 100.0;
+  assert!((compounded / total - 1.0).abs() < 0.01);
+  let quarters = summary.periodic_returns(&ticker, CalendarPeriod::Quarter)?;
+  let months = summary.periodic_returns(&ticker, CalendarPeriod::Month)?;
+  assert!(yearly.len() <= quarters.len() && quarters.len() <= months.len());
+
+  // underwater curve bottoms at the max drawdown, measured from the initial capital as well as later highs
+  let underwater = summary.underwater_curve(&ticker)?;
+  assert_eq!(underwater.data().len(), summary.total_trades(&ticker));
+  assert!(underwater.data().iter().all(|d| d.y <= 0.0));
+  let deepest = underwater.data().iter().map(|d| d.y).fold(0.0, f64::min);
+  assert!(deepest <= summary.max_drawdown(&ticker));
+
+  // ROI of each rolling window of 20 trades, a falling trend is the edge decaying
+  let windows = summary.windowed(20);
+  assert_eq!(windows.len(), summary.total_trades(&ticker).saturating_sub(19));
+  let window_rois = windows.iter().map(|window| {
+    let last = window.cum_pct(&ticker)?.data().last().ok_or(anyhow::anyhow!("Empty window"))?.x;
+    Ok(Data { x: last, y: window.pct_roi(&ticker) })
+  }).collect::<anyhow::Result<Vec<Data<i64, f64>>>>()?;
+  Plot::plot_with_options(
+    vec![window_rois],
+    "dreamrunner_btc_1d_windowed_roi.png",
+    &format!("{} Dreamrunner ROI per 20 Trades", ticker),
+    "% ROI",
+    "Date",
+    PlotOptions { x_is_time: true, ..PlotOptions::default() }
+  )?;
+
   let all_buy_and_hold = backtest.buy_and_hold()?;
   let buy_and_hold = all_buy_and_hold
     .get(&ticker)
@@ -336,6 +758,43 @@ async fn btc_1d_backtest() -> anyhow::Result<()> {
   Ok(())
 }
 
+#[tokio::test]
+async fn btc_1d_percent_reversal() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let capital = 1_000.0;
+  let fee = 0.02;
+  let bet = Bet::Percent(100.0);
+  let leverage = 1;
+  let short_selling = true;
+  let interval = Interval::OneDay;
+  let funding_rate = 0.0;
+  let ticker = "BTCUSD".to_string();
+
+  let start_time = Time::new(2012, &Month::from_num(1), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(5), &Day::from_num(1), None, None, None);
+
+  let csv = PathBuf::from("btcusd_1d.csv");
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+
+  let mut results = vec![];
+  for reversal in [KagiReversal::Absolute(58.0), KagiReversal::Percent(5.0)] {
+    let mut strategy = Dreamrunner::btcusd_1d_optimized(Some(5.0));
+    strategy.k_rev = reversal;
+    let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+    backtest.candles.insert(ticker.clone(), csv_series.candles.clone());
+    let summary = backtest.backtest()?;
+    println!("{:?} reversal: {}% ROI, {} trades", reversal, summary.pct_roi(&ticker), summary.total_trades(&ticker));
+    results.push(summary);
+  }
+  // a percent reversal scales with a price that rose from ~$5 to ~$70k
+  assert!(results[1].total_trades(&ticker) > 0);
+  assert_ne!(results[0].total_trades(&ticker), results[1].total_trades(&ticker));
+
+  Ok(())
+}
+
 #[tokio::test]
 async fn btc_30m_backtest() -> anyhow::Result<()> {
   use super::*;
@@ -347,6 +806,8 @@ async fn btc_30m_backtest() -> anyhow::Result<()> {
   let bet = Bet::Percent(90.0);
   let leverage = 1;
   let short_selling = false;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
   let ticker = "BTCUSDT".to_string();
   let out_file = "btcusdt_30m.csv";
 
@@ -354,8 +815,8 @@ async fn btc_30m_backtest() -> anyhow::Result<()> {
   let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(24), None, None, None);
   
   let csv = PathBuf::from(out_file);
-  let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling);
-  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone())?;
+  let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
   backtest.candles.insert(ticker.clone(), csv_series.candles);
 
   let summary = backtest.backtest()?;
@@ -388,6 +849,8 @@ async fn atlas_1h_backtest() -> anyhow::Result<()> {
   let bet = Bet::Percent(90.0);
   let leverage = 1;
   let short_selling = false;
+  let interval = Interval::OneHour;
+  let funding_rate = 0.0;
   let strategy = Dreamrunner::atlasusd_1h_optimized(Some(stop_loss));
 
   let time_series = "atlasusd_1h.csv";
@@ -401,8 +864,8 @@ async fn atlas_1h_backtest() -> anyhow::Result<()> {
   // let end_time = Time::new(2022, &Month::from_num(12), &Day::from_num(5), None, None, None);
 
   let csv = PathBuf::from(time_series);
-  let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling);
-  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone())?;
+  let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
   backtest.candles.insert(ticker.clone(), csv_series.candles);
 
   let summary = backtest.backtest()?;
@@ -476,56 +939,51 @@ async fn optimize() -> anyhow::Result<()> {
   let bet = Bet::Percent(100.0);
   let leverage = 1;
   let short_selling = false;
+  let interval = Interval::OneHour;
+  let funding_rate = 0.0;
 
   let start_time = Time::new(2022, &Month::from_num(12), &Day::from_num(5), None, None, None);
   let end_time = Time::new(2023, &Month::from_num(10), &Day::from_num(25), None, None, None);
 
   let csv = PathBuf::from(time_series);
-  let mut backtest = Backtest::new(strategy.clone(), capital, fee, bet, leverage, short_selling);
-  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone())?;
-
-  #[derive(Debug, Clone)]
-  struct BacktestResult {
-    pub k_rev: f64,
-    pub wma_period: usize,
-    pub summary: Summary
-  }
-
-  let mut results: Vec<BacktestResult> = (0..10).collect::<Vec<usize>>().into_par_iter().flat_map(|i| {
-    let k_rev = trunc!(k_rev_start + (i as f64 * k_rev_step), 7);
-
-    let results: Vec<BacktestResult> = (1..11).collect::<Vec<usize>>().into_par_iter().flat_map(|j| {
-      let wma_period = j + 1;
-      let mut strat = strategy.clone();
-      strat.ma_period = wma_period;
-      strat.k_rev = k_rev;
-      let mut backtest = Backtest::new(strat, capital, fee, bet, leverage, short_selling);
-      backtest.candles.insert(ticker.clone(), csv_series.candles.clone());
-      let summary = backtest.backtest()?;
-      let res = BacktestResult {
-        k_rev,
-        wma_period,
-        summary
-      };
-      Result::<_, anyhow::Error>::Ok(res)
-    }).collect();
-    Result::<_, anyhow::Error>::Ok(results)
-  }).flatten().collect();
-
-  results.retain(|r| r.summary.total_trades(&ticker) > 1);
-
-  // sort for highest percent ROI first
-  results.sort_by(|a, b| {
-    b.summary.pct_roi(&ticker).partial_cmp(&a.summary.pct_roi(&ticker)).unwrap()
-  });
-
-  if !results.is_empty() {
-    let optimized = results.first().unwrap().clone();
+  let mut backtest = Backtest::new(strategy.clone(), capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+
+  let k_revs: Vec<f64> = (0..10).map(|i| trunc!(k_rev_start + (i as f64 * k_rev_step), 7)).collect();
+  let wma_periods: Vec<usize> = (2..12).collect();
+  let run = |k_rev: f64, wma_period: usize| {
+    let mut strat = strategy.clone().with_ma_period(wma_period);
+    strat.k_rev = KagiReversal::Absolute(k_rev);
+    let mut backtest = Backtest::new(strat, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+    backtest.candles.insert(ticker.clone(), csv_series.candles.clone());
+    backtest.backtest()
+  };
+
+  // % ROI of every pair, a single trade isn't a result worth optimizing for
+  let grid = grid_search_2d(&k_revs, &wma_periods, &ticker, 2, run, |summary| summary.pct_roi(&ticker), |completed, total| {
+    println!("Optimization progress: {}/{}", completed, total);
+  })?;
+  Plot::heatmap(&grid, "dreamrunner_atlas_1h_heatmap.png", "Dreamrunner % ROI by Kagi Rev (x) and WMA Period (y)")?;
+
+  let optimized = rank_grid(&grid).into_iter().next();
+  if let Some((k_rev, wma_period, _)) = optimized {
     println!("==== Optimized Backtest ====");
-    println!("WMA Period: {}", optimized.wma_period);
-    println!("Kagi Rev: {}", optimized.k_rev);
-    let summary = optimized.summary;
+    println!("WMA Period: {}", wma_period);
+    println!("Kagi Rev: {}", k_rev);
+    let summary = run(k_rev, wma_period)?;
     summary.print(&ticker);
+
+    // save the optimized params for the live engine, a loaded config backtests the same.
+    // generated configs are git ignored, they change with every data refresh
+    let mut optimized = strategy.clone().with_ma_period(wma_period);
+    optimized.k_rev = KagiReversal::Absolute(k_rev);
+    std::fs::create_dir_all("configs")?;
+    let config = PathBuf::from("configs/dreamrunner_atlas_1h_optimized.json");
+    optimized.to_config_file(&config)?;
+    let mut loaded = Backtest::new(Dreamrunner::from_config_file(&config)?, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+    loaded.candles.insert(ticker.clone(), csv_series.candles.clone());
+    assert_eq!(loaded.backtest()?.pct_roi(&ticker), summary.pct_roi(&ticker));
+
     backtest.candles.insert(ticker.clone(), csv_series.candles);
     let all_buy_and_hold = backtest.buy_and_hold()?;
     let _buy_and_hold = all_buy_and_hold
@@ -543,4 +1001,176 @@ async fn optimize() -> anyhow::Result<()> {
 
 
   Ok(())
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn dreamrunner_config_round_trip() -> anyhow::Result<()> {
+  use super::*;
+
+  let mut strategy = Dreamrunner::solusdt_optimized().with_atr_stop(14, 2.0).record_kagi_history();
+  strategy.k_rev = KagiReversal::Percent(1.5);
+  strategy.push_candle(Candle { date: Time::from_unix(0), open: 1.0, high: 1.0, low: 1.0, close: 1.0, volume: None }, None);
+
+  let path = std::env::temp_dir().join("dreamrunner_config_round_trip.json");
+  strategy.to_config_file(&path)?;
+  let loaded = Dreamrunner::from_config_file(&path)?;
+  std::fs::remove_file(&path)?;
+
+  assert_eq!(loaded.ticker, strategy.ticker);
+  assert_eq!(loaded.k_rev, strategy.k_rev);
+  assert_eq!(loaded.ma_period, strategy.ma_period);
+  assert_eq!(loaded.stop_loss_pct, strategy.stop_loss_pct);
+  assert_eq!(loaded.atr_stop, strategy.atr_stop);
+  assert_eq!(loaded.candles.capacity, strategy.candles.capacity);
+  assert_eq!(loaded.candles.id, strategy.candles.id);
+  // runtime state starts empty
+  assert!(loaded.candles.vec.is_empty());
+  assert!(loaded.kagi_history.is_none());
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn dreamrunner_sol_kagi_history() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let ticker = "SOLUSDT".to_string();
+  let strategy = Dreamrunner::solusdt_optimized().record_kagi_history();
+  let capital = 1_000.0;
+  let fee = 0.02;
+  let bet = Bet::Percent(100.0);
+  let leverage = 1;
+  let short_selling = false;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
+
+  let start_time = Time::new(2024, &Month::from_num(4), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(30), None, None, None);
+
+  let csv = PathBuf::from("solusdt_30m.csv");
+  let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+  let candles = csv_series.candles.clone();
+  backtest.candles.insert(ticker.clone(), csv_series.candles);
+  backtest.backtest()?;
+
+  let history = backtest.strategy.kagi_history().ok_or(anyhow::anyhow!("Kagi history not recorded"))?;
+  // one update per candle once the WMA cache is full
+  let warmup = backtest.strategy.warmup() - 1;
+  assert_eq!(history.len(), candles.len() - warmup);
+  assert_eq!(history.first().unwrap().0.to_unix_ms(), candles[warmup].date.to_unix_ms());
+
+  let kagi: Vec<Data<i64, f64>> = history.iter().map(|(date, line, _)| Data {
+    x: date.to_unix_ms(),
+    y: *line
+  }).collect();
+  let close: Vec<Data<i64, f64>> = candles.iter().map(|c| Data {
+    x: c.date.to_unix_ms(),
+    y: c.close
+  }).collect();
+  Plot::plot(
+    vec![close, kagi],
+    "dreamrunner_sol_30m_kagi.png",
+    "SOL/USDT Dreamrunner Kagi",
+    "Price",
+    "Unix Millis"
+  )?;
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn kagi_parity() -> anyhow::Result<()> {
+  use super::*;
+
+  // SOL CSV is a TradingView export with the Pine kagi line and WMA of each candle
+  let ticker = "SOLUSDT".to_string();
+  let csv_series = Dataframe::csv_series(&PathBuf::from("solusdt_30m.csv"), None, None, ticker, false)?;
+  let candles = &csv_series.candles;
+  assert_eq!(csv_series.kagis.len(), candles.len());
+  assert_eq!(csv_series.wmas.len(), candles.len());
+  let strategy = Dreamrunner::solusdt_optimized();
+  let tolerance = 1e-6;
+
+  // Pine updates the kagi from the first candle, before the WMA has enough candles
+  let mut kagi = Kagi::default();
+  let mut ties = 0;
+  for (i, (c, tv)) in candles.iter().zip(csv_series.kagis.iter()).enumerate() {
+    let prev = candles[i.saturating_sub(1)];
+    let mut next = Kagi::update(&kagi, strategy.k_rev, c, &prev);
+    // the export's last bar was still open and has no Pine values
+    if !tv.y.is_nan() && (next.line - tv.y).abs() > tolerance {
+      // a close exactly the reversal amount from the line is a float tie, Pine doesn't reverse on it
+      let tie = ((c.close - kagi.line).abs() - strategy.k_rev.threshold(kagi.line)).abs() < 1e-9;
+      assert!(tie, "Kagi diverges at candle {} ({}): {} != Pine {}", i, c.date.to_string(), next.line, tv.y);
+      ties += 1;
+      next = kagi;
+    }
+    kagi = next;
+  }
+  println!("Kagi matches Pine on {} candles, {} reversal ties", candles.len(), ties);
+
+  for i in strategy.ma_period - 1..candles.len() {
+    let period: Vec<&Candle> = candles[i + 1 - strategy.ma_period..=i].iter().rev().collect();
+    let wma = strategy.wma(&period);
+    let tv = csv_series.wmas[i].y;
+    if tv.is_nan() {
+      continue;
+    }
+    assert!((wma - tv).abs() <= tolerance, "WMA diverges at candle {} ({}): {} != Pine {}", i, candles[i].date.to_string(), wma, tv);
+  }
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn dreamrunner_async_matches_sync() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let ticker = "SOLUSDT".to_string();
+  let start_time = Time::new(2024, &Month::from_num(4), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(30), None, None, None);
+  let csv = PathBuf::from("solusdt_30m.csv");
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+
+  let mut sync = Dreamrunner::solusdt_optimized();
+  let mut live = Dreamrunner::solusdt_optimized();
+  for candle in csv_series.candles {
+    let sync_signals = sync.process_candle(candle, Some(ticker.clone()))?;
+    let async_signals = live.process_candle_async(candle, Some(ticker.clone())).await?;
+    assert_eq!(
+      sync_signals.iter().map(|s| s.print()).collect::<Vec<String>>(),
+      async_signals.iter().map(|s| s.print()).collect::<Vec<String>>()
+    );
+  }
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn dreamrunner_live_signals_match_backtest() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let ticker = "SOLUSDT".to_string();
+  let start_time = Time::new(2023, &Month::from_num(1), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(30), None, None, None);
+  let csv = PathBuf::from("solusdt_30m.csv");
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+
+  let mut backtest = Backtest::new(Dreamrunner::solusdt_optimized(), 1_000.0, 0.02, Bet::Percent(100.0), 1, true, Interval::ThirtyMinutes, 0.0);
+  backtest.candles.insert(ticker.clone(), csv_series.candles.clone());
+  backtest.backtest()?;
+  let backtest_signals = backtest.signals.get(&ticker).ok_or(anyhow::anyhow!("Backtest signals not found"))?;
+
+  let live_signals = crate::replay_signals(Dreamrunner::solusdt_optimized(), &csv_series.candles, &ticker)?;
+  assert!(!live_signals.is_empty());
+  assert_eq!(live_signals.len(), backtest_signals.len());
+  if let Some(i) = live_signals.iter().zip(backtest_signals.iter()).position(|(live, backtest)| live != backtest) {
+    panic!("Signal {} differs, live {} != backtest {}", i, live_signals[i].print(), backtest_signals[i].print());
+  }
+
+  Ok(())
+}
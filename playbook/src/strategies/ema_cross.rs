@@ -0,0 +1,148 @@
+#![allow(unused_imports)]
+
+use std::path::PathBuf;
+use log::warn;
+use crate::Strategy;
+use time_series::*;
+use crate::Backtest;
+use lib::Interval;
+
+#[derive(Debug, Clone)]
+pub struct EmaCross {
+  pub ticker: String,
+  pub fast_period: usize,
+  pub slow_period: usize,
+  pub src: Source,
+  /// Last N candles from current candle.
+  /// 0th index is current candle, Nth index is oldest candle.
+  pub candles: DataCache<Candle>,
+  pub stop_loss_pct: Option<f64>
+}
+
+impl EmaCross {
+  pub fn new(ticker: String, fast_period: usize, slow_period: usize, src: Source, stop_loss_pct: Option<f64>) -> Self {
+    Self {
+      ticker: ticker.clone(),
+      fast_period,
+      slow_period,
+      src,
+      // several slow periods to warm up the EMA, plus one for the previous candle
+      candles: DataCache::new(slow_period * 4 + 1, ticker),
+      stop_loss_pct
+    }
+  }
+
+  pub fn signal(&mut self) -> anyhow::Result<Vec<Signal>> {
+    if self.candles.vec.len() < self.candles.capacity {
+      warn!("Insufficient candles to generate EMA");
+      return Ok(vec![]);
+    }
+
+    // oldest to newest
+    let series: Vec<f64> = self.candles.vec().iter().map(|c| self.src.value(c)).collect();
+    let period_1 = &series[..series.len() - 1];
+    let period_0 = &series[1..];
+
+    let calc_ema = |series: &[f64], period: usize| {
+      ema(series, period).ok_or(anyhow::anyhow!("Failed to compute EMA"))
+    };
+    let fast_1 = calc_ema(period_1, self.fast_period)?;
+    let slow_1 = calc_ema(period_1, self.slow_period)?;
+    let fast_0 = calc_ema(period_0, self.fast_period)?;
+    let slow_0 = calc_ema(period_0, self.slow_period)?;
+
+    // long if fast EMA crosses above slow EMA
+    let enter_long = fast_0 > slow_0 && fast_1 <= slow_1;
+    // exit if fast EMA crosses below slow EMA
+    let exit_long = fast_0 < slow_0 && fast_1 >= slow_1;
+
+    let c_0 = self.candles.vec[0];
+    let info = SignalInfo {
+      price: c_0.close,
+      date: c_0.date,
+      ticker: self.ticker.clone(),
+      exit_fraction: None,
+      order_type: None,
+      time_in_force: None
+    };
+
+    let mut signals = vec![];
+    if exit_long {
+      signals.push(Signal::ExitLong(info.clone()));
+    }
+    if enter_long {
+      signals.push(Signal::EnterLong(info));
+    }
+    Ok(signals)
+  }
+}
+
+impl Strategy<Candle> for EmaCross {
+  /// Appends candle to candle cache and returns a signal (long, short, or do nothing).
+  fn process_candle(&mut self, candle: Candle, _ticker: Option<String>) -> anyhow::Result<Vec<Signal>> {
+    self.candles.push(candle);
+    self.signal()
+  }
+
+  fn push_candle(&mut self, candle: Candle, _ticker: Option<String>) {
+    self.candles.push(candle);
+  }
+
+  fn cache(&self, _ticker: Option<String>) -> Option<&DataCache<Candle>> {
+    Some(&self.candles)
+  }
+
+  fn stop_loss_pct(&self) -> Option<f64> {
+    self.stop_loss_pct
+  }
+
+  fn sources(&self) -> Vec<Source> {
+    vec![self.src]
+  }
+}
+
+
+// ==========================================================================================
+//                                 EMA Cross Backtests
+// ==========================================================================================
+
+#[tokio::test]
+async fn ema_cross_sol() -> anyhow::Result<()> {
+  use super::*;
+  dotenv::dotenv().ok();
+
+  let ticker = "SOLUSDT".to_string();
+  let strategy = EmaCross::new(ticker.clone(), 12, 26, Source::Close, Some(1.0));
+  let capital = 1_000.0;
+  let fee = 0.02;
+  let bet = Bet::Percent(100.0);
+  let leverage = 1;
+  let short_selling = false;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
+
+  let start_time = Time::new(2023, &Month::from_num(1), &Day::from_num(1), None, None, None);
+  let end_time = Time::new(2024, &Month::from_num(4), &Day::from_num(30), None, None, None);
+
+  let csv = PathBuf::from("solusdt_30m.csv");
+  let mut backtest = Backtest::new(strategy, capital, fee, bet, leverage, short_selling, interval, funding_rate);
+  let csv_series = Dataframe::csv_series(&csv, Some(start_time), Some(end_time), ticker.clone(), false)?;
+  backtest.candles.insert(ticker.clone(), csv_series.candles);
+
+  let summary = backtest.backtest()?;
+  let all_buy_and_hold = backtest.buy_and_hold()?;
+  let buy_and_hold = all_buy_and_hold
+    .get(&ticker)
+    .ok_or(anyhow::anyhow!("Buy and hold not found for ticker"))?
+    .clone();
+  summary.print(&ticker);
+  Plot::plot(
+    vec![summary.cum_pct(&ticker)?.data().clone(), buy_and_hold],
+    "ema_cross_sol_30m_backtest.png",
+    "SOL/USDT EMA Cross Backtest",
+    "% ROI",
+    "Unix Millis"
+  )?;
+
+  Ok(())
+}
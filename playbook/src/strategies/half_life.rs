@@ -6,6 +6,7 @@ use crate::Strategy;
 use time_series::*;
 use std::path::PathBuf;
 use crate::Backtest;
+use lib::Interval;
 use tradestats::metrics::*;
 
 #[derive(Debug, Clone)]
@@ -34,25 +35,6 @@ impl HalfLife {
     }
   }
 
-  /// ZScore of last index in a spread time series
-  pub fn zscore(series: &[f64], window: usize) -> anyhow::Result<f64> {
-    // Guard: Ensure correct window size
-    if window > series.len() {
-      return Err(anyhow::anyhow!("Window size is greater than vector length"));
-    }
-
-    // last z score
-    let window_data: &[f64] = &series[series.len()-window..];
-    let mean: f64 = window_data.iter().sum::<f64>() / window_data.len() as f64;
-    let var: f64 = window_data.iter().map(|&val| (val - mean).powi(2)).sum::<f64>() / (window_data.len()-1) as f64;
-    let std_dev: f64 = var.sqrt();
-    if std_dev == 0.0 {
-      return Err(anyhow::anyhow!("Standard deviation is zero"));
-    }
-    let z_score = (series[series.len()-1] - mean) / std_dev;
-    Ok(z_score)
-  }
-
   pub fn signal(&mut self, ticker: Option<String>) -> anyhow::Result<Vec<Signal>> {
     match ticker {
       None => Ok(vec![]),
@@ -84,7 +66,7 @@ impl HalfLife {
 
         let z_0 = Data {
           x: y_0.x(),
-          y: Self::zscore(&spread, self.window)?
+          y: zscore_last(&spread, self.window)?
         };
 
         let enter_long = z_0.y() < -self.zscore_threshold;
@@ -95,7 +77,10 @@ impl HalfLife {
         let info = SignalInfo {
           price: y_0.y(),
           date: Time::from_unix_ms(y_0.x()),
-          ticker: ticker.clone()
+          ticker: ticker.clone(),
+          exit_fraction: None,
+          order_type: None,
+          time_in_force: None
         };
         let mut signals = vec![];
         // process exits before any new entries
@@ -172,13 +157,16 @@ async fn btc_30m_half_life() -> anyhow::Result<()> {
   let bet = Bet::Percent(100.0);
   let leverage = 1;
   let short_selling = true;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
 
   let btc_csv = PathBuf::from("btcusdt_30m.csv");
   let mut btc_candles = Dataframe::csv_series(
     &btc_csv,
     Some(start_time),
     Some(end_time),
-    ticker.clone()
+    ticker.clone(),
+    false
   )?.candles;
   btc_candles.sort_by_key(|c| c.date.to_unix_ms());
 
@@ -219,7 +207,9 @@ async fn btc_30m_half_life() -> anyhow::Result<()> {
     fee,
     bet,
     leverage,
-    short_selling
+    short_selling,
+    interval,
+    funding_rate
   );
 
   // out-of-sample data (index 1000 to end)
@@ -264,7 +254,8 @@ async fn btc_30m_hurst() -> anyhow::Result<()> {
     &btc_csv,
     Some(start_time),
     Some(end_time),
-    ticker.clone()
+    ticker.clone(),
+    false
   )?.candles;
   btc_candles.sort_by_key(|c| c.date.to_unix_ms());
 
@@ -330,6 +321,8 @@ async fn btc_1d_half_life() -> anyhow::Result<()> {
   let bet = Bet::Percent(100.0);
   let leverage = 1;
   let short_selling = false;
+  let interval = Interval::OneDay;
+  let funding_rate = 0.0;
 
   let btc_csv = PathBuf::from("btcusd_1d.csv");
   let out_file = "half_life_btc_1d_backtest.png";
@@ -337,7 +330,8 @@ async fn btc_1d_half_life() -> anyhow::Result<()> {
     &btc_csv,
     Some(start_time),
     Some(end_time),
-    ticker.clone()
+    ticker.clone(),
+    false
   )?.candles;
   btc_candles.sort_by_key(|c| c.date.to_unix_ms());
 
@@ -358,7 +352,9 @@ async fn btc_1d_half_life() -> anyhow::Result<()> {
     fee,
     bet,
     leverage,
-    short_selling
+    short_selling,
+    interval,
+    funding_rate
   );
 
   // out-of-sample data (index sample split to end)
@@ -422,7 +418,8 @@ async fn btc_1d_hurst() -> anyhow::Result<()> {
     &btc_csv,
     Some(start_time),
     Some(end_time),
-    ticker.clone()
+    ticker.clone(),
+    false
   )?.candles;
   btc_candles.sort_by_key(|c| c.date.to_unix_ms());
 
@@ -1,7 +1,11 @@
 pub mod dreamrunner;
 pub mod stat_arb;
 pub mod half_life;
+pub mod ema_cross;
+pub mod buy_and_hold;
 
 pub use dreamrunner::*;
 pub use stat_arb::*;
-pub use half_life::*;
\ No newline at end of file
+pub use half_life::*;
+pub use ema_cross::*;
+pub use buy_and_hold::*;
\ No newline at end of file
@@ -2,16 +2,18 @@
 
 use log::{warn};
 use rayon::prelude::*;
-use crate::Strategy;
+use serde::{Serialize, Deserialize};
+use crate::{Strategy, StrategyConfig};
 use time_series::*;
 use tradestats::kalman::*;
 use tradestats::metrics::*;
 use tradestats::utils::*;
 use std::path::PathBuf;
-use crate::Backtest;
+use crate::{Backtest, PairHealth, PairStatus, SpreadAnalytics};
+use lib::Interval;
 use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatArb {
   /// Capacity of data caches
   pub capacity: usize,
@@ -24,9 +26,17 @@ pub struct StatArb {
   /// 0th index is current datum, Nth index is oldest datum.
   pub y: DataCache<Data<i64, f64>>,
   pub zscore_threshold: f64,
-  pub stop_loss_pct: Option<f64>
+  pub stop_loss_pct: Option<f64>,
+  /// Stop new entries while the pair is [`PairStatus::Broken`], unchecked if None
+  #[serde(default)]
+  pub pair_health: Option<PairHealth>,
+  /// Exit both legs while the pair is [`PairStatus::Broken`]
+  #[serde(default)]
+  pub flatten_on_break: bool
 }
 
+impl StrategyConfig for StatArb {}
+
 impl StatArb {
   pub fn new(capacity: usize, window: usize, zscore_threshold: f64, x_ticker: String, y_ticker: String, stop_loss_pct: Option<f64>) -> Self {
     Self {
@@ -35,27 +45,30 @@ impl StatArb {
       x: DataCache::new(capacity, x_ticker),
       y: DataCache::new(capacity, y_ticker),
       zscore_threshold,
-      stop_loss_pct
+      stop_loss_pct,
+      pair_health: None,
+      flatten_on_break: false
     }
   }
 
-  /// ZScore of last index in a spread time series
-  pub fn zscore(series: &[f64], window: usize) -> anyhow::Result<f64> {
-    // Guard: Ensure correct window size
-    if window > series.len() {
-      return Err(anyhow::anyhow!("Window size is greater than vector length"));
-    }
-    
-    // last z score 
-    let window_data: &[f64] = &series[series.len() - window..];
-    let mean: f64 = window_data.iter().sum::<f64>() / window_data.len() as f64;
-    let var: f64 = window_data.iter().map(|&val| (val - mean).powi(2)).sum::<f64>() / (window_data.len()-1) as f64;
-    let std_dev: f64 = var.sqrt();
-    if std_dev == 0.0 {
-      return Err(anyhow::anyhow!("Standard deviation is zero"));
+  /// Monitor the pair with `health`, and exit both legs if it breaks when `flatten` is true
+  pub fn with_pair_health(mut self, health: PairHealth, flatten: bool) -> Self {
+    self.pair_health = Some(health);
+    self.flatten_on_break = flatten;
+    self
+  }
+
+  fn pair_status(&self) -> anyhow::Result<PairStatus> {
+    let status = match &self.pair_health {
+      Some(health) => health.status(&self.x, &self.y)?,
+      None => PairStatus::Healthy
+    };
+    match status {
+      PairStatus::Healthy => (),
+      PairStatus::Degrading => warn!("Pair {}/{} is degrading", self.x.id, self.y.id),
+      PairStatus::Broken => warn!("Pair {}/{} is broken, no new entries", self.x.id, self.y.id)
     }
-    let z_score = (series[series.len()-1] - mean) / std_dev;
-    Ok(z_score)
+    Ok(status)
   }
 
   pub fn signal(&mut self, ticker: Option<String>) -> anyhow::Result<Vec<Signal>> {
@@ -95,11 +108,13 @@ impl StatArb {
 
         let z_0 = Data {
           x: x_0.x(),
-          y: Self::zscore(&spread, self.window)?
+          y: zscore_last(&spread, self.window)?
         };
+        // only read by the original exits kept commented out below
+        #[allow(unused_variables)]
         let z_1 = Data {
           x: x_0.x(),
-          y: Self::zscore(&lag_spread, self.window)?
+          y: zscore_last(&lag_spread, self.window)?
         };
 
         // original
@@ -109,20 +124,27 @@ impl StatArb {
         // let exit_short = z_0.y() < 0.0 && z_1.y() > 0.0;
 
         // good
-        let exit_long = z_0.y() < -self.zscore_threshold;
-        let enter_long = z_0.y() > self.zscore_threshold;
+        let broken = self.pair_status()? == PairStatus::Broken;
+        let exit_long = z_0.y() < -self.zscore_threshold || (broken && self.flatten_on_break);
+        let enter_long = z_0.y() > self.zscore_threshold && !broken;
         let exit_short = exit_long;
         let enter_short = enter_long;
 
         let x_info = SignalInfo {
           price: x_0.y(),
           date: Time::from_unix_ms(x_0.x()),
-          ticker: self.x.id.clone()
+          ticker: self.x.id.clone(),
+          exit_fraction: None,
+          order_type: None,
+          time_in_force: None
         };
         let y_info = SignalInfo {
           price: y_0.y(),
           date: Time::from_unix_ms(y_0.x()),
-          ticker: self.y.id.clone()
+          ticker: self.y.id.clone(),
+          exit_fraction: None,
+          order_type: None,
+          time_in_force: None
         };
 
         let mut signals = vec![];
@@ -215,14 +237,16 @@ async fn btc_eth_30m_stat_arb() -> anyhow::Result<()> {
   let bet = Bet::Percent(100.0);
   let leverage = 1;
   let short_selling = true;
+  let interval = Interval::ThirtyMinutes;
+  let funding_rate = 0.0;
 
   let x_ticker = "BTCUSDT".to_string();
   let y_ticker = "ETHUSDT".to_string();
 
   let btc_csv = PathBuf::from("btcusdt_30m.csv");
-  let mut x_candles = Dataframe::csv_series(&btc_csv, Some(start_time), Some(end_time), x_ticker.clone())?.candles;
+  let mut x_candles = Dataframe::csv_series(&btc_csv, Some(start_time), Some(end_time), x_ticker.clone(), false)?.candles;
   let eth_csv = PathBuf::from("ethusdt_30m.csv");
-  let mut y_candles = Dataframe::csv_series(&eth_csv, Some(start_time), Some(end_time), y_ticker.clone())?.candles;
+  let mut y_candles = Dataframe::csv_series(&eth_csv, Some(start_time), Some(end_time), y_ticker.clone(), false)?.candles;
 
   Dataframe::align_pair_series(&mut x_candles, &mut y_candles)?;
   assert_eq!(x_candles.len(), y_candles.len());
@@ -236,7 +260,7 @@ async fn btc_eth_30m_stat_arb() -> anyhow::Result<()> {
   println!("Spread Hurst Exponent: {}", trunc!(hurst(spread.clone()), 2));
 
   let strat = StatArb::new(capacity, window, threshold, x_ticker.clone(), y_ticker.clone(), stop_loss);
-  let mut backtest = Backtest::new(strat, 1000.0, fee, bet, leverage, short_selling);
+  let mut backtest = Backtest::new(strat, 1000.0, fee, bet, leverage, short_selling, interval, funding_rate);
   // Append to backtest data
   backtest.candles.insert(x_ticker.clone(), x_candles.clone());
   backtest.candles.insert(y_ticker.clone(), y_candles.clone());
@@ -246,38 +270,28 @@ async fn btc_eth_30m_stat_arb() -> anyhow::Result<()> {
   let summary = backtest.backtest()?;
   let all_buy_and_hold = backtest.buy_and_hold()?;
 
-  if let Some(trades) = backtest.trades.get(&x_ticker) {
-    if trades.len() > 1 {
-      summary.print(&x_ticker);
-      let x_bah = all_buy_and_hold
-        .get(&x_ticker)
-        .ok_or(anyhow::anyhow!("Buy and hold not found for ticker"))?
-        .clone();
-      Plot::plot(
-        vec![summary.cum_pct(&x_ticker)?.data().clone(), x_bah],
-        "stat_arb_btc_30m_backtest.png",
-        &format!("{} Stat Arb Backtest", x_ticker),
-        "% ROI",
-        "Unix Millis"
-      )?;
-    }
-  }
-  if let Some(trades) = backtest.trades.get(&y_ticker) {
-    if trades.len() > 1 {
-      summary.print(&y_ticker);
-      let y_bah = all_buy_and_hold
-        .get(&y_ticker)
-        .ok_or(anyhow::anyhow!("Buy and hold not found for ticker"))?
-        .clone();
-      Plot::plot(
-        vec![summary.cum_pct(&y_ticker)?.data().clone(), y_bah],
-        "stat_arb_eth_30m_backtest.png",
-        &format!("{} Stat Arb Backtest", y_ticker),
-        "% ROI",
-        "Unix Millis"
-      )?;
+  let mut series = vec![summary.portfolio_cum_pct().0];
+  for ticker in [&x_ticker, &y_ticker] {
+    if let Some(trades) = backtest.trades.get(ticker) {
+      if trades.len() > 1 {
+        summary.print(ticker);
+      }
     }
+    series.push(all_buy_and_hold
+      .get(ticker)
+      .ok_or(anyhow::anyhow!("Buy and hold not found for ticker"))?
+      .clone());
   }
+  let benchmark = backtest.equal_weight_benchmark()?;
+  println!("Equal weight buy and hold: {}%", trunc!(benchmark.data().last().map(|d| d.y).unwrap_or(0.0), 2));
+  series.push(benchmark.0);
+  Plot::plot(
+    series,
+    "stat_arb_btc_eth_30m_backtest.png",
+    &format!("{}/{} Stat Arb Portfolio Backtest", x_ticker, y_ticker),
+    "% ROI",
+    "Unix Millis"
+  )?;
 
   Ok(())
 }
@@ -294,9 +308,9 @@ async fn btc_eth_30m_spread_attributes() -> anyhow::Result<()> {
   let y_ticker = "ETHUSDT".to_string();
   
   let btc_csv = PathBuf::from("btcusdt_30m.csv");
-  let mut x_candles = Dataframe::csv_series(&btc_csv, Some(start_time), Some(end_time), x_ticker.clone())?.candles;
+  let mut x_candles = Dataframe::csv_series(&btc_csv, Some(start_time), Some(end_time), x_ticker.clone(), false)?.candles;
   let eth_csv = PathBuf::from("ethusdt_30m.csv");
-  let mut y_candles = Dataframe::csv_series(&eth_csv, Some(start_time), Some(end_time), y_ticker.clone())?.candles;
+  let mut y_candles = Dataframe::csv_series(&eth_csv, Some(start_time), Some(end_time), y_ticker.clone(), false)?.candles;
 
   Dataframe::align_pair_series(&mut x_candles, &mut y_candles)?;
 
@@ -432,9 +446,9 @@ async fn btc_eth_30m_spread() -> anyhow::Result<()> {
 
   let mut backtest = Backtest::default();
   let btc_csv = PathBuf::from("btcusdt_30m.csv");
-  let mut x_candles = Dataframe::csv_series(&btc_csv, Some(start_time), Some(end_time), x_ticker.clone())?.candles;
+  let mut x_candles = Dataframe::csv_series(&btc_csv, Some(start_time), Some(end_time), x_ticker.clone(), false)?.candles;
   let eth_csv = PathBuf::from("ethusdt_30m.csv");
-  let mut y_candles = Dataframe::csv_series(&eth_csv, Some(start_time), Some(end_time), y_ticker.clone())?.candles;
+  let mut y_candles = Dataframe::csv_series(&eth_csv, Some(start_time), Some(end_time), y_ticker.clone(), false)?.candles;
 
   Dataframe::align_pair_series(&mut x_candles, &mut y_candles)?;
   // Append to backtest data
@@ -449,14 +463,6 @@ async fn btc_eth_30m_spread() -> anyhow::Result<()> {
   let y = Dataframe::normalize_series::<Candle>(backtest.candles.get(&y_ticker).unwrap())?;
   assert_eq!(x.len(), y.len());
 
-  Plot::plot(
-    vec![x.data().clone(), y.data().clone()],
-    "btc_eth_30m_normalized.png",
-    "BTC & ETH Percent Changes",
-    "Percent from Origin",
-    "Unix Millis"
-  )?;
-  
   let correlation = rolling_correlation(&x.y(), &y.y(), window).map_err(
     |e| anyhow::anyhow!("Error calculating rolling correlation: {}", e)
   )?;
@@ -478,27 +484,11 @@ async fn btc_eth_30m_spread() -> anyhow::Result<()> {
     let x = x.x()[i];
     Data { x, y: *y }
   }).collect());
-  Plot::plot(
-    vec![spread_data.data().clone()],
-    "btc_eth_30m_spread.png",
-    "BTC/ETH Spread",
-    "Spread",
-    "Unix Millis"
-  )?;
-  
   assert_eq!(spread.len(), y.len());
   assert_eq!(spread.len(), x.len());
-  let zscore: Vec<f64> = rolling_zscore(&spread, window).unwrap();
-  assert_eq!(zscore.len(), spread.len());
-  let zscore = Dataset::new(zscore.iter().enumerate().map(|(i, x)| Data { x: i as i64, y: *x }).collect());
-  Plot::plot(
-    vec![zscore.data().clone()],
-    // vec![spread_data.data().clone(), zscore.data().clone()],
-    "btc_eth_30m_spread_zscore.png",
-    "BTC/ETH Spread Z Score",
-    "Z Score",
-    "Time"
-  )?;
+  let zscore = spread_data.rolling_zscore(window);
+  assert_eq!(zscore.len(), spread.len() - window + 1);
+  Plot::pair_dashboard(&x, &y, &spread_data, &zscore, 2.0, "btc_eth_30m_pair_dashboard.png")?;
   
   let half_life: f64 = half_life(&spread).unwrap();
   let half_life = half_life.abs().round() as usize;
@@ -520,9 +510,9 @@ async fn btc_eth_30m_cointegration() -> anyhow::Result<()> {
 
   let mut backtest = Backtest::default();
   let btc_csv = PathBuf::from("btcusdt_30m.csv");
-  let mut x_candles = Dataframe::csv_series(&btc_csv, Some(start_time), Some(end_time), x_ticker.clone())?.candles;
+  let mut x_candles = Dataframe::csv_series(&btc_csv, Some(start_time), Some(end_time), x_ticker.clone(), false)?.candles;
   let eth_csv = PathBuf::from("ethusdt_30m.csv");
-  let mut y_candles = Dataframe::csv_series(&eth_csv, Some(start_time), Some(end_time), y_ticker.clone())?.candles;
+  let mut y_candles = Dataframe::csv_series(&eth_csv, Some(start_time), Some(end_time), y_ticker.clone(), false)?.candles;
 
   Dataframe::align_pair_series(&mut x_candles, &mut y_candles)?;
   // Append to backtest data
@@ -541,31 +531,22 @@ async fn btc_eth_30m_cointegration() -> anyhow::Result<()> {
   )?;
   assert_eq!(x.len(), y.len());
 
-  assert_eq!(x.len(), y.len());
-
-  let dynamic_kalman_hedge = Dataset::new(dynamic_hedge_kalman_filter(&x.y(), &y.y()).map_err(
-    |e| anyhow::anyhow!("Error calculating dynamic hedge ratio: {}", e)
-  )?.iter().enumerate().map(|(i, x)| Data { x: i as i64, y: *x }).collect());
-
   let coint = engle_granger_cointegration_test(&x.y(), &y.y()).map_err(
     |e| anyhow::anyhow!("Error calculating Engle-Granger cointegration test: {}", e)
   )?;
   println!("Engle-Granger Cointegration Test: {:#?}", coint);
 
-  let spread: Vec<f64> = spread_standard(&x.y(), &y.y()).map_err(
-    |e| anyhow::anyhow!("Error calculating dynamic spread: {}", e)
-  )?;
-  assert_eq!(spread.len(), y.len());
-  assert_eq!(spread.len(), x.len());
+  let analytics = SpreadAnalytics::new(x, y)?;
+  let dynamic_kalman_hedge = analytics.hedge_ratio_kalman()?;
+  assert_eq!(dynamic_kalman_hedge.len(), analytics.x.len());
 
-
-  let half_life: f64 = half_life(&spread).unwrap();
+  let half_life = analytics.half_life()?;
+  println!("Spread half life: {} bars", trunc!(half_life, 1));
   // let window = 20;
   let window = half_life.abs().round() as usize;
 
-  let roll_coint = Dataset::new(rolling_cointegration(&x.y(), &y.y(), window).map_err(
-    |e| anyhow::anyhow!("Error calculating rolling cointegration: {}", e)
-  )?.iter().enumerate().map(|(i, x)| Data { x: i as i64, y: *x }).collect());
+  let roll_coint = analytics.rolling_cointegration(window)?;
+  assert_eq!(roll_coint.len(), analytics.x.len());
 
   Plot::plot(
     vec![dynamic_kalman_hedge.data().clone()],
@@ -583,4 +564,49 @@ async fn btc_eth_30m_cointegration() -> anyhow::Result<()> {
   )?;
 
   Ok(())
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn stat_arb_pair_health() -> anyhow::Result<()> {
+  use super::*;
+
+  let x_ticker = "BTCUSDT".to_string();
+  let y_ticker = "ETHUSDT".to_string();
+  let health = PairHealth::default();
+  let pair = |y: &dyn Fn(f64) -> f64| -> StatArb {
+    let mut strategy = StatArb::new(100, 99, 0.0, x_ticker.clone(), y_ticker.clone(), None);
+    for i in 0..100 {
+      let t = i as f64;
+      let x = 100.0 + 10.0 * (t * 0.3).sin() + t * 0.2;
+      strategy.x.push(Data { x: i, y: x });
+      strategy.y.push(Data { x: i, y: y(x) + 0.5 * (t * 1.7).sin() });
+    }
+    strategy
+  };
+
+  // y tracks x with stationary noise
+  let strategy = pair(&|x| 2.0 * x);
+  assert_eq!(health.status(&strategy.x, &strategy.y)?, PairStatus::Healthy);
+
+  // y moves against x
+  let mut strategy = pair(&|x| 400.0 - 2.0 * x);
+  assert_eq!(health.status(&strategy.x, &strategy.y)?, PairStatus::Broken);
+
+  // a broken pair stops entries, and exits both legs if it flattens
+  let enters = |signals: &[Signal]| signals.iter().any(|s| matches!(s, Signal::EnterLong(_) | Signal::EnterShort(_)));
+  // zero threshold always signals
+  assert!(!strategy.signal(Some(x_ticker.clone()))?.is_empty());
+
+  let mut strategy = strategy.with_pair_health(health.clone(), false);
+  assert!(!enters(&strategy.signal(Some(x_ticker.clone()))?));
+
+  let mut strategy = strategy.with_pair_health(health, true);
+  let signals = strategy.signal(Some(x_ticker.clone()))?;
+  assert!(!enters(&signals));
+  let exits: Vec<String> = signals.iter().filter_map(|s| match s {
+    Signal::ExitLong(info) => Some(info.ticker.clone()),
+    _ => None
+  }).collect();
+  assert_eq!(exits, vec![x_ticker, y_ticker]);
+  Ok(())
+}
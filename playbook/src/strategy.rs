@@ -1,12 +1,131 @@
-use time_series::{Signal, DataCache, Candle};
+use std::path::Path;
+use async_trait::async_trait;
+use serde::{Serialize, de::DeserializeOwned};
+use time_series::{atr, Signal, DataCache, Candle, Source};
 
-pub trait Strategy<T>: Clone {
+/// How far against an entry the position is stopped out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopLoss {
+  /// Fixed % of the entry price
+  Pct(f64),
+  /// `mult` times the ATR over `period` candles at entry, so the stop widens with volatility
+  Atr { period: usize, mult: f64 }
+}
+
+impl StopLoss {
+  /// Stop distance as a % of `entry_price`, from `candles` ordered oldest to newest up to the entry candle.
+  /// The ATR is smoothed over at most the last 4 periods, None if there are fewer than `period + 1` candles.
+  pub fn pct(&self, entry_price: f64, candles: &[Candle]) -> Option<f64> {
+    match self {
+      StopLoss::Pct(pct) => Some(*pct),
+      StopLoss::Atr { period, mult } => {
+        let lookback = candles.len().saturating_sub(period * 4 + 1);
+        atr(&candles[lookback..], *period).map(|atr| atr * mult / entry_price * 100.0)
+      }
+    }
+  }
+}
+
+#[async_trait]
+pub trait Strategy<T>: Clone + Send {
   /// Receives new candle and returns a signal (long, short, or do nothing).
   fn process_candle(&mut self, candle: Candle, ticker: Option<String>) -> anyhow::Result<Vec<Signal>>;
+  /// Async variant of [`Strategy::process_candle`] awaited by the live engine,
+  /// so a strategy can consult IO (a REST endpoint, model server, etc.) per candle.
+  /// Defaults to the sync method. Backtests always use the sync path.
+  async fn process_candle_async(&mut self, candle: Candle, ticker: Option<String>) -> anyhow::Result<Vec<Signal>> {
+    self.process_candle(candle, ticker)
+  }
   /// Appends a candle to the candle cache
   fn push_candle(&mut self, candle: Candle, ticker: Option<String>);
   /// Returns a reference to the candle cache
   fn cache(&self, ticker: Option<String>) -> Option<&DataCache<T>>;
+
+  /// Number of candles after which signals are trustworthy.
+  /// The live engine loads at least this many recent candles at startup, so indicators kept outside
+  /// the cache (updated in [`Strategy::push_candle`]) can need more history than the cache capacity.
+  /// Defaults to the cache capacity.
+  fn min_history(&self) -> usize {
+    self.cache(None).map(|cache| cache.capacity).unwrap_or(0)
+  }
   
   fn stop_loss_pct(&self) -> Option<f64>;
-}
\ No newline at end of file
+
+  /// Stop loss of each entry, a fixed [`StopLoss::Pct`] of [`Strategy::stop_loss_pct`] by default
+  fn stop_loss(&self) -> Option<StopLoss> {
+    self.stop_loss_pct().map(StopLoss::Pct)
+  }
+
+  /// % move in favor of an entry at which the live engine takes profit with a limit order.
+  /// Placed alongside the stop loss as a bracket, whichever fills first closes the position.
+  fn take_profit_pct(&self) -> Option<f64> {
+    None
+  }
+
+  /// Number of candles after an entry during which new entries are suppressed.
+  /// Exits are still allowed during the cooldown.
+  fn cooldown(&self) -> usize {
+    0
+  }
+
+  /// Candle prices the strategy's signals are computed from, logged by the live engine for each closed candle
+  fn sources(&self) -> Vec<Source> {
+    vec![Source::Close]
+  }
+}
+
+/// Signals `strategy` emits for `candles` fed one at a time through [`Strategy::process_candle`], oldest first,
+/// as the live engine does after warmup. Compare with [`crate::Backtest::signals`] for the same candles
+/// to check the backtest acts on the signals the live path would.
+pub fn replay_signals<T, S: Strategy<T>>(mut strategy: S, candles: &[Candle], ticker: &str) -> anyhow::Result<Vec<Signal>> {
+  let mut signals = vec![];
+  for candle in candles {
+    signals.extend(strategy.process_candle(*candle, Some(ticker.to_string()))?);
+  }
+  Ok(signals)
+}
+
+/// Save and load a strategy's parameters as JSON, e.g. an optimized set for the live engine to trade.
+/// Runtime state such as candle caches is skipped, so a loaded strategy starts empty.
+pub trait StrategyConfig: Serialize + DeserializeOwned {
+  fn from_config_file(path: &Path) -> anyhow::Result<Self> {
+    let file = std::fs::File::open(path)?;
+    Ok(serde_json::from_reader(file)?)
+  }
+
+  fn to_config_file(&self, path: &Path) -> anyhow::Result<()> {
+    let file = std::fs::File::create(path)?;
+    Ok(serde_json::to_writer_pretty(file, self)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use time_series::Time;
+
+  fn candle(high: f64, low: f64, close: f64) -> Candle {
+    Candle { date: Time::from_unix(0), open: close, high, low, close, volume: None }
+  }
+
+  #[test]
+  fn stop_loss_pct() {
+    let candles = [candle(101.0, 99.0, 100.0), candle(102.0, 99.0, 101.0), candle(102.0, 101.0, 101.0)];
+    assert_eq!(StopLoss::Pct(2.5).pct(100.0, &[]), Some(2.5));
+    // true ranges 3 and 1, ATR 2, 1.5 ATR is 3% of the entry
+    assert_eq!(StopLoss::Atr { period: 2, mult: 1.5 }.pct(100.0, &candles), Some(3.0));
+    // a period of 2 needs 3 candles
+    assert_eq!(StopLoss::Atr { period: 2, mult: 1.5 }.pct(100.0, &candles[..2]), None);
+    assert_eq!(StopLoss::Atr { period: 2, mult: 1.5 }.pct(100.0, &[]), None);
+  }
+
+  #[test]
+  fn dreamrunner_sources() {
+    let mut strategy = crate::Dreamrunner::solusdt_optimized();
+    strategy.k_src = Source::Close;
+    strategy.ma_src = Source::Open;
+    assert_eq!(strategy.sources(), vec![Source::Close, Source::Open]);
+    strategy.ma_src = Source::Close;
+    assert_eq!(strategy.sources(), vec![Source::Close]);
+  }
+}
@@ -1,18 +1,18 @@
+use std::str::FromStr;
 use std::sync::Arc;
 use actix_web::{get, web, App, HttpResponse, HttpServer, Responder};
 use actix_web::web::Data;
 use lib::*;
 use dotenv::dotenv;
 use log::*;
+use serde::{Deserialize, Serialize};
 use simplelog::{ColorChoice, Config as SimpleLogConfig, TermLogger, TerminalMode};
-use time_series::Plot;
+use time_series::{Dataset, PerformanceSummary, Plot, PlotOptions, Time, Trade};
 
 // Binance spot TEST network
 pub const BINANCE_TEST_API: &str = "https://testnet.binance.vision";
 // Binance spot LIVE network
 pub const BINANCE_LIVE_API: &str = "https://api.binance.us";
-const BASE_ASSET: &str = "SOL";
-const QUOTE_ASSET: &str = "USDT";
 const TICKER: &str = "SOLUSDT";
 const INTERVAL: Interval = Interval::ThirtyMinutes;
 
@@ -24,35 +24,39 @@ async fn main() -> anyhow::Result<()> {
     let port = std::env::var("PORT").unwrap_or_else(|_| "8080".to_string());
     let bind_address = format!("0.0.0.0:{}", port);
     
-    let account = match std::env::var("TESTNET")?.parse::<bool>()? {
-        true => {
-            Account {
-                client: Client::new(
-                    Some(std::env::var("BINANCE_TEST_API_KEY")?),
-                    Some(std::env::var("BINANCE_TEST_API_SECRET")?),
-                    BINANCE_TEST_API.to_string(),
-                )?,
-                recv_window: 5000,
-                base_asset: BASE_ASSET.to_string(),
-                quote_asset: QUOTE_ASSET.to_string(),
-                ticker: TICKER.to_string(),
-                interval: INTERVAL
-            }
-        }
-        false => {
-            Account {
-                client: Client::new(
-                    Some(std::env::var("BINANCE_LIVE_API_KEY")?),
-                    Some(std::env::var("BINANCE_LIVE_API_SECRET")?),
-                    BINANCE_LIVE_API.to_string(),
-                )?,
-                recv_window: 5000,
-                base_asset: BASE_ASSET.to_string(),
-                quote_asset: QUOTE_ASSET.to_string(),
-                ticker: TICKER.to_string(),
-                interval: INTERVAL
-            }
-        }
+    // bump if the clock drifts and Binance rejects requests as outside the recv window
+    let recv_window = match std::env::var("RECV_WINDOW") {
+        Ok(recv_window) => recv_window.parse::<u32>()?,
+        Err(_) => DEFAULT_RECV_WINDOW,
+    };
+
+    let client = match std::env::var("TESTNET")?.parse::<bool>()? {
+        true => Client::new(
+            Some(std::env::var("BINANCE_TEST_API_KEY")?),
+            Some(std::env::var("BINANCE_TEST_API_SECRET")?),
+            BINANCE_TEST_API.to_string(),
+        )?,
+        false => Client::new(
+            Some(std::env::var("BINANCE_LIVE_API_KEY")?),
+            Some(std::env::var("BINANCE_LIVE_API_SECRET")?),
+            BINANCE_LIVE_API.to_string(),
+        )?,
+    };
+    client.sync_time().await?;
+
+    // base and quote assets are read from the exchange so only the ticker needs configuring
+    let info = client
+        .get::<ExchangeInformation>(API::Spot(Spot::ExchangeInfo), Some(ExchangeInfo::request(TICKER.to_string())))
+        .await?;
+    let (base_asset, quote_asset) = Symbol::split_ticker(TICKER, &info)?;
+    let account = Account {
+        client,
+        recv_window,
+        base_asset,
+        quote_asset,
+        ticker: TICKER.to_string(),
+        interval: INTERVAL,
+        filters: SymbolFilters::from_exchange_info(&info, TICKER)?
     };
 
     let state = Data::new(Arc::new(account));
@@ -63,11 +67,14 @@ async fn main() -> anyhow::Result<()> {
             .service(get_assets)
             .service(balance)
             .service(cancel_orders)
+            .service(flatten)
             .service(get_price)
             .service(exchange_info)
             .service(trades)
             .service(open_orders)
             .service(pnl)
+            .service(summary)
+            .service(unrealized_pnl)
             .service(plot_pnl)
             .service(klines)
             .service(kline_history)
@@ -122,6 +129,13 @@ async fn cancel_orders(account: Data<Arc<Account>>) -> DreamrunnerResult<HttpRes
     Ok(HttpResponse::Ok().json(res))
 }
 
+#[get("/flatten")]
+async fn flatten(account: Data<Arc<Account>>) -> DreamrunnerResult<HttpResponse> {
+    info!("Flatten base asset to quote");
+    let res = account.flatten().await?;
+    Ok(HttpResponse::Ok().json(res))
+}
+
 #[get("/price")]
 async fn get_price(account: Data<Arc<Account>>) -> DreamrunnerResult<HttpResponse> {
     let res = account.price().await?;
@@ -165,29 +179,112 @@ async fn pnl(account: Data<Arc<Account>>) -> DreamrunnerResult<HttpResponse> {
     Ok(HttpResponse::Ok().json(res.summarize(&account.ticker)?))
 }
 
+#[derive(Debug, Serialize)]
+struct SummaryResponse {
+    ticker: String,
+    /// Unix millis of the first trade
+    start: Option<i64>,
+    /// Unix millis of the last trade
+    end: Option<i64>,
+    performance: PerformanceSummary,
+    cum_quote: Dataset<i64, f64>,
+    cum_pct: Dataset<i64, f64>,
+    pct_per_trade: Dataset<i64, f64>,
+    trades: Vec<Trade>,
+}
+
+#[get("/summary")]
+async fn summary(account: Data<Arc<Account>>) -> DreamrunnerResult<HttpResponse> {
+    let ticker = &account.ticker;
+    let res = account
+      .summary().await?;
+    let ticker_trades = res.trades(ticker)?.clone();
+    let dates = ticker_trades.iter().map(|t| t.date.to_unix_ms());
+    Ok(HttpResponse::Ok().json(SummaryResponse {
+        ticker: ticker.clone(),
+        start: dates.clone().min(),
+        end: dates.max(),
+        performance: res.summarize(ticker)?,
+        cum_quote: res.cum_quote(ticker)?.clone(),
+        cum_pct: res.cum_pct(ticker)?.clone(),
+        pct_per_trade: res.pct_per_trade(ticker)?.clone(),
+        trades: ticker_trades,
+    }))
+}
+
+#[get("/unrealizedPnl")]
+async fn unrealized_pnl(account: Data<Arc<Account>>) -> DreamrunnerResult<HttpResponse> {
+    let res = account
+      .unrealized_pnl().await?;
+    Ok(HttpResponse::Ok().json(res))
+}
+
 #[get("/plotPnl")]
 async fn plot_pnl(account: Data<Arc<Account>>) -> DreamrunnerResult<HttpResponse> {
     let res = account
       .summary().await?;
-    Plot::plot(
+    Plot::plot_with_options(
         vec![res.cum_quote(&account.ticker)?.data().clone()],
         "dreamrunner_roi.png",
         "Quote Pnl",
-        QUOTE_ASSET,
-        "Unix Millis"
+        &account.quote_asset,
+        "Date",
+        PlotOptions { x_is_time: true, ..PlotOptions::default() }
     )?;
 
     Ok(HttpResponse::Ok().body("Ok"))
 }
 
+#[derive(Debug, Deserialize)]
+struct KlineQuery {
+    /// Days back from `end`, ignored if `start` is provided
+    days: Option<i64>,
+    /// Binance interval such as "30m", defaults to the account interval
+    interval: Option<String>,
+    /// Unix millis
+    start: Option<i64>,
+    /// Unix millis, defaults to now
+    end: Option<i64>,
+}
+
+impl KlineQuery {
+    fn interval(&self, default: Interval) -> DreamrunnerResult<Interval> {
+        match &self.interval {
+            Some(interval) => Interval::from_str(interval),
+            None => Ok(default),
+        }
+    }
+
+    fn has_range(&self) -> bool {
+        self.start.is_some() || self.days.is_some()
+    }
+
+    /// Time range in unix millis
+    fn range(&self, default_days: i64) -> (i64, i64) {
+        let end = self.end.unwrap_or(Time::now().to_unix_ms());
+        let days = self.days.unwrap_or(default_days);
+        let start = self.start.unwrap_or(end - days * 24 * 60 * 60 * 1000);
+        (start, end)
+    }
+}
+
 #[get("/klines")]
-async fn klines(account: Data<Arc<Account>>) -> DreamrunnerResult<HttpResponse> {
-    let res = account.klines(None, None, None).await?;
+async fn klines(account: Data<Arc<Account>>, query: web::Query<KlineQuery>) -> DreamrunnerResult<HttpResponse> {
+    let interval = query.interval(account.interval)?;
+    let res = match query.has_range() {
+        true => {
+            let (start, end) = query.range(0);
+            account.paginated_klines(interval, start, end).await?
+        }
+        false => account.interval_klines(interval, None, None, query.end).await?,
+    };
     Ok(HttpResponse::Ok().json(res))
 }
 
 #[get("/klineHistory")]
-async fn kline_history(account: Data<Arc<Account>>) -> DreamrunnerResult<HttpResponse> {
-    let res = account.kline_history(60).await?;
+async fn kline_history(account: Data<Arc<Account>>, query: web::Query<KlineQuery>) -> DreamrunnerResult<HttpResponse> {
+    let interval = query.interval(account.interval)?;
+    let (start, end) = query.range(60);
+    let res = account.paginated_klines(interval, start, end).await?;
     Ok(HttpResponse::Ok().json(res))
 }
\ No newline at end of file
@@ -0,0 +1,48 @@
+use crate::Candle;
+
+/// Greatest of the candle range and the gaps from the previous close
+pub fn true_range(candle: &Candle, prev_close: f64) -> f64 {
+  (candle.high - candle.low)
+    .max((candle.high - prev_close).abs())
+    .max((candle.low - prev_close).abs())
+}
+
+/// Average true range of `candles` ordered oldest to newest, with Wilder's smoothing.
+/// Seeded with the mean of the first `period` true ranges, so at least `period + 1` candles are required.
+pub fn atr(candles: &[Candle], period: usize) -> Option<f64> {
+  if period == 0 || candles.len() < period + 1 {
+    return None;
+  }
+  let ranges: Vec<f64> = candles.windows(2).map(|w| true_range(&w[1], w[0].close)).collect();
+  let seed = ranges[..period].iter().sum::<f64>() / period as f64;
+  Some(ranges[period..].iter().fold(seed, |atr, tr| (atr * (period - 1) as f64 + tr) / period as f64))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Time;
+
+  fn candle(high: f64, low: f64, close: f64) -> Candle {
+    Candle { date: Time::from_unix(0), open: close, high, low, close, volume: None }
+  }
+
+  #[test]
+  fn atr_wilder_smoothing() {
+    let candles = [
+      candle(10.0, 9.0, 9.5),
+      // range 2
+      candle(11.0, 9.0, 10.0),
+      // gap up from 10, high - prev close 4
+      candle(14.0, 13.0, 13.5),
+      // range 1
+      candle(14.0, 13.0, 13.5),
+    ];
+    assert_eq!(true_range(&candles[2], candles[1].close), 4.0);
+    assert_eq!(atr(&candles[..3], 2), Some(3.0));
+    // (3 * 1 + 1) / 2
+    assert_eq!(atr(&candles, 2), Some(2.0));
+    assert_eq!(atr(&candles, 4), None);
+    assert_eq!(atr(&candles, 0), None);
+  }
+}
@@ -35,6 +35,26 @@ impl Candle {
     pub fn percent_change(&self, prev_close: f64) -> f64 {
         ((100.0 / prev_close) * self.close) - 100.0
     }
+
+    /// Check the OHLC is consistent: `low <= open, close <= high` and volume is non-negative.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.high < self.low {
+            return Err(anyhow::anyhow!("high {} below low {}", self.high, self.low));
+        }
+        let in_range = |price: f64| self.low <= price && price <= self.high;
+        if !in_range(self.open) {
+            return Err(anyhow::anyhow!("open {} outside low {} and high {}", self.open, self.low, self.high));
+        }
+        if !in_range(self.close) {
+            return Err(anyhow::anyhow!("close {} outside low {} and high {}", self.close, self.low, self.high));
+        }
+        if let Some(volume) = self.volume {
+            if volume.is_nan() || volume < 0.0 {
+                return Err(anyhow::anyhow!("negative volume {}", volume));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl PartialEq for Candle {
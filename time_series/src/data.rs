@@ -1,4 +1,9 @@
+use std::path::PathBuf;
 use serde::{Serialize, Deserialize};
+use crate::{mean, sample_std_dev, Time};
+
+/// Unix millis from 2000-01-01 to 2100-01-01, x values in this range are taken to be timestamps
+const UNIX_MS_RANGE: std::ops::Range<i64> = 946_684_800_000..4_102_444_800_000;
 
 pub trait Y: Clone {
   fn y(&self) -> f64;
@@ -80,4 +85,247 @@ impl<XX: X, YY: Y> Dataset<XX, YY> {
   pub fn is_empty(&self) -> bool {
     self.0.is_empty()
   }
-}
\ No newline at end of file
+
+  /// Write `x,y` rows under a `x_label,y_label` header, the numeric counterpart to `Plot::plot`.
+  /// If every x is a unix millis timestamp, a third `date` column has it as a date string.
+  pub fn write_csv(&self, path: &PathBuf, x_label: &str, y_label: &str) -> anyhow::Result<()> {
+    let dates = !self.0.is_empty() && self.0.iter().all(|d| UNIX_MS_RANGE.contains(&d.x()));
+    let mut csv = csv::Writer::from_path(path)?;
+    match dates {
+      true => csv.write_record([x_label, y_label, "date"])?,
+      false => csv.write_record([x_label, y_label])?
+    }
+    for d in self.0.iter() {
+      let (x, y) = (d.x().to_string(), d.y().to_string());
+      match dates {
+        true => csv.write_record([x, y, Time::from_unix_ms(d.x()).to_string()])?,
+        false => csv.write_record([x, y])?
+      }
+    }
+    csv.flush()?;
+    Ok(())
+  }
+}
+
+/// How [`Dataset::resample_time`] values a grid time between two observations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fill {
+  /// Last observation carried forward, the value of the latest point at or before the grid time,
+  /// held however long the gap to the next point. The grid ends at the last point, so the last observation
+  /// is never carried past the end of the series, add a point at the end time to carry it further.
+  Forward,
+  /// Linearly interpolated between the points either side of the grid time
+  Linear
+}
+
+impl Dataset<i64, f64> {
+  /// Resample a series with unix millis `x` (e.g. `Summary::cum_pct`) onto a regular grid every `step_ms`,
+  /// such as a day of `lib::Interval::OneDay`, so curves with irregular points can be overlaid or correlated.
+  /// The grid is aligned to multiples of `step_ms` and spans the first to last point.
+  pub fn resample_time(&self, step_ms: i64, fill: Fill) -> Dataset<i64, f64> {
+    let data = self.asc_order();
+    let (first, last) = match (data.first(), data.last()) {
+      (Some(first), Some(last)) if step_ms > 0 => (first.x, last.x),
+      _ => return Dataset::new(vec![])
+    };
+    let mut resampled = vec![];
+    // index of the latest point at or before the grid time
+    let mut i = 0;
+    let mut t = (first + step_ms - 1).div_euclid(step_ms) * step_ms;
+    while t <= last {
+      while i + 1 < data.len() && data[i + 1].x <= t {
+        i += 1;
+      }
+      let prev = &data[i];
+      let y = match (fill, data.get(i + 1)) {
+        (Fill::Linear, Some(next)) if prev.x < t => {
+          prev.y + (next.y - prev.y) * (t - prev.x) as f64 / (next.x - prev.x) as f64
+        },
+        _ => prev.y
+      };
+      resampled.push(Data { x: t, y });
+      t += step_ms;
+    }
+    Dataset::new(resampled)
+  }
+
+  /// Sharpe ratio over a sliding window of returns of an equity curve in % ROI (e.g. `Summary::cum_pct`).
+  /// Each point is the annualized Sharpe of the `window` returns ending at that point's `x`,
+  /// so nothing is emitted until the window fills.
+  pub fn rolling_sharpe(&self, window: usize, periods_per_year: f64) -> Dataset<i64, f64> {
+    if window < 2 || self.0.len() <= window {
+      return Dataset::new(vec![]);
+    }
+    let returns: Vec<f64> = self.0.windows(2).map(|w| {
+      let prev = 1.0 + w[0].y / 100.0;
+      let curr = 1.0 + w[1].y / 100.0;
+      if prev == 0.0 { 0.0 } else { curr / prev - 1.0 }
+    }).collect();
+
+    let data = returns.windows(window).enumerate().map(|(i, r)| {
+      let mean = r.iter().sum::<f64>() / window as f64;
+      let variance = r.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (window - 1) as f64;
+      let std_dev = variance.sqrt();
+      let sharpe = match std_dev == 0.0 {
+        true => 0.0,
+        false => mean / std_dev * periods_per_year.sqrt()
+      };
+      Data {
+        // returns[i + window - 1] ends at series point i + window
+        x: self.0[i + window].x,
+        y: sharpe
+      }
+    }).collect();
+    Dataset::new(data)
+  }
+
+  /// Z score of each point against the `window` values ending at it, keeping that point's `x`.
+  /// Same as [`crate::zscore_last`] at every point, so nothing is emitted until the window fills
+  /// and a flat window scores 0.
+  pub fn rolling_zscore(&self, window: usize) -> Dataset<i64, f64> {
+    if window < 2 || self.0.len() < window {
+      return Dataset::new(vec![]);
+    }
+    let y = self.y();
+    let data = y.windows(window).enumerate().map(|(i, w)| {
+      let std_dev = sample_std_dev(w);
+      let z = match std_dev == 0.0 {
+        true => 0.0,
+        false => (w[window - 1] - mean(w)) / std_dev
+      };
+      Data {
+        x: self.0[i + window - 1].x,
+        y: z
+      }
+    }).collect();
+    Dataset::new(data)
+  }
+
+  /// % return between consecutive points of an equity curve in % ROI (e.g. `Summary::cum_pct`),
+  /// so each point is the return since the prior point rather than since inception.
+  pub fn pct_returns(&self) -> Dataset<i64, f64> {
+    let data = self.0.windows(2).map(|w| {
+      let prev = 1.0 + w[0].y / 100.0;
+      let curr = 1.0 + w[1].y / 100.0;
+      Data {
+        x: w[1].x,
+        y: if prev == 0.0 { 0.0 } else { (curr / prev - 1.0) * 100.0 }
+      }
+    }).collect();
+    Dataset::new(data)
+  }
+
+  /// Historical value at risk of a series of % returns (e.g. `Summary::pct_per_trade` or [`Dataset::pct_returns`]).
+  /// The loss, as a positive %, that returns fell below only `1 - confidence` of the time (e.g. 0.95 for 95%).
+  pub fn value_at_risk(&self, confidence: f64) -> f64 {
+    let tail = self.tail(confidence);
+    match tail.last() {
+      Some(worst) => -worst,
+      None => 0.0
+    }
+  }
+
+  /// Historical conditional value at risk (expected shortfall), the mean loss as a positive %
+  /// of the returns at or beyond [`Dataset::value_at_risk`] at the same `confidence`.
+  pub fn conditional_var(&self, confidence: f64) -> f64 {
+    let tail = self.tail(confidence);
+    match tail.is_empty() {
+      true => 0.0,
+      false => -tail.iter().sum::<f64>() / tail.len() as f64
+    }
+  }
+
+  /// Worst `1 - confidence` of the returns in ascending order, always including the worst return
+  fn tail(&self, confidence: f64) -> Vec<f64> {
+    let mut returns = self.y();
+    returns.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    // epsilon so float error in 1 - confidence doesn't drop a return, e.g. (1 - 0.9) * 20 = 1.999..
+    let count = ((1.0 - confidence) * returns.len() as f64 + 1e-9).floor() as usize;
+    returns.truncate(count.max(1));
+    returns
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trunc;
+
+  fn dataset(y: &[f64]) -> Dataset<i64, f64> {
+    Dataset::new(y.iter().enumerate().map(|(i, y)| Data { x: i as i64, y: *y }).collect())
+  }
+
+  #[test]
+  fn value_at_risk() {
+    // 20 returns, the worst 5% is one return and the worst 10% is two
+    let mut returns = vec![1.0; 18];
+    returns.extend([-4.0, -8.0]);
+    let returns = dataset(&returns);
+    assert_eq!(returns.value_at_risk(0.95), 8.0);
+    assert_eq!(returns.conditional_var(0.95), 8.0);
+    assert_eq!(returns.value_at_risk(0.90), 4.0);
+    assert_eq!(returns.conditional_var(0.90), 6.0);
+    assert_eq!(dataset(&[]).value_at_risk(0.95), 0.0);
+  }
+
+  #[test]
+  fn rolling_zscore() -> anyhow::Result<()> {
+    let series = dataset(&[1.0, 2.0, 3.0, 3.0, 3.0, 10.0]);
+    let z = series.rolling_zscore(3);
+    assert_eq!(z.x(), vec![2, 3, 4, 5]);
+    for (i, point) in z.data().iter().enumerate() {
+      match i {
+        // flat window
+        2 => assert_eq!(point.y, 0.0),
+        _ => assert_eq!(point.y, crate::zscore_last(&series.y()[..i + 3], 3)?)
+      }
+    }
+    assert!(series.rolling_zscore(7).is_empty());
+    Ok(())
+  }
+
+  #[test]
+  fn resample_time() {
+    let day = 86_400_000;
+    let start = 1_700_006_400_000;
+    // out of order, with a two day gap
+    let equity = Dataset::new(vec![
+      Data { x: start + day * 3 + 1_000, y: 40.0 },
+      Data { x: start - 1_000, y: 0.0 },
+      Data { x: start + day + 1_000, y: 10.0 },
+    ]);
+    let forward = equity.resample_time(day, Fill::Forward);
+    assert_eq!(forward.x(), vec![start, start + day, start + day * 2, start + day * 3]);
+    assert_eq!(forward.y(), vec![0.0, 0.0, 10.0, 10.0]);
+
+    let linear = equity.resample_time(day, Fill::Linear);
+    assert_eq!(linear.x(), forward.x());
+    let y: Vec<f64> = linear.y().iter().map(|y| trunc!(*y, 2)).collect();
+    assert_eq!(y, vec![0.0, 10.0, 25.0, 40.0]);
+
+    assert!(Dataset::new(vec![]).resample_time(day, Fill::Forward).is_empty());
+  }
+
+  #[test]
+  fn write_csv() -> anyhow::Result<()> {
+    let path = std::env::temp_dir().join("dataset_write_csv.csv");
+    dataset(&[1.5, -2.0]).write_csv(&path, "index", "value")?;
+    assert_eq!(std::fs::read_to_string(&path)?, "index,value\n0,1.5\n1,-2\n");
+
+    let unix_ms = 1_700_000_000_000;
+    Dataset::new(vec![Data { x: unix_ms, y: 10.0 }]).write_csv(&path, "unix_ms", "roi")?;
+    let written = std::fs::read_to_string(&path)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(written, format!("unix_ms,roi,date\n{},10,{}\n", unix_ms, Time::from_unix_ms(unix_ms).to_string()));
+    Ok(())
+  }
+
+  #[test]
+  fn pct_returns() {
+    // +10% then back to 0% is a -9.09% return
+    let returns = dataset(&[0.0, 10.0, 0.0]).pct_returns();
+    assert_eq!(returns.x(), vec![1, 2]);
+    assert_eq!(trunc!(returns.0[0].y, 2), 10.0);
+    assert_eq!(trunc!(returns.0[1].y, 2), -9.09);
+  }
+}
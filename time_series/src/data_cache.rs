@@ -1,7 +1,12 @@
 use std::collections::VecDeque;
+use serde::{Serialize, Deserialize};
+use crate::{mean, sample_std_dev, zscore_last, Y};
 
-#[derive(Debug, Clone)]
+/// Serializes only the capacity and id, so a strategy config round-trips without its cached data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct DataCache<T> {
+  #[serde(skip)]
   pub vec: VecDeque<T>,
   pub capacity: usize,
   pub id: String
@@ -39,4 +44,77 @@ impl<T: Clone> DataCache<T> {
   pub fn is_empty(&self) -> bool {
     self.vec.is_empty()
   }
-}
\ No newline at end of file
+}
+
+/// Statistics over every value in the cache, projected to f64 via [`Y`]
+/// (e.g. `DataCache<f64>` or the `y` of `DataCache<Data<i64, f64>>`).
+impl<T: Clone + Y> DataCache<T> {
+  /// Values ordered oldest to newest
+  pub fn values(&self) -> Vec<f64> {
+    self.vec.iter().rev().map(|t| t.y()).collect()
+  }
+
+  pub fn mean(&self) -> Option<f64> {
+    match self.vec.is_empty() {
+      true => None,
+      false => Some(mean(&self.values()))
+    }
+  }
+
+  /// Sample standard deviation, None if fewer than 2 values
+  pub fn stddev(&self) -> Option<f64> {
+    match self.vec.len() < 2 {
+      true => None,
+      false => Some(sample_std_dev(&self.values()))
+    }
+  }
+
+  pub fn min(&self) -> Option<f64> {
+    self.vec.iter().map(|t| t.y()).reduce(f64::min)
+  }
+
+  pub fn max(&self) -> Option<f64> {
+    self.vec.iter().map(|t| t.y()).reduce(f64::max)
+  }
+
+  /// ZScore of the most recent value over the last `window` values
+  pub fn zscore_last(&self, window: usize) -> anyhow::Result<f64> {
+    zscore_last(&self.values(), window)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Data;
+
+  #[test]
+  fn data_cache_stats() -> anyhow::Result<()> {
+    let mut cache = DataCache::<f64>::new(4, "test".to_string());
+    assert_eq!(cache.mean(), None);
+    assert_eq!(cache.min(), None);
+    cache.push(2.0);
+    assert_eq!(cache.stddev(), None);
+    // 2 is pushed out of the window
+    for y in [4.0, 4.0, 5.0, 7.0] {
+      cache.push(y);
+    }
+    assert_eq!(cache.values(), vec![4.0, 4.0, 5.0, 7.0]);
+    assert_eq!(cache.mean(), Some(5.0));
+    // squared deviations 1, 1, 0, 4 over n - 1
+    assert_eq!(cache.stddev(), Some(2.0_f64.sqrt()));
+    assert_eq!(cache.min(), Some(4.0));
+    assert_eq!(cache.max(), Some(7.0));
+    // (7 - 5) / sqrt(2) over all 4, (7 - 6) / sqrt(2) over the last 2
+    assert_eq!(cache.zscore_last(4)?, 2.0 / 2.0_f64.sqrt());
+    assert_eq!(cache.zscore_last(2)?, 1.0 / 2.0_f64.sqrt());
+    assert!(cache.zscore_last(5).is_err());
+
+    // the y of each point
+    let mut points = DataCache::<Data<i64, f64>>::new(2, "test".to_string());
+    points.push(Data { x: 0, y: 1.0 });
+    points.push(Data { x: 1, y: 3.0 });
+    assert_eq!(points.mean(), Some(2.0));
+    Ok(())
+  }
+}
@@ -1,14 +1,19 @@
 #![allow(dead_code)]
 #![allow(clippy::unnecessary_cast)]
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use crate::{Candle, Data, Dataset, Time, X, Y};
 use std::fs::File;
+use std::io::Read;
 use std::path::PathBuf;
 use std::str::FromStr;
 
 pub struct CsvSeries {
   pub candles: Vec<Candle>,
+  /// TradingView kagi line per candle from a `Kagi` column, empty if the CSV has none
+  pub kagis: Vec<Data<i64, f64>>,
+  /// TradingView WMA per candle from an `MA` column, empty if the CSV has none
+  pub wmas: Vec<Data<i64, f64>>,
 }
 
 pub struct Dataframe;
@@ -18,9 +23,16 @@ impl Dataframe {
   /// Handles duplicate candles and sorts candles by date.
   /// Expects date of candle to be in UNIX timestamp format.
   /// CSV format: date,open,high,low,close,volume
-  pub fn csv_series(csv_path: &PathBuf, start_time: Option<Time>, end_time: Option<Time>, _ticker: String) -> anyhow::Result<CsvSeries> {
-    let file_buffer = File::open(csv_path)?;
-    let mut csv = csv::Reader::from_reader(file_buffer);
+  /// If `strict`, a candle with inconsistent OHLC (see [`Candle::validate`]) is an error.
+  /// A `.gz` file is decompressed as it's read.
+  pub fn csv_series(csv_path: &PathBuf, start_time: Option<Time>, end_time: Option<Time>, _ticker: String, strict: bool) -> anyhow::Result<CsvSeries> {
+    let file = File::open(csv_path)?;
+    let file_buffer: Box<dyn Read> = match csv_path.extension().is_some_and(|ext| ext == "gz") {
+      true => Box::new(flate2::read::GzDecoder::new(file)),
+      false => Box::new(file)
+    };
+    // rows may have extra columns (e.g. TradingView exports) beyond OHLC
+    let mut csv = csv::ReaderBuilder::new().flexible(true).from_reader(file_buffer);
 
     let mut headers = vec![];
     if let Ok(result) = csv.headers() {
@@ -29,25 +41,60 @@ impl Dataframe {
       }
     }
 
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let kagi_column = column("Kagi");
+    let wma_column = column("MA");
+
     let mut candles = vec![];
+    // later rows overwrite duplicate timestamps, same as the candles
+    let mut kagis = HashMap::new();
+    let mut wmas = HashMap::new();
 
-    for record in csv.records().flatten() {
-      let date = Time::from_unix(
-        record[0]
-          .parse::<i64>()
-          .expect("failed to parse candle UNIX timestamp into i64"),
-      );
-      let volume = None;
+    for (i, record) in csv.records().enumerate() {
+      // header is line 1
+      let line = i + 2;
+      let record = record.map_err(|e| anyhow::anyhow!("{:?} line {}: {}", csv_path, line, e))?;
+      let field = |index: usize, name: &str| -> anyhow::Result<&str> {
+        record.get(index).ok_or(anyhow::anyhow!("{:?} line {}: missing {} column", csv_path, line, name))
+      };
+      let price = |index: usize, name: &str| -> anyhow::Result<f64> {
+        let value = field(index, name)?;
+        f64::from_str(value).map_err(|e| anyhow::anyhow!("{:?} line {}: invalid {} {:?}: {}", csv_path, line, name, value, e))
+      };
+      let unix = field(0, "date")?;
+      let unix = unix
+        .parse::<i64>()
+        .map_err(|e| anyhow::anyhow!("{:?} line {}: invalid UNIX timestamp {:?}: {}", csv_path, line, unix, e))?;
       let candle = Candle {
-        date,
-        open: f64::from_str(&record[1])?,
-        high: f64::from_str(&record[2])?,
-        low: f64::from_str(&record[3])?,
-        close: f64::from_str(&record[4])?,
-        volume,
+        date: Time::from_unix(unix),
+        open: price(1, "open")?,
+        high: price(2, "high")?,
+        low: price(3, "low")?,
+        close: price(4, "close")?,
+        volume: None,
       };
+      if let Some(index) = kagi_column {
+        kagis.insert(candle.date.to_unix_ms(), price(index, "Kagi")?);
+      }
+      if let Some(index) = wma_column {
+        wmas.insert(candle.date.to_unix_ms(), price(index, "MA")?);
+      }
+      if strict {
+        candle.validate().map_err(|e| anyhow::anyhow!("{:?} line {}: invalid candle: {}", csv_path, line, e))?;
+      }
       candles.push(candle);
     }
+    // stable sort so duplicates keep their order in the file
+    candles.sort_by_key(|candle| candle.date.to_unix_ms());
+    // keep the last occurrence of each timestamp
+    let mut deduped: Vec<Candle> = Vec::with_capacity(candles.len());
+    for candle in candles {
+      match deduped.last_mut() {
+        Some(last) if last.date.to_unix_ms() == candle.date.to_unix_ms() => *last = candle,
+        _ => deduped.push(candle)
+      }
+    }
+    let mut candles = deduped;
     // only take candles greater than a timestamp
     candles.retain(|candle| {
       match (start_time, end_time) {
@@ -64,11 +111,27 @@ impl Dataframe {
       }
     });
 
+    let reference = |values: &HashMap<i64, f64>| -> Vec<Data<i64, f64>> {
+      candles.iter().filter_map(|c| values.get(&c.date.to_unix_ms()).map(|y| Data {
+        x: c.date.to_unix_ms(),
+        y: *y
+      })).collect()
+    };
+    let kagis = reference(&kagis);
+    let wmas = reference(&wmas);
+
     Ok(CsvSeries {
       candles,
+      kagis,
+      wmas,
     })
   }
 
+  /// Indices of candles that fail [`Candle::validate`]
+  pub fn validate_series(candles: &[Candle]) -> Vec<usize> {
+    candles.iter().enumerate().filter(|(_, c)| c.validate().is_err()).map(|(i, _)| i).collect()
+  }
+
   pub fn align_pair_series(x: &mut Vec<Candle>, y: &mut Vec<Candle>) -> anyhow::Result<()> {
     // retain the overlapping dates between the two time series
     // Step 1: Create sets of timestamps from both vectors
@@ -86,6 +149,27 @@ impl Dataframe {
     Ok(())
   }
 
+  /// Retain the timestamps common to every series and sort each oldest first,
+  /// so all series are the same length and the ith candle of each is at the same time.
+  pub fn align_series(series: &mut HashMap<String, Vec<Candle>>) -> anyhow::Result<()> {
+    let mut common: Option<HashSet<i64>> = None;
+    for candles in series.values() {
+      let dates: HashSet<i64> = candles.iter().map(|c| c.date.to_unix_ms()).collect();
+      common = Some(match common {
+        Some(common) => common.intersection(&dates).copied().collect(),
+        None => dates
+      });
+    }
+    let common = common.unwrap_or_default();
+    for candles in series.values_mut() {
+      candles.retain(|c| common.contains(&c.date.to_unix_ms()));
+      candles.sort_by_key(|c| c.date.to_unix_ms());
+      // a duplicate timestamp within one series would misalign the rest
+      candles.dedup_by_key(|c| c.date.to_unix_ms());
+    }
+    Ok(())
+  }
+
   /// Redefine each price point as a percentage change relative to the starting price.
   pub fn normalize_series<T: X + Y>(series: &[T]) -> anyhow::Result<Dataset<i64, f64>> {
     let mut series = series.to_vec();
@@ -107,4 +191,113 @@ impl Dataframe {
     }).collect();
     Ok(Dataset::new(spread))
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn csv_series_dedup() -> anyhow::Result<()> {
+    let csv_path = std::env::temp_dir().join("csv_series_dedup.csv");
+    std::fs::write(&csv_path, "\
+date,open,high,low,close,volume
+1700001780,2.0,2.0,2.0,2.0,0
+1699999980,1.0,1.0,1.0,1.0,0
+1700001780,3.0,3.0,3.0,3.0,0
+1700003580,4.0,4.0,4.0,4.0,0
+1699999980,5.0,5.0,5.0,5.0,0
+")?;
+    let candles = Dataframe::csv_series(&csv_path, None, None, "TEST".to_string(), false)?.candles;
+    std::fs::remove_file(&csv_path)?;
+
+    let dates: Vec<i64> = candles.iter().map(|c| c.date.to_unix_ms()).collect();
+    assert_eq!(dates, vec![1699999980000, 1700001780000, 1700003580000]);
+    // last occurrence of each duplicate timestamp wins
+    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+    assert_eq!(closes, vec![5.0, 3.0, 4.0]);
+    Ok(())
+  }
+
+  #[test]
+  fn csv_series_gzip() -> anyhow::Result<()> {
+    use std::io::Write;
+    let csv_path = std::env::temp_dir().join("csv_series_gzip.csv.gz");
+    let mut encoder = flate2::write::GzEncoder::new(File::create(&csv_path)?, flate2::Compression::default());
+    encoder.write_all(b"\
+date,open,high,low,close,volume
+1699999980,1.0,1.0,1.0,1.0,0
+1700001780,2.0,2.0,2.0,2.0,0
+")?;
+    encoder.finish()?;
+    let candles = Dataframe::csv_series(&csv_path, None, None, "TEST".to_string(), false)?.candles;
+    std::fs::remove_file(&csv_path)?;
+
+    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+    assert_eq!(closes, vec![1.0, 2.0]);
+    Ok(())
+  }
+
+  #[test]
+  fn csv_series_invalid_row() -> anyhow::Result<()> {
+    let csv_path = std::env::temp_dir().join("csv_series_invalid_row.csv");
+    std::fs::write(&csv_path, "\
+date,open,high,low,close
+1699999980,1.0,1.0,1.0,1.0
+1700001780,2.0,2.0
+")?;
+    let res = Dataframe::csv_series(&csv_path, None, None, "TEST".to_string(), false);
+    std::fs::remove_file(&csv_path)?;
+
+    let err = res.err().ok_or(anyhow::anyhow!("expected missing column error"))?;
+    assert!(err.to_string().contains("line 3: missing low column"));
+    Ok(())
+  }
+  #[test]
+  fn csv_series_strict() -> anyhow::Result<()> {
+    let csv_path = std::env::temp_dir().join("csv_series_strict.csv");
+    std::fs::write(&csv_path, "\
+date,open,high,low,close
+1699999980,1.0,2.0,0.5,1.5
+1700001780,2.0,1.0,3.0,2.0
+1700003580,2.0,2.5,1.5,3.0
+")?;
+    let lenient = Dataframe::csv_series(&csv_path, None, None, "TEST".to_string(), false);
+    let strict = Dataframe::csv_series(&csv_path, None, None, "TEST".to_string(), true);
+    std::fs::remove_file(&csv_path)?;
+
+    let candles = lenient?.candles;
+    assert_eq!(Dataframe::validate_series(&candles), vec![1, 2]);
+    let err = strict.err().ok_or(anyhow::anyhow!("expected invalid candle error"))?;
+    assert!(err.to_string().contains("line 3: invalid candle: high 1 below low 3"));
+    Ok(())
+  }
+
+  #[test]
+  fn align_series() -> anyhow::Result<()> {
+    let candle = |minute: i64, close: f64| Candle {
+      date: Time::from_unix_ms(1_700_000_000_000 + minute * 60_000),
+      open: close,
+      high: close,
+      low: close,
+      close,
+      volume: None
+    };
+    let mut series = HashMap::from([
+      ("A".to_string(), vec![candle(0, 1.0), candle(1, 1.0), candle(2, 1.0), candle(3, 1.0), candle(4, 1.0)]),
+      // out of order and starts late
+      ("B".to_string(), vec![candle(3, 2.0), candle(1, 2.0), candle(4, 2.0), candle(2, 2.0)]),
+      // missing a candle in the middle and ends early
+      ("C".to_string(), vec![candle(0, 3.0), candle(1, 3.0), candle(3, 3.0)]),
+    ]);
+    Dataframe::align_series(&mut series)?;
+
+    let expected = vec![candle(1, 0.0).date.to_unix_ms(), candle(3, 0.0).date.to_unix_ms()];
+    for (ticker, candles) in series.iter() {
+      let dates: Vec<i64> = candles.iter().map(|c| c.date.to_unix_ms()).collect();
+      assert_eq!(dates, expected, "{} not aligned", ticker);
+    }
+    assert_eq!(series["B"][0].close, 2.0);
+    Ok(())
+  }
+}
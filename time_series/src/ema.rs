@@ -0,0 +1,23 @@
+/// Exponential moving average of `series` ordered oldest to newest.
+/// Seeded with the first value, so the series should be several `period`s long to warm up.
+pub fn ema(series: &[f64], period: usize) -> Option<f64> {
+  let alpha = 2.0 / (period as f64 + 1.0);
+  let mut iter = series.iter();
+  let first = *iter.next()?;
+  Some(iter.fold(first, |ema, x| alpha * x + (1.0 - alpha) * ema))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn ema_known_values() {
+    // period 3 weighs each new value by 0.5: 2 -> 3 -> 4.5 -> 6.25
+    assert_eq!(ema(&[2.0, 4.0, 6.0, 8.0], 3), Some(6.25));
+    // period 1 is the latest value
+    assert_eq!(ema(&[2.0, 4.0, 6.0, 8.0], 1), Some(8.0));
+    assert_eq!(ema(&[5.0], 10), Some(5.0));
+    assert_eq!(ema(&[], 3), None);
+  }
+}
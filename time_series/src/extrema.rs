@@ -0,0 +1,58 @@
+use std::collections::VecDeque;
+
+/// Max of each `window` of `series` ordered oldest to newest, one per full window.
+/// The jth value covers `series[j..j + window]`, empty if the series is shorter than the window.
+pub fn rolling_max(series: &[f64], window: usize) -> Vec<f64> {
+  rolling_extrema(series, window, |a, b| a >= b)
+}
+
+/// Min of each `window` of `series` ordered oldest to newest, see [`rolling_max`]
+pub fn rolling_min(series: &[f64], window: usize) -> Vec<f64> {
+  rolling_extrema(series, window, |a, b| a <= b)
+}
+
+/// Monotonic deque of indices whose values are ordered by `keeps`, so the front is the window extreme.
+/// Each index is pushed and popped once, O(n) regardless of the window.
+fn rolling_extrema<F: Fn(f64, f64) -> bool>(series: &[f64], window: usize, keeps: F) -> Vec<f64> {
+  if window == 0 || series.len() < window {
+    return vec![];
+  }
+  let mut deque: VecDeque<usize> = VecDeque::with_capacity(window);
+  let mut extrema = Vec::with_capacity(series.len() - window + 1);
+  for (i, value) in series.iter().enumerate() {
+    // drop values the new one beats, they can never be the extreme again
+    while let Some(&back) = deque.back() {
+      match keeps(series[back], *value) {
+        true => break,
+        false => deque.pop_back()
+      };
+    }
+    deque.push_back(i);
+    if deque.front().is_some_and(|&front| front + window <= i) {
+      deque.pop_front();
+    }
+    if i + 1 >= window {
+      extrema.push(series[deque[0]]);
+    }
+  }
+  extrema
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rolling_extrema_match_window_scan() {
+    let series = [3.0, 1.0, 4.0, 1.0, 5.0, 9.0, 2.0, 6.0, 5.0, 3.0, 5.0, 5.0];
+    for window in 1..=series.len() {
+      let max: Vec<f64> = series.windows(window).map(|w| w.iter().cloned().fold(f64::MIN, f64::max)).collect();
+      let min: Vec<f64> = series.windows(window).map(|w| w.iter().cloned().fold(f64::MAX, f64::min)).collect();
+      assert_eq!(rolling_max(&series, window), max);
+      assert_eq!(rolling_min(&series, window), min);
+    }
+    assert_eq!(rolling_max(&series[..3], 3), vec![4.0]);
+    assert!(rolling_max(&series, 0).is_empty());
+    assert!(rolling_min(&series, series.len() + 1).is_empty());
+  }
+}
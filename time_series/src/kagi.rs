@@ -1,3 +1,4 @@
+use serde::{Serialize, Deserialize};
 use crate::Candle;
 
 #[derive(Debug, Clone, Copy)]
@@ -6,6 +7,40 @@ pub enum KagiDirection {
   Down,
 }
 
+/// How far price must move against the kagi line to reverse it
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum KagiReversal {
+  /// Fixed price amount, tuned per asset
+  Absolute(f64),
+  /// Percent of the current kagi line, portable across price regimes
+  Percent(f64)
+}
+
+impl Default for KagiReversal {
+  fn default() -> Self {
+    Self::Absolute(0.0)
+  }
+}
+
+impl KagiReversal {
+  /// Reversal amount in price for the current kagi `line`
+  pub fn threshold(&self, line: f64) -> f64 {
+    match self {
+      KagiReversal::Absolute(amount) => *amount,
+      KagiReversal::Percent(pct) => line * pct / 100.0
+    }
+  }
+}
+
+impl std::fmt::Display for KagiReversal {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      KagiReversal::Absolute(amount) => write!(f, "{}", amount),
+      KagiReversal::Percent(pct) => write!(f, "{}%", pct)
+    }
+  }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Kagi {
   pub direction: KagiDirection,
@@ -21,8 +56,9 @@ impl Default for Kagi {
 }
 
 impl Kagi {
-  pub fn update(kagi: &Kagi, rev_amt: f64, candle: &Candle, _prev_candle: &Candle) -> Self {
+  pub fn update(kagi: &Kagi, reversal: KagiReversal, candle: &Candle, _prev_candle: &Candle) -> Self {
     let mut new_kagi = *kagi;
+    let rev_amt = reversal.threshold(kagi.line);
 
     match kagi.direction {
       KagiDirection::Up => {
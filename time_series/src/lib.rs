@@ -9,6 +9,11 @@ pub mod data;
 pub mod data_cache;
 pub mod hurst;
 pub mod dataframe;
+pub mod ema;
+pub mod atr;
+pub mod extrema;
+pub mod stats;
+pub mod synthetic;
 
 pub use candle::*;
 pub use time::*;
@@ -19,6 +24,11 @@ pub use data::*;
 pub use data_cache::*;
 pub use hurst::*;
 pub use dataframe::*;
+pub use ema::*;
+pub use atr::*;
+pub use extrema::*;
+pub use stats::*;
+pub use synthetic::*;
 
 use log::*;
 use simplelog::{
@@ -1,33 +1,59 @@
+use std::fmt::Display;
 use plotters::prelude::*;
 use plotters::style::full_palette::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use plotters::coord::Shift;
 use plotters::style::{BLACK, WHITE};
-use crate::Data;
+use crate::{Data, Dataset, Time};
 
 pub struct Plot;
 
-impl Plot {
-  pub fn plot(series: Vec<Vec<Data<i64, f64>>>, out_file: &str, title: &str, y_label: &str, x_label: &str) -> anyhow::Result<()> {
+/// Axis label formatting of [`Plot::plot_with_options`]
+#[derive(Debug, Clone, Copy)]
+pub struct PlotOptions {
+  /// X values are unix millis, labeled as dates
+  pub x_is_time: bool,
+  /// Decimal places of the y axis labels
+  pub y_decimals: usize
+}
 
-    let all: Vec<&Data<i64, f64>> = series.iter().flatten().collect();
+impl Default for PlotOptions {
+  fn default() -> Self {
+    Self {
+      x_is_time: false,
+      y_decimals: 2
+    }
+  }
+}
 
-    let mut min_x = i64::MAX;
-    let mut max_x = i64::MIN;
-    let mut min_y = f64::MAX;
-    let mut max_y = f64::MIN;
-    for datum in all.iter() {
-      if datum.x < min_x {
-        min_x = datum.x;
-      }
-      if datum.x > max_x {
-        max_x = datum.x;
-      }
-      if datum.y < min_y {
-        min_y = datum.y;
-      }
-      if datum.y > max_y {
-        max_y = datum.y;
-      }
+impl PlotOptions {
+  pub fn x_label(&self, x: i64) -> String {
+    match self.x_is_time {
+      true => Time::from_unix_ms(x).to_string(),
+      false => x.to_string()
     }
+  }
+
+  pub fn y_label(&self, y: f64) -> String {
+    format!("{:.*}", self.y_decimals, y)
+  }
+}
+
+impl Plot {
+  pub fn plot(series: Vec<Vec<Data<i64, f64>>>, out_file: &str, title: &str, y_label: &str, x_label: &str) -> anyhow::Result<()> {
+    Self::plot_with_options(series, out_file, title, y_label, x_label, PlotOptions::default())
+  }
+
+  /// Same as [`Plot::plot`] with the axis labels formatted by `options`
+  pub fn plot_with_options(
+    series: Vec<Vec<Data<i64, f64>>>,
+    out_file: &str,
+    title: &str,
+    y_label: &str,
+    x_label: &str,
+    options: PlotOptions
+  ) -> anyhow::Result<()> {
+    let (x_range, y_range) = Self::ranges(&series);
 
     let root = BitMapBackend::new(out_file, (2048, 1024)).into_drawing_area();
     root.fill(&WHITE).map_err(
@@ -43,13 +69,17 @@ impl Plot {
         title,
         ("sans-serif", 40.0).into_font(),
       )
-      .build_cartesian_2d(min_x..max_x, min_y..max_y).map_err(
+      .build_cartesian_2d(x_range, y_range).map_err(
       |e| anyhow::anyhow!("Failed to build cartesian 2d: {}", e)
     )?;
+    let x_label_formatter = |x: &i64| options.x_label(*x);
+    let y_label_formatter = |y: &f64| options.y_label(*y);
     chart
       .configure_mesh()
       .light_line_style(WHITE)
       .label_style(("sans-serif", 30, &BLACK).into_text_style(&root))
+      .x_label_formatter(&x_label_formatter)
+      .y_label_formatter(&y_label_formatter)
       .x_desc(x_label)
       .y_desc(y_label)
       .draw().map_err(
@@ -91,6 +121,233 @@ impl Plot {
     Ok(())
   }
 
+  /// X and y axis ranges spanning every point of `series`
+  fn ranges(series: &[Vec<Data<i64, f64>>]) -> (std::ops::Range<i64>, std::ops::Range<f64>) {
+    let mut min_x = i64::MAX;
+    let mut max_x = i64::MIN;
+    let mut min_y = f64::MAX;
+    let mut max_y = f64::MIN;
+    for datum in series.iter().flatten() {
+      if datum.x < min_x {
+        min_x = datum.x;
+      }
+      if datum.x > max_x {
+        max_x = datum.x;
+      }
+      if datum.y < min_y {
+        min_y = datum.y;
+      }
+      if datum.y > max_y {
+        max_y = datum.y;
+      }
+    }
+    (min_x..max_x, min_y..max_y)
+  }
+
+  /// 2D grid of `(x, y, value)` cells, e.g. % ROI of each pair of params from a grid search.
+  /// Cells are colored red at the lowest value to green at the highest, with the value printed in the cell.
+  /// Axes are the distinct x and y values in ascending order, NaN values are drawn grey.
+  pub fn heatmap<X: Display + PartialOrd, Y: Display + PartialOrd>(data: &[(X, Y, f64)], out_file: &str, title: &str) -> anyhow::Result<()> {
+    let (xs, ys) = Self::heatmap_axes(data);
+    let values = data.iter().map(|d| d.2).filter(|v| !v.is_nan());
+    let min = values.clone().fold(f64::INFINITY, f64::min);
+    let max = values.fold(f64::NEG_INFINITY, f64::max);
+
+    let root = BitMapBackend::new(out_file, (2048, 1024)).into_drawing_area();
+    root.fill(&WHITE).map_err(
+      |e| anyhow::anyhow!("Failed to fill drawing area with white: {}", e)
+    )?;
+    let mut chart = ChartBuilder::on(&root)
+      .margin_top(20)
+      .margin_bottom(20)
+      .margin_left(30)
+      .margin_right(30)
+      .x_label_area_size(120)
+      .y_label_area_size(170)
+      .caption(
+        title,
+        ("sans-serif", 40.0).into_font(),
+      )
+      // a segmented range has a segment for the end value too
+      .build_cartesian_2d((0..xs.len().saturating_sub(1)).into_segmented(), (0..ys.len().saturating_sub(1)).into_segmented()).map_err(
+      |e| anyhow::anyhow!("Failed to build cartesian 2d: {}", e)
+    )?;
+    let label = |labels: &[String], v: &SegmentValue<usize>| match v {
+      SegmentValue::CenterOf(i) => labels.get(*i).cloned().unwrap_or_default(),
+      _ => String::new()
+    };
+    let x_labels: Vec<String> = xs.iter().map(|x| x.to_string()).collect();
+    let y_labels: Vec<String> = ys.iter().map(|y| y.to_string()).collect();
+    chart
+      .configure_mesh()
+      .disable_mesh()
+      .x_labels(xs.len())
+      .y_labels(ys.len())
+      .x_label_formatter(&|v| label(&x_labels, v))
+      .y_label_formatter(&|v| label(&y_labels, v))
+      .label_style(("sans-serif", 30, &BLACK).into_text_style(&root))
+      .draw().map_err(
+      |e| anyhow::anyhow!("Failed to draw mesh: {}", e)
+    )?;
+
+    let color = |value: f64| -> RGBAColor {
+      match value.is_nan() {
+        true => GREY_300.to_rgba(),
+        // hue 0.0 is red, 0.33 is green
+        false => match max > min {
+          true => HSLColor((value - min) / (max - min) / 3.0, 0.7, 0.5).to_rgba(),
+          false => HSLColor(1.0 / 3.0, 0.7, 0.5).to_rgba()
+        }
+      }
+    };
+    let cell = |x: &X, y: &Y| -> (usize, usize) {
+      (xs.iter().position(|v| *v == x).unwrap_or(0), ys.iter().position(|v| *v == y).unwrap_or(0))
+    };
+    chart.draw_series(data.iter().map(|(x, y, value)| {
+      let (i, j) = cell(x, y);
+      Rectangle::new(
+        [(SegmentValue::Exact(i), SegmentValue::Exact(j)), (SegmentValue::Exact(i + 1), SegmentValue::Exact(j + 1))],
+        color(*value).filled()
+      )
+    })).map_err(
+      |e| anyhow::anyhow!("Failed to draw cells: {}", e)
+    )?;
+    chart.draw_series(data.iter().filter(|d| !d.2.is_nan()).map(|(x, y, value)| {
+      let (i, j) = cell(x, y);
+      Text::new(
+        format!("{:.1}", value),
+        (SegmentValue::CenterOf(i), SegmentValue::CenterOf(j)),
+        ("sans-serif", 20).into_font().color(&BLACK).pos(Pos::new(HPos::Center, VPos::Center))
+      )
+    })).map_err(
+      |e| anyhow::anyhow!("Failed to draw values: {}", e)
+    )?;
+
+    root.present().map_err(
+      |e| anyhow::anyhow!("Failed to present root: {}", e)
+    )?;
+
+    Ok(())
+  }
+
+  /// Distinct x and y values of heatmap cells, each in ascending order
+  fn heatmap_axes<X: PartialOrd, Y: PartialOrd>(data: &[(X, Y, f64)]) -> (Vec<&X>, Vec<&Y>) {
+    let mut xs: Vec<&X> = vec![];
+    let mut ys: Vec<&Y> = vec![];
+    for (x, y, _) in data.iter() {
+      if !xs.contains(&x) {
+        xs.push(x);
+      }
+      if !ys.contains(&y) {
+        ys.push(y);
+      }
+    }
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    ys.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    (xs, ys)
+  }
+
+  /// Stacked pair trading panels sharing the x axis: normalized `x` and `y` prices on top,
+  /// their spread in the middle, and the spread z-score with bands at +/- `threshold` on the bottom.
+  pub fn pair_dashboard(
+    x: &Dataset<i64, f64>,
+    y: &Dataset<i64, f64>,
+    spread: &Dataset<i64, f64>,
+    zscore: &Dataset<i64, f64>,
+    threshold: f64,
+    out_file: &str
+  ) -> anyhow::Result<()> {
+    let all = [x, y, spread, zscore];
+    let min_x = all.iter().flat_map(|d| d.data().iter().map(|d| d.x)).min().unwrap_or(0);
+    let max_x = all.iter().flat_map(|d| d.data().iter().map(|d| d.x)).max().unwrap_or(0);
+
+    let root = BitMapBackend::new(out_file, (2048, 2048)).into_drawing_area();
+    root.fill(&WHITE).map_err(
+      |e| anyhow::anyhow!("Failed to fill drawing area with white: {}", e)
+    )?;
+    let panels = root.split_evenly((3, 1));
+    Self::panel(&panels[0], "Normalized Prices", "% Change from Origin", &[x, y], min_x..max_x, &[])?;
+    Self::panel(&panels[1], "Spread", "Spread", &[spread], min_x..max_x, &[])?;
+    Self::panel(&panels[2], "Spread Z Score", "Z Score", &[zscore], min_x..max_x, &[-threshold, 0.0, threshold])?;
+
+    root.present().map_err(
+      |e| anyhow::anyhow!("Failed to present root: {}", e)
+    )?;
+    Ok(())
+  }
+
+  /// Line chart of `series` on one panel of a figure, with horizontal lines at each of `bands`
+  fn panel(
+    area: &DrawingArea<BitMapBackend, Shift>,
+    caption: &str,
+    y_label: &str,
+    series: &[&Dataset<i64, f64>],
+    x_range: std::ops::Range<i64>,
+    bands: &[f64]
+  ) -> anyhow::Result<()> {
+    let mut chart = ChartBuilder::on(area)
+      .margin_top(20)
+      .margin_bottom(20)
+      .margin_left(30)
+      .margin_right(30)
+      .x_label_area_size(60)
+      .y_label_area_size(170)
+      .caption(
+        caption,
+        ("sans-serif", 40.0).into_font(),
+      )
+      .build_cartesian_2d(x_range.clone(), Self::panel_y_range(series, bands)).map_err(
+      |e| anyhow::anyhow!("Failed to build cartesian 2d: {}", e)
+    )?;
+    chart
+      .configure_mesh()
+      .light_line_style(WHITE)
+      .label_style(("sans-serif", 30, &BLACK).into_text_style(area))
+      .y_desc(y_label)
+      .draw().map_err(
+      |e| anyhow::anyhow!("Failed to draw mesh: {}", e)
+    )?;
+
+    let colors = [CYAN_800, RED_800];
+    for (index, data) in series.iter().enumerate() {
+      chart.draw_series(
+        LineSeries::new(
+          data.data().iter().filter(|data| data.y.is_finite()).map(|data| (data.x, data.y)),
+          ShapeStyle {
+            color: RGBAColor::from(colors[index % colors.len()]),
+            filled: true,
+            // wider strokes spike past the panel on sharp turns
+            stroke_width: 1,
+          },
+        )
+      ).map_err(
+        |e| anyhow::anyhow!("Failed to draw series: {}", e)
+      )?;
+    }
+    for band in bands {
+      chart.draw_series(LineSeries::new(
+        [(x_range.start, *band), (x_range.end, *band)],
+        ShapeStyle {
+          color: GREY_900.to_rgba(),
+          filled: true,
+          stroke_width: 1,
+        },
+      )).map_err(
+        |e| anyhow::anyhow!("Failed to draw band: {}", e)
+      )?;
+    }
+    Ok(())
+  }
+
+  /// Y axis range of a [`Plot::panel`] spanning its series and bands
+  fn panel_y_range(series: &[&Dataset<i64, f64>], bands: &[f64]) -> std::ops::Range<f64> {
+    // a rolling z-score is NaN or infinite over a flat window, which would break the axis range
+    let ys = series.iter().flat_map(|d| d.data().iter().map(|d| d.y)).chain(bands.iter().copied()).filter(|y| y.is_finite());
+    let min_y = ys.clone().fold(f64::MAX, f64::min);
+    let max_y = ys.fold(f64::MIN, f64::max);
+    min_y..max_y
+  }
+
   pub fn random_color() -> RGBAColor {
     let colors = [
       PINK_600,
@@ -110,4 +367,77 @@ impl Plot {
     // get random color
     RGBAColor::from(colors[rand::random::<usize>() % colors.len()])
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn series(points: &[(i64, f64)]) -> Vec<Data<i64, f64>> {
+    points.iter().map(|(x, y)| Data { x: *x, y: *y }).collect()
+  }
+
+  /// Path in the temp dir to render to, removed once the plot is checked
+  fn out_file(name: &str) -> String {
+    std::env::temp_dir().join(name).to_string_lossy().to_string()
+  }
+
+  fn assert_rendered(out_file: &str) -> anyhow::Result<()> {
+    assert!(std::fs::metadata(out_file)?.len() > 0);
+    std::fs::remove_file(out_file)?;
+    Ok(())
+  }
+
+  #[test]
+  fn plot_options_labels() {
+    let default = PlotOptions::default();
+    assert_eq!(default.x_label(1_700_000_000_000), "1700000000000");
+    assert_eq!(default.y_label(12.345), "12.35");
+
+    let options = PlotOptions { x_is_time: true, y_decimals: 0 };
+    assert_eq!(options.x_label(1_700_000_000_000), Time::from_unix_ms(1_700_000_000_000).to_string());
+    assert_eq!(options.y_label(12.5), "12");
+    assert_eq!(PlotOptions { x_is_time: false, y_decimals: 4 }.y_label(-0.5), "-0.5000");
+  }
+
+  #[test]
+  fn plot_spans_every_series() -> anyhow::Result<()> {
+    let all = vec![series(&[(1, 5.0), (3, -2.0)]), series(&[(0, 1.0), (4, 8.5)])];
+    let (x_range, y_range) = Plot::ranges(&all);
+    assert_eq!(x_range, 0..4);
+    assert_eq!(y_range, -2.0..8.5);
+
+    let out_file = out_file("plot_spans_every_series.png");
+    Plot::plot_with_options(all, &out_file, "Test", "% ROI", "Date", PlotOptions { x_is_time: true, y_decimals: 1 })?;
+    assert_rendered(&out_file)
+  }
+
+  #[test]
+  fn heatmap_axes_ascending() -> anyhow::Result<()> {
+    let grid = vec![(0.2, 3, 1.0), (0.1, 3, f64::NAN), (0.2, 2, -4.0), (0.1, 2, 2.5)];
+    let (xs, ys) = Plot::heatmap_axes(&grid);
+    assert_eq!(xs, vec![&0.1, &0.2]);
+    assert_eq!(ys, vec![&2, &3]);
+
+    let out_file = out_file("heatmap_axes_ascending.png");
+    Plot::heatmap(&grid, &out_file, "Test")?;
+    assert_rendered(&out_file)
+  }
+
+  #[test]
+  fn pair_dashboard_ignores_undefined_zscores() -> anyhow::Result<()> {
+    let dataset = |points: &[(i64, f64)]| Dataset::new(series(points));
+    let x = dataset(&[(0, 0.0), (1, 1.0), (2, 2.0)]);
+    let y = dataset(&[(0, 0.0), (1, -1.0), (2, 3.0)]);
+    let spread = dataset(&[(0, 0.0), (1, 2.0), (2, -1.0)]);
+    let zscore = dataset(&[(0, f64::NAN), (1, f64::INFINITY), (2, 1.5)]);
+
+    assert_eq!(Plot::panel_y_range(&[&x, &y], &[]), -1.0..3.0);
+    // the bands widen the range, the undefined z-scores don't
+    assert_eq!(Plot::panel_y_range(&[&zscore], &[-2.0, 0.0, 2.0]), -2.0..2.0);
+
+    let out_file = out_file("pair_dashboard_ignores_undefined_zscores.png");
+    Plot::pair_dashboard(&x, &y, &spread, &zscore, 2.0, &out_file)?;
+    assert_rendered(&out_file)
+  }
+}
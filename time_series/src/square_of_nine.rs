@@ -1,4 +1,5 @@
 use log::debug;
+use crate::{Candle, Data, Dataset};
 
 #[derive(Clone, Copy, Debug)]
 pub enum Harmonic {
@@ -481,4 +482,47 @@ impl SquareOfNine {
     }
     ring_size
   }
-}
\ No newline at end of file
+}
+/// Gann angle lines projected forward from `pivot` over the timeline of `candles`, one [`Dataset`] per slope to overlay with [`crate::Plot`].
+/// Each slope is price units per candle, e.g. `[u, 2.0 * u, 0.5 * u]` for the 1x1, 2x1 and 1x2 angles of a unit `u`.
+/// Rising angles start from the pivot low and falling angles from the pivot high.
+/// Lines start at the first candle at or after the pivot date.
+pub fn gann_fan(pivot: &Candle, slopes: &[f64], candles: &[Candle]) -> Vec<Dataset<i64, f64>> {
+  let pivot_ms = pivot.date.to_unix_ms();
+  let forward: Vec<&Candle> = candles.iter().filter(|c| c.date.to_unix_ms() >= pivot_ms).collect();
+  slopes.iter().map(|slope| {
+    let origin = match *slope >= 0.0 {
+      true => pivot.low,
+      false => pivot.high
+    };
+    Dataset::new(forward.iter().enumerate().map(|(bars, c)| Data {
+      x: c.date.to_unix_ms(),
+      y: origin + slope * bars as f64
+    }).collect())
+  }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Time;
+
+  #[test]
+  fn gann_fan() {
+    let candles: Vec<Candle> = (0..5).map(|i| Candle {
+      date: Time::from_unix_ms(1_700_000_000_000 + i * 1_800_000),
+      open: 100.0,
+      high: 105.0,
+      low: 95.0,
+      close: 100.0,
+      volume: None
+    }).collect();
+    let fan = super::gann_fan(&candles[2], &[1.0, 2.0, 0.5, -1.0], &candles);
+    assert_eq!(fan.len(), 4);
+    assert_eq!(fan[0].x(), candles[2..].iter().map(|c| c.date.to_unix_ms()).collect::<Vec<i64>>());
+    assert_eq!(fan[0].y(), vec![95.0, 96.0, 97.0]);
+    assert_eq!(fan[1].y(), vec![95.0, 97.0, 99.0]);
+    assert_eq!(fan[2].y(), vec![95.0, 95.5, 96.0]);
+    assert_eq!(fan[3].y(), vec![105.0, 104.0, 103.0]);
+  }
+}
@@ -0,0 +1,26 @@
+use crate::mean;
+
+/// Sample standard deviation (n - 1 denominator)
+pub fn sample_std_dev(x: &[f64]) -> f64 {
+  let mean_x = mean(x);
+  let var = x.iter().map(|a| (a - mean_x).powi(2)).sum::<f64>() / (x.len() - 1) as f64;
+  var.sqrt()
+}
+
+/// ZScore of last index in a time series, over the last `window` values
+pub fn zscore_last(series: &[f64], window: usize) -> anyhow::Result<f64> {
+  // Guard: Ensure correct window size
+  if window > series.len() {
+    return Err(anyhow::anyhow!("Window size is greater than vector length"));
+  }
+  if window < 2 {
+    return Err(anyhow::anyhow!("Window size must be at least 2"));
+  }
+
+  let window_data: &[f64] = &series[series.len() - window..];
+  let std_dev = sample_std_dev(window_data);
+  if std_dev == 0.0 {
+    return Err(anyhow::anyhow!("Standard deviation is zero"));
+  }
+  Ok((series[series.len() - 1] - mean(window_data)) / std_dev)
+}
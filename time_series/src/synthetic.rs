@@ -0,0 +1,113 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Normal};
+use crate::{Candle, Time};
+
+/// Geometric Brownian motion parameters, per candle
+#[derive(Debug, Clone, Copy)]
+pub struct GbmParams {
+  /// Price of the first open
+  pub start_price: f64,
+  /// Expected log return per candle
+  pub drift: f64,
+  /// Standard deviation of the log return per candle
+  pub volatility: f64,
+  /// Ticks simulated within each candle, high and low are the extremes of these
+  pub ticks_per_candle: usize,
+}
+
+impl Default for GbmParams {
+  fn default() -> Self {
+    Self {
+      start_price: 100.0,
+      drift: 0.0,
+      volatility: 0.01,
+      ticks_per_candle: 60,
+    }
+  }
+}
+
+/// Generate `count` candles every `interval_minutes` from `start` by geometric Brownian motion.
+///
+/// Each candle opens at the previous close and steps through `ticks_per_candle` ticks, so the close is
+/// the last tick and high/low are the extremes of the ticks. The same seed always generates the same candles.
+pub fn synthetic_candles(seed: u64, count: usize, start: Time, interval_minutes: u32, params: GbmParams) -> anyhow::Result<Vec<Candle>> {
+  if params.start_price <= 0.0 {
+    return Err(anyhow::anyhow!("Start price must be positive"));
+  }
+  if params.ticks_per_candle == 0 {
+    return Err(anyhow::anyhow!("Ticks per candle must be at least 1"));
+  }
+  let ticks = params.ticks_per_candle as f64;
+  // Ito correction so the expected log return per candle is `drift`
+  let tick_drift = params.drift / ticks - params.volatility.powi(2) / (2.0 * ticks);
+  let tick_returns = Normal::new(tick_drift, params.volatility / ticks.sqrt())?;
+  let mut rng = StdRng::seed_from_u64(seed);
+
+  let start = start.to_unix();
+  let mut price = params.start_price;
+  let mut candles = Vec::with_capacity(count);
+  for i in 0..count {
+    let open = price;
+    let mut high = open;
+    let mut low = open;
+    for _ in 0..params.ticks_per_candle {
+      price *= tick_returns.sample(&mut rng).exp();
+      high = high.max(price);
+      low = low.min(price);
+    }
+    candles.push(Candle {
+      date: Time::from_unix(start + i as i64 * interval_minutes as i64 * 60),
+      open,
+      high,
+      low,
+      close: price,
+      volume: None,
+    });
+  }
+  Ok(candles)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Day, Month};
+
+  fn start() -> Time {
+    Time::new(2023, &Month::January, &Day::One, Some(0), Some(0), Some(0))
+  }
+
+  #[test]
+  fn synthetic_candles_deterministic() -> anyhow::Result<()> {
+    let a = synthetic_candles(7, 500, start(), 30, GbmParams::default())?;
+    let b = synthetic_candles(7, 500, start(), 30, GbmParams::default())?;
+    let c = synthetic_candles(8, 500, start(), 30, GbmParams::default())?;
+    assert_eq!(a.len(), 500);
+    assert!(a.iter().zip(b.iter()).all(|(a, b)| a.open == b.open && a.high == b.high && a.low == b.low && a.close == b.close));
+    assert!(a.iter().zip(c.iter()).any(|(a, c)| a.close != c.close));
+
+    assert_eq!(a[0].open, 100.0);
+    assert_eq!(a[0].date.to_unix(), start().to_unix());
+    for w in a.windows(2) {
+      assert_eq!(w[1].date.to_unix() - w[0].date.to_unix(), 30 * 60);
+      assert_eq!(w[1].open, w[0].close);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn synthetic_candles_extreme_volatility() -> anyhow::Result<()> {
+    let params = GbmParams {
+      volatility: 0.5,
+      ..Default::default()
+    };
+    let candles = synthetic_candles(1, 1000, start(), 1, params)?;
+    for candle in &candles {
+      candle.validate()?;
+      assert!(candle.low > 0.0);
+    }
+    // intrabar ticks put the extremes outside the open and close
+    assert!(candles.iter().any(|c| c.high > c.open.max(c.close) && c.low < c.open.min(c.close)));
+    Ok(())
+  }
+}
@@ -1,7 +1,7 @@
 #![allow(clippy::unnecessary_cast)]
 
 use std::collections::HashMap;
-use crate::{Dataset, Time, trunc};
+use crate::{Candle, Data, Dataset, Time, trunc};
 use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -11,7 +11,115 @@ pub enum Bet {
   Percent(f64)
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Calendar bucket for [`Summary::periodic_returns`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CalendarPeriod {
+  Month,
+  Quarter,
+  Year
+}
+
+impl CalendarPeriod {
+  /// Label of the bucket `time` falls in, e.g. "2024-03", "2024-Q1" or "2024"
+  pub fn label(&self, time: &Time) -> String {
+    let month = time.month.to_num();
+    match self {
+      CalendarPeriod::Month => format!("{}-{:02}", time.year, month),
+      CalendarPeriod::Quarter => format!("{}-Q{}", time.year, (month - 1) / 3 + 1),
+      CalendarPeriod::Year => time.year.to_string()
+    }
+  }
+}
+
+/// How backtest capital is split between the quote and base asset on the first candle
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Allocation {
+  /// All capital starts in the quote asset and is traded
+  #[default]
+  AllQuote,
+  /// This % of capital is bought as base on the first candle and held, the rest is traded.
+  /// 50.0 matches how the live engine equalizes the account on ignition.
+  Split(f64)
+}
+
+impl Allocation {
+  /// Share of `capital` that starts in the quote asset, an error if a split isn't between 0% and 100% base
+  pub fn quote_capital(&self, capital: f64) -> anyhow::Result<f64> {
+    match self {
+      Allocation::AllQuote => Ok(capital),
+      Allocation::Split(base_pct) if (0.0..=100.0).contains(base_pct) => Ok(capital * (1.0 - base_pct / 100.0)),
+      Allocation::Split(base_pct) => Err(anyhow::anyhow!("Allocation split of {}% base must be within 0 and 100", base_pct))
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ProfitPolicy {
+  /// All profits stay in the account and compound
+  #[default]
+  Reinvest,
+  /// Equity above this quote amount is withdrawn after each trade
+  WithdrawAbove(f64)
+}
+
+impl ProfitPolicy {
+  /// Skim any excess above the threshold out of `capital` and return the amount withdrawn
+  pub fn withdraw(&self, capital: &mut f64) -> f64 {
+    match self {
+      ProfitPolicy::WithdrawAbove(threshold) if *capital > *threshold => {
+        let excess = *capital - threshold;
+        *capital = *threshold;
+        excess
+      },
+      _ => 0.0
+    }
+  }
+}
+
+/// When a backtest fills a signal
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExecutionTiming {
+  /// Fill at the signal price, the close of the candle that produced the signal.
+  /// A strategy computed on the close can't act until the close is known, so this assumes a fill
+  /// the live engine can't get, and flatters strategies that buy strength or sell weakness.
+  #[default]
+  SameBarClose,
+  /// Fill at the open of the candle after the signal, as the live engine would.
+  /// Gaps between the close and the next open are paid, and the stop loss applies from that open.
+  /// Signals of the last candle never fill, except the exits of a backtest that closes at the end.
+  NextBarOpen
+}
+
+/// Order a live engine places for a signal, see [`SignalInfo::order_type`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignalOrderType {
+  Market,
+  Limit
+}
+
+/// How long a limit order rests before it expires
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeInForce {
+  /// Good til canceled
+  #[default]
+  Gtc,
+  /// Immediate or cancel, whatever doesn't fill at once is expired
+  Ioc,
+  /// Fill or kill, expired unless the whole order fills at once
+  Fok
+}
+
+impl TimeInForce {
+  pub fn fmt_binance(&self) -> &str {
+    match self {
+      TimeInForce::Gtc => "GTC",
+      TimeInForce::Ioc => "IOC",
+      TimeInForce::Fok => "FOK"
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Source {
   Open,
   High,
@@ -19,11 +127,69 @@ pub enum Source {
   Close
 }
 
+impl Source {
+  /// Price of `candle` this source reads
+  pub fn value(&self, candle: &Candle) -> f64 {
+    match self {
+      Source::Open => candle.open,
+      Source::High => candle.high,
+      Source::Low => candle.low,
+      Source::Close => candle.close
+    }
+  }
+}
+
+impl std::str::FromStr for Source {
+  type Err = anyhow::Error;
+
+  /// Parse a source name such as "close", case insensitive
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "open" => Ok(Source::Open),
+      "high" => Ok(Source::High),
+      "low" => Ok(Source::Low),
+      "close" => Ok(Source::Close),
+      _ => Err(anyhow::anyhow!("Invalid candle source: {}", s))
+    }
+  }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct SignalInfo {
   pub price: f64,
   pub date: Time,
-  pub ticker: String
+  pub ticker: String,
+  /// Fraction of the open position an exit closes, all of it if None, so a position can be scaled out of in tranches
+  pub exit_fraction: Option<f64>,
+  /// Order type the live engine places for this signal, the engine's configured order type if None
+  pub order_type: Option<SignalOrderType>,
+  /// Time in force of the live limit order for this signal, GTC if None
+  pub time_in_force: Option<TimeInForce>
+}
+
+impl SignalInfo {
+  /// Close `fraction` of the open position on exit rather than all of it
+  pub fn with_exit_fraction(mut self, fraction: f64) -> Self {
+    self.exit_fraction = Some(fraction);
+    self
+  }
+
+  /// Place the live order for this signal as `order_type`
+  pub fn with_order_type(mut self, order_type: SignalOrderType) -> Self {
+    self.order_type = Some(order_type);
+    self
+  }
+
+  /// Place the live order for this signal with `time_in_force`
+  pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+    self.time_in_force = Some(time_in_force);
+    self
+  }
+
+  /// Fraction of the open position an exit closes, between 0 and 1
+  pub fn fraction(&self) -> f64 {
+    self.exit_fraction.unwrap_or(1.0).clamp(0.0, 1.0)
+  }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -54,6 +220,40 @@ impl Signal {
     }
   }
 
+  /// Order the signal asks for, None for [`Signal::None`]
+  pub fn order(&self) -> Option<Order> {
+    match self {
+      Signal::EnterLong(_) => Some(Order::EnterLong),
+      Signal::ExitLong(_) => Some(Order::ExitLong),
+      Signal::EnterShort(_) => Some(Order::EnterShort),
+      Signal::ExitShort(_) => Some(Order::ExitShort),
+      Signal::None => None
+    }
+  }
+
+  /// Same signal filled at `price` on `date`, such as the open of the candle after it
+  pub fn repriced(self, price: f64, date: Time) -> Self {
+    let at = |info: SignalInfo| SignalInfo { price, date, ..info };
+    match self {
+      Signal::EnterLong(info) => Signal::EnterLong(at(info)),
+      Signal::ExitLong(info) => Signal::ExitLong(at(info)),
+      Signal::EnterShort(info) => Signal::EnterShort(at(info)),
+      Signal::ExitShort(info) => Signal::ExitShort(at(info)),
+      Signal::None => Signal::None
+    }
+  }
+
+  /// Date of the candle the signal was emitted on, None for [`Signal::None`]
+  pub fn date(&self) -> Option<Time> {
+    match self {
+      Signal::EnterLong(info) => Some(info.date),
+      Signal::ExitLong(info) => Some(info.date),
+      Signal::EnterShort(info) => Some(info.date),
+      Signal::ExitShort(info) => Some(info.date),
+      Signal::None => None
+    }
+  }
+
   #[allow(dead_code)]
   pub fn price(&self) -> Option<f64> {
     match self {
@@ -66,7 +266,7 @@ impl Signal {
   }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Order {
   EnterLong,
   ExitLong,
@@ -83,7 +283,7 @@ impl Order {
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade {
   pub ticker: String,
   pub date: Time,
@@ -111,8 +311,20 @@ pub struct PerformanceSummary {
 
 #[derive(Debug, Clone)]
 pub struct Summary {
+  /// Starting capital allocated to each ticker
+  pub initial_capital: f64,
+  /// Starting capital of tickers allocated other than `initial_capital`, such as those of a [`Summary::merge`]
+  pub ticker_capital: HashMap<String, f64>,
+  /// Minutes per candle of the traded series, to express durations in candles
+  pub interval_minutes: u32,
+  /// Number of candles backtested per ticker, empty for live trading history
+  pub total_candles: HashMap<String, usize>,
+  /// Quote withdrawn per ticker by [`ProfitPolicy::WithdrawAbove`], included in `cum_pct`
+  pub withdrawn: HashMap<String, f64>,
   pub cum_quote: HashMap<String, Dataset<i64, f64>>,
   pub cum_pct: HashMap<String, Dataset<i64, f64>>,
+  /// Net liquidation value in quote at the close of every candle, empty for live trading history
+  pub equity: HashMap<String, Dataset<i64, f64>>,
   pub pct_per_trade: HashMap<String, Dataset<i64, f64>>,
   pub trades: HashMap<String, Vec<Trade>>,
 }
@@ -121,6 +333,9 @@ impl Summary {
     println!("==== {} Backtest Summary ====", ticker);
     println!("Return: {}%", self.pct_roi(ticker));
     println!("Return: ${}", self.quote_roi(ticker));
+    if self.withdrawn(ticker) > 0.0 {
+      println!("Withdrawn: ${}", trunc!(self.withdrawn(ticker), 2));
+    }
     println!("Total Trades: {}", self.total_trades(ticker));
     println!("Win Rate: {}%", self.win_rate(ticker));
     println!("Avg Trade Size: ${}", self.avg_trade_size(ticker).unwrap());
@@ -130,6 +345,18 @@ impl Summary {
     println!("Best Trade: {}%", self.best_trade(ticker));
     println!("Worst Trade: {}%", self.worst_trade(ticker));
     println!("Max Drawdown: {}%", self.max_drawdown(ticker));
+    println!("Max Consecutive Wins: {}", self.max_consecutive_wins(ticker));
+    println!("Max Consecutive Losses: {}", self.max_consecutive_losses(ticker));
+    for (label, minutes) in [
+      ("Avg", self.avg_trade_duration(ticker)),
+      ("Median", self.median_trade_duration(ticker)),
+      ("Max", self.max_trade_duration(ticker))
+    ] {
+      println!("{} Trade Duration: {} candles ({}h)", label, trunc!(self.to_candles(minutes), 2), trunc!(minutes / 60.0, 2));
+    }
+    if let Ok(exposure) = self.exposure_pct(ticker) {
+      println!("Exposure: {}%", exposure);
+    }
   }
   
   pub fn cum_quote(&self, ticker: &str) -> anyhow::Result<&Dataset<i64, f64>> {
@@ -144,10 +371,209 @@ impl Summary {
     self.pct_per_trade.get(ticker).ok_or(anyhow::anyhow!("No pct per trade for ticker"))
   }
 
+  /// Net liquidation value per candle: quote, withdrawals and held base marked to the candle close,
+  /// plus the unrealized PnL of an open trade. Unlike `cum_pct` it moves while a position is held.
+  pub fn equity_curve(&self, ticker: &str) -> anyhow::Result<&Dataset<i64, f64>> {
+    self.equity.get(ticker).ok_or(anyhow::anyhow!("No equity curve for ticker"))
+  }
+
+  /// Starting capital allocated to `ticker`
+  pub fn capital(&self, ticker: &str) -> f64 {
+    self.ticker_capital.get(ticker).copied().unwrap_or(self.initial_capital)
+  }
+
+  pub fn withdrawn(&self, ticker: &str) -> f64 {
+    self.withdrawn.get(ticker).copied().unwrap_or(0.0)
+  }
+
   pub fn trades(&self, ticker: &str) -> anyhow::Result<&Vec<Trade>> {
     self.trades.get(ticker).ok_or(anyhow::anyhow!("No trades for ticker"))
   }
 
+  /// Net % ROI of all tickers combined.
+  /// Sums the latest quote PnL of each ticker at every trade event (across all tickers)
+  /// and normalizes by the initial capital of all tickers, so a pair produces a single equity curve.
+  pub fn portfolio_cum_pct(&self) -> Dataset<i64, f64> {
+    let total_capital = self.cum_quote.keys().map(|ticker| self.capital(ticker)).sum::<f64>();
+    let mut events: Vec<(i64, &String, f64)> = self.cum_quote.iter().flat_map(|(ticker, data)| {
+      data.data().iter().map(move |d| (d.x, ticker, d.y))
+    }).collect();
+    events.sort_by_key(|e| e.0);
+
+    let mut latest: HashMap<&String, f64> = HashMap::new();
+    let mut portfolio: Vec<Data<i64, f64>> = Vec::new();
+    for (x, ticker, y) in events {
+      latest.insert(ticker, y);
+      let y = trunc!(latest.values().sum::<f64>() / total_capital * 100.0, 2);
+      match portfolio.last_mut() {
+        // multiple tickers traded on the same candle
+        Some(last) if last.x == x => last.y = y,
+        _ => portfolio.push(Data { x, y })
+      }
+    }
+    Dataset::new(portfolio)
+  }
+
+  /// Combine summaries of separate backtests, e.g. one per ticker, into a single portfolio summary.
+  /// Per-ticker datasets are unioned, a ticker in more than one summary keeps the last one's.
+  /// Each ticker keeps the starting capital of its summary, and the interval is taken from the first summary.
+  pub fn merge(summaries: Vec<Summary>) -> Summary {
+    let mut merged = Summary {
+      initial_capital: summaries.first().map(|s| s.initial_capital).unwrap_or(0.0),
+      interval_minutes: summaries.first().map(|s| s.interval_minutes).unwrap_or(0),
+      ticker_capital: HashMap::new(),
+      total_candles: HashMap::new(),
+      withdrawn: HashMap::new(),
+      cum_quote: HashMap::new(),
+      cum_pct: HashMap::new(),
+      equity: HashMap::new(),
+      pct_per_trade: HashMap::new(),
+      trades: HashMap::new()
+    };
+    for summary in summaries {
+      for ticker in summary.cum_pct.keys().chain(summary.trades.keys()) {
+        merged.ticker_capital.insert(ticker.clone(), summary.capital(ticker));
+      }
+      merged.total_candles.extend(summary.total_candles);
+      merged.withdrawn.extend(summary.withdrawn);
+      merged.cum_quote.extend(summary.cum_quote);
+      merged.cum_pct.extend(summary.cum_pct);
+      merged.equity.extend(summary.equity);
+      merged.pct_per_trade.extend(summary.pct_per_trade);
+      merged.trades.extend(summary.trades);
+    }
+    merged
+  }
+
+  /// Tickers with closed trades, in alphabetical order
+  fn traded_tickers(&self) -> Vec<&String> {
+    let mut tickers: Vec<&String> = self.cum_pct.iter().filter(|(_, d)| !d.data().is_empty()).map(|(t, _)| t).collect();
+    tickers.sort();
+    tickers
+  }
+
+  /// % ROI of all tickers combined, each ticker's ROI weighted by its starting capital
+  pub fn portfolio_pct_roi(&self) -> f64 {
+    let tickers = self.traded_tickers();
+    let capital = tickers.iter().map(|t| self.capital(t)).sum::<f64>();
+    match capital > 0.0 {
+      true => trunc!(tickers.iter().map(|t| self.pct_roi(t) * self.capital(t)).sum::<f64>() / capital, 3),
+      false => 0.0
+    }
+  }
+
+  /// Closed trades of all tickers combined
+  pub fn portfolio_total_trades(&self) -> usize {
+    self.traded_tickers().iter().map(|t| self.total_trades(t)).sum()
+  }
+
+  /// % of winning trades across all tickers, so tickers that trade more count more
+  pub fn portfolio_win_rate(&self) -> f64 {
+    let pcts: Vec<f64> = self.pct_per_trade.values().flat_map(|d| d.data().iter().map(|d| d.y)).collect();
+    match pcts.is_empty() {
+      true => 0.0,
+      false => trunc!(pcts.iter().filter(|pct| **pct > 0.0).count() as f64 / pcts.len() as f64 * 100.0, 3)
+    }
+  }
+
+  /// Summary of every rolling window of `trades_per_window` closed trades per ticker, stepping one trade at a time, oldest first.
+  /// Each window's `cum_pct` and `cum_quote` are rebased to the equity at its start, which is also its starting capital,
+  /// so its ROI is the return within the window. Tickers with fewer closed trades than the window are left out.
+  pub fn windowed(&self, trades_per_window: usize) -> Vec<Summary> {
+    if trades_per_window == 0 {
+      return vec![];
+    }
+    let windows = self.cum_pct.values().map(|d| (d.data().len() + 1).saturating_sub(trades_per_window)).max().unwrap_or(0);
+    (0..windows).map(|start| {
+      let end = start + trades_per_window;
+      let mut window = Summary {
+        initial_capital: self.initial_capital,
+        interval_minutes: self.interval_minutes,
+        ticker_capital: HashMap::new(),
+        total_candles: HashMap::new(),
+        withdrawn: HashMap::new(),
+        cum_quote: HashMap::new(),
+        cum_pct: HashMap::new(),
+        equity: HashMap::new(),
+        pct_per_trade: HashMap::new(),
+        trades: HashMap::new()
+      };
+      for (ticker, cum_pct) in self.cum_pct.iter() {
+        let cum_pct = cum_pct.data();
+        if end > cum_pct.len() {
+          continue;
+        }
+        let cum_quote = self.cum_quote.get(ticker).map(|d| d.data().as_slice()).unwrap_or_default();
+        let prior_pct = match start {
+          0 => 0.0,
+          _ => cum_pct[start - 1].y
+        };
+        let prior_quote = match start {
+          0 => 0.0,
+          _ => cum_quote.get(start - 1).map(|d| d.y).unwrap_or(0.0)
+        };
+        let base = 1.0 + prior_pct / 100.0;
+        window.ticker_capital.insert(ticker.clone(), self.capital(ticker) * base);
+        window.cum_pct.insert(ticker.clone(), Dataset::new(cum_pct[start..end].iter().map(|d| Data {
+          x: d.x,
+          y: trunc!(((1.0 + d.y / 100.0) / base - 1.0) * 100.0, 2)
+        }).collect()));
+        window.cum_quote.insert(ticker.clone(), Dataset::new(cum_quote.get(start..end).unwrap_or_default().iter().map(|d| Data {
+          x: d.x,
+          y: trunc!(d.y - prior_quote, 2)
+        }).collect()));
+        if let Some(pct_per_trade) = self.pct_per_trade.get(ticker) {
+          window.pct_per_trade.insert(ticker.clone(), Dataset::new(pct_per_trade.data().get(start..end).unwrap_or_default().to_vec()));
+        }
+        // every closed trade is recorded as its entry and exit
+        if let Some(trades) = self.trades.get(ticker) {
+          window.trades.insert(ticker.clone(), trades.get(start * 2..end * 2).unwrap_or_default().to_vec());
+        }
+        // candles from the window's first trade up to the trade after it
+        if let Some(equity) = self.equity.get(ticker) {
+          let from = cum_pct[start].x;
+          let until = cum_pct.get(end).map(|d| d.x).unwrap_or(i64::MAX);
+          let equity: Vec<Data<i64, f64>> = equity.data().iter().filter(|d| d.x >= from && d.x < until).cloned().collect();
+          window.total_candles.insert(ticker.clone(), equity.len());
+          window.equity.insert(ticker.clone(), Dataset::new(equity));
+        }
+      }
+      window
+    }).collect()
+  }
+
+  /// % return within each calendar period, oldest first, compounding from the equity at the end of the prior period.
+  /// Buckets the `cum_pct` curve by trade timestamp, so periods without a closed trade are omitted.
+  pub fn periodic_returns(&self, ticker: &str, period: CalendarPeriod) -> anyhow::Result<Vec<(String, f64)>> {
+    let mut returns: Vec<(String, f64)> = vec![];
+    // equity relative to initial capital at the end of the prior period
+    let mut prev_equity = 1.0;
+    let mut equity = 1.0;
+    for d in self.cum_pct(ticker)?.data() {
+      let label = period.label(&Time::from_unix_ms(d.x));
+      match returns.last() {
+        Some((last, _)) if *last == label => (),
+        _ => {
+          prev_equity = equity;
+          returns.push((label, 0.0));
+        }
+      }
+      equity = 1.0 + d.y / 100.0;
+      if let Some(last) = returns.last_mut() {
+        last.1 = trunc!((equity / prev_equity - 1.0) * 100.0, 2);
+      }
+    }
+    Ok(returns)
+  }
+
+  pub fn print_periodic_returns(&self, ticker: &str, period: CalendarPeriod) -> anyhow::Result<()> {
+    println!("==== {} {:?} Returns ====", ticker, period);
+    for (label, pct) in self.periodic_returns(ticker, period)? {
+      println!("{:<8} {:>10}%", label, pct);
+    }
+    Ok(())
+  }
+
   pub fn summarize(&self, ticker: &str) -> anyhow::Result<PerformanceSummary> {
     Ok(PerformanceSummary {
       ticker: ticker.to_string(),
@@ -211,6 +637,20 @@ impl Summary {
     trunc!(max_dd, 3)
   }
 
+  /// % below the running peak of `cum_pct` at each point, 0 at new highs and negative while underwater.
+  /// The initial capital is the first peak, so losses before the first new high are underwater too.
+  pub fn underwater_curve(&self, ticker: &str) -> anyhow::Result<Dataset<i64, f64>> {
+    let mut peak = 1.0;
+    let underwater = self.cum_pct(ticker)?.data().iter().map(|d| {
+      let equity = 1.0 + d.y / 100.0;
+      if equity > peak {
+        peak = equity;
+      }
+      Data { x: d.x, y: trunc!((equity - peak) / peak * 100.0, 3) }
+    }).collect();
+    Ok(Dataset::new(underwater))
+  }
+
   pub fn avg_trade(&self, ticker: &str) -> f64 {
     let len = self.pct_per_trade.get(ticker).unwrap().data().len();
     let avg_trade = self.pct_per_trade
@@ -267,6 +707,90 @@ impl Summary {
     trunc!(worst_trade, 3)
   }
 
+  /// Longest run of winning trades in `pct_per_trade` order, 0 if no trades
+  pub fn max_consecutive_wins(&self, ticker: &str) -> usize {
+    self.max_streak(ticker, |pct| pct > 0.0)
+  }
+
+  /// Longest run of losing trades in `pct_per_trade` order, 0 if no trades
+  pub fn max_consecutive_losses(&self, ticker: &str) -> usize {
+    self.max_streak(ticker, |pct| pct < 0.0)
+  }
+
+  /// Longest run of consecutive trades whose % PnL satisfies `pred`, a breakeven trade ends both streaks
+  fn max_streak<F: Fn(f64) -> bool>(&self, ticker: &str, pred: F) -> usize {
+    let mut max = 0;
+    let mut streak = 0;
+    for d in self.pct_per_trade.get(ticker).map(|d| d.asc_order()).unwrap_or_default() {
+      match pred(d.y) {
+        true => {
+          streak += 1;
+          max = max.max(streak);
+        }
+        false => streak = 0
+      }
+    }
+    max
+  }
+
+  /// Minutes each closed trade was held, pairing each exit with the entry before it, oldest first
+  pub fn trade_durations(&self, ticker: &str) -> Vec<f64> {
+    let mut durations = vec![];
+    let mut entry: Option<&Trade> = None;
+    for trade in self.trades.get(ticker).into_iter().flatten() {
+      if trade.side.is_entry() {
+        entry = Some(trade);
+      } else if let Some(e) = entry.take() {
+        durations.push((trade.date.to_unix_ms() - e.date.to_unix_ms()) as f64 / 60_000.0);
+      }
+    }
+    durations
+  }
+
+  /// Mean minutes a trade is held, 0 if no trades closed
+  pub fn avg_trade_duration(&self, ticker: &str) -> f64 {
+    let durations = self.trade_durations(ticker);
+    match durations.is_empty() {
+      true => 0.0,
+      false => durations.iter().sum::<f64>() / durations.len() as f64
+    }
+  }
+
+  /// Median minutes a trade is held, 0 if no trades closed
+  pub fn median_trade_duration(&self, ticker: &str) -> f64 {
+    let mut durations = self.trade_durations(ticker);
+    durations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let len = durations.len();
+    match len {
+      0 => 0.0,
+      _ if len % 2 == 0 => (durations[len / 2 - 1] + durations[len / 2]) / 2.0,
+      _ => durations[len / 2]
+    }
+  }
+
+  /// Longest minutes a trade was held, 0 if no trades closed
+  pub fn max_trade_duration(&self, ticker: &str) -> f64 {
+    self.trade_durations(ticker).into_iter().fold(0.0, f64::max)
+  }
+
+  /// % of candles during which a position was open, the candles held by closed trades over the total candles
+  pub fn exposure_pct(&self, ticker: &str) -> anyhow::Result<f64> {
+    let total = *self.total_candles.get(ticker).ok_or(anyhow::anyhow!("No candle count for ticker"))?;
+    if total == 0 {
+      return Ok(0.0);
+    }
+    let held = self.to_candles(self.trade_durations(ticker).iter().sum::<f64>());
+    Ok(trunc!((held / total as f64 * 100.0).min(100.0), 3))
+  }
+
+  /// Convert minutes to a number of candles of the series interval, gaps in the series are counted as candles
+  pub fn to_candles(&self, minutes: f64) -> f64 {
+    match self.interval_minutes {
+      0 => 0.0,
+      interval => minutes / interval as f64
+    }
+  }
+
   pub fn win_rate(&self, ticker: &str) -> f64 {
     let len = self.pct_per_trade.get(ticker).unwrap().data().len();
     let win_rate = self.pct_per_trade
@@ -277,4 +801,119 @@ impl Summary {
       .filter(|d| d.y > 0.0).count() as f64 / len as f64 * 100.0;
     trunc!(win_rate, 3)
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn summary(ticker: &str, initial_capital: f64, pcts: &[f64]) -> Summary {
+    let mut cum = 0.0;
+    let cum_pct: Vec<Data<i64, f64>> = pcts.iter().enumerate().map(|(i, pct)| {
+      cum += pct;
+      Data { x: i as i64, y: cum }
+    }).collect();
+    let cum_quote = cum_pct.iter().map(|d| Data { x: d.x, y: d.y / 100.0 * initial_capital }).collect();
+    let pct_per_trade = pcts.iter().enumerate().map(|(i, pct)| Data { x: i as i64, y: *pct }).collect();
+    Summary {
+      initial_capital,
+      interval_minutes: 60,
+      ticker_capital: HashMap::new(),
+      total_candles: HashMap::from([(ticker.to_string(), 10)]),
+      withdrawn: HashMap::new(),
+      cum_quote: HashMap::from([(ticker.to_string(), Dataset::new(cum_quote))]),
+      cum_pct: HashMap::from([(ticker.to_string(), Dataset::new(cum_pct))]),
+      equity: HashMap::new(),
+      pct_per_trade: HashMap::from([(ticker.to_string(), Dataset::new(pct_per_trade))]),
+      trades: HashMap::from([(ticker.to_string(), vec![])])
+    }
+  }
+
+  #[test]
+  fn merge_summaries() {
+    let merged = Summary::merge(vec![
+      summary("SOLUSDT", 1000.0, &[10.0, -5.0]),
+      summary("ETHUSDT", 3000.0, &[2.0, 2.0, -1.0])
+    ]);
+    assert_eq!(merged.total_candles.len(), 2);
+    assert_eq!(merged.capital("SOLUSDT"), 1000.0);
+    assert_eq!(merged.capital("ETHUSDT"), 3000.0);
+    assert_eq!(merged.pct_roi("SOLUSDT"), 5.0);
+    assert_eq!(merged.pct_roi("ETHUSDT"), 3.0);
+    // (5% * 1000 + 3% * 3000) / 4000
+    assert_eq!(merged.portfolio_pct_roi(), 3.5);
+    assert_eq!(merged.portfolio_total_trades(), 5);
+    assert_eq!(merged.portfolio_win_rate(), 60.0);
+    // $50 + $90 on $4000
+    assert_eq!(merged.portfolio_cum_pct().data().last().map(|d| d.y), Some(3.5));
+  }
+
+  #[test]
+  fn windowed_summaries() {
+    let summary = summary("SOLUSDT", 1000.0, &[10.0, -5.0, 20.0]);
+    let windows = summary.windowed(2);
+    assert_eq!(windows.len(), 2);
+    assert_eq!(windows[0].pct_roi("SOLUSDT"), 5.0);
+    assert_eq!(windows[0].win_rate("SOLUSDT"), 50.0);
+    assert_eq!(windows[0].quote_roi("SOLUSDT"), 50.0);
+    // rebased to the 1100 of equity after the first trade
+    assert_eq!(windows[1].capital("SOLUSDT"), 1100.0);
+    let cum_pct: Vec<f64> = windows[1].cum_pct["SOLUSDT"].data().iter().map(|d| d.y).collect();
+    assert_eq!(cum_pct, vec![-4.55, 13.64]);
+    assert_eq!(windows[1].quote_roi("SOLUSDT"), 150.0);
+    assert_eq!(windows[1].win_rate("SOLUSDT"), 50.0);
+
+    assert_eq!(summary.windowed(3).len(), 1);
+    assert!(summary.windowed(4).is_empty());
+    assert!(summary.windowed(0).is_empty());
+  }
+
+  #[test]
+  fn allocation_split_bounds() -> anyhow::Result<()> {
+    assert_eq!(Allocation::AllQuote.quote_capital(1000.0)?, 1000.0);
+    assert_eq!(Allocation::Split(0.0).quote_capital(1000.0)?, 1000.0);
+    assert_eq!(Allocation::Split(25.0).quote_capital(1000.0)?, 750.0);
+    assert_eq!(Allocation::Split(100.0).quote_capital(1000.0)?, 0.0);
+    assert!(Allocation::Split(-10.0).quote_capital(1000.0).is_err());
+    assert!(Allocation::Split(150.0).quote_capital(1000.0).is_err());
+    assert!(Allocation::Split(f64::NAN).quote_capital(1000.0).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn max_streaks() {
+    // W W L W W W L L
+    let streaks = summary("SOLUSDT", 1000.0, &[1.0, 2.0, -1.0, 3.0, 1.0, 2.0, -2.0, -1.0]);
+    assert_eq!(streaks.max_consecutive_wins("SOLUSDT"), 3);
+    assert_eq!(streaks.max_consecutive_losses("SOLUSDT"), 2);
+
+    // a breakeven trade ends both streaks
+    let breakeven = summary("SOLUSDT", 1000.0, &[1.0, 0.0, 1.0, -1.0, 0.0, -1.0]);
+    assert_eq!(breakeven.max_consecutive_wins("SOLUSDT"), 1);
+    assert_eq!(breakeven.max_consecutive_losses("SOLUSDT"), 1);
+    assert_eq!(breakeven.max_consecutive_wins("ETHUSDT"), 0);
+  }
+
+  #[test]
+  fn parse_source() -> anyhow::Result<()> {
+    assert_eq!("open".parse::<Source>()?, Source::Open);
+    assert_eq!("High".parse::<Source>()?, Source::High);
+    assert_eq!("LOW".parse::<Source>()?, Source::Low);
+    assert_eq!("close".parse::<Source>()?, Source::Close);
+    assert!("".parse::<Source>().is_err());
+    assert!("hl2".parse::<Source>().is_err());
+    assert!(" close".parse::<Source>().is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn underwater_curve() -> anyhow::Result<()> {
+    // equity 0.9, 1.1, 1.05, 1.15, 1.0 of the initial capital
+    let summary = summary("SOLUSDT", 1000.0, &[-10.0, 20.0, -5.0, 10.0, -15.0]);
+    let underwater: Vec<f64> = summary.underwater_curve("SOLUSDT")?.data().iter().map(|d| d.y).collect();
+    // underwater from the initial capital before the first new high, then 0 at each new high
+    assert_eq!(underwater, vec![-10.0, 0.0, -4.545, 0.0, -13.043]);
+    assert!(summary.underwater_curve("ETHUSDT").is_err());
+    Ok(())
+  }
+}